@@ -0,0 +1,77 @@
+//! Replay a capture saved by `RecordingBackend` without opening a window
+//! and save the RGBA8 color attachments of every render pass as PPM images.
+//!
+//! cargo run --example capture_replay -- frame.mqcap [frames]
+
+use miniquad::*;
+
+struct Stage {
+    ctx: Box<dyn RenderingBackend>,
+    replayer: Replayer,
+    frames: usize,
+}
+
+impl Stage {
+    fn save_attachments(&mut self) {
+        for (i, pass) in self.replayer.render_passes().into_iter().enumerate() {
+            let attachments = self.ctx.render_pass_color_attachments(pass).to_vec();
+            for (j, texture) in attachments.into_iter().enumerate() {
+                let params = self.ctx.texture_params(texture);
+                if params.format != TextureFormat::RGBA8 || params.sample_count > 1 {
+                    continue;
+                }
+                let mut pixels = vec![0; params.width as usize * params.height as usize * 4];
+                self.ctx.texture_read_pixels(texture, &mut pixels);
+
+                let mut ppm = format!("P6\n{} {}\n255\n", params.width, params.height).into_bytes();
+                // GL textures are stored bottom row first
+                for row in pixels.chunks(params.width as usize * 4).rev() {
+                    for pixel in row.chunks(4) {
+                        ppm.extend_from_slice(&pixel[..3]);
+                    }
+                }
+                let path = format!("pass{}_{}.ppm", i, j);
+                std::fs::write(&path, ppm).unwrap();
+                println!("{}", path);
+            }
+        }
+    }
+}
+
+impl EventHandler for Stage {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {
+        if self.replayer.frame() < self.frames {
+            self.replayer.replay_frame(&mut *self.ctx).unwrap();
+        }
+        if self.replayer.frame() == self.frames {
+            self.save_attachments();
+            window::order_quit();
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("usage: capture_replay <capture> [frames]");
+    let replayer = Replayer::new(&std::fs::read(path).unwrap()).unwrap();
+    let frames = args
+        .next()
+        .map(|frames| frames.parse().unwrap())
+        .unwrap_or(replayer.frame_count())
+        .min(replayer.frame_count());
+
+    let mut conf = conf::Conf::default();
+    conf.platform.linux_backend = conf::LinuxBackend::Headless;
+
+    miniquad::start(conf, move || {
+        Box::new(Stage {
+            ctx: window::new_rendering_backend(),
+            replayer,
+            frames,
+        })
+    });
+}
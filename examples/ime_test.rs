@@ -196,7 +196,7 @@ impl Stage {
 
         // Color pipeline
         let cs = ctx.new_shader(ShaderSource::Glsl { vertex: COLOR_VS, fragment: COLOR_FS },
-            ShaderMeta { images: vec![], uniforms: UniformBlockLayout { uniforms: vec![] } }).unwrap();
+            ShaderMeta { images: vec![], uniforms: UniformBlockLayout { uniforms: vec![] }, uniform_blocks: vec![] }).unwrap();
         let color_pl = ctx.new_pipeline(&[BufferLayout::default()],
             &[VertexAttribute::new("in_pos", VertexFormat::Float2),
               VertexAttribute::new("in_color", VertexFormat::Float4)], cs,
@@ -205,12 +205,12 @@ impl Stage {
                 BlendFactor::OneMinusValue(BlendValue::SourceAlpha))), ..Default::default() });
         let cvb = ctx.new_buffer(BufferType::VertexBuffer, BufferUsage::Stream, BufferSource::empty::<ColorVert>(1024));
         let cib = ctx.new_buffer(BufferType::IndexBuffer, BufferUsage::Stream, BufferSource::empty::<u16>(2048));
-        let color_bind = Bindings { vertex_buffers: vec![cvb], index_buffer: cib, images: vec![] };
+        let color_bind = Bindings { vertex_buffers: vec![cvb], index_buffer: Some(cib), images: vec![], samplers: vec![] };
 
         // Text renderer & pipeline
         let tr = TextRenderer::new(&mut ctx, 22.0);
         let ts = ctx.new_shader(ShaderSource::Glsl { vertex: TEXT_VS, fragment: TEXT_FS },
-            ShaderMeta { images: vec!["tex".into()], uniforms: UniformBlockLayout { uniforms: vec![] } }).unwrap();
+            ShaderMeta { images: vec!["tex".into()], uniforms: UniformBlockLayout { uniforms: vec![] }, uniform_blocks: vec![] }).unwrap();
         let text_pl = ctx.new_pipeline(&[BufferLayout::default()],
             &[VertexAttribute::new("in_pos", VertexFormat::Float2),
               VertexAttribute::new("in_uv", VertexFormat::Float2),
@@ -220,7 +220,7 @@ impl Stage {
                 BlendFactor::OneMinusValue(BlendValue::SourceAlpha))), ..Default::default() });
         let tvb = ctx.new_buffer(BufferType::VertexBuffer, BufferUsage::Stream, BufferSource::empty::<TextVert>(4096));
         let tib = ctx.new_buffer(BufferType::IndexBuffer, BufferUsage::Stream, BufferSource::empty::<u16>(8192));
-        let text_bind = Bindings { vertex_buffers: vec![tvb], index_buffer: tib, images: vec![tr.atlas] };
+        let text_bind = Bindings { vertex_buffers: vec![tvb], index_buffer: Some(tib), images: vec![tr.atlas], samplers: vec![] };
 
         let boxes = vec![
             InputBox::new(50.0, 80.0, 500.0, 36.0),
@@ -299,9 +299,9 @@ impl EventHandler for Stage {
 
         self.tr.flush(&mut self.ctx);
         self.ctx.buffer_update(self.color_bind.vertex_buffers[0], BufferSource::slice(&cv));
-        self.ctx.buffer_update(self.color_bind.index_buffer, BufferSource::slice(&ci));
+        self.ctx.buffer_update(self.color_bind.index_buffer.unwrap(), BufferSource::slice(&ci));
         self.ctx.buffer_update(self.text_bind.vertex_buffers[0], BufferSource::slice(&tv));
-        self.ctx.buffer_update(self.text_bind.index_buffer, BufferSource::slice(&ti));
+        self.ctx.buffer_update(self.text_bind.index_buffer.unwrap(), BufferSource::slice(&ti));
 
         self.ctx.begin_default_pass(Default::default());
         self.ctx.apply_pipeline(&self.color_pl);
@@ -58,8 +58,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: Some(index_buffer),
             images: vec![],
+            samplers: vec![],
         };
 
         let shader = ctx
@@ -254,6 +255,7 @@ mod shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
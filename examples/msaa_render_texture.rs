@@ -105,8 +105,9 @@ impl Stage {
 
         let offscreen_bind = Bindings {
             vertex_buffers: vec![vertex_buffer.clone()],
-            index_buffer: index_buffer.clone(),
+            index_buffer: Some(index_buffer.clone()),
             images: vec![],
+            samplers: vec![],
         };
 
         let display_bind = {
@@ -130,8 +131,9 @@ impl Stage {
             );
             Bindings {
                 vertex_buffers: vec![vertex_buffer],
-                index_buffer: index_buffer,
+                index_buffer: Some(index_buffer),
                 images: vec![color_resolve_img],
+                samplers: vec![],
             }
         };
 
@@ -336,6 +338,7 @@ mod display_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -412,6 +415,7 @@ mod offscreen_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 }
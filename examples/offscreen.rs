@@ -90,14 +90,16 @@ impl Stage {
 
         let offscreen_bind = Bindings {
             vertex_buffers: vec![vertex_buffer.clone()],
-            index_buffer: index_buffer.clone(),
+            index_buffer: Some(index_buffer.clone()),
             images: vec![],
+            samplers: vec![],
         };
 
         let display_bind = Bindings {
             vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: Some(index_buffer),
             images: vec![color_img],
+            samplers: vec![],
         };
 
         let source = match ctx.info().backend {
@@ -300,6 +302,7 @@ mod display_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -376,6 +379,7 @@ mod offscreen_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 }
@@ -91,8 +91,9 @@ impl Stage {
 
         let offscreen_bind = Bindings {
             vertex_buffers: vec![vertex_buffer.clone()],
-            index_buffer: index_buffer.clone(),
+            index_buffer: Some(index_buffer.clone()),
             images: vec![],
+            samplers: vec![],
         };
 
         #[rustfmt::skip]
@@ -120,8 +121,9 @@ impl Stage {
 
         let post_processing_bind = Bindings {
             vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: Some(index_buffer),
             images: vec![color_img],
+            samplers: vec![],
         };
 
         let default_shader = ctx
@@ -307,6 +309,7 @@ mod post_processing_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("resolution", UniformType::Float2)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -348,6 +351,7 @@ mod offscreen_shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -53,8 +53,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: Some(index_buffer),
             images: vec![texture],
+            samplers: vec![],
         };
 
         let shader = ctx
@@ -197,6 +198,7 @@ mod shader {
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
             },
+            uniform_blocks: vec![],
         }
     }
 
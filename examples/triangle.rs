@@ -28,17 +28,11 @@ impl Stage {
             BufferSource::slice(&vertices),
         );
 
-        let indices: [u16; 3] = [0, 1, 2];
-        let index_buffer = ctx.new_buffer(
-            BufferType::IndexBuffer,
-            BufferUsage::Immutable,
-            BufferSource::slice(&indices),
-        );
-
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: None,
             images: vec![],
+            samplers: vec![],
         };
 
         let shader = ctx
@@ -82,7 +76,7 @@ impl EventHandler for Stage {
 
         self.ctx.apply_pipeline(&self.pipeline);
         self.ctx.apply_bindings(&self.bindings);
-        self.ctx.draw(0, 3, 1);
+        self.ctx.draw_arrays(0, 3, 1);
         self.ctx.end_render_pass();
 
         self.ctx.commit_frame();
@@ -158,6 +152,7 @@ mod shader {
         ShaderMeta {
             images: vec![],
             uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
         }
     }
 }
@@ -37,8 +37,9 @@ impl Stage {
 
         let bindings = Bindings {
             vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
+            index_buffer: Some(index_buffer),
             images: vec![],
+            samplers: vec![],
         };
 
         let shader = ctx
@@ -161,6 +162,7 @@ mod shader {
         ShaderMeta {
             images: vec![],
             uniforms: UniformBlockLayout { uniforms: vec![] },
+            uniform_blocks: vec![],
         }
     }
 }
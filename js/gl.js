@@ -61,6 +61,7 @@ function init_webgl(version) {
                 ctx['endQuery'] = function (target) { return ext['endQueryEXT'](target); };
                 ctx['deleteQuery'] = function (query) { ext['deleteQueryEXT'](query); };
                 ctx['getQueryObject'] = function (query, pname) { return ext['getQueryObjectEXT'](query, pname); };
+                ctx['queryCounter'] = function (query, target) { ext['queryCounterEXT'](query, target); };
             }
         }
 
@@ -89,10 +90,43 @@ function init_webgl(version) {
         }
     } else {
         gl = canvas.getContext("webgl2");
+
+        // float render targets and filtering are reported in features, if available
+        if (gl !== null) {
+            gl.getExtension("EXT_color_buffer_float");
+            gl.getExtension("OES_texture_float_linear");
+
+            gl['getQueryObject'] = function (query, pname) { return gl.getQueryParameter(query, pname); };
+            var timer_ext = gl.getExtension("EXT_disjoint_timer_query_webgl2");
+            if (timer_ext) {
+                gl['queryCounter'] = function (query, target) { timer_ext['queryCounterEXT'](query, target); };
+            }
+            // per attachment blending, reported in features.independent_blend
+            var indexed_ext = gl.getExtension("OES_draw_buffers_indexed");
+            if (indexed_ext) {
+                gl['enablei'] = function (target, index) { indexed_ext['enableiOES'](target, index); };
+                gl['disablei'] = function (target, index) { indexed_ext['disableiOES'](target, index); };
+                gl['blendEquationSeparatei'] = function (buf, modeRGB, modeAlpha) { indexed_ext['blendEquationSeparateiOES'](buf, modeRGB, modeAlpha); };
+                gl['blendFuncSeparatei'] = function (buf, srcRGB, dstRGB, srcAlpha, dstAlpha) { indexed_ext['blendFuncSeparateiOES'](buf, srcRGB, dstRGB, srcAlpha, dstAlpha); };
+                gl['colorMaski'] = function (buf, r, g, b, a) { indexed_ext['colorMaskiOES'](buf, r, g, b, a); };
+            }
+        }
     }
     if (gl === null) {
         alert("Unable to initialize WebGL. Your browser or machine may not support it.");
+        return;
     }
+
+    // compressed formats are reported in features, if available
+    gl.getExtension("WEBGL_compressed_texture_s3tc");
+    gl.getExtension("EXT_texture_compression_rgtc");
+    gl.getExtension("EXT_texture_compression_bptc");
+    gl.getExtension("WEBGL_compressed_texture_etc");
+    gl.getExtension("WEBGL_compressed_texture_astc");
+
+    // sampler state reported in ContextInfo::max_anisotropy and features.mirror_clamp_to_edge
+    gl.getExtension("EXT_texture_filter_anisotropic");
+    gl.getExtension("EXT_texture_mirror_clamp_to_edge");
 }
 
 canvas.focus();
@@ -213,6 +247,10 @@ var GL = {
     shaders: [],
     vaos: [],
     timerQueries: [],
+    samplers: [],
+    syncs: [],
+    // glReadPixels writes into the bound GL_PIXEL_PACK_BUFFER instead of wasm memory
+    packBufferBound: false,
     contexts: {},
     programInfos: {},
 
@@ -288,6 +326,17 @@ var GL = {
     }
 }
 
+// WebGLActiveInfo into glGetActiveUniform/glGetActiveAttrib out parameters
+function _glWriteActiveInfo(info, bufSize, length, size, type, name) {
+    var written = stringToUTF8(info.name, getArray(name, Uint8Array, bufSize), 0, bufSize - 1);
+    getArray(name + written, Uint8Array, 1)[0] = 0;
+    if (length != 0) {
+        getArray(length, Int32Array, 1)[0] = written;
+    }
+    getArray(size, Int32Array, 1)[0] = info.size;
+    getArray(type, Uint32Array, 1)[0] = info.type;
+}
+
 function _glGenObject(n, buffers, createFunction, objectTable, functionName) {
     for (var i = 0; i < n; i++) {
         var buffer = gl[createFunction]();
@@ -691,13 +740,64 @@ var importObject = {
             gl.texSubImage2D(target, level, xoffset, yoffset, width, height, format, type,
                 pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height)) : null);
         },
+        glCompressedTexImage2D: function (target, level, internalFormat, width, height, border, imageSize, data) {
+            gl.compressedTexImage2D(target, level, internalFormat, width, height, border,
+                data ? getArray(data, Uint8Array, imageSize) : new Uint8Array(imageSize));
+        },
+        glCompressedTexSubImage2D: function (target, level, xoffset, yoffset, width, height, format, imageSize, data) {
+            gl.compressedTexSubImage2D(target, level, xoffset, yoffset, width, height, format,
+                getArray(data, Uint8Array, imageSize));
+        },
+        glTexImage3D: function (target, level, internalFormat, width, height, depth, border, format, type, pixels) {
+            gl.texImage3D(target, level, internalFormat, width, height, depth, border, format, type,
+                pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height) * depth) : null);
+        },
+        glTexSubImage3D: function (target, level, xoffset, yoffset, zoffset, width, height, depth, format, type, pixels) {
+            gl.texSubImage3D(target, level, xoffset, yoffset, zoffset, width, height, depth, format, type,
+                pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height) * depth) : null);
+        },
         glReadPixels: function (x, y, width, height, format, type, pixels) {
+            if (GL.packBufferBound) {
+                // pixels is an offset into the pack buffer
+                gl.readPixels(x, y, width, height, format, type, pixels);
+                return;
+            }
             var pixelData = getArray(pixels, Uint8Array, texture_size(format, width, height));
             gl.readPixels(x, y, width, height, format, type, pixelData);
         },
         glTexParameteri: function (target, pname, param) {
             gl.texParameteri(target, pname, param);
         },
+        glTexParameterf: function (target, pname, param) {
+            gl.texParameterf(target, pname, param);
+        },
+        glGenSamplers: function (n, ids) {
+            _glGenObject(n, ids, 'createSampler', GL.samplers, 'glGenSamplers');
+        },
+        glDeleteSamplers: function (n, ids) {
+            for (var i = 0; i < n; i++) {
+                var id = getArray(ids + i * 4, Uint32Array, 1)[0];
+                var sampler = GL.samplers[id];
+                if (!sampler) {
+                    continue;
+                }
+                gl.deleteSampler(sampler);
+                sampler.name = 0;
+                GL.samplers[id] = null;
+            }
+        },
+        glBindSampler: function (unit, sampler) {
+            GL.validateGLObjectID(GL.samplers, sampler, 'glBindSampler', 'sampler');
+            gl.bindSampler(unit, sampler ? GL.samplers[sampler] : null);
+        },
+        glSamplerParameteri: function (sampler, pname, param) {
+            GL.validateGLObjectID(GL.samplers, sampler, 'glSamplerParameteri', 'sampler');
+            gl.samplerParameteri(GL.samplers[sampler], pname, param);
+        },
+        glSamplerParameterf: function (sampler, pname, param) {
+            GL.validateGLObjectID(GL.samplers, sampler, 'glSamplerParameterf', 'sampler');
+            gl.samplerParameterf(GL.samplers[sampler], pname, param);
+        },
         glUniform1fv: function (location, count, value) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniform1fv', 'location');
             assert((value & 3) == 0, 'Pointer to float data passed to glUniform1fv must be aligned to four bytes!');
@@ -761,6 +861,9 @@ var importObject = {
         glGetIntegerv: function (name_, p) {
             _webglGet(name_, p, 'EM_FUNC_SIG_PARAM_I');
         },
+        glGetFloatv: function (name_, p) {
+            _webglGet(name_, p, 'EM_FUNC_SIG_PARAM_F');
+        },
         glUniform1f: function (location, v0) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniform1f', 'location');
             gl.uniform1f(GL.uniforms[location], v0);
@@ -802,6 +905,45 @@ var importObject = {
                 return -1;
             }
         },
+        glGetActiveAttrib: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveAttrib', 'program');
+            var info = gl.getActiveAttrib(GL.programs[program], index);
+            _glWriteActiveInfo(info, bufSize, length, size, type, name);
+        },
+        glGetActiveUniform: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniform', 'program');
+            var info = gl.getActiveUniform(GL.programs[program], index);
+            _glWriteActiveInfo(info, bufSize, length, size, type, name);
+        },
+        glGetActiveUniformsiv: function (program, count, indices, pname, params) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniformsiv', 'program');
+            var values = gl.getActiveUniforms(GL.programs[program], getArray(indices, Uint32Array, count), pname);
+            var array = getArray(params, Int32Array, count);
+            for (var i = 0; i < count; i++) {
+                array[i] = values[i];
+            }
+        },
+        glGetActiveUniformBlockName: function (program, index, bufSize, length, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniformBlockName', 'program');
+            var blockName = gl.getActiveUniformBlockName(GL.programs[program], index);
+            var written = stringToUTF8(blockName, getArray(name, Uint8Array, bufSize), 0, bufSize - 1);
+            getArray(name + written, Uint8Array, 1)[0] = 0;
+            if (length != 0) {
+                getArray(length, Int32Array, 1)[0] = written;
+            }
+        },
+        glGetUniformBlockIndex: function (program, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetUniformBlockIndex', 'program');
+            return gl.getUniformBlockIndex(GL.programs[program], UTF8ToString(name));
+        },
+        glUniformBlockBinding: function (program, index, binding) {
+            GL.validateGLObjectID(GL.programs, program, 'glUniformBlockBinding', 'program');
+            gl.uniformBlockBinding(GL.programs[program], index, binding);
+        },
+        glBindBufferRange: function (target, index, buffer, offset, size) {
+            GL.validateGLObjectID(GL.buffers, buffer, 'glBindBufferRange', 'buffer');
+            gl.bindBufferRange(target, index, GL.buffers[buffer], offset, size);
+        },
         glUniformMatrix4fv: function (location, count, transpose, value) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix4fv', 'location');
             assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix4fv must be aligned to four bytes!');
@@ -840,6 +982,9 @@ var importObject = {
         glBindBuffer: function (target, buffer) {
             GL.validateGLObjectID(GL.buffers, buffer, 'glBindBuffer', 'buffer');
             gl.bindBuffer(target, GL.buffers[buffer]);
+            if (target == 0x88EB /* GL_PIXEL_PACK_BUFFER */) {
+                GL.packBufferBound = buffer != 0;
+            }
         },
         glBufferData: function (target, size, data, usage) {
             gl.bufferData(target, data ? getArray(data, Uint8Array, size) : size, usage);
@@ -847,6 +992,9 @@ var importObject = {
         glBufferSubData: function (target, offset, size, data) {
             gl.bufferSubData(target, offset, data ? getArray(data, Uint8Array, size) : size);
         },
+        glGetBufferSubData: function (target, offset, size, data) {
+            gl.getBufferSubData(target, offset, getArray(data, Uint8Array, size));
+        },
         glEnable: function (cap) {
             gl.enable(cap);
         },
@@ -900,6 +1048,10 @@ var importObject = {
             GL.validateGLObjectID(GL.textures, texture, 'glFramebufferTexture2D', 'texture');
             gl.framebufferTexture2D(target, attachment, textarget, GL.textures[texture], level);
         },
+        glFramebufferTextureLayer: function (target, attachment, texture, level, layer) {
+            GL.validateGLObjectID(GL.textures, texture, 'glFramebufferTextureLayer', 'texture');
+            gl.framebufferTextureLayer(target, attachment, GL.textures[texture], level, layer);
+        },
         glGetProgramiv: function (program, pname, p) {
             assert(p);
             GL.validateGLObjectID(GL.programs, program, 'glGetProgramiv', 'program');
@@ -935,9 +1087,27 @@ var importObject = {
             GL.shaders[id] = gl.createShader(shaderType);
             return id;
         },
+        glEnablei: function (target, index) {
+            gl.enablei(target, index);
+        },
+        glDisablei: function (target, index) {
+            gl.disablei(target, index);
+        },
+        glBlendEquationSeparatei: function (buf, modeRGB, modeAlpha) {
+            gl.blendEquationSeparatei(buf, modeRGB, modeAlpha);
+        },
+        glBlendFuncSeparatei: function (buf, srcRGB, dstRGB, srcAlpha, dstAlpha) {
+            gl.blendFuncSeparatei(buf, srcRGB, dstRGB, srcAlpha, dstAlpha);
+        },
+        glColorMaski: function (buf, r, g, b, a) {
+            gl.colorMaski(buf, r, g, b, a);
+        },
         glStencilFuncSeparate: function (face, func, ref_, mask) {
             gl.stencilFuncSeparate(face, func, ref_, mask);
         },
+        glStencilMask: function (mask) {
+            gl.stencilMask(mask);
+        },
         glStencilMaskSeparate: function (face, mask) {
             gl.stencilMaskSeparate(face, mask);
         },
@@ -1002,7 +1172,9 @@ var importObject = {
         glGetString: function (id) {
             // getParameter returns "any": it could be GLenum, String or whatever,
             // depending on the id.
-            var parameter = gl.getParameter(id).toString();
+            var parameter = id == 0x1F03 /* GL_EXTENSIONS */
+                ? gl.getSupportedExtensions().join(" ")
+                : gl.getParameter(id).toString();
             var len = parameter.length + 1;
             var msg = wasm_exports.allocate_vec_u8(len);
             var array = new Uint8Array(wasm_memory.buffer, msg, len);
@@ -1118,7 +1290,7 @@ var importObject = {
         },
         glDeleteQueries: function (n, ids) {
             for (var i = 0; i < n; i++) {
-                var id = getArray(textures + i * 4, Uint32Array, 1)[0];
+                var id = getArray(ids + i * 4, Uint32Array, 1)[0];
                 var query = GL.timerQueries[id];
                 if (!query) {
                     continue;
@@ -1140,6 +1312,32 @@ var importObject = {
             let result = gl.getQueryObject(GL.timerQueries[id], pname);
             getArray(ptr, Uint32Array, 1)[0] = result;
         },
+        glGetQueryObjectuiv: function (id, pname, ptr) {
+            GL.validateGLObjectID(GL.timerQueries, id, 'glGetQueryObjectuiv', 'id');
+            let result = gl.getQueryObject(GL.timerQueries[id], pname);
+            getArray(ptr, Uint32Array, 1)[0] = result;
+        },
+        glQueryCounter: function (id, target) {
+            GL.validateGLObjectID(GL.timerQueries, id, 'glQueryCounter', 'id');
+            gl.queryCounter(GL.timerQueries[id], target);
+        },
+        glFenceSync: function (condition, flags) {
+            var id = GL.getNewId(GL.syncs);
+            GL.syncs[id] = gl.fenceSync(condition, flags);
+            return id;
+        },
+        glDeleteSync: function (id) {
+            GL.validateGLObjectID(GL.syncs, id, 'glDeleteSync', 'id');
+            gl.deleteSync(GL.syncs[id]);
+            GL.syncs[id] = null;
+        },
+        glGetSynciv: function (id, pname, bufSize, length, values) {
+            GL.validateGLObjectID(GL.syncs, id, 'glGetSynciv', 'id');
+            getArray(values, Int32Array, 1)[0] = gl.getSyncParameter(GL.syncs[id], pname);
+            if (length != 0) {
+                getArray(length, Int32Array, 1)[0] = 1;
+            }
+        },
         glGetQueryObjectui64v: function (id, pname, ptr) {
             GL.validateGLObjectID(GL.timerQueries, id, 'glGetQueryObjectui64v', 'id');
             let result = gl.getQueryObject(GL.timerQueries[id], pname);
@@ -1160,6 +1358,9 @@ var importObject = {
         glCheckFramebufferStatus: function(target) {
             return gl.checkFramebufferStatus(target);
         },
+        glGetError: function() {
+            return gl.getError();
+        },
         glReadBuffer: function(source) {
             gl.readBuffer(source)
         },
@@ -70,6 +70,11 @@ pub enum LinuxBackend {
     X11WithWaylandFallback,
     /// Prefer Wayland, fall back to X11 if Wayland is unavailable.
     WaylandWithX11Fallback,
+    /// Do not open a window at all: render into an EGL pbuffer (or a surfaceless
+    /// context), no display server required. Input events are never delivered
+    /// and `blocking_event_loop` is ignored, as nothing would wake the loop up.
+    /// Useful for offscreen rendering and tests, see [`Platform::headless_frames`].
+    Headless,
 }
 
 /// On Apple platforms, choose the rendering API for creating contexts.
@@ -181,6 +186,12 @@ pub struct Platform {
     /// Whether to automatically setup the panic hook for Android.
     /// Set this to false if your app does its own panic_hook setup to avoid conflicts.
     pub android_panic_hook: bool,
+
+    /// With [`LinuxBackend::Headless`], quit after drawing this many frames.
+    /// `None` runs until [`order_quit`] is called.
+    ///
+    /// [`order_quit`]: super::window::order_quit
+    pub headless_frames: Option<u32>,
 }
 
 impl Default for Platform {
@@ -197,6 +208,7 @@ impl Default for Platform {
             wayland_decorations: WaylandDecorations::default(),
             linux_wm_class: "miniquad-application",
             android_panic_hook: true,
+            headless_frames: None,
         }
     }
 }
@@ -239,6 +251,15 @@ pub struct Conf {
     ///   an external `.desktop` file
     pub icon: Option<Icon>,
 
+    /// Directory for the on-disk cache of linked OpenGL program binaries.
+    /// `None` by default, the cache is opt-in.
+    ///
+    /// Binaries are keyed by the shader sources and the driver's version, renderer and vendor
+    /// strings, so switching GPUs or drivers does not reuse them. They are used only with
+    /// `GL_ARB_get_program_binary` or GLES3. A binary rejected by the driver is removed and
+    /// the program is compiled from source. Ignored on WASM and Metal.
+    pub program_cache_dir: Option<std::path::PathBuf>,
+
     /// Platform-specific hints (e.g., context creation, driver settings).
     pub platform: Platform,
 }
@@ -284,6 +305,7 @@ impl Default for Conf {
             sample_count: 1,
             window_resizable: true,
             icon: Some(Icon::miniquad_logo()),
+            program_cache_dir: None,
             platform: Default::default(),
         }
     }
@@ -301,6 +323,7 @@ impl Default for Conf {
             sample_count: 1,
             window_resizable: false, //
             icon: Some(Icon::miniquad_logo()),
+            program_cache_dir: None,
             platform: Default::default(),
         }
     }
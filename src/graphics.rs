@@ -1,14 +1,23 @@
 //mod texture;
 
-use crate::native::gl::*;
+use crate::{native::gl::*, ResourceId};
 
-use std::{error::Error, fmt::Display};
+use std::{convert::TryFrom, error::Error, fmt::Display};
 
 //pub use texture::{FilterMode, TextureAccess, TextureFormat, TextureParams, TextureWrap};
 
+mod capture;
 mod gl;
+mod null;
+mod software;
+pub mod texture_container;
+mod validation;
 
+pub use capture::{CaptureError, RecordingBackend, Replayer};
 pub use gl::raw_gl;
+pub use null::{NullContext, ResourceCounts};
+pub use software::{FragmentInput, NativeShader, SoftwareContext, VertexInput};
+pub use validation::ValidatingBackend;
 
 #[cfg(target_vendor = "apple")]
 mod metal;
@@ -18,7 +27,7 @@ pub use gl::GlContext;
 #[cfg(target_vendor = "apple")]
 pub use metal::MetalContext;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UniformType {
     /// One 32-bit wide float (equivalent to `f32`)
     Float1,
@@ -55,6 +64,15 @@ impl UniformType {
             UniformType::Mat4 => 64,
         }
     }
+
+    /// Base alignment in bytes according to std140 rules
+    pub fn std140_align(&self) -> usize {
+        match self {
+            UniformType::Float1 | UniformType::Int1 => 4,
+            UniformType::Float2 | UniformType::Int2 => 8,
+            _ => 16,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -64,11 +82,73 @@ pub struct UniformDesc {
     pub array_count: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UniformBlockLayout {
     pub uniforms: Vec<UniformDesc>,
 }
 
+impl UniformBlockLayout {
+    /// Byte offset of each uniform inside a std140 block.
+    pub fn std140_offsets(&self) -> Vec<usize> {
+        self.std140().0
+    }
+
+    /// Byte size of a std140 block with this layout.
+    pub fn std140_size(&self) -> usize {
+        self.std140().1
+    }
+
+    fn std140(&self) -> (Vec<usize>, usize) {
+        let round_up = |offset: usize, align: usize| (offset + align - 1) / align * align;
+
+        let mut offset = 0;
+        let offsets = self
+            .uniforms
+            .iter()
+            .map(|uniform| {
+                let (align, size) = if uniform.array_count > 1 {
+                    // array elements are always padded to vec4
+                    let stride = round_up(uniform.uniform_type.size(), 16);
+                    (16, stride * uniform.array_count)
+                } else {
+                    (
+                        uniform.uniform_type.std140_align(),
+                        uniform.uniform_type.size(),
+                    )
+                };
+                offset = round_up(offset, align);
+                let uniform_offset = offset;
+                offset += size;
+                uniform_offset
+            })
+            .collect();
+        (offsets, round_up(offset, 16))
+    }
+}
+
+/// Named uniform block, `layout(std140) uniform Name { ... };` in glsl.
+///
+/// Blocks are updated independently with `apply_uniform_block`, the data
+/// is expected to follow std140 layout rules, `UniformBlockLayout::std140_offsets`
+/// may help to build a matching `#[repr(C)]` struct.
+///
+/// On GL2 and WebGL1 there are no uniform blocks, the shader should declare
+/// each field as a plain uniform with the same name.
+#[derive(Debug, Clone)]
+pub struct UniformBlockDesc {
+    pub name: String,
+    pub layout: UniformBlockLayout,
+}
+
+impl UniformBlockDesc {
+    pub fn new(name: &str, uniforms: Vec<UniformDesc>) -> UniformBlockDesc {
+        UniformBlockDesc {
+            name: name.to_string(),
+            layout: UniformBlockLayout { uniforms },
+        }
+    }
+}
+
 impl UniformDesc {
     pub fn new(name: &str, uniform_type: UniformType) -> UniformDesc {
         UniformDesc {
@@ -86,12 +166,220 @@ impl UniformDesc {
     }
 }
 
-#[derive(Clone)]
+/// Everything the shader takes from outside besides vertex attributes.
+///
+/// Fields a shader does not need could be left out with `..Default::default()`:
+/// ```ignore
+/// ShaderMeta {
+///     images: vec!["tex".to_string()],
+///     uniforms: UniformBlockLayout {
+///         uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
+///     },
+///     ..Default::default()
+/// }
+/// ```
+#[derive(Clone, Default)]
 pub struct ShaderMeta {
     pub uniforms: UniformBlockLayout,
+    /// Up to `MAX_UNIFORM_BLOCKS` std140 blocks, in addition to `uniforms`.
+    pub uniform_blocks: Vec<UniformBlockDesc>,
+    /// Sampler uniform names, in `Bindings::images` order.
+    /// Sampler type should match the bound texture kind:
+    /// `sampler2D`, `samplerCube`, `sampler2DArray` or `sampler3D`.
     pub images: Vec<String>,
 }
 
+impl ShaderMeta {
+    /// `ShaderMeta` with everything the shader actually uses.
+    ///
+    /// Plain uniforms are in the order the driver reports them, usually but not
+    /// necessarily the declaration order, and `apply_uniforms` expects exactly this order.
+    pub fn reflect(reflection: &ShaderReflection) -> ShaderMeta {
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: reflection.uniforms.clone(),
+            },
+            uniform_blocks: reflection
+                .uniform_blocks
+                .iter()
+                .map(|block| block.block.clone())
+                .collect(),
+            images: reflection.images.clone(),
+        }
+    }
+
+    /// Compare with what the shader actually uses,
+    /// `ShaderError::LayoutMismatch` lists all the differences.
+    ///
+    /// Uniforms, blocks and images absent from the reflection are returned as warnings
+    /// instead: the driver optimizes out everything unused, so there is no telling an unused
+    /// name from a misspelled one.
+    pub fn validate(&self, reflection: &ShaderReflection) -> Result<Vec<String>, ShaderError> {
+        fn check_uniform(
+            differences: &mut Vec<String>,
+            warnings: &mut Vec<String>,
+            uniform: &UniformDesc,
+            reflected: &[UniformDesc],
+        ) {
+            match reflected.iter().find(|r| r.name == uniform.name) {
+                None => warnings.push(format!(
+                    "uniform `{}` is not used by the shader",
+                    uniform.name
+                )),
+                Some(r) if r.uniform_type != uniform.uniform_type => differences.push(format!(
+                    "uniform `{}` is {:?} in ShaderMeta, {:?} in the shader",
+                    uniform.name, uniform.uniform_type, r.uniform_type
+                )),
+                // drivers may trim unused array elements
+                Some(r) if r.array_count > uniform.array_count => differences.push(format!(
+                    "uniform `{}` has {} elements in ShaderMeta, {} in the shader",
+                    uniform.name, uniform.array_count, r.array_count
+                )),
+                Some(_) => {}
+            }
+        }
+
+        let mut differences = vec![];
+        let mut warnings = vec![];
+
+        for uniform in &self.uniforms.uniforms {
+            check_uniform(
+                &mut differences,
+                &mut warnings,
+                uniform,
+                &reflection.uniforms,
+            );
+        }
+        for block in &self.uniform_blocks {
+            if reflection.uniform_blocks.is_empty() {
+                // no uniform buffers, the members are plain uniforms
+                for uniform in &block.layout.uniforms {
+                    check_uniform(
+                        &mut differences,
+                        &mut warnings,
+                        uniform,
+                        &reflection.uniforms,
+                    );
+                }
+                continue;
+            }
+            let Some(reflected) = reflection
+                .uniform_blocks
+                .iter()
+                .find(|r| r.block.name == block.name)
+            else {
+                warnings.push(format!(
+                    "uniform block `{}` is not used by the shader",
+                    block.name
+                ));
+                continue;
+            };
+            let offsets = block.layout.std140_offsets();
+            for (i, uniform) in block.layout.uniforms.iter().enumerate() {
+                let position = reflected
+                    .block
+                    .layout
+                    .uniforms
+                    .iter()
+                    .position(|r| r.name == uniform.name);
+                match position {
+                    Some(j) if reflected.offsets[j] != offsets[i] => differences.push(format!(
+                        "uniform block `{}`: `{}` is at offset {} in ShaderMeta, {} in the shader",
+                        block.name, uniform.name, offsets[i], reflected.offsets[j]
+                    )),
+                    _ => check_uniform(
+                        &mut differences,
+                        &mut warnings,
+                        uniform,
+                        &reflected.block.layout.uniforms,
+                    ),
+                }
+            }
+            for uniform in &reflected.block.layout.uniforms {
+                if !block.layout.uniforms.iter().any(|u| u.name == uniform.name) {
+                    differences.push(format!(
+                        "uniform block `{}`: `{}` is missing in ShaderMeta",
+                        block.name, uniform.name
+                    ));
+                }
+            }
+        }
+
+        let block_uniforms = self
+            .uniform_blocks
+            .iter()
+            .flat_map(|block| &block.layout.uniforms);
+        let known: Vec<&str> = self
+            .uniforms
+            .uniforms
+            .iter()
+            .chain(block_uniforms)
+            .map(|uniform| uniform.name.as_str())
+            .collect();
+        for uniform in &reflection.uniforms {
+            if !known.contains(&uniform.name.as_str()) {
+                differences.push(format!(
+                    "uniform `{}` is missing in ShaderMeta",
+                    uniform.name
+                ));
+            }
+        }
+        for block in &reflection.uniform_blocks {
+            if !self
+                .uniform_blocks
+                .iter()
+                .any(|b| b.name == block.block.name)
+            {
+                differences.push(format!(
+                    "uniform block `{}` is missing in ShaderMeta",
+                    block.block.name
+                ));
+            }
+        }
+
+        for image in &self.images {
+            if !reflection.images.contains(image) {
+                warnings.push(format!("image `{}` is not used by the shader", image));
+            }
+        }
+        for image in &reflection.images {
+            if !self.images.contains(image) {
+                differences.push(format!("image `{}` is missing in ShaderMeta", image));
+            }
+        }
+
+        if differences.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(ShaderError::LayoutMismatch(differences))
+        }
+    }
+}
+
+/// Active vertex attributes, uniforms and samplers of a linked shader,
+/// as reported by the driver. See `RenderingBackend::shader_reflection`.
+///
+/// Uniforms of types without a matching `UniformType` (bool vectors are
+/// reported as `Int`, but mat2 or mat3 are not) are left out.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub attributes: Vec<String>,
+    /// Uniforms outside of uniform blocks.
+    pub uniforms: Vec<UniformDesc>,
+    /// Empty without `features.uniform_buffers`, block members are plain `uniforms` then.
+    pub uniform_blocks: Vec<UniformBlockReflection>,
+    /// Sampler uniforms.
+    pub images: Vec<String>,
+}
+
+/// Uniform block of `ShaderReflection`, with members sorted by offset.
+#[derive(Clone, Debug)]
+pub struct UniformBlockReflection {
+    pub block: UniformBlockDesc,
+    /// Byte offset of each member, as laid out by the driver.
+    pub offsets: Vec<usize>,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum VertexFormat {
     /// One 32-bit wide float (equivalent to `f32`)
@@ -271,6 +559,7 @@ pub struct PipelineLayout {
 pub enum ShaderType {
     Vertex,
     Fragment,
+    Compute,
 }
 
 impl Display for ShaderType {
@@ -278,6 +567,7 @@ impl Display for ShaderType {
         match self {
             Self::Vertex => write!(f, "Vertex"),
             Self::Fragment => write!(f, "Fragment"),
+            Self::Compute => write!(f, "Compute"),
         }
     }
 }
@@ -291,6 +581,9 @@ pub enum ShaderError {
     LinkError(String),
     /// Shader strings should never contains \00 in the middle
     FFINulError(std::ffi::NulError),
+    /// `ShaderMeta` does not match the shader, one line per difference.
+    /// See `ShaderMeta::validate`.
+    LayoutMismatch(Vec<String>),
 }
 
 impl From<std::ffi::NulError> for ShaderError {
@@ -308,14 +601,80 @@ impl Display for ShaderError {
             } => write!(f, "{shader_type} shader error:\n{error_message}"),
             Self::LinkError(msg) => write!(f, "Link shader error:\n{msg}"),
             Self::FFINulError(e) => write!(f, "{e}"),
+            Self::LayoutMismatch(differences) => {
+                write!(f, "ShaderMeta does not match the shader:")?;
+                for difference in differences {
+                    write!(f, "\n{difference}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl Error for ShaderError {}
 
+/// Error from the `try_new_*` and `try_delete_*` family of `RenderingBackend` functions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphicsError {
+    /// The driver could not allocate the resource, GL_OUT_OF_MEMORY on GL.
+    OutOfMemory,
+    /// Texture width or height is above `ContextInfo::max_texture_size`.
+    TextureTooLarge {
+        width: u32,
+        height: u32,
+        max_size: u32,
+    },
+    /// The format could not be used the requested way,
+    /// see `ContextInfo::texture_format_caps`.
+    UnsupportedFormat(TextureFormat),
+    /// The resource requires a feature missing on the current backend,
+    /// the name of the `Features` field.
+    UnsupportedFeature(&'static str),
+    /// Render pass attachments do not form a complete framebuffer.
+    IncompleteFramebuffer(String),
+    /// The id was already deleted or does not belong to this context.
+    InvalidId(String),
+    /// The parameters or the data do not describe a valid resource on any backend,
+    /// like a multisampled `TextureAccess::Static` texture.
+    InvalidParams(String),
+}
+
+impl Display for GraphicsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfMemory => write!(f, "Out of GPU memory"),
+            Self::TextureTooLarge {
+                width,
+                height,
+                max_size,
+            } => write!(
+                f,
+                "{width}x{height} texture is larger than max_texture_size {max_size}"
+            ),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "{format:?} is not supported for this use")
+            }
+            Self::UnsupportedFeature(feature) => {
+                write!(f, "features.{feature} is not available")
+            }
+            Self::IncompleteFramebuffer(reason) => write!(f, "Incomplete framebuffer: {reason}"),
+            Self::InvalidId(id) => {
+                write!(f, "{id} was deleted or does not belong to this context")
+            }
+            Self::InvalidParams(reason) => write!(f, "Invalid parameters: {reason}"),
+        }
+    }
+}
+
+impl Error for GraphicsError {}
+
 /// List of all the possible formats of input data when uploading to texture.
-/// The list is built by intersection of texture formats supported by 3.3 core profile and webgl1.
+/// The first six are the intersection of texture formats supported by 3.3 core profile and webgl1,
+/// `R8` to `Depth24Stencil8` require `features.sized_texture_formats` and
+/// the block compressed formats require their `features.texture_compression_*`.
+///
+/// Use `ContextInfo::texture_format_caps` to check what a format could be used for.
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum TextureFormat {
@@ -325,22 +684,137 @@ pub enum TextureFormat {
     Depth,
     Depth32,
     Alpha,
+    R8,
+    RG8,
+    /// Half floats, 2 bytes per pixel.
+    R16F,
+    /// Half floats, 4 bytes per pixel.
+    RG16F,
+    R32F,
+    RGBA32F,
+    /// Unsigned integer texture, `usampler2D` in GLSL. Could not be filtered.
+    R32UI,
+    /// RGBA8 data in sRGB color space, converted to linear when sampled.
+    SRGB8A8,
+    /// Packed into a single u32, 10 bits per color channel and 2 bits of alpha.
+    RGB10A2,
+    /// Packed into a single u32, 24 bits of depth and 8 bits of stencil.
+    /// As a render pass `depth_img` provides both depth and stencil buffers.
+    /// Metal stores it as 32 bit float depth and 8 bits of stencil.
+    Depth24Stencil8,
+    /// S3TC DXT1, RGBA in 8 bytes per 4x4 block. Requires `features.texture_compression_s3tc`.
+    BC1,
+    /// S3TC DXT3, RGBA in 16 bytes per 4x4 block. Requires `features.texture_compression_s3tc`.
+    BC2,
+    /// S3TC DXT5, RGBA in 16 bytes per 4x4 block. Requires `features.texture_compression_s3tc`.
+    BC3,
+    /// RGTC1, R in 8 bytes per 4x4 block. Requires `features.texture_compression_rgtc`.
+    BC4,
+    /// RGTC2, RG in 16 bytes per 4x4 block. Requires `features.texture_compression_rgtc`.
+    BC5,
+    /// BPTC unsigned float RGB in 16 bytes per 4x4 block.
+    /// Requires `features.texture_compression_bptc`.
+    BC6H,
+    /// BPTC RGBA in 16 bytes per 4x4 block. Requires `features.texture_compression_bptc`.
+    BC7,
+    /// RGB in 8 bytes per 4x4 block. Requires `features.texture_compression_etc2`.
+    ETC2RGB8,
+    /// RGBA in 16 bytes per 4x4 block. Requires `features.texture_compression_etc2`.
+    ETC2RGBA8,
+    /// R in 8 bytes per 4x4 block. Requires `features.texture_compression_etc2`.
+    EACR11,
+    /// RG in 16 bytes per 4x4 block. Requires `features.texture_compression_etc2`.
+    EACRG11,
+    /// ASTC LDR, 16 bytes per block. Requires `features.texture_compression_astc`.
+    ASTC4x4,
+    ASTC5x5,
+    ASTC6x6,
+    ASTC8x8,
+    ASTC10x10,
+    ASTC12x12,
 }
 impl TextureFormat {
     /// Returns the size in bytes of texture with `dimensions`.
+    ///
+    /// For compressed formats the dimensions are rounded up to whole blocks.
+    /// Panics if the size does not fit in `u32`, see `checked_size`.
     pub fn size(self, width: u32, height: u32) -> u32 {
-        let square = width * height;
+        self.checked_size(width, height)
+            .and_then(|size| u32::try_from(size).ok())
+            .unwrap_or_else(|| panic!("{}x{} {:?} texture is too large", width, height, self))
+    }
+
+    /// Same as `size`, but in `u64` and `None` on overflow.
+    pub fn checked_size(self, width: u32, height: u32) -> Option<u64> {
+        let (width, height) = (width as u64, height as u64);
+        if let Some((block_width, block_height, block_size)) = self.block() {
+            let (block_width, block_height) = (block_width as u64, block_height as u64);
+            let blocks_x = (width + block_width - 1) / block_width;
+            let blocks_y = (height + block_height - 1) / block_height;
+            return (blocks_x * blocks_y).checked_mul(block_size as u64);
+        }
+        let pixel_size = match self {
+            TextureFormat::RGB8 => 3,
+            TextureFormat::RGBA8 => 4,
+            TextureFormat::RGBA16F => 8,
+            TextureFormat::Depth => 2,
+            TextureFormat::Depth32 => 4,
+            TextureFormat::Alpha => 1,
+            TextureFormat::R8 => 1,
+            TextureFormat::RG8 => 2,
+            TextureFormat::R16F => 2,
+            TextureFormat::RG16F => 4,
+            TextureFormat::R32F => 4,
+            TextureFormat::RGBA32F => 16,
+            TextureFormat::R32UI => 4,
+            TextureFormat::SRGB8A8 => 4,
+            TextureFormat::RGB10A2 => 4,
+            TextureFormat::Depth24Stencil8 => 4,
+            _ => unreachable!(),
+        };
+        (width * height).checked_mul(pixel_size)
+    }
+
+    /// Block compressed formats could only be uploaded, they could not be
+    /// rendered to or read back.
+    pub fn is_compressed(self) -> bool {
+        self.block().is_some()
+    }
+
+    /// Formats with a stencil component. Used as a render pass `depth_img`
+    /// they are attached as both depth and stencil buffers.
+    pub fn has_stencil(self) -> bool {
+        self == TextureFormat::Depth24Stencil8
+    }
+
+    /// (block width, block height, bytes per block) of compressed formats.
+    fn block(self) -> Option<(u32, u32, u32)> {
+        use TextureFormat::*;
+
         match self {
-            TextureFormat::RGB8 => 3 * square,
-            TextureFormat::RGBA8 => 4 * square,
-            TextureFormat::RGBA16F => 8 * square,
-            TextureFormat::Depth => 2 * square,
-            TextureFormat::Depth32 => 4 * square,
-            TextureFormat::Alpha => 1 * square,
+            BC1 | BC4 | ETC2RGB8 | EACR11 => Some((4, 4, 8)),
+            BC2 | BC3 | BC5 | BC6H | BC7 | ETC2RGBA8 | EACRG11 | ASTC4x4 => Some((4, 4, 16)),
+            ASTC5x5 => Some((5, 5, 16)),
+            ASTC6x6 => Some((6, 6, 16)),
+            ASTC8x8 => Some((8, 8, 16)),
+            ASTC10x10 => Some((10, 10, 16)),
+            ASTC12x12 => Some((12, 12, 16)),
+            _ => None,
         }
     }
 }
 
+/// What a `TextureFormat` could be used for on the current backend.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct TextureFormatCaps {
+    /// Could be created and sampled with `FilterMode::Nearest`.
+    pub sample: bool,
+    /// Could be sampled with `FilterMode::Linear`.
+    pub filter: bool,
+    /// Could be used as a render pass attachment.
+    pub render: bool,
+}
+
 /// Sets the wrap parameter for texture.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TextureWrap {
@@ -350,6 +824,13 @@ pub enum TextureWrap {
     Mirror,
     /// Samples at coord x + 1 map to coord 1.
     Clamp,
+    /// Samples outside of [0, 1] return the border color, `SamplerParams::border_color`
+    /// or transparent black for the texture's own wrap.
+    /// Requires `features.clamp_to_border`.
+    ClampToBorder,
+    /// Samples at coord -x map to coord x, and the result is clamped as with `Clamp`.
+    /// Requires `features.mirror_clamp_to_edge`.
+    MirrorClampToEdge,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Hash)]
@@ -371,12 +852,22 @@ pub enum TextureAccess {
     Static,
     /// Can be written to from GPU
     RenderTarget,
+    /// Can be written to from compute shaders with image load/store,
+    /// and used as a render target.
+    /// Requires `features.compute`. Storage is immutable, could not be resized.
+    Storage,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextureKind {
     Texture2D,
     CubeMap,
+    /// `TextureParams::depth` layers of 2D images, `sampler2DArray` in GLSL.
+    /// Requires `features.texture_3d`.
+    Texture2DArray,
+    /// `TextureParams::depth` slices deep volume, `sampler3D` in GLSL.
+    /// Requires `features.texture_3d`.
+    Texture3D,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -389,6 +880,9 @@ pub struct TextureParams {
     pub mipmap_filter: MipmapFilterMode,
     pub width: u32,
     pub height: u32,
+    /// Number of layers of a `Texture2DArray` or depth of a `Texture3D`.
+    /// Should be 1 for other texture kinds.
+    pub depth: u32,
     // All miniquad API could work without this flag being explicit.
     // We can decide if mipmaps are required by the data provided
     // And reallocate non-mipmapped texture(on metal) on generateMipmaps call
@@ -413,19 +907,118 @@ impl Default for TextureParams {
             mipmap_filter: MipmapFilterMode::None,
             width: 0,
             height: 0,
+            depth: 1,
             allocate_mipmaps: false,
             sample_count: 1,
         }
     }
 }
 
+impl TextureParams {
+    /// Number of mip levels of the texture: the full chain down to 1x1
+    /// with `allocate_mipmaps`, 1 otherwise.
+    pub fn mip_levels(&self) -> u32 {
+        if !self.allocate_mipmaps {
+            return 1;
+        }
+        let size = match self.kind {
+            TextureKind::Texture3D => self.width.max(self.height).max(self.depth),
+            _ => self.width.max(self.height),
+        };
+        32 - size.max(1).leading_zeros()
+    }
+
+    /// Width and height of a mip level.
+    pub fn mip_size(&self, mip_level: u32) -> (u32, u32) {
+        let size = |size: u32| size.checked_shr(mip_level).unwrap_or(0).max(1);
+        (size(self.width), size(self.height))
+    }
+
+    /// Number of layers a mip level has: 6 cubemap faces, `depth` layers of a `Texture2DArray`
+    /// or slices of a `Texture3D`, halved on each level, and 1 for a `Texture2D`.
+    pub fn mip_layers(&self, mip_level: u32) -> u32 {
+        match self.kind {
+            TextureKind::Texture2D => 1,
+            TextureKind::CubeMap => 6,
+            TextureKind::Texture2DArray => self.depth,
+            TextureKind::Texture3D => self.depth.checked_shr(mip_level).unwrap_or(0).max(1),
+        }
+    }
+}
+
+/// Sampling state independent of the texture, see `RenderingBackend::new_sampler`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplerParams {
+    pub min_filter: FilterMode,
+    pub mag_filter: FilterMode,
+    pub mipmap_filter: MipmapFilterMode,
+    pub wrap_x: TextureWrap,
+    pub wrap_y: TextureWrap,
+    /// Only used by `Texture3D`.
+    pub wrap_z: TextureWrap,
+    /// RGBA returned outside of the texture with `TextureWrap::ClampToBorder`.
+    /// Metal only has transparent black, opaque black and opaque white,
+    /// the closest one is used.
+    pub border_color: [f32; 4],
+    /// 1.0 disables anisotropic filtering.
+    /// Clamped to `ContextInfo::max_anisotropy`.
+    pub max_anisotropy: f32,
+    /// Added to the mip level computed by the GPU.
+    /// Desktop GL only, ignored on GLES, WebGL and Metal.
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// Compare the texel with the reference value instead of returning it, for shadow maps.
+    /// The texture should have a depth format and the shader should use `sampler2DShadow`.
+    /// Requires `features.sampler_compare`.
+    pub compare: Option<Comparison>,
+}
+
+impl Default for SamplerParams {
+    fn default() -> Self {
+        SamplerParams {
+            min_filter: FilterMode::Linear,
+            mag_filter: FilterMode::Linear,
+            mipmap_filter: MipmapFilterMode::None,
+            wrap_x: TextureWrap::Clamp,
+            wrap_y: TextureWrap::Clamp,
+            wrap_z: TextureWrap::Clamp,
+            border_color: [0.; 4],
+            max_anisotropy: 1.,
+            lod_bias: 0.,
+            // GL defaults
+            min_lod: -1000.,
+            max_lod: 1000.,
+            compare: None,
+        }
+    }
+}
+
+impl SamplerParams {
+    /// The sampling state a texture gets from its `TextureParams`.
+    pub(crate) fn from_texture_params(params: &TextureParams) -> SamplerParams {
+        SamplerParams {
+            min_filter: params.min_filter,
+            mag_filter: params.mag_filter,
+            mipmap_filter: params.mipmap_filter,
+            wrap_x: params.wrap,
+            wrap_y: params.wrap,
+            wrap_z: params.wrap,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerId(ResourceId);
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
-pub struct ShaderId(usize);
+pub struct ShaderId(ResourceId);
 
 // Inner hence we can't have private data in enum fields
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum TextureIdInner {
-    Managed(usize),
+    Managed(ResourceId),
     Raw(RawId),
 }
 
@@ -474,6 +1067,25 @@ impl BlendState {
     }
 }
 
+/// Blending and write mask of a single color attachment,
+/// see `PipelineParams::attachment_blend`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AttachmentBlend {
+    pub color_blend: Option<BlendState>,
+    pub alpha_blend: Option<BlendState>,
+    pub color_write: ColorMask,
+}
+
+impl Default for AttachmentBlend {
+    fn default() -> AttachmentBlend {
+        AttachmentBlend {
+            color_blend: None,
+            alpha_blend: None,
+            color_write: (true, true, true, true),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct StencilState {
     pub front: StencilFaceState,
@@ -566,11 +1178,33 @@ impl Default for PassAction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct RenderPass(usize);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderPass(ResourceId);
+
+/// A single mip level and layer of a texture, see `RenderingBackend::new_render_pass_targets`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderTarget {
+    pub texture: TextureId,
+    pub mip_level: u32,
+    /// Cubemap face, layer of a `Texture2DArray` or slice of a `Texture3D`.
+    /// Should be 0 for a `Texture2D`.
+    pub layer: u32,
+}
+
+impl From<TextureId> for RenderTarget {
+    fn from(texture: TextureId) -> RenderTarget {
+        RenderTarget {
+            texture,
+            mip_level: 0,
+            layer: 0,
+        }
+    }
+}
 
 pub const MAX_VERTEX_ATTRIBUTES: usize = 16;
 pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
+pub const MAX_UNIFORM_BLOCKS: usize = 4;
+pub const MAX_COLOR_ATTACHMENTS: usize = 4;
 
 #[derive(Clone, Debug)]
 pub struct Features {
@@ -582,6 +1216,109 @@ pub struct Features {
     /// With resolve_attachments: false, not-none resolve_img in new_render_pass will
     /// result in a runtime panic.
     pub resolve_attachments: bool,
+    /// Compute shaders, storage buffers and image load/store.
+    /// Requires GL 4.3 or GLES 3.1, would be false on GL2, WebGL and Metal.
+    ///
+    /// With compute: false, `ShaderSource::GlslCompute` and `BufferType::StorageBuffer`
+    /// will result in a runtime panic.
+    pub compute: bool,
+    /// Uniform blocks are backed by real uniform buffers.
+    /// Requires GL 3.1, GLES3 or WebGL2.
+    ///
+    /// With uniform_buffers: false, `apply_uniform_block` is still available,
+    /// but falls back to a glUniform call per block field.
+    pub uniform_buffers: bool,
+    /// `TextureKind::Texture2DArray` and `TextureKind::Texture3D`.
+    /// Requires GL3, GLES3 or WebGL2.
+    pub texture_3d: bool,
+    /// All the `TextureFormat`s after `Alpha`.
+    /// Requires GL3, GLES3 or WebGL2.
+    pub sized_texture_formats: bool,
+    /// R16F, RG16F, R32F and RGBA32F textures could be used as render pass attachments.
+    /// Always available on desktop GL, requires EXT_color_buffer_float on GLES and WebGL.
+    pub float_render_targets: bool,
+    /// R32F and RGBA32F could be sampled with `FilterMode::Linear`.
+    /// Always available on desktop GL, requires OES_texture_float_linear on GLES and WebGL.
+    pub float32_filtering: bool,
+    /// BC1, BC2 and BC3 compressed textures.
+    /// Requires EXT_texture_compression_s3tc (WEBGL_compressed_texture_s3tc on the web).
+    pub texture_compression_s3tc: bool,
+    /// BC4 and BC5 compressed textures. Core in GL3, requires EXT_texture_compression_rgtc otherwise.
+    pub texture_compression_rgtc: bool,
+    /// BC6H and BC7 compressed textures. Core in GL4.2, requires EXT_texture_compression_bptc otherwise.
+    pub texture_compression_bptc: bool,
+    /// ETC2 and EAC compressed textures. Core in GLES3 and GL4.3,
+    /// requires WEBGL_compressed_texture_etc on the web.
+    pub texture_compression_etc2: bool,
+    /// ASTC LDR compressed textures. Requires KHR_texture_compression_astc_ldr
+    /// (WEBGL_compressed_texture_astc on the web).
+    pub texture_compression_astc: bool,
+    /// Non-zero `DrawRange::base_vertex` and `DrawIndirectArgs::base_vertex`.
+    /// Requires GL 3.2 or GLES 3.2, would be false on WebGL.
+    ///
+    /// With base_vertex: false, base vertex is emulated by offsetting
+    /// per-vertex attributes, which costs a few extra GL calls per draw.
+    pub base_vertex: bool,
+    /// `BufferType::IndirectBuffer` lives on the GPU and draw arguments
+    /// are read by the GPU. Requires GL 4.0 or GLES 3.1, would be false on WebGL.
+    ///
+    /// With draw_indirect: false, indirect buffers are kept on the CPU
+    /// and `multi_draw_indirect` is a loop over regular draw calls.
+    pub draw_indirect: bool,
+    /// `multi_draw_indirect` is a single GPU command.
+    /// Requires GL 4.3 or ARB_multi_draw_indirect, would be false on GLES and WebGL.
+    pub multi_draw_indirect: bool,
+    /// `buffer_read` is available.
+    /// Requires GL, GLES3 or WebGL2, would be false on GLES2 and WebGL1.
+    pub buffer_read: bool,
+    /// `buffer_map_write` maps the GPU buffer directly.
+    /// Requires GL3, GLES3 or ARB_map_buffer_range, would be false on WebGL.
+    ///
+    /// With buffer_map: false, `buffer_map_write` goes through a CPU staging copy.
+    pub buffer_map: bool,
+    /// `QueryKind::TimeElapsed`. Requires GL 3.3, ARB_timer_query or EXT_disjoint_timer_query,
+    /// would be false on Metal.
+    pub query_time_elapsed: bool,
+    /// `QueryKind::Timestamp`. Requires GL 3.3, ARB_timer_query or, on the web,
+    /// EXT_disjoint_timer_query. Would be false on GLES and Metal.
+    pub query_timestamp: bool,
+    /// `QueryKind::AnySamplesPassed`. Requires GL 3.3, GLES3 or WebGL2.
+    pub query_any_samples_passed: bool,
+    /// `QueryKind::SamplesPassed`. Desktop GL and Metal only.
+    pub query_samples_passed: bool,
+    /// `QueryKind::PrimitivesGenerated`. Requires GL3 or GLES 3.2, would be false on Metal.
+    pub query_primitives_generated: bool,
+    /// Fence objects and pixel buffer objects.
+    /// Requires GL 3.2, GLES3 or WebGL2.
+    ///
+    /// With async_readback: false, `texture_read_pixels_async` is a synchronous read
+    /// and `insert_fence` waits for the GPU to finish.
+    pub async_readback: bool,
+    /// Different `PipelineParams::attachment_blend` states for each color attachment.
+    /// Requires GL 4.0, GLES 3.2 or WebGL2 with OES_draw_buffers_indexed.
+    ///
+    /// With independent_blend: false, `attachment_blend` is accepted only
+    /// if all the attachments use the same state.
+    pub independent_blend: bool,
+    /// `PrimitiveType::LineLoop` and `PrimitiveType::TriangleFan`, would be false on Metal.
+    pub line_loop_triangle_fan: bool,
+    /// `PipelineParams::primitive_restart`.
+    /// Requires GL 4.3, GLES3, WebGL2 or Metal.
+    pub primitive_restart: bool,
+    /// `new_sampler` creates GPU sampler objects.
+    /// Requires GL 3.3, GLES3, WebGL2 or Metal.
+    ///
+    /// With sampler_objects: false, samplers are emulated by changing the state
+    /// of the bound texture each time it is used with another sampler.
+    pub sampler_objects: bool,
+    /// `TextureWrap::ClampToBorder`. Requires desktop GL, GLES 3.2 or EXT_texture_border_clamp,
+    /// would be false on WebGL and iOS.
+    pub clamp_to_border: bool,
+    /// `TextureWrap::MirrorClampToEdge`. Requires GL 4.4 or EXT_texture_mirror_clamp_to_edge,
+    /// would be false on iOS.
+    pub mirror_clamp_to_edge: bool,
+    /// `SamplerParams::compare`. Requires GL3, GLES3, WebGL2 or Metal.
+    pub sampler_compare: bool,
 }
 
 impl Default for Features {
@@ -589,6 +1326,35 @@ impl Default for Features {
         Features {
             instancing: true,
             resolve_attachments: true,
+            compute: false,
+            uniform_buffers: true,
+            texture_3d: true,
+            sized_texture_formats: true,
+            float_render_targets: true,
+            float32_filtering: true,
+            texture_compression_s3tc: false,
+            texture_compression_rgtc: false,
+            texture_compression_bptc: false,
+            texture_compression_etc2: false,
+            texture_compression_astc: false,
+            base_vertex: true,
+            draw_indirect: false,
+            multi_draw_indirect: false,
+            buffer_read: true,
+            buffer_map: true,
+            query_time_elapsed: false,
+            query_timestamp: false,
+            query_any_samples_passed: false,
+            query_samples_passed: false,
+            query_primitives_generated: false,
+            async_readback: true,
+            independent_blend: false,
+            line_loop_triangle_fan: true,
+            primitive_restart: false,
+            sampler_objects: true,
+            clamp_to_border: true,
+            mirror_clamp_to_edge: false,
+            sampler_compare: true,
         }
     }
 }
@@ -676,6 +1442,13 @@ pub enum PrimitiveType {
     Triangles,
     Lines,
     Points,
+    TriangleStrip,
+    LineStrip,
+    /// LineStrip with the last vertex connected to the first one.
+    /// Requires `features.line_loop_triangle_fan`.
+    LineLoop,
+    /// Requires `features.line_loop_triangle_fan`.
+    TriangleFan,
 }
 
 impl From<PrimitiveType> for GLenum {
@@ -684,6 +1457,10 @@ impl From<PrimitiveType> for GLenum {
             PrimitiveType::Triangles => GL_TRIANGLES,
             PrimitiveType::Lines => GL_LINES,
             PrimitiveType::Points => GL_POINTS,
+            PrimitiveType::TriangleStrip => GL_TRIANGLE_STRIP,
+            PrimitiveType::LineStrip => GL_LINE_STRIP,
+            PrimitiveType::LineLoop => GL_LINE_LOOP,
+            PrimitiveType::TriangleFan => GL_TRIANGLE_FAN,
         }
     }
 }
@@ -733,12 +1510,26 @@ pub struct PipelineParams {
     pub alpha_blend: Option<BlendState>,
     pub stencil_test: Option<StencilState>,
     pub color_write: ColorMask,
+    /// Blend state and write mask for each color attachment of a
+    /// `new_render_pass_mrt` pass, `color_blend`, `alpha_blend` and `color_write`
+    /// are ignored when this is set.
+    ///
+    /// Different states for different attachments require `features.independent_blend`,
+    /// without it `new_pipeline` will panic.
+    pub attachment_blend: Option<[AttachmentBlend; MAX_COLOR_ATTACHMENTS]>,
     pub primitive_type: PrimitiveType,
+    /// The maximum value of the index type (0xFFFF for u16 indices, 0xFFFFFFFF for u32)
+    /// starts a new strip, loop or fan in `draw`.
+    /// Requires `features.primitive_restart`.
+    ///
+    /// WebGL2 and Metal can't turn it off: with strips, the maximum index always
+    /// restarts the primitive there, whatever this is set to.
+    pub primitive_restart: bool,
 }
 
 // TODO(next major version bump): should be PipelineId
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub struct Pipeline(usize);
+pub struct Pipeline(ResourceId);
 
 impl Default for PipelineParams {
     fn default() -> PipelineParams {
@@ -752,7 +1543,9 @@ impl Default for PipelineParams {
             alpha_blend: None,
             stencil_test: None,
             color_write: (true, true, true, true),
+            attachment_blend: None,
             primitive_type: PrimitiveType::Triangles,
+            primitive_restart: false,
         }
     }
 }
@@ -770,16 +1563,137 @@ pub struct Bindings {
     /// Index buffer which instructs the GPU in which order to draw vertices
     /// from a vertex buffer, with each subsequent 3 indices forming a
     /// triangle.
-    pub index_buffer: BufferId,
+    ///
+    /// Required by `draw`. May be `None` for geometry drawn with `draw_arrays`.
+    pub index_buffer: Option<BufferId>,
     /// Textures to be used with when drawing the geometry in the fragment
     /// shader.
     pub images: Vec<TextureId>,
+    /// `samplers[n]` replaces the sampling state of `images[n]`.
+    /// Missing and `None` entries sample with the texture's own state.
+    pub samplers: Vec<Option<SamplerId>>,
+}
+
+/// A single draw of `multi_draw`, same arguments as `draw` plus `base_vertex`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawRange {
+    /// Starting offset in `index_buffer`.
+    pub base_element: i32,
+    /// Length of the slice of `index_buffer` to draw.
+    pub num_elements: i32,
+    /// Value added to each index before fetching the vertex.
+    pub base_vertex: i32,
+    pub num_instances: i32,
+}
+
+/// Arguments of a single indexed draw, as stored in a `BufferType::IndirectBuffer`.
+///
+/// Memory layout matches both GL `DrawElementsIndirectCommand`
+/// and Metal `MTLDrawIndexedPrimitivesIndirectArguments`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawIndirectArgs {
+    pub num_elements: u32,
+    pub num_instances: u32,
+    pub base_element: u32,
+    pub base_vertex: i32,
+    /// Should be 0, base instance is not supported on GL < 4.2.
+    pub base_instance: u32,
+}
+
+/// Resources used by a compute dispatch.
+///
+/// Storage buffers and storage images are bound by their position in
+/// the vec, matching `layout(binding = n)` in the compute shader.
+#[derive(Clone, Debug, Default)]
+pub struct ComputeBindings {
+    /// Buffers created with `BufferType::StorageBuffer`, bound as `buffer` blocks.
+    pub storage_buffers: Vec<BufferId>,
+    /// Textures for `imageLoad`/`imageStore`.
+    pub storage_images: Vec<StorageImage>,
+    /// Sampled textures, in the same order as `ShaderMeta::images`.
+    pub images: Vec<TextureId>,
+}
+
+/// A texture mip level bound for image load/store.
+/// The texture should be created with `TextureAccess::Storage`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StorageImage {
+    pub texture: TextureId,
+    pub level: u32,
+    pub access: ImageAccess,
+}
+
+impl StorageImage {
+    pub fn new(texture: TextureId, access: ImageAccess) -> StorageImage {
+        StorageImage {
+            texture,
+            level: 0,
+            access,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+/// Which kind of access should see the writes done by previous dispatches.
+/// Used with `RenderingBackend::memory_barrier`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryBarrier {
+    /// Vertex data sourced from buffers written by a compute shader.
+    VertexAttribArray,
+    /// Indices sourced from buffers written by a compute shader.
+    ElementArray,
+    Uniform,
+    /// Sampling textures written with image store.
+    TextureFetch,
+    /// Image load/store.
+    ShaderImageAccess,
+    /// Readback and updates of buffers: `buffer_update` and friends.
+    BufferUpdate,
+    /// Readback and updates of textures: `texture_read_pixels`, `texture_update` etc.
+    TextureUpdate,
+    /// Rendering into textures written with image store.
+    Framebuffer,
+    /// Storage buffer access.
+    ShaderStorage,
+    All,
+}
+
+impl From<MemoryBarrier> for GLbitfield {
+    fn from(barrier: MemoryBarrier) -> GLbitfield {
+        match barrier {
+            MemoryBarrier::VertexAttribArray => GL_VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            MemoryBarrier::ElementArray => GL_ELEMENT_ARRAY_BARRIER_BIT,
+            MemoryBarrier::Uniform => GL_UNIFORM_BARRIER_BIT,
+            MemoryBarrier::TextureFetch => GL_TEXTURE_FETCH_BARRIER_BIT,
+            MemoryBarrier::ShaderImageAccess => GL_SHADER_IMAGE_ACCESS_BARRIER_BIT,
+            MemoryBarrier::BufferUpdate => GL_BUFFER_UPDATE_BARRIER_BIT,
+            MemoryBarrier::TextureUpdate => GL_TEXTURE_UPDATE_BARRIER_BIT,
+            MemoryBarrier::Framebuffer => GL_FRAMEBUFFER_BARRIER_BIT,
+            MemoryBarrier::ShaderStorage => GL_SHADER_STORAGE_BARRIER_BIT,
+            MemoryBarrier::All => GL_ALL_BARRIER_BITS,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BufferType {
     VertexBuffer,
     IndexBuffer,
+    /// Shader storage buffer, read and written by compute shaders.
+    /// Could also be used as a vertex buffer, e.g. for GPU-simulated particles.
+    /// Requires `Features::compute`.
+    StorageBuffer,
+    /// Draw arguments for `draw_indirect` and `multi_draw_indirect`,
+    /// an array of `DrawIndirectArgs`.
+    /// Without `Features::draw_indirect` the buffer is kept on the CPU.
+    IndirectBuffer,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -793,6 +1707,8 @@ fn gl_buffer_target(buffer_type: &BufferType) -> GLenum {
     match buffer_type {
         BufferType::VertexBuffer => GL_ARRAY_BUFFER,
         BufferType::IndexBuffer => GL_ELEMENT_ARRAY_BUFFER,
+        BufferType::StorageBuffer => GL_SHADER_STORAGE_BUFFER,
+        BufferType::IndirectBuffer => GL_DRAW_INDIRECT_BUFFER,
     }
 }
 
@@ -805,7 +1721,34 @@ fn gl_usage(usage: &BufferUsage) -> GLenum {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub struct BufferId(usize);
+pub struct BufferId(ResourceId);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct QueryId(ResourceId);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ReadbackId(ResourceId);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FenceId(ResourceId);
+
+/// What a GPU query measures, see `RenderingBackend::new_query`.
+/// Support for each kind is reported by `ContextInfo::query_supported`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum QueryKind {
+    /// GPU time spent between `begin_query` and `end_query`, in nanoseconds.
+    TimeElapsed,
+    /// GPU time at which all the previous commands are done, in nanoseconds.
+    /// Recorded with `end_query` alone, `begin_query` is not used.
+    Timestamp,
+    /// 1 if any sample passed the depth and stencil tests between
+    /// `begin_query` and `end_query`, 0 otherwise. Used for occlusion culling.
+    AnySamplesPassed,
+    /// Number of samples that passed the depth and stencil tests.
+    SamplesPassed,
+    /// Number of primitives emitted by the vertex stage.
+    PrimitivesGenerated,
+}
 
 /// `ElapsedQuery` is used to measure duration of GPU operations.
 ///
@@ -815,7 +1758,7 @@ pub struct BufferId(usize);
 /// was measured on GPU rather than CPU side.
 ///
 /// The query is created using [`ElapsedQuery::new()`] function.
-/// ```
+/// ```no_run
 /// use miniquad::graphics::ElapsedQuery;
 /// // initialization
 /// let mut query = ElapsedQuery::new();
@@ -823,7 +1766,7 @@ pub struct BufferId(usize);
 /// Measurement is performed by calling [`ElapsedQuery::begin_query()`] and
 /// [`ElapsedQuery::end_query()`]
 ///
-/// ```
+/// ```no_run
 /// # use miniquad::graphics::ElapsedQuery;
 /// # let mut query = ElapsedQuery::new();
 ///
@@ -835,7 +1778,7 @@ pub struct BufferId(usize);
 /// Retreival of measured duration is only possible at a later point in time. Often a frame or
 /// couple frames later. Measurement latency can especially be high on WASM/WebGL target.
 ///
-/// ```
+/// ```no_run
 /// // couple frames later:
 /// # use miniquad::graphics::ElapsedQuery;
 /// # let mut query = ElapsedQuery::new();
@@ -848,7 +1791,7 @@ pub struct BufferId(usize);
 /// ```
 ///
 /// And during finalization:
-/// ```
+/// ```no_run
 /// // clean-up
 /// # use miniquad::graphics::ElapsedQuery;
 /// # let mut query = ElapsedQuery::new();
@@ -863,6 +1806,9 @@ pub struct BufferId(usize);
 ///
 /// It is only possible to measure single query at once.
 ///
+/// `ElapsedQuery` is OpenGL only, [`RenderingBackend::new_query()`] with
+/// [`QueryKind::TimeElapsed`] is the backend-agnostic alternative.
+///
 /// On OpenGL/WebGL platforms implementation relies on [`EXT_disjoint_timer_query`] extension.
 ///
 /// [`EXT_disjoint_timer_query`]: https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_disjoint_timer_query.txt
@@ -920,17 +1866,19 @@ impl ElapsedQuery {
     ///
     /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
     pub fn get_result(&self) -> u64 {
-        // let mut time: GLuint64 = 0;
-        // assert!(self.gl_query != 0);
-        // unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
-        // time
-        0
+        let mut time: GLuint64 = 0;
+        assert!(self.gl_query != 0);
+        unsafe { glGetQueryObjectui64v(self.gl_query, GL_QUERY_RESULT, &mut time) };
+        time
     }
 
     /// Reports whenever elapsed timer is supported and other methods can be invoked.
+    ///
+    /// Asks the current GL context, going through all of its extensions, so better
+    /// called once after the context is created than every frame. Same as
+    /// `ctx.info().features.query_time_elapsed`.
     pub fn is_supported() -> bool {
-        unimplemented!();
-        //unsafe { sapp_is_elapsed_timer_supported() }
+        gl::gl_info().features.query_time_elapsed
     }
 
     /// Reports whenever result of submitted query is available for retrieval with
@@ -941,17 +1889,15 @@ impl ElapsedQuery {
     ///
     /// Use [`ElapsedQuery::is_supported()`] to check if functionality is available and the method can be called.
     pub fn is_available(&self) -> bool {
-        // let mut available: GLint = 0;
-
-        // // begin_query was not called yet
-        // if self.gl_query == 0 {
-        //     return false;
-        // }
+        let mut available: GLuint = 0;
 
-        //unsafe { glGetQueryObjectiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
-        //available != 0
+        // begin_query was not called yet
+        if self.gl_query == 0 {
+            return false;
+        }
 
-        false
+        unsafe { glGetQueryObjectuiv(self.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
     }
 
     /// Delete query.
@@ -978,8 +1924,12 @@ pub struct Arg<'a> {
 
 pub enum TextureSource<'a> {
     Empty,
+    /// All the layers of a `Texture2DArray` or `Texture3D` one after another.
     Bytes(&'a [u8]),
     /// Array of `[cubemap_face][mipmap_level][bytes]`
+    ///
+    /// For `Texture2DArray` and `Texture3D` the outer array should have one element,
+    /// and each mipmap level holds all its layers, one after another.
     Array(&'a [&'a [&'a [u8]]]),
 }
 
@@ -1038,10 +1988,22 @@ impl<'a> UniformsSource<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ShaderSource<'a> {
-    Glsl { vertex: &'a str, fragment: &'a str },
-    Msl { program: &'a str },
+    Glsl {
+        vertex: &'a str,
+        fragment: &'a str,
+    },
+    /// Requires `Features::compute`, the program could only be used
+    /// with `new_compute_pipeline`.
+    GlslCompute {
+        compute: &'a str,
+    },
+    Msl {
+        program: &'a str,
+    },
+    /// Rust closures, only `SoftwareContext` could run them.
+    Native(&'a NativeShader),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
@@ -1084,6 +2046,10 @@ pub struct ContextInfo {
     /// List of platform-dependent features that miniquad failed to make cross-platforms
     /// and therefore they might be missing.
     pub features: Features,
+    /// Largest width or height of a texture, GL_MAX_TEXTURE_SIZE on GL.
+    pub max_texture_size: u32,
+    /// Largest `SamplerParams::max_anisotropy`, 1.0 without anisotropic filtering.
+    pub max_anisotropy: f32,
 }
 
 impl ContextInfo {
@@ -1095,6 +2061,247 @@ impl ContextInfo {
             }
         }
     }
+
+    /// Check if `format` could be sampled, filtered or rendered to.
+    /// `new_texture` with an unsupported format will result in a runtime panic.
+    pub fn texture_format_caps(&self, format: TextureFormat) -> TextureFormatCaps {
+        use TextureFormat::*;
+
+        let sized = self.features.sized_texture_formats;
+        let float_render = self.features.float_render_targets;
+        // compressed textures could not be rendered to
+        let compressed = |supported| (supported, supported, false);
+        let (sample, filter, render) = match format {
+            RGB8 | RGBA8 | RGBA16F => (true, true, true),
+            Alpha => (true, true, sized),
+            Depth | Depth32 => (true, false, true),
+            R8 | RG8 | SRGB8A8 | RGB10A2 => (sized, sized, sized),
+            R16F | RG16F => (sized, sized, sized && float_render),
+            R32F | RGBA32F => (
+                sized,
+                sized && self.features.float32_filtering,
+                sized && float_render,
+            ),
+            R32UI | Depth24Stencil8 => (sized, false, sized),
+            BC1 | BC2 | BC3 => compressed(self.features.texture_compression_s3tc),
+            BC4 | BC5 => compressed(self.features.texture_compression_rgtc),
+            BC6H | BC7 => compressed(self.features.texture_compression_bptc),
+            ETC2RGB8 | ETC2RGBA8 | EACR11 | EACRG11 => {
+                compressed(self.features.texture_compression_etc2)
+            }
+            ASTC4x4 | ASTC5x5 | ASTC6x6 | ASTC8x8 | ASTC10x10 | ASTC12x12 => {
+                compressed(self.features.texture_compression_astc)
+            }
+        };
+        TextureFormatCaps {
+            sample,
+            filter,
+            render,
+        }
+    }
+
+    /// Errors `try_new_texture` reports before creating anything,
+    /// everything `new_texture` would otherwise panic on.
+    pub(crate) fn check_texture_params(
+        &self,
+        access: TextureAccess,
+        source: &TextureSource,
+        params: &TextureParams,
+    ) -> Result<(), GraphicsError> {
+        let invalid = |reason: &str| Err(GraphicsError::InvalidParams(reason.to_string()));
+        if params.width > self.max_texture_size || params.height > self.max_texture_size {
+            return Err(GraphicsError::TextureTooLarge {
+                width: params.width,
+                height: params.height,
+                max_size: self.max_texture_size,
+            });
+        }
+        let layered = matches!(
+            params.kind,
+            TextureKind::Texture2DArray | TextureKind::Texture3D
+        );
+        if layered {
+            if !self.features.texture_3d {
+                return Err(GraphicsError::UnsupportedFeature("texture_3d"));
+            }
+            if params.sample_count > 1 {
+                return invalid("multisampled texture arrays and 3D textures are not supported");
+            }
+            if params.format.is_compressed() {
+                return invalid("compressed texture arrays and 3D textures are not supported");
+            }
+        } else if params.depth != 1 {
+            return invalid("depth is only used by layered textures");
+        }
+        if access != TextureAccess::RenderTarget && params.sample_count > 1 {
+            return invalid("multisampling is only supported for render textures");
+        }
+        if access == TextureAccess::Storage && !self.features.compute {
+            return Err(GraphicsError::UnsupportedFeature("compute"));
+        }
+        let caps = self.texture_format_caps(params.format);
+        if !caps.sample || access != TextureAccess::Static && !caps.render {
+            return Err(GraphicsError::UnsupportedFormat(params.format));
+        }
+        match source {
+            TextureSource::Empty => {}
+            TextureSource::Bytes(bytes) => {
+                if params.kind == TextureKind::CubeMap {
+                    return invalid("cubemaps require TextureSource::Array of 6 textures");
+                }
+                let size = params
+                    .format
+                    .checked_size(params.width, params.height)
+                    .and_then(|size| size.checked_mul(params.depth as u64));
+                if size != Some(bytes.len() as u64) {
+                    return Err(GraphicsError::InvalidParams(format!(
+                        "{} bytes for a {}x{}x{} {:?} texture",
+                        bytes.len(),
+                        params.width,
+                        params.height,
+                        params.depth,
+                        params.format
+                    )));
+                }
+            }
+            TextureSource::Array(array) => {
+                if params.kind == TextureKind::CubeMap && array.len() != 6 {
+                    return invalid("cubemaps require TextureSource::Array of 6 textures");
+                }
+                if params.kind != TextureKind::CubeMap
+                    && array.len() != 1
+                    && (layered || params.format.is_compressed())
+                {
+                    return invalid(
+                        "layered and compressed textures require TextureSource::Array of 1 element",
+                    );
+                }
+                if params.format.is_compressed() {
+                    for mipmaps in array.iter() {
+                        for (level, bytes) in mipmaps.iter().enumerate() {
+                            let width = params.width.checked_shr(level as u32).unwrap_or(0).max(1);
+                            let height =
+                                params.height.checked_shr(level as u32).unwrap_or(0).max(1);
+                            if params.format.checked_size(width, height) != Some(bytes.len() as u64)
+                            {
+                                return Err(GraphicsError::InvalidParams(format!(
+                                    "{} bytes for a {}x{} {:?} mipmap",
+                                    bytes.len(),
+                                    width,
+                                    height,
+                                    params.format
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.check_wrap(params.wrap)
+    }
+
+    fn check_wrap(&self, wrap: TextureWrap) -> Result<(), GraphicsError> {
+        match wrap {
+            TextureWrap::ClampToBorder if !self.features.clamp_to_border => {
+                Err(GraphicsError::UnsupportedFeature("clamp_to_border"))
+            }
+            TextureWrap::MirrorClampToEdge if !self.features.mirror_clamp_to_edge => {
+                Err(GraphicsError::UnsupportedFeature("mirror_clamp_to_edge"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Errors `try_new_sampler` reports before creating anything.
+    pub(crate) fn check_sampler_params(&self, params: &SamplerParams) -> Result<(), GraphicsError> {
+        self.check_wrap(params.wrap_x)?;
+        self.check_wrap(params.wrap_y)?;
+        self.check_wrap(params.wrap_z)?;
+        if params.compare.is_some() && !self.features.sampler_compare {
+            return Err(GraphicsError::UnsupportedFeature("sampler_compare"));
+        }
+        Ok(())
+    }
+
+    /// Errors `try_new_pipeline` reports before creating anything.
+    pub(crate) fn check_pipeline_params(
+        &self,
+        params: &PipelineParams,
+    ) -> Result<(), GraphicsError> {
+        if matches!(
+            params.primitive_type,
+            PrimitiveType::LineLoop | PrimitiveType::TriangleFan
+        ) && !self.features.line_loop_triangle_fan
+        {
+            return Err(GraphicsError::UnsupportedFeature("line_loop_triangle_fan"));
+        }
+        if params.primitive_restart && !self.features.primitive_restart {
+            return Err(GraphicsError::UnsupportedFeature("primitive_restart"));
+        }
+        if let Some(attachments) = params.attachment_blend {
+            if attachments.iter().any(|a| *a != attachments[0]) && !self.features.independent_blend
+            {
+                return Err(GraphicsError::UnsupportedFeature("independent_blend"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if queries of the given kind could be created with `new_query`.
+    pub fn query_supported(&self, kind: QueryKind) -> bool {
+        match kind {
+            QueryKind::TimeElapsed => self.features.query_time_elapsed,
+            QueryKind::Timestamp => self.features.query_timestamp,
+            QueryKind::AnySamplesPassed => self.features.query_any_samples_passed,
+            QueryKind::SamplesPassed => self.features.query_samples_passed,
+            QueryKind::PrimitivesGenerated => self.features.query_primitives_generated,
+        }
+    }
+}
+
+/// Errors `try_new_render_pass_mrt` reports before creating anything.
+pub(crate) fn check_render_pass_attachments<B: RenderingBackend + ?Sized>(
+    ctx: &B,
+    color_img: &[RenderTarget],
+    resolve_img: Option<&[TextureId]>,
+    depth_img: Option<RenderTarget>,
+) -> Result<(), GraphicsError> {
+    let info = ctx.info();
+    if color_img.is_empty() && depth_img.is_none() {
+        return Err(GraphicsError::IncompleteFramebuffer(
+            "no attachments".to_string(),
+        ));
+    }
+    if color_img.len() > MAX_COLOR_ATTACHMENTS {
+        return Err(GraphicsError::IncompleteFramebuffer(format!(
+            "{} color attachments, MAX_COLOR_ATTACHMENTS is {}",
+            color_img.len(),
+            MAX_COLOR_ATTACHMENTS
+        )));
+    }
+    if resolve_img.is_some() && !info.features.resolve_attachments {
+        return Err(GraphicsError::UnsupportedFeature("resolve_attachments"));
+    }
+    let resolves = resolve_img
+        .into_iter()
+        .flatten()
+        .map(|&texture| texture.into());
+    let attachments = color_img.iter().copied().chain(resolves).chain(depth_img);
+    for target in attachments {
+        let params = ctx.texture_params(target.texture);
+        if !info.texture_format_caps(params.format).render {
+            return Err(GraphicsError::UnsupportedFormat(params.format));
+        }
+        if target.mip_level >= params.mip_levels()
+            || target.layer >= params.mip_layers(target.mip_level)
+        {
+            return Err(GraphicsError::IncompleteFramebuffer(format!(
+                "{:?} is out of range of the texture",
+                target
+            )));
+        }
+    }
+    Ok(())
 }
 
 pub trait RenderingBackend {
@@ -1128,12 +2335,74 @@ pub trait RenderingBackend {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError>;
+    /// Same as `new_shader`, but fails with `ShaderError::LayoutMismatch` when `meta`
+    /// does not match the shader. Names of `meta` the shader does not use are returned
+    /// along with the shader, see `ShaderMeta::validate`.
+    ///
+    /// Without `shader_reflection` (Metal) there is nothing to validate against,
+    /// and this is just `new_shader`.
+    fn new_shader_validated(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(ShaderId, Vec<String>), ShaderError> {
+        let expected = meta.clone();
+        let shader = self.new_shader(shader, meta)?;
+        let validation = match self.shader_reflection(shader) {
+            Some(reflection) => expected.validate(reflection),
+            None => Ok(vec![]),
+        };
+        match validation {
+            Ok(warnings) => Ok((shader, warnings)),
+            Err(err) => {
+                self.delete_shader(shader);
+                Err(err)
+            }
+        }
+    }
+    /// Same as `new_shader`, with `ShaderMeta::reflect` instead of a hand-written meta.
+    /// The meta is returned to build the uniforms struct against.
+    ///
+    /// Panics without `shader_reflection`, MSL shaders always need a `ShaderMeta`.
+    fn new_shader_reflected(
+        &mut self,
+        _shader: ShaderSource,
+    ) -> Result<(ShaderId, ShaderMeta), ShaderError> {
+        panic!("Shader reflection is not supported by the backend")
+    }
+    /// Recompile `shader` in place, for hot-reloading. The `ShaderId` and the pipelines created
+    /// with it stay valid and get the uniform, attribute and image locations of the new program.
+    ///
+    /// On error the old program is kept. After a successful replace the pipeline should be
+    /// applied again before the next draw.
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError>;
+    /// Active attributes, uniforms and samplers of the shader, as reported by the driver.
+    /// `None` on Metal.
+    fn shader_reflection(&self, shader: ShaderId) -> Option<&ShaderReflection>;
     fn new_texture(
         &mut self,
         access: TextureAccess,
         data: TextureSource,
         params: TextureParams,
     ) -> TextureId;
+    /// Same as `new_texture`, but returns an error instead of panicking or
+    /// silently creating a broken texture. The size is checked against
+    /// `ContextInfo::max_texture_size`, the format against `ContextInfo::texture_format_caps`,
+    /// the data length against the size and format, and, on GL, GL_OUT_OF_MEMORY is reported as `GraphicsError::OutOfMemory`.
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, GraphicsError> {
+        self.info().check_texture_params(access, &data, &params)?;
+        Ok(self.new_texture(access, data, params))
+    }
     fn new_render_texture(&mut self, params: TextureParams) -> TextureId {
         self.new_texture(TextureAccess::RenderTarget, TextureSource::Empty, params)
     }
@@ -1144,6 +2413,21 @@ pub trait RenderingBackend {
     ) -> TextureId {
         self.new_texture(TextureAccess::Static, TextureSource::Bytes(bytes), params)
     }
+    /// Same as `new_texture_from_data_and_format`, but with all the mip levels given:
+    /// `mips[0]` is the full size image and each next level is half the size of the previous.
+    /// `allocate_mipmaps` is implied for more than one level.
+    ///
+    /// For cubemaps use `new_texture` with `TextureSource::Array`.
+    fn new_texture_from_mips(&mut self, mips: &[&[u8]], params: TextureParams) -> TextureId {
+        self.new_texture(
+            TextureAccess::Static,
+            TextureSource::Array(&[mips]),
+            TextureParams {
+                allocate_mipmaps: mips.len() > 1,
+                ..params
+            },
+        )
+    }
     fn new_texture_from_rgba8(&mut self, width: u16, height: u16, bytes: &[u8]) -> TextureId {
         assert_eq!(width as usize * height as usize * 4, bytes.len());
 
@@ -1153,6 +2437,7 @@ pub trait RenderingBackend {
                 kind: TextureKind::Texture2D,
                 width: width as _,
                 height: height as _,
+                depth: 1,
                 format: TextureFormat::RGBA8,
                 wrap: TextureWrap::Clamp,
                 min_filter: FilterMode::Linear,
@@ -1174,9 +2459,23 @@ pub trait RenderingBackend {
 
     /// Update whole texture content
     /// bytes should be width * height * 4 size - non rgba8 textures are not supported yet anyway
+    ///
+    /// For `Texture2DArray` and `Texture3D` bytes are all the layers, one after another.
     fn texture_update(&mut self, texture: TextureId, bytes: &[u8]) {
-        let (width, height) = self.texture_size(texture);
-        self.texture_update_part(texture, 0 as _, 0 as _, width as _, height as _, bytes)
+        let params = self.texture_params(texture);
+        let layer_size = params.format.size(params.width, params.height) as usize;
+        assert_eq!(layer_size * params.depth.max(1) as usize, bytes.len());
+        for (layer, bytes) in bytes.chunks(layer_size.max(1)).enumerate() {
+            self.texture_update_layer_part(
+                texture,
+                layer as _,
+                0 as _,
+                0 as _,
+                params.width as _,
+                params.height as _,
+                bytes,
+            )
+        }
     }
     fn texture_set_filter(
         &mut self,
@@ -1195,6 +2494,17 @@ pub trait RenderingBackend {
     );
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode);
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap);
+    /// Sampling state used instead of the texture's own for `Bindings::samplers`,
+    /// one texture could be sampled a few different ways without changing it.
+    ///
+    /// Panics on a wrap mode or compare function missing in `features`.
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId;
+    /// Same as `new_sampler`, but returns an error instead of panicking,
+    /// see `ContextInfo::features`.
+    fn try_new_sampler(&mut self, params: SamplerParams) -> Result<SamplerId, GraphicsError> {
+        self.info().check_sampler_params(&params)?;
+        Ok(self.new_sampler(params))
+    }
     /// Metal-specific note: if texture was created without `params.generate_mipmaps`
     /// `generate_mipmaps` will do nothing.
     ///
@@ -1202,7 +2512,41 @@ pub trait RenderingBackend {
     /// generated.
     fn texture_generate_mipmaps(&mut self, texture: TextureId);
     fn texture_resize(&mut self, texture: TextureId, width: u32, height: u32, bytes: Option<&[u8]>);
-    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]);
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        self.texture_read_layer_pixels(texture, 0, bytes)
+    }
+    /// Read a single layer of a `Texture2DArray` or slice of a `Texture3D`.
+    /// For other texture kinds `layer` should be 0.
+    fn texture_read_layer_pixels(&mut self, texture: TextureId, layer: u32, bytes: &mut [u8]) {
+        self.texture_read_mip_pixels(texture, 0, layer, bytes)
+    }
+    /// Read a single layer of a mip level, `bytes` should be `TextureParams::mip_size` big.
+    /// `layer` is a cubemap face, a layer of a `Texture2DArray` or a slice of a `Texture3D`,
+    /// see `TextureParams::mip_layers`.
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        bytes: &mut [u8],
+    );
+    /// Start reading `rect` (x, y, width, height) of the texture without stalling
+    /// the pipeline. The result will be available with `take_readback` later,
+    /// usually a frame or couple frames later.
+    ///
+    /// Without `features.async_readback` the pixels are read immediately.
+    ///
+    /// On Metal should be called outside of a render pass.
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId;
+    /// Is the readback finished, so `take_readback` will return the pixels.
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool;
+    /// Pixels of a finished readback, `None` if it is not finished yet.
+    /// Once the pixels are returned, the readback is deleted and its handle is invalid.
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>>;
     fn texture_update_part(
         &mut self,
         texture: TextureId,
@@ -1211,7 +2555,49 @@ pub trait RenderingBackend {
         width: i32,
         height: i32,
         bytes: &[u8],
+    ) {
+        self.texture_update_layer_part(texture, 0, x_offset, y_offset, width, height, bytes)
+    }
+    /// Update a part of a single layer of a `Texture2DArray` or slice of a `Texture3D`.
+    /// For other texture kinds `layer` should be 0.
+    ///
+    /// For compressed formats the updated rectangle should be aligned to the format blocks.
+    #[allow(clippy::too_many_arguments)]
+    fn texture_update_layer_part(
+        &mut self,
+        texture: TextureId,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.texture_update_mip_part(texture, 0, layer, x_offset, y_offset, width, height, bytes)
+    }
+    /// Update a part of a single layer of a mip level, the rectangle should fit into
+    /// `TextureParams::mip_size`. `layer` is a cubemap face, a layer of a `Texture2DArray`
+    /// or a slice of a `Texture3D`, see `TextureParams::mip_layers`.
+    ///
+    /// The level should be allocated: created with `allocate_mipmaps` or
+    /// with all the levels in `TextureSource::Array`.
+    #[allow(clippy::too_many_arguments)]
+    fn texture_update_mip_part(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
     );
+    /// Offscreen render pass drawing into `color_img`.
+    ///
+    /// `depth_img` with `TextureFormat::Depth24Stencil8` gives the pass a stencil
+    /// buffer as well, so `PassAction::Clear { stencil, .. }` and
+    /// `PipelineParams::stencil_test` work the same as on the default framebuffer.
     fn new_render_pass(
         &mut self,
         color_img: TextureId,
@@ -1231,7 +2617,51 @@ pub trait RenderingBackend {
         color_img: &[TextureId],
         resolve_img: Option<&[TextureId]>,
         depth_img: Option<TextureId>,
+    ) -> RenderPass {
+        let color_img: Vec<RenderTarget> = color_img.iter().map(|&img| img.into()).collect();
+        self.new_render_pass_targets(&color_img, resolve_img, depth_img.map(Into::into))
+    }
+    /// Same as `new_render_pass_mrt`, but each attachment is a single mip level
+    /// and cubemap face, array layer or 3D slice of its texture.
+    /// The pass is as big as the mip level of the first attachment, all of them
+    /// should be the same size.
+    fn new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
     ) -> RenderPass;
+    /// Same as `new_render_pass`, see `try_new_render_pass_mrt`.
+    fn try_new_render_pass(
+        &mut self,
+        color_img: TextureId,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, GraphicsError> {
+        self.try_new_render_pass_mrt(&[color_img], None, depth_img)
+    }
+    /// Same as `new_render_pass_mrt`, but returns an error for attachments that
+    /// could not be rendered to and, on GL, when glCheckFramebufferStatus
+    /// reports an incomplete framebuffer.
+    fn try_new_render_pass_mrt(
+        &mut self,
+        color_img: &[TextureId],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, GraphicsError> {
+        let color_img: Vec<RenderTarget> = color_img.iter().map(|&img| img.into()).collect();
+        self.try_new_render_pass_targets(&color_img, resolve_img, depth_img.map(Into::into))
+    }
+    /// Same as `new_render_pass_targets`, see `try_new_render_pass_mrt`.
+    /// Mip levels and layers out of range of their textures are reported as well.
+    fn try_new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> Result<RenderPass, GraphicsError> {
+        check_render_pass_attachments(self, color_img, resolve_img, depth_img)?;
+        Ok(self.new_render_pass_targets(color_img, resolve_img, depth_img))
+    }
     /// panics for depth-only or multiple color attachment render pass
     /// This function is, mostly, legacy. Using "render_pass_color_attachments"
     /// is recommended instead.
@@ -1248,7 +2678,14 @@ pub trait RenderingBackend {
     }
     /// For depth-only render pass returns empty slice.
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId];
+    /// Deletes the attachments as well, those already deleted with another pass are skipped.
     fn delete_render_pass(&mut self, render_pass: RenderPass);
+    /// Same as `delete_render_pass`, but returns `GraphicsError::InvalidId`
+    /// on a double delete or a stale id instead of crashing.
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        self.delete_render_pass(render_pass);
+        Ok(())
+    }
     fn new_pipeline(
         &mut self,
         buffer_layout: &[BufferLayout],
@@ -1256,8 +2693,29 @@ pub trait RenderingBackend {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline;
+    /// Same as `new_pipeline`, but returns an error if `params` require a feature
+    /// missing on the current backend or, on GL, if `shader` was deleted.
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, GraphicsError> {
+        self.info().check_pipeline_params(&params)?;
+        Ok(self.new_pipeline(buffer_layout, attributes, shader, params))
+    }
+    /// Pipeline for a shader created from `ShaderSource::GlslCompute`.
+    /// Could be used with `apply_pipeline`, `apply_compute_bindings`,
+    /// `apply_uniforms` and `dispatch`, but not with `draw`.
+    fn new_compute_pipeline(&mut self, shader: ShaderId) -> Pipeline;
     fn apply_pipeline(&mut self, pipeline: &Pipeline);
     fn delete_pipeline(&mut self, pipeline: Pipeline);
+    /// Same as `delete_pipeline`, see `try_delete_render_pass`.
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.delete_pipeline(pipeline);
+        Ok(())
+    }
 
     /// Create a buffer resource object.
     /// ```ignore
@@ -1280,7 +2738,51 @@ pub trait RenderingBackend {
     /// ```
     fn new_buffer(&mut self, type_: BufferType, usage: BufferUsage, data: BufferSource)
         -> BufferId;
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource);
+    /// Same as `new_buffer`, but returns an error instead of panicking on a
+    /// `BufferType::StorageBuffer` without `features.compute` and, on GL,
+    /// reports GL_OUT_OF_MEMORY as `GraphicsError::OutOfMemory`.
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, GraphicsError> {
+        if type_ == BufferType::StorageBuffer && !self.info().features.compute {
+            return Err(GraphicsError::UnsupportedFeature("compute"));
+        }
+        Ok(self.new_buffer(type_, usage, data))
+    }
+    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+        self.buffer_update_part(buffer, 0, data)
+    }
+
+    /// Upload `data` into the buffer starting at `byte_offset`,
+    /// the rest of the buffer is left untouched.
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource);
+
+    /// Read `data.len()` bytes of the buffer starting at `byte_offset`.
+    ///
+    /// Stalls until the GPU is done with the buffer.
+    /// Requires `features.buffer_read`, will panic otherwise.
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]);
+
+    /// Write `size` bytes of the buffer starting at `byte_offset` in place, with `f`.
+    ///
+    /// The slice passed to `f` is write-only: its initial contents are undefined
+    /// and all of it should be written.
+    /// For `BufferUsage::Stream` buffers the whole buffer is orphaned: the GPU keeps
+    /// drawing from the old storage while the new one is written, and everything
+    /// outside of the mapped range becomes undefined as well.
+    ///
+    /// With `features.buffer_map` the slice is driver memory, otherwise
+    /// it is a CPU staging copy uploaded after `f` returns.
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    );
 
     /// Size of buffer in bytes.
     /// For 1 element, u16 buffer this will return 2.
@@ -1295,6 +2797,45 @@ pub trait RenderingBackend {
     /// and thats why this function is not marked as unsafe
     fn delete_buffer(&mut self, buffer: BufferId);
 
+    /// Same as `delete_buffer`, see `try_delete_render_pass`.
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        self.delete_buffer(buffer);
+        Ok(())
+    }
+
+    /// Create a GPU query.
+    /// `kind` should be supported, check it with `ContextInfo::query_supported`.
+    fn new_query(&mut self, kind: QueryKind) -> QueryId;
+
+    /// Start measuring. Only one query of each kind could be active at a time.
+    ///
+    /// Occlusion queries should begin and end within the same render pass.
+    fn begin_query(&mut self, query: QueryId);
+
+    /// Stop measuring. Result will be available with `query_result` later,
+    /// often a frame or couple frames later.
+    fn end_query(&mut self, query: QueryId);
+
+    /// Non-blocking query result readback.
+    ///
+    /// Returns `None` until the GPU is done with the measured commands.
+    /// The query can be used again once its result is retrieved.
+    fn query_result(&mut self, query: QueryId) -> Option<u64>;
+
+    /// Delete GPU query, leaving handle unmodified.
+    fn delete_query(&mut self, query: QueryId);
+
+    /// Insert a fence after all the commands submitted so far.
+    /// Use `fence_signaled` to check if the GPU is done with them.
+    ///
+    /// Without `features.async_readback` there are no fence objects
+    /// and this waits for the GPU right away.
+    fn insert_fence(&mut self) -> FenceId;
+    /// Non-blocking check if all the commands before the fence are finished.
+    fn fence_signaled(&mut self, fence: FenceId) -> bool;
+    /// Delete fence, leaving handle unmodified.
+    fn delete_fence(&mut self, fence: FenceId);
+
     /// Delete GPU texture, leaving handle unmodified.
     ///
     /// More high-level code on top of miniquad probably is going to call this in Drop
@@ -1304,6 +2845,21 @@ pub trait RenderingBackend {
     /// and thats why this function is not marked as unsafe
     fn delete_texture(&mut self, texture: TextureId);
 
+    /// Same as `delete_texture`, see `try_delete_render_pass`.
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        self.delete_texture(texture);
+        Ok(())
+    }
+
+    /// Delete sampler, leaving handle unmodified.
+    fn delete_sampler(&mut self, sampler: SamplerId);
+
+    /// Same as `delete_sampler`, see `try_delete_render_pass`.
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        self.delete_sampler(sampler);
+        Ok(())
+    }
+
     /// Delete GPU program, leaving handle unmodified.
     ///
     /// More high-level code on top of miniquad probably is going to call this in Drop
@@ -1313,6 +2869,12 @@ pub trait RenderingBackend {
     /// Porgram and thats why this function is not marked as unsafe
     fn delete_shader(&mut self, program: ShaderId);
 
+    /// Same as `delete_shader`, see `try_delete_render_pass`.
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        self.delete_shader(program);
+        Ok(())
+    }
+
     /// Set a new viewport rectangle.
     /// Should be applied after begin_pass.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32);
@@ -1324,15 +2886,28 @@ pub trait RenderingBackend {
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
-        index_buffer: BufferId,
+        index_buffer: Option<BufferId>,
+        textures: &[TextureId],
+    ) {
+        self.apply_bindings_with_samplers(vertex_buffers, index_buffer, textures, &[]);
+    }
+
+    /// Same as `apply_bindings_from_slice`, `samplers[n]` replaces the sampling
+    /// state of `textures[n]`, see `Bindings::samplers`.
+    fn apply_bindings_with_samplers(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
         textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
     );
 
     fn apply_bindings(&mut self, bindings: &Bindings) {
-        self.apply_bindings_from_slice(
+        self.apply_bindings_with_samplers(
             &bindings.vertex_buffers,
             bindings.index_buffer,
             &bindings.images,
+            &bindings.samplers,
         );
     }
 
@@ -1341,6 +2916,24 @@ pub trait RenderingBackend {
     }
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize);
 
+    /// Upload std140 data for `ShaderMeta::uniform_blocks[block_index]`.
+    ///
+    /// Blocks are bound by index, so a block applied once stays applied
+    /// for the following draws, even with a different pipeline, until the end of the frame.
+    /// Typical usage is a per-frame block applied once after `begin_pass`, a per-material
+    /// block applied on material change and a per-draw block applied before each draw.
+    ///
+    /// On Metal, block `i` is bound to `[[buffer(MAX_VERTEX_ATTRIBUTES + 1 + i)]]`.
+    fn apply_uniform_block(&mut self, block_index: usize, uniforms: UniformsSource) {
+        self.apply_uniform_block_from_bytes(block_index, uniforms.0.ptr as _, uniforms.0.size)
+    }
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    );
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1365,4 +2958,154 @@ pub trait RenderingBackend {
     /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
     /// `features.instancing` check is required.
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32);
+
+    /// Draw vertices using currently applied bindings and pipeline, without an index buffer.
+    ///
+    /// + `first_vertex` specifies the first vertex to draw from the vertex buffers.
+    /// + `num_vertices` specifies how many consecutive vertices should be drawn.
+    /// + `num_instances` specifies how many instances should be rendered.
+    ///
+    /// `Bindings::index_buffer` is ignored and may be `None`.
+    /// Same as with `draw`, num_instances > 1 requires `features.instancing`.
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32);
+
+    /// Issue a batch of indexed draws with currently applied bindings and pipeline.
+    ///
+    /// Same as calling `draw` for each of `draws`, but also allows to offset
+    /// the vertex buffers with `DrawRange::base_vertex`, so multiple meshes could
+    /// share one vertex/index buffer pair.
+    fn multi_draw(&self, draws: &[DrawRange]);
+
+    /// Issue an indexed draw with arguments read from an `IndirectBuffer`.
+    ///
+    /// + `offset` is a byte offset of a `DrawIndirectArgs` in `indirect_buffer`.
+    fn draw_indirect(&self, indirect_buffer: BufferId, offset: usize) {
+        self.multi_draw_indirect(indirect_buffer, offset, 1);
+    }
+
+    /// Issue `draw_count` indexed draws with arguments read from a tightly packed array
+    /// of `DrawIndirectArgs` in an `IndirectBuffer`, starting at byte `offset`.
+    ///
+    /// A single GPU command with `features.multi_draw_indirect`,
+    /// a loop over draw calls otherwise.
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize);
+
+    /// Bind storage buffers, storage images and sampled textures
+    /// for the currently applied compute pipeline.
+    fn apply_compute_bindings(&mut self, bindings: &ComputeBindings);
+
+    /// Run the currently applied compute pipeline over a
+    /// `groups_x * groups_y * groups_z` grid of work groups.
+    ///
+    /// NOTE: writes done by the dispatch are not guaranteed to be visible to
+    /// the following draws or dispatches, `memory_barrier` is required.
+    fn dispatch(&mut self, groups_x: u32, groups_y: u32, groups_z: u32);
+
+    /// Make the writes done by previous dispatches visible to the given kinds of access.
+    fn memory_barrier(&mut self, barriers: &[MemoryBarrier]);
+}
+
+/// Shader and pipeline with a single `in_pos` Float2 attribute,
+/// and a vertex buffer with one triangle covering the whole viewport.
+#[cfg(test)]
+pub(crate) fn new_fullscreen_triangle(
+    ctx: &mut dyn RenderingBackend,
+    source: ShaderSource,
+    meta: ShaderMeta,
+) -> (ShaderId, Pipeline, BufferId) {
+    let shader = ctx.new_shader(source, meta).unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    );
+    let vertices: [f32; 6] = [-1., -1., 3., -1., -1., 3.];
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    (shader, pipeline, vertex_buffer)
+}
+
+#[test]
+fn test_std140_layout() {
+    let layout = |uniforms: Vec<UniformDesc>| UniformBlockLayout { uniforms };
+
+    // a float fits into the padding after a vec3
+    let vec3_float = layout(vec![
+        UniformDesc::new("a", UniformType::Float3),
+        UniformDesc::new("b", UniformType::Float1),
+        UniformDesc::new("c", UniformType::Float2),
+    ]);
+    assert_eq!(vec3_float.std140_offsets(), [0, 12, 16]);
+    assert_eq!(vec3_float.std140_size(), 32);
+
+    // array elements are padded to 16 bytes, and so is the next field
+    let arrays = layout(vec![
+        UniformDesc::new("a", UniformType::Float1),
+        UniformDesc::new("b", UniformType::Float1).array(3),
+        UniformDesc::new("c", UniformType::Float2).array(2),
+        UniformDesc::new("d", UniformType::Float1),
+    ]);
+    assert_eq!(arrays.std140_offsets(), [0, 16, 64, 96]);
+    assert_eq!(arrays.std140_size(), 112);
+
+    let mat4 = layout(vec![
+        UniformDesc::new("a", UniformType::Float1),
+        UniformDesc::new("mvp", UniformType::Mat4),
+        UniformDesc::new("bones", UniformType::Mat4).array(2),
+        UniformDesc::new("b", UniformType::Int1),
+    ]);
+    assert_eq!(mat4.std140_offsets(), [0, 16, 80, 208]);
+    assert_eq!(mat4.std140_size(), 224);
+}
+
+#[test]
+fn test_shader_meta_validate() {
+    let reflection = ShaderReflection {
+        attributes: vec!["in_pos".to_string()],
+        uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
+        uniform_blocks: vec![UniformBlockReflection {
+            block: UniformBlockDesc::new(
+                "Light",
+                vec![
+                    UniformDesc::new("color", UniformType::Float3),
+                    UniformDesc::new("power", UniformType::Float1),
+                ],
+            ),
+            offsets: vec![0, 12],
+        }],
+        images: vec!["tex".to_string()],
+    };
+    let meta = ShaderMeta::reflect(&reflection);
+    assert_eq!(meta.uniforms.uniforms.len(), 1);
+    assert_eq!(meta.uniform_blocks[0].name, "Light");
+    assert_eq!(meta.images, ["tex"]);
+    assert_eq!(meta.validate(&reflection).unwrap(), Vec::<String>::new());
+
+    // optimized out by the driver or misspelled, not an error
+    let mut unused = meta.clone();
+    unused
+        .uniforms
+        .uniforms
+        .push(UniformDesc::new("unused", UniformType::Mat4));
+    unused.images.push("unused_tex".to_string());
+    assert_eq!(
+        unused.validate(&reflection).unwrap(),
+        [
+            "uniform `unused` is not used by the shader",
+            "image `unused_tex` is not used by the shader"
+        ]
+    );
+
+    let mut wrong = meta.clone();
+    wrong.uniforms.uniforms[0].uniform_type = UniformType::Float3;
+    wrong.uniform_blocks[0].layout.uniforms.swap(0, 1);
+    wrong.images.clear();
+    match wrong.validate(&reflection) {
+        Err(ShaderError::LayoutMismatch(differences)) => assert_eq!(differences.len(), 4),
+        _ => panic!("mismatched meta passed validation"),
+    }
 }
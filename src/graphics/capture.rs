@@ -0,0 +1,1958 @@
+//! Frame capture and replay, see `RecordingBackend` and `Replayer`.
+//!
+//! A capture is a header followed by a stream of commands, one per recorded call:
+//! an opcode and the call arguments, little-endian, with all the data needed to
+//! repeat the call. Ids are written as the recording backend returned them and
+//! are mapped to the ids of the replaying backend on replay.
+
+use super::*;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    path::Path,
+};
+
+const MAGIC: &[u8; 4] = b"MQCP";
+const VERSION: u32 = 3;
+
+/// Error from `Replayer`.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The data does not start with a capture header.
+    NotACapture,
+    /// The capture was written by a different version of miniquad.
+    UnsupportedVersion(u32),
+    /// The capture ends in the middle of a command.
+    Truncated,
+    /// The capture is damaged or could not be replayed, with the reason.
+    Invalid(String),
+    /// A recorded shader failed to compile on the replaying backend.
+    Shader(ShaderError),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotACapture => write!(f, "Not a miniquad capture"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Capture version {version} is not supported")
+            }
+            Self::Truncated => write!(f, "Capture is truncated"),
+            Self::Invalid(reason) => write!(f, "Invalid capture: {reason}"),
+            Self::Shader(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CaptureError {}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    // vertex attribute names already leaked by this reader
+    names: HashSet<&'static str>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CaptureError> {
+        if len > self.data.len() {
+            return Err(CaptureError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CaptureError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, CaptureError> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+/// Binary encoding of a call argument.
+trait Wire: Sized {
+    fn write(&self, w: &mut Vec<u8>);
+    fn read(r: &mut Reader) -> Result<Self, CaptureError>;
+}
+
+macro_rules! wire_number {
+    ($($ty:ty),*) => {$(
+        impl Wire for $ty {
+            fn write(&self, w: &mut Vec<u8>) {
+                w.extend_from_slice(&self.to_le_bytes());
+            }
+            fn read(r: &mut Reader) -> Result<$ty, CaptureError> {
+                Ok(<$ty>::from_le_bytes(r.array()?))
+            }
+        }
+    )*};
+}
+
+wire_number!(u32, i32, u64, f32);
+
+// Fieldless enums, written as the variant position in the list.
+macro_rules! wire_enum {
+    ($($ty:ident { $($variant:ident),* $(,)? })*) => {$(
+        impl Wire for $ty {
+            fn write(&self, w: &mut Vec<u8>) {
+                let variants = [$($ty::$variant),*];
+                w.push(variants.iter().position(|v| v == self).unwrap() as u8);
+            }
+            fn read(r: &mut Reader) -> Result<$ty, CaptureError> {
+                let variants = [$($ty::$variant),*];
+                let tag = r.u8()?;
+                variants.get(tag as usize).copied().ok_or_else(|| {
+                    CaptureError::Invalid(format!("{} is not a {}", tag, stringify!($ty)))
+                })
+            }
+        }
+    )*};
+}
+
+wire_enum! {
+    UniformType { Float1, Float2, Float3, Float4, Int1, Int2, Int3, Int4, Mat4 }
+    VertexFormat {
+        Float1, Float2, Float3, Float4, Byte1, Byte2, Byte3, Byte4,
+        Short1, Short2, Short3, Short4, Int1, Int2, Int3, Int4, Mat4,
+    }
+    VertexStep { PerVertex, PerInstance }
+    TextureFormat {
+        RGB8, RGBA8, RGBA16F, Depth, Depth32, Alpha, R8, RG8, R16F, RG16F, R32F, RGBA32F,
+        R32UI, SRGB8A8, RGB10A2, Depth24Stencil8, BC1, BC2, BC3, BC4, BC5, BC6H, BC7,
+        ETC2RGB8, ETC2RGBA8, EACR11, EACRG11,
+        ASTC4x4, ASTC5x5, ASTC6x6, ASTC8x8, ASTC10x10, ASTC12x12,
+    }
+    TextureWrap { Repeat, Mirror, Clamp, ClampToBorder, MirrorClampToEdge }
+    FilterMode { Linear, Nearest }
+    MipmapFilterMode { None, Linear, Nearest }
+    TextureAccess { Static, RenderTarget, Storage }
+    TextureKind { Texture2D, CubeMap, Texture2DArray, Texture3D }
+    StencilOp {
+        Keep, Zero, Replace, IncrementClamp, DecrementClamp, Invert, IncrementWrap, DecrementWrap,
+    }
+    CompareFunc { Always, Never, Less, Equal, LessOrEqual, Greater, NotEqual, GreaterOrEqual }
+    CullFace { Nothing, Front, Back }
+    FrontFaceOrder { Clockwise, CounterClockwise }
+    Comparison { Never, Less, LessOrEqual, Greater, GreaterOrEqual, Equal, NotEqual, Always }
+    Equation { Add, Subtract, ReverseSubtract }
+    BlendValue { SourceColor, SourceAlpha, DestinationColor, DestinationAlpha }
+    PrimitiveType { Triangles, Lines, Points, TriangleStrip, LineStrip, LineLoop, TriangleFan }
+    BufferType { VertexBuffer, IndexBuffer, StorageBuffer, IndirectBuffer }
+    BufferUsage { Immutable, Dynamic, Stream }
+    ImageAccess { ReadOnly, WriteOnly, ReadWrite }
+    MemoryBarrier {
+        VertexAttribArray, ElementArray, Uniform, TextureFetch, ShaderImageAccess,
+        BufferUpdate, TextureUpdate, Framebuffer, ShaderStorage, All,
+    }
+}
+
+macro_rules! wire_struct {
+    ($($ty:ident { $($field:ident),* $(,)? })*) => {$(
+        impl Wire for $ty {
+            fn write(&self, w: &mut Vec<u8>) {
+                $(self.$field.write(w);)*
+            }
+            fn read(r: &mut Reader) -> Result<$ty, CaptureError> {
+                Ok($ty { $($field: Wire::read(r)?),* })
+            }
+        }
+    )*};
+}
+
+wire_struct! {
+    UniformDesc { name, uniform_type, array_count }
+    UniformBlockLayout { uniforms }
+    UniformBlockDesc { name, layout }
+    ShaderMeta { uniforms, uniform_blocks, images }
+    BufferLayout { stride, step_func, step_rate }
+    VertexAttribute { name, format, buffer_index, gl_pass_as_float }
+    TextureParams {
+        kind, format, wrap, min_filter, mag_filter, mipmap_filter,
+        width, height, depth, allocate_mipmaps, sample_count,
+    }
+    SamplerParams {
+        min_filter, mag_filter, mipmap_filter, wrap_x, wrap_y, wrap_z,
+        border_color, max_anisotropy, lod_bias, min_lod, max_lod, compare,
+    }
+    BlendState { equation, sfactor, dfactor }
+    AttachmentBlend { color_blend, alpha_blend, color_write }
+    StencilFaceState { fail_op, depth_fail_op, pass_op, test_func, test_ref, test_mask, write_mask }
+    StencilState { front, back }
+    PipelineParams {
+        cull_face, front_face_order, depth_test, depth_write, depth_write_offset,
+        color_blend, alpha_blend, stencil_test, color_write, attachment_blend,
+        primitive_type, primitive_restart,
+    }
+    DrawRange { base_element, num_elements, base_vertex, num_instances }
+    StorageImage { texture, level, access }
+    RenderTarget { texture, mip_level, layer }
+}
+
+impl Wire for usize {
+    fn write(&self, w: &mut Vec<u8>) {
+        (*self as u64).write(w)
+    }
+    fn read(r: &mut Reader) -> Result<usize, CaptureError> {
+        let value = u64::read(r)?;
+        usize::try_from(value)
+            .map_err(|_| CaptureError::Invalid(format!("{} does not fit into usize", value)))
+    }
+}
+
+impl Wire for bool {
+    fn write(&self, w: &mut Vec<u8>) {
+        w.push(*self as u8)
+    }
+    fn read(r: &mut Reader) -> Result<bool, CaptureError> {
+        Ok(r.u8()? != 0)
+    }
+}
+
+impl Wire for Vec<u8> {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.len().write(w);
+        w.extend_from_slice(self);
+    }
+    fn read(r: &mut Reader) -> Result<Vec<u8>, CaptureError> {
+        let len = usize::read(r)?;
+        Ok(r.take(len)?.to_vec())
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn write(&self, w: &mut Vec<u8>) {
+        (self.len() as u32).write(w);
+        for item in self {
+            item.write(w);
+        }
+    }
+    fn read(r: &mut Reader) -> Result<Vec<T>, CaptureError> {
+        let len = u32::read(r)?;
+        (0..len).map(|_| T::read(r)).collect()
+    }
+}
+
+impl<T: Wire, const N: usize> Wire for [T; N] {
+    fn write(&self, w: &mut Vec<u8>) {
+        for item in self {
+            item.write(w);
+        }
+    }
+    fn read(r: &mut Reader) -> Result<[T; N], CaptureError> {
+        let items = (0..N).map(|_| T::read(r)).collect::<Result<Vec<T>, _>>()?;
+        Ok(items.try_into().ok().unwrap())
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            None => w.push(0),
+            Some(value) => {
+                w.push(1);
+                value.write(w);
+            }
+        }
+    }
+    fn read(r: &mut Reader) -> Result<Option<T>, CaptureError> {
+        match r.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(T::read(r)?)),
+        }
+    }
+}
+
+impl<A: Wire, B: Wire> Wire for (A, B) {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.0.write(w);
+        self.1.write(w);
+    }
+    fn read(r: &mut Reader) -> Result<(A, B), CaptureError> {
+        Ok((A::read(r)?, B::read(r)?))
+    }
+}
+
+impl<A: Wire, B: Wire, C: Wire, D: Wire> Wire for (A, B, C, D) {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.0.write(w);
+        self.1.write(w);
+        self.2.write(w);
+        self.3.write(w);
+    }
+    fn read(r: &mut Reader) -> Result<(A, B, C, D), CaptureError> {
+        Ok((A::read(r)?, B::read(r)?, C::read(r)?, D::read(r)?))
+    }
+}
+
+impl Wire for String {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.as_bytes().to_vec().write(w)
+    }
+    fn read(r: &mut Reader) -> Result<String, CaptureError> {
+        String::from_utf8(Vec::<u8>::read(r)?)
+            .map_err(|_| CaptureError::Invalid("string is not utf-8".to_string()))
+    }
+}
+
+// Vertex attribute names. `new_pipeline` wants them 'static, so replayed names
+// are leaked, once per distinct name in a capture.
+impl Wire for &'static str {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.to_string().write(w)
+    }
+    fn read(r: &mut Reader) -> Result<&'static str, CaptureError> {
+        let name = String::read(r)?;
+        if let Some(name) = r.names.get(name.as_str()) {
+            return Ok(name);
+        }
+        let name: &'static str = Box::leak(name.into_boxed_str());
+        r.names.insert(name);
+        Ok(name)
+    }
+}
+
+impl Wire for BlendFactor {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            BlendFactor::Zero => w.push(0),
+            BlendFactor::One => w.push(1),
+            BlendFactor::Value(value) => {
+                w.push(2);
+                value.write(w);
+            }
+            BlendFactor::OneMinusValue(value) => {
+                w.push(3);
+                value.write(w);
+            }
+            BlendFactor::SourceAlphaSaturate => w.push(4),
+        }
+    }
+    fn read(r: &mut Reader) -> Result<BlendFactor, CaptureError> {
+        match r.u8()? {
+            0 => Ok(BlendFactor::Zero),
+            1 => Ok(BlendFactor::One),
+            2 => Ok(BlendFactor::Value(Wire::read(r)?)),
+            3 => Ok(BlendFactor::OneMinusValue(Wire::read(r)?)),
+            4 => Ok(BlendFactor::SourceAlphaSaturate),
+            tag => Err(CaptureError::Invalid(format!(
+                "{} is not a BlendFactor",
+                tag
+            ))),
+        }
+    }
+}
+
+impl Wire for PassAction {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            PassAction::Nothing => w.push(0),
+            PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => {
+                w.push(1);
+                color.write(w);
+                depth.write(w);
+                stencil.write(w);
+            }
+        }
+    }
+    fn read(r: &mut Reader) -> Result<PassAction, CaptureError> {
+        match r.u8()? {
+            0 => Ok(PassAction::Nothing),
+            1 => Ok(PassAction::Clear {
+                color: Wire::read(r)?,
+                depth: Wire::read(r)?,
+                stencil: Wire::read(r)?,
+            }),
+            tag => Err(CaptureError::Invalid(format!(
+                "{} is not a PassAction",
+                tag
+            ))),
+        }
+    }
+}
+
+impl Wire for ResourceId {
+    fn write(&self, w: &mut Vec<u8>) {
+        self.index.write(w);
+        self.generation.write(w);
+    }
+    fn read(r: &mut Reader) -> Result<ResourceId, CaptureError> {
+        Ok(ResourceId {
+            index: Wire::read(r)?,
+            generation: Wire::read(r)?,
+        })
+    }
+}
+
+macro_rules! wire_id {
+    ($($ty:ident),*) => {$(
+        impl Wire for $ty {
+            fn write(&self, w: &mut Vec<u8>) {
+                self.0.write(w)
+            }
+            fn read(r: &mut Reader) -> Result<$ty, CaptureError> {
+                Ok($ty(Wire::read(r)?))
+            }
+        }
+    )*};
+}
+
+wire_id!(ShaderId, RenderPass, Pipeline, BufferId, SamplerId);
+
+impl Wire for TextureId {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self.0 {
+            TextureIdInner::Managed(id) => {
+                w.push(0);
+                id.write(w);
+            }
+            TextureIdInner::Raw(RawId::OpenGl(texture)) => {
+                w.push(1);
+                texture.write(w);
+            }
+            #[cfg(target_vendor = "apple")]
+            TextureIdInner::Raw(RawId::Metal(_)) => w.push(2),
+        }
+    }
+    fn read(r: &mut Reader) -> Result<TextureId, CaptureError> {
+        match r.u8()? {
+            0 => Ok(TextureId(TextureIdInner::Managed(Wire::read(r)?))),
+            1 => Ok(TextureId::from_raw_id(RawId::OpenGl(Wire::read(r)?))),
+            2 => Err(CaptureError::Invalid(
+                "raw Metal textures could not be replayed".to_string(),
+            )),
+            tag => Err(CaptureError::Invalid(format!("{} is not a TextureId", tag))),
+        }
+    }
+}
+
+/// Owned `ShaderSource`.
+enum Source {
+    Glsl {
+        vertex: String,
+        fragment: String,
+    },
+    GlslCompute {
+        compute: String,
+    },
+    Msl {
+        program: String,
+    },
+    /// Closures could not be saved, only that there was a shader.
+    Native,
+}
+
+impl Source {
+    fn new(source: &ShaderSource) -> Source {
+        match *source {
+            ShaderSource::Glsl { vertex, fragment } => Source::Glsl {
+                vertex: vertex.to_string(),
+                fragment: fragment.to_string(),
+            },
+            ShaderSource::GlslCompute { compute } => Source::GlslCompute {
+                compute: compute.to_string(),
+            },
+            ShaderSource::Msl { program } => Source::Msl {
+                program: program.to_string(),
+            },
+            ShaderSource::Native(_) => Source::Native,
+        }
+    }
+
+    fn as_source(&self) -> ShaderSource<'_> {
+        match self {
+            Source::Glsl { vertex, fragment } => ShaderSource::Glsl { vertex, fragment },
+            Source::GlslCompute { compute } => ShaderSource::GlslCompute { compute },
+            Source::Msl { program } => ShaderSource::Msl { program },
+            Source::Native => unreachable!("native shaders are never read from a capture"),
+        }
+    }
+}
+
+impl Wire for Source {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            Source::Glsl { vertex, fragment } => {
+                w.push(0);
+                vertex.write(w);
+                fragment.write(w);
+            }
+            Source::GlslCompute { compute } => {
+                w.push(1);
+                compute.write(w);
+            }
+            Source::Msl { program } => {
+                w.push(2);
+                program.write(w);
+            }
+            Source::Native => w.push(3),
+        }
+    }
+    fn read(r: &mut Reader) -> Result<Source, CaptureError> {
+        match r.u8()? {
+            0 => Ok(Source::Glsl {
+                vertex: Wire::read(r)?,
+                fragment: Wire::read(r)?,
+            }),
+            1 => Ok(Source::GlslCompute {
+                compute: Wire::read(r)?,
+            }),
+            2 => Ok(Source::Msl {
+                program: Wire::read(r)?,
+            }),
+            3 => Err(CaptureError::Invalid(
+                "native shaders could not be replayed".to_string(),
+            )),
+            tag => Err(CaptureError::Invalid(format!(
+                "{} is not a ShaderSource",
+                tag
+            ))),
+        }
+    }
+}
+
+/// Owned `TextureSource`.
+enum TextureData {
+    Empty,
+    Bytes(Vec<u8>),
+    Array(Vec<Vec<Vec<u8>>>),
+}
+
+impl TextureData {
+    fn new(source: &TextureSource) -> TextureData {
+        match source {
+            TextureSource::Empty => TextureData::Empty,
+            TextureSource::Bytes(bytes) => TextureData::Bytes(bytes.to_vec()),
+            TextureSource::Array(faces) => TextureData::Array(
+                faces
+                    .iter()
+                    .map(|levels| levels.iter().map(|level| level.to_vec()).collect())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Wire for TextureData {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            TextureData::Empty => w.push(0),
+            TextureData::Bytes(bytes) => {
+                w.push(1);
+                bytes.write(w);
+            }
+            TextureData::Array(faces) => {
+                w.push(2);
+                faces.write(w);
+            }
+        }
+    }
+    fn read(r: &mut Reader) -> Result<TextureData, CaptureError> {
+        match r.u8()? {
+            0 => Ok(TextureData::Empty),
+            1 => Ok(TextureData::Bytes(Wire::read(r)?)),
+            2 => Ok(TextureData::Array(Wire::read(r)?)),
+            tag => Err(CaptureError::Invalid(format!(
+                "{} is not a TextureSource",
+                tag
+            ))),
+        }
+    }
+}
+
+/// Owned `BufferSource`.
+enum BufferData {
+    Slice { bytes: Vec<u8>, element_size: usize },
+    Empty { size: usize, element_size: usize },
+}
+
+impl BufferData {
+    fn new(source: &BufferSource) -> BufferData {
+        match source {
+            BufferSource::Slice(data) => BufferData::Slice {
+                bytes: arg_bytes(data).to_vec(),
+                element_size: data.element_size,
+            },
+            BufferSource::Empty { size, element_size } => BufferData::Empty {
+                size: *size,
+                element_size: *element_size,
+            },
+        }
+    }
+
+    fn as_source(&self) -> BufferSource<'_> {
+        match self {
+            BufferData::Slice {
+                bytes,
+                element_size,
+            } => unsafe { BufferSource::pointer(bytes.as_ptr(), bytes.len(), *element_size) },
+            BufferData::Empty { size, element_size } => BufferSource::Empty {
+                size: *size,
+                element_size: *element_size,
+            },
+        }
+    }
+}
+
+impl Wire for BufferData {
+    fn write(&self, w: &mut Vec<u8>) {
+        match self {
+            BufferData::Slice {
+                bytes,
+                element_size,
+            } => {
+                w.push(0);
+                bytes.write(w);
+                element_size.write(w);
+            }
+            BufferData::Empty { size, element_size } => {
+                w.push(1);
+                size.write(w);
+                element_size.write(w);
+            }
+        }
+    }
+    fn read(r: &mut Reader) -> Result<BufferData, CaptureError> {
+        match r.u8()? {
+            0 => Ok(BufferData::Slice {
+                bytes: Wire::read(r)?,
+                element_size: Wire::read(r)?,
+            }),
+            1 => Ok(BufferData::Empty {
+                size: Wire::read(r)?,
+                element_size: Wire::read(r)?,
+            }),
+            tag => Err(CaptureError::Invalid(format!(
+                "{} is not a BufferSource",
+                tag
+            ))),
+        }
+    }
+}
+
+fn arg_bytes<'a>(arg: &Arg<'a>) -> &'a [u8] {
+    unsafe { std::slice::from_raw_parts(arg.ptr as *const u8, arg.size) }
+}
+
+macro_rules! commands {
+    ($($opcode:literal => $name:ident { $($field:ident: $ty:ty),* $(,)? },)*) => {
+        enum Command {
+            $($name { $($field: $ty),* },)*
+        }
+
+        impl Wire for Command {
+            fn write(&self, w: &mut Vec<u8>) {
+                match self {
+                    $(Command::$name { $($field),* } => {
+                        w.push($opcode);
+                        $($field.write(w);)*
+                    })*
+                }
+            }
+            fn read(r: &mut Reader) -> Result<Command, CaptureError> {
+                match r.u8()? {
+                    $($opcode => Ok(Command::$name { $($field: Wire::read(r)?),* }),)*
+                    opcode => Err(CaptureError::Invalid(format!("unknown command {}", opcode))),
+                }
+            }
+        }
+    };
+}
+
+// Opcodes are part of the format: new commands get new opcodes,
+// changes to the existing ones bump VERSION.
+commands! {
+    0 => NewShader { id: ShaderId, source: Source, meta: ShaderMeta },
+    1 => DeleteShader { shader: ShaderId },
+    2 => NewTexture { id: TextureId, access: TextureAccess, data: TextureData, params: TextureParams },
+    3 => TextureSetMinFilter { texture: TextureId, filter: FilterMode, mipmap_filter: MipmapFilterMode },
+    4 => TextureSetMagFilter { texture: TextureId, filter: FilterMode },
+    5 => TextureSetWrap { texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap },
+    6 => TextureGenerateMipmaps { texture: TextureId },
+    7 => TextureResize { texture: TextureId, width: u32, height: u32, bytes: Option<Vec<u8>> },
+    8 => TextureUpdateMipPart {
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: Vec<u8>,
+    },
+    9 => DeleteTexture { texture: TextureId },
+    10 => NewRenderPass {
+        id: RenderPass,
+        color_img: Vec<RenderTarget>,
+        resolve_img: Option<Vec<TextureId>>,
+        depth_img: Option<RenderTarget>,
+    },
+    11 => DeleteRenderPass { render_pass: RenderPass },
+    12 => NewPipeline {
+        id: Pipeline,
+        buffer_layout: Vec<BufferLayout>,
+        attributes: Vec<VertexAttribute>,
+        shader: ShaderId,
+        params: PipelineParams,
+    },
+    13 => NewComputePipeline { id: Pipeline, shader: ShaderId },
+    14 => ApplyPipeline { pipeline: Pipeline },
+    15 => DeletePipeline { pipeline: Pipeline },
+    16 => NewBuffer { id: BufferId, type_: BufferType, usage: BufferUsage, data: BufferData },
+    17 => BufferUpdatePart { buffer: BufferId, byte_offset: usize, element_size: usize, bytes: Vec<u8> },
+    18 => BufferMapWrite { buffer: BufferId, byte_offset: usize, bytes: Vec<u8> },
+    19 => DeleteBuffer { buffer: BufferId },
+    20 => ApplyViewport { x: i32, y: i32, w: i32, h: i32 },
+    21 => ApplyScissorRect { x: i32, y: i32, w: i32, h: i32 },
+    22 => ApplyBindings {
+        vertex_buffers: Vec<BufferId>,
+        index_buffer: Option<BufferId>,
+        textures: Vec<TextureId>,
+        samplers: Vec<Option<SamplerId>>,
+    },
+    23 => ApplyUniforms { bytes: Vec<u8> },
+    24 => ApplyUniformBlock { block_index: usize, bytes: Vec<u8> },
+    25 => Clear { color: Option<(f32, f32, f32, f32)>, depth: Option<f32>, stencil: Option<i32> },
+    26 => BeginDefaultPass { action: PassAction },
+    27 => BeginPass { pass: Option<RenderPass>, action: PassAction },
+    28 => EndRenderPass {},
+    29 => CommitFrame {},
+    30 => Draw { base_element: i32, num_elements: i32, num_instances: i32 },
+    31 => DrawArrays { first_vertex: i32, num_vertices: i32, num_instances: i32 },
+    32 => MultiDraw { draws: Vec<DrawRange> },
+    33 => MultiDrawIndirect { indirect_buffer: BufferId, offset: usize, draw_count: usize },
+    34 => ApplyComputeBindings {
+        storage_buffers: Vec<BufferId>,
+        storage_images: Vec<StorageImage>,
+        images: Vec<TextureId>,
+    },
+    35 => Dispatch { groups_x: u32, groups_y: u32, groups_z: u32 },
+    36 => MemoryBarrier { barriers: Vec<MemoryBarrier> },
+    37 => ShaderReplace { shader: ShaderId, source: Source, meta: ShaderMeta },
+    38 => NewSampler { id: SamplerId, params: SamplerParams },
+    39 => DeleteSampler { sampler: SamplerId },
+}
+
+/// Wraps any `RenderingBackend` and records every call that changes what is
+/// rendered into a capture: resource creation with all the data, pipelines,
+/// passes, bindings, uniforms and draws. The capture could be saved and
+/// replayed later with `Replayer`, on another machine as well.
+///
+/// ```ignore
+/// let ctx = Box::new(RecordingBackend::new(window::new_rendering_backend()));
+/// // ... a few frames later, for a bug report:
+/// ctx.save("frame.mqcap").unwrap();
+/// ```
+///
+/// Queries, fences and reads only observe the GPU and are forwarded without
+/// being recorded. The capture is kept in memory, starting from the creation
+/// of the wrapper, so the wrapper should be created before any resource.
+pub struct RecordingBackend<B: RenderingBackend + ?Sized = dyn RenderingBackend> {
+    backend: Box<B>,
+    capture: RefCell<Vec<u8>>,
+    frames: usize,
+}
+
+impl<B: RenderingBackend + ?Sized> RecordingBackend<B> {
+    pub fn new(backend: Box<B>) -> RecordingBackend<B> {
+        let mut capture = MAGIC.to_vec();
+        VERSION.write(&mut capture);
+        RecordingBackend {
+            backend,
+            capture: RefCell::new(capture),
+            frames: 0,
+        }
+    }
+
+    /// Number of `commit_frame` calls recorded so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Everything recorded so far, ready for `Replayer::new`.
+    pub fn capture(&self) -> Vec<u8> {
+        self.capture.borrow().clone()
+    }
+
+    /// Write the capture into a file, recording continues.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, &*self.capture.borrow())
+    }
+
+    pub fn into_inner(self) -> Box<B> {
+        self.backend
+    }
+
+    fn record(&self, command: Command) {
+        command.write(&mut self.capture.borrow_mut());
+    }
+}
+
+impl<B: RenderingBackend + ?Sized> RenderingBackend for RecordingBackend<B> {
+    fn info(&self) -> ContextInfo {
+        self.backend.info()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let id = self.backend.new_shader(shader, meta.clone())?;
+        self.record(Command::NewShader {
+            id,
+            source: Source::new(&shader),
+            meta,
+        });
+        Ok(id)
+    }
+
+    fn new_shader_reflected(
+        &mut self,
+        shader: ShaderSource,
+    ) -> Result<(ShaderId, ShaderMeta), ShaderError> {
+        let (id, meta) = self.backend.new_shader_reflected(shader)?;
+        self.record(Command::NewShader {
+            id,
+            source: Source::new(&shader),
+            meta: meta.clone(),
+        });
+        Ok((id, meta))
+    }
+
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        self.backend.shader_replace(shader, source, meta.clone())?;
+        self.record(Command::ShaderReplace {
+            shader,
+            source: Source::new(&source),
+            meta,
+        });
+        Ok(())
+    }
+
+    fn shader_reflection(&self, shader: ShaderId) -> Option<&ShaderReflection> {
+        self.backend.shader_reflection(shader)
+    }
+
+    fn new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        let recorded = TextureData::new(&data);
+        let id = self.backend.new_texture(access, data, params);
+        self.record(Command::NewTexture {
+            id,
+            access,
+            data: recorded,
+            params,
+        });
+        id
+    }
+
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, GraphicsError> {
+        let recorded = TextureData::new(&data);
+        let id = self.backend.try_new_texture(access, data, params)?;
+        self.record(Command::NewTexture {
+            id,
+            access,
+            data: recorded,
+            params,
+        });
+        Ok(id)
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.backend.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.backend.texture_raw_id(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        self.record(Command::TextureSetMinFilter {
+            texture,
+            filter,
+            mipmap_filter,
+        });
+        self.backend
+            .texture_set_min_filter(texture, filter, mipmap_filter)
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.record(Command::TextureSetMagFilter { texture, filter });
+        self.backend.texture_set_mag_filter(texture, filter)
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        self.record(Command::TextureSetWrap {
+            texture,
+            wrap_x,
+            wrap_y,
+        });
+        self.backend.texture_set_wrap(texture, wrap_x, wrap_y)
+    }
+
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId {
+        let id = self.backend.new_sampler(params);
+        self.record(Command::NewSampler { id, params });
+        id
+    }
+
+    fn try_new_sampler(&mut self, params: SamplerParams) -> Result<SamplerId, GraphicsError> {
+        let id = self.backend.try_new_sampler(params)?;
+        self.record(Command::NewSampler { id, params });
+        Ok(id)
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        self.record(Command::TextureGenerateMipmaps { texture });
+        self.backend.texture_generate_mipmaps(texture)
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        self.record(Command::TextureResize {
+            texture,
+            width,
+            height,
+            bytes: bytes.map(|bytes| bytes.to_vec()),
+        });
+        self.backend.texture_resize(texture, width, height, bytes)
+    }
+
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        bytes: &mut [u8],
+    ) {
+        self.backend
+            .texture_read_mip_pixels(texture, mip_level, layer, bytes)
+    }
+
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
+        self.backend.texture_read_pixels_async(texture, rect)
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        self.backend.poll_readback(readback)
+    }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        self.backend.take_readback(readback)
+    }
+
+    fn texture_update_mip_part(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.record(Command::TextureUpdateMipPart {
+            texture,
+            mip_level,
+            layer,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            bytes: bytes.to_vec(),
+        });
+        self.backend.texture_update_mip_part(
+            texture, mip_level, layer, x_offset, y_offset, width, height, bytes,
+        )
+    }
+
+    fn new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> RenderPass {
+        let id = self
+            .backend
+            .new_render_pass_targets(color_img, resolve_img, depth_img);
+        self.record(Command::NewRenderPass {
+            id,
+            color_img: color_img.to_vec(),
+            resolve_img: resolve_img.map(|resolve_img| resolve_img.to_vec()),
+            depth_img,
+        });
+        id
+    }
+
+    fn try_new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> Result<RenderPass, GraphicsError> {
+        let id = self
+            .backend
+            .try_new_render_pass_targets(color_img, resolve_img, depth_img)?;
+        self.record(Command::NewRenderPass {
+            id,
+            color_img: color_img.to_vec(),
+            resolve_img: resolve_img.map(|resolve_img| resolve_img.to_vec()),
+            depth_img,
+        });
+        Ok(id)
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        self.backend.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.record(Command::DeleteRenderPass { render_pass });
+        self.backend.delete_render_pass(render_pass)
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        self.backend.try_delete_render_pass(render_pass)?;
+        self.record(Command::DeleteRenderPass { render_pass });
+        Ok(())
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        let id = self
+            .backend
+            .new_pipeline(buffer_layout, attributes, shader, params);
+        self.record(Command::NewPipeline {
+            id,
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
+            shader,
+            params,
+        });
+        id
+    }
+
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, GraphicsError> {
+        let id = self
+            .backend
+            .try_new_pipeline(buffer_layout, attributes, shader, params)?;
+        self.record(Command::NewPipeline {
+            id,
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
+            shader,
+            params,
+        });
+        Ok(id)
+    }
+
+    fn new_compute_pipeline(&mut self, shader: ShaderId) -> Pipeline {
+        let id = self.backend.new_compute_pipeline(shader);
+        self.record(Command::NewComputePipeline { id, shader });
+        id
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.record(Command::ApplyPipeline {
+            pipeline: *pipeline,
+        });
+        self.backend.apply_pipeline(pipeline)
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.record(Command::DeletePipeline { pipeline });
+        self.backend.delete_pipeline(pipeline)
+    }
+
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.backend.try_delete_pipeline(pipeline)?;
+        self.record(Command::DeletePipeline { pipeline });
+        Ok(())
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let recorded = BufferData::new(&data);
+        let id = self.backend.new_buffer(type_, usage, data);
+        self.record(Command::NewBuffer {
+            id,
+            type_,
+            usage,
+            data: recorded,
+        });
+        id
+    }
+
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, GraphicsError> {
+        let recorded = BufferData::new(&data);
+        let id = self.backend.try_new_buffer(type_, usage, data)?;
+        self.record(Command::NewBuffer {
+            id,
+            type_,
+            usage,
+            data: recorded,
+        });
+        Ok(id)
+    }
+
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
+        let (bytes, element_size) = match &data {
+            BufferSource::Slice(data) => (arg_bytes(data).to_vec(), data.element_size),
+            BufferSource::Empty { size, element_size } => (vec![0; *size], *element_size),
+        };
+        self.record(Command::BufferUpdatePart {
+            buffer,
+            byte_offset,
+            element_size,
+            bytes,
+        });
+        self.backend.buffer_update_part(buffer, byte_offset, data)
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        self.backend.buffer_read(buffer, byte_offset, data)
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        // mapped memory is write-only, so the data goes through a copy
+        let mut bytes = vec![0; size];
+        f(&mut bytes);
+        self.backend
+            .buffer_map_write(buffer, byte_offset, size, &mut |data| {
+                data.copy_from_slice(&bytes)
+            });
+        self.record(Command::BufferMapWrite {
+            buffer,
+            byte_offset,
+            bytes,
+        });
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.backend.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.record(Command::DeleteBuffer { buffer });
+        self.backend.delete_buffer(buffer)
+    }
+
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_buffer(buffer)?;
+        self.record(Command::DeleteBuffer { buffer });
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        self.backend.new_query(kind)
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        self.backend.begin_query(query)
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        self.backend.end_query(query)
+    }
+
+    fn query_result(&mut self, query: QueryId) -> Option<u64> {
+        self.backend.query_result(query)
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        self.backend.delete_query(query)
+    }
+
+    fn insert_fence(&mut self) -> FenceId {
+        self.backend.insert_fence()
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        self.backend.fence_signaled(fence)
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        self.backend.delete_fence(fence)
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.record(Command::DeleteTexture { texture });
+        self.backend.delete_texture(texture)
+    }
+
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_texture(texture)?;
+        self.record(Command::DeleteTexture { texture });
+        Ok(())
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        self.record(Command::DeleteSampler { sampler });
+        self.backend.delete_sampler(sampler)
+    }
+
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_sampler(sampler)?;
+        self.record(Command::DeleteSampler { sampler });
+        Ok(())
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.record(Command::DeleteShader { shader: program });
+        self.backend.delete_shader(program)
+    }
+
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_shader(program)?;
+        self.record(Command::DeleteShader { shader: program });
+        Ok(())
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(Command::ApplyViewport { x, y, w, h });
+        self.backend.apply_viewport(x, y, w, h)
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(Command::ApplyScissorRect { x, y, w, h });
+        self.backend.apply_scissor_rect(x, y, w, h)
+    }
+
+    fn apply_bindings_with_samplers(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
+        textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
+    ) {
+        self.record(Command::ApplyBindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+            textures: textures.to_vec(),
+            samplers: samplers.to_vec(),
+        });
+        self.backend
+            .apply_bindings_with_samplers(vertex_buffers, index_buffer, textures, samplers)
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.record(Command::ApplyUniforms {
+            bytes: bytes.to_vec(),
+        });
+        self.backend.apply_uniforms_from_bytes(uniform_ptr, size)
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.record(Command::ApplyUniformBlock {
+            block_index,
+            bytes: bytes.to_vec(),
+        });
+        self.backend
+            .apply_uniform_block_from_bytes(block_index, uniform_ptr, size)
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record(Command::Clear {
+            color,
+            depth,
+            stencil,
+        });
+        self.backend.clear(color, depth, stencil)
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.record(Command::BeginDefaultPass {
+            action: copy_action(&action),
+        });
+        self.backend.begin_default_pass(action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.record(Command::BeginPass {
+            pass,
+            action: copy_action(&action),
+        });
+        self.backend.begin_pass(pass, action)
+    }
+
+    fn end_render_pass(&mut self) {
+        self.record(Command::EndRenderPass {});
+        self.backend.end_render_pass()
+    }
+
+    fn commit_frame(&mut self) {
+        self.record(Command::CommitFrame {});
+        self.frames += 1;
+        self.backend.commit_frame()
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.record(Command::Draw {
+            base_element,
+            num_elements,
+            num_instances,
+        });
+        self.backend.draw(base_element, num_elements, num_instances)
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        self.record(Command::DrawArrays {
+            first_vertex,
+            num_vertices,
+            num_instances,
+        });
+        self.backend
+            .draw_arrays(first_vertex, num_vertices, num_instances)
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        self.record(Command::MultiDraw {
+            draws: draws.to_vec(),
+        });
+        self.backend.multi_draw(draws)
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize) {
+        self.record(Command::MultiDrawIndirect {
+            indirect_buffer,
+            offset,
+            draw_count,
+        });
+        self.backend
+            .multi_draw_indirect(indirect_buffer, offset, draw_count)
+    }
+
+    fn apply_compute_bindings(&mut self, bindings: &ComputeBindings) {
+        self.record(Command::ApplyComputeBindings {
+            storage_buffers: bindings.storage_buffers.clone(),
+            storage_images: bindings.storage_images.clone(),
+            images: bindings.images.clone(),
+        });
+        self.backend.apply_compute_bindings(bindings)
+    }
+
+    fn dispatch(&mut self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        self.record(Command::Dispatch {
+            groups_x,
+            groups_y,
+            groups_z,
+        });
+        self.backend.dispatch(groups_x, groups_y, groups_z)
+    }
+
+    fn memory_barrier(&mut self, barriers: &[MemoryBarrier]) {
+        self.record(Command::MemoryBarrier {
+            barriers: barriers.to_vec(),
+        });
+        self.backend.memory_barrier(barriers)
+    }
+}
+
+/// Replays a capture made with `RecordingBackend` against any `RenderingBackend`,
+/// usually a fresh `GlContext`, headless or not.
+///
+/// The capture is replayed frame by frame, so it could be bisected: replay
+/// the first N frames, read the render pass attachments back with
+/// `texture_read_pixels` and diff them against golden images.
+/// See `examples/capture_replay.rs`.
+///
+/// The default framebuffer is presented by the windowing code, not by the
+/// backend, and its content could not be read back after `commit_frame`.
+pub struct Replayer {
+    commands: Vec<Command>,
+    next: usize,
+    frame: usize,
+    frame_count: usize,
+    ids: ReplayedIds,
+}
+
+/// Ids created by the replay, by the recorded ids.
+#[derive(Default)]
+struct ReplayedIds {
+    shaders: HashMap<ShaderId, ShaderId>,
+    textures: HashMap<TextureId, TextureId>,
+    samplers: HashMap<SamplerId, SamplerId>,
+    passes: HashMap<RenderPass, RenderPass>,
+    pipelines: HashMap<Pipeline, Pipeline>,
+    buffers: HashMap<BufferId, BufferId>,
+}
+
+fn replayed<K, V>(ids: &HashMap<K, V>, id: K) -> Result<V, CaptureError>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug,
+    V: Copy,
+{
+    ids.get(&id)
+        .copied()
+        .ok_or_else(|| CaptureError::Invalid(format!("{:?} is not created by the capture", id)))
+}
+
+impl Replayer {
+    /// Decode the whole capture, nothing is replayed yet.
+    pub fn new(capture: &[u8]) -> Result<Replayer, CaptureError> {
+        if !capture.starts_with(MAGIC) {
+            return Err(CaptureError::NotACapture);
+        }
+        let mut reader = Reader {
+            data: &capture[MAGIC.len()..],
+            names: HashSet::new(),
+        };
+        let version = u32::read(&mut reader)?;
+        if version != VERSION {
+            return Err(CaptureError::UnsupportedVersion(version));
+        }
+        let mut commands = vec![];
+        while !reader.data.is_empty() {
+            commands.push(Command::read(&mut reader)?);
+        }
+        let frame_count = commands
+            .iter()
+            .filter(|command| matches!(command, Command::CommitFrame {}))
+            .count();
+        Ok(Replayer {
+            commands,
+            next: 0,
+            frame: 0,
+            frame_count,
+            ids: ReplayedIds::default(),
+        })
+    }
+
+    /// Number of frames in the capture.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Number of frames replayed so far.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Replay the commands of the next frame, up to and including its `commit_frame`.
+    /// Returns `false` once the whole capture is replayed.
+    ///
+    /// On error the replay stops at the failed command.
+    pub fn replay_frame(&mut self, ctx: &mut dyn RenderingBackend) -> Result<bool, CaptureError> {
+        if self.next == self.commands.len() {
+            return Ok(false);
+        }
+        while self.next < self.commands.len() {
+            let command = &self.commands[self.next];
+            let frame_end = matches!(command, Command::CommitFrame {});
+            self.ids.execute(ctx, command)?;
+            self.next += 1;
+            if frame_end {
+                self.frame += 1;
+                break;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Render passes alive at this point of the replay, in the order they were
+    /// created while recording. Their color attachments are the golden images.
+    pub fn render_passes(&self) -> Vec<RenderPass> {
+        let mut passes: Vec<_> = self.ids.passes.iter().collect();
+        passes.sort_by_key(|(recorded, _)| (recorded.0.index, recorded.0.generation));
+        passes.into_iter().map(|(_, pass)| *pass).collect()
+    }
+}
+
+impl ReplayedIds {
+    fn texture(&self, texture: TextureId) -> Result<TextureId, CaptureError> {
+        match texture.0 {
+            TextureIdInner::Raw(_) => Ok(texture),
+            TextureIdInner::Managed(_) => replayed(&self.textures, texture),
+        }
+    }
+
+    fn render_target(&self, target: RenderTarget) -> Result<RenderTarget, CaptureError> {
+        Ok(RenderTarget {
+            texture: self.texture(target.texture)?,
+            ..target
+        })
+    }
+
+    fn textures(&self, textures: &[TextureId]) -> Result<Vec<TextureId>, CaptureError> {
+        textures
+            .iter()
+            .map(|&texture| self.texture(texture))
+            .collect()
+    }
+
+    fn buffers(&self, buffers: &[BufferId]) -> Result<Vec<BufferId>, CaptureError> {
+        buffers
+            .iter()
+            .map(|&buffer| replayed(&self.buffers, buffer))
+            .collect()
+    }
+
+    fn execute(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        command: &Command,
+    ) -> Result<(), CaptureError> {
+        match command {
+            Command::NewShader { id, source, meta } => {
+                let shader = ctx
+                    .new_shader(source.as_source(), meta.clone())
+                    .map_err(CaptureError::Shader)?;
+                self.shaders.insert(*id, shader);
+            }
+            Command::DeleteShader { shader } => {
+                ctx.delete_shader(replayed(&self.shaders, *shader)?);
+                self.shaders.remove(shader);
+            }
+            Command::ShaderReplace {
+                shader,
+                source,
+                meta,
+            } => {
+                ctx.shader_replace(
+                    replayed(&self.shaders, *shader)?,
+                    source.as_source(),
+                    meta.clone(),
+                )
+                .map_err(CaptureError::Shader)?;
+            }
+            Command::NewTexture {
+                id,
+                access,
+                data,
+                params,
+            } => {
+                let faces: Vec<Vec<&[u8]>>;
+                let levels: Vec<&[&[u8]]>;
+                let source = match data {
+                    TextureData::Empty => TextureSource::Empty,
+                    TextureData::Bytes(bytes) => TextureSource::Bytes(bytes),
+                    TextureData::Array(array) => {
+                        faces = array
+                            .iter()
+                            .map(|face| face.iter().map(|level| &level[..]).collect())
+                            .collect();
+                        levels = faces.iter().map(|face| &face[..]).collect();
+                        TextureSource::Array(&levels)
+                    }
+                };
+                let texture = ctx.new_texture(*access, source, *params);
+                self.textures.insert(*id, texture);
+            }
+            Command::TextureSetMinFilter {
+                texture,
+                filter,
+                mipmap_filter,
+            } => ctx.texture_set_min_filter(self.texture(*texture)?, *filter, *mipmap_filter),
+            Command::TextureSetMagFilter { texture, filter } => {
+                ctx.texture_set_mag_filter(self.texture(*texture)?, *filter)
+            }
+            Command::TextureSetWrap {
+                texture,
+                wrap_x,
+                wrap_y,
+            } => ctx.texture_set_wrap(self.texture(*texture)?, *wrap_x, *wrap_y),
+            Command::TextureGenerateMipmaps { texture } => {
+                ctx.texture_generate_mipmaps(self.texture(*texture)?)
+            }
+            Command::TextureResize {
+                texture,
+                width,
+                height,
+                bytes,
+            } => ctx.texture_resize(self.texture(*texture)?, *width, *height, bytes.as_deref()),
+            Command::TextureUpdateMipPart {
+                texture,
+                mip_level,
+                layer,
+                x_offset,
+                y_offset,
+                width,
+                height,
+                bytes,
+            } => ctx.texture_update_mip_part(
+                self.texture(*texture)?,
+                *mip_level,
+                *layer,
+                *x_offset,
+                *y_offset,
+                *width,
+                *height,
+                bytes,
+            ),
+            Command::DeleteTexture { texture } => {
+                ctx.delete_texture(self.texture(*texture)?);
+                self.textures.remove(texture);
+            }
+            Command::NewSampler { id, params } => {
+                let sampler = ctx.new_sampler(*params);
+                self.samplers.insert(*id, sampler);
+            }
+            Command::DeleteSampler { sampler } => {
+                ctx.delete_sampler(replayed(&self.samplers, *sampler)?);
+                self.samplers.remove(sampler);
+            }
+            Command::NewRenderPass {
+                id,
+                color_img,
+                resolve_img,
+                depth_img,
+            } => {
+                let color_img = color_img
+                    .iter()
+                    .map(|&target| self.render_target(target))
+                    .collect::<Result<Vec<_>, CaptureError>>()?;
+                let resolve_img = match resolve_img {
+                    Some(resolve_img) => Some(self.textures(resolve_img)?),
+                    None => None,
+                };
+                let depth_img = match depth_img {
+                    Some(depth_img) => Some(self.render_target(*depth_img)?),
+                    None => None,
+                };
+                let pass =
+                    ctx.new_render_pass_targets(&color_img, resolve_img.as_deref(), depth_img);
+                self.passes.insert(*id, pass);
+            }
+            Command::DeleteRenderPass { render_pass } => {
+                ctx.delete_render_pass(replayed(&self.passes, *render_pass)?);
+                self.passes.remove(render_pass);
+            }
+            Command::NewPipeline {
+                id,
+                buffer_layout,
+                attributes,
+                shader,
+                params,
+            } => {
+                let shader = replayed(&self.shaders, *shader)?;
+                let pipeline = ctx.new_pipeline(buffer_layout, attributes, shader, *params);
+                self.pipelines.insert(*id, pipeline);
+            }
+            Command::NewComputePipeline { id, shader } => {
+                let pipeline = ctx.new_compute_pipeline(replayed(&self.shaders, *shader)?);
+                self.pipelines.insert(*id, pipeline);
+            }
+            Command::ApplyPipeline { pipeline } => {
+                ctx.apply_pipeline(&replayed(&self.pipelines, *pipeline)?)
+            }
+            Command::DeletePipeline { pipeline } => {
+                ctx.delete_pipeline(replayed(&self.pipelines, *pipeline)?);
+                self.pipelines.remove(pipeline);
+            }
+            Command::NewBuffer {
+                id,
+                type_,
+                usage,
+                data,
+            } => {
+                let buffer = ctx.new_buffer(*type_, *usage, data.as_source());
+                self.buffers.insert(*id, buffer);
+            }
+            Command::BufferUpdatePart {
+                buffer,
+                byte_offset,
+                element_size,
+                bytes,
+            } => {
+                let data =
+                    unsafe { BufferSource::pointer(bytes.as_ptr(), bytes.len(), *element_size) };
+                ctx.buffer_update_part(replayed(&self.buffers, *buffer)?, *byte_offset, data)
+            }
+            Command::BufferMapWrite {
+                buffer,
+                byte_offset,
+                bytes,
+            } => ctx.buffer_map_write(
+                replayed(&self.buffers, *buffer)?,
+                *byte_offset,
+                bytes.len(),
+                &mut |data| data.copy_from_slice(bytes),
+            ),
+            Command::DeleteBuffer { buffer } => {
+                ctx.delete_buffer(replayed(&self.buffers, *buffer)?);
+                self.buffers.remove(buffer);
+            }
+            Command::ApplyViewport { x, y, w, h } => ctx.apply_viewport(*x, *y, *w, *h),
+            Command::ApplyScissorRect { x, y, w, h } => ctx.apply_scissor_rect(*x, *y, *w, *h),
+            Command::ApplyBindings {
+                vertex_buffers,
+                index_buffer,
+                textures,
+                samplers,
+            } => {
+                let index_buffer = match index_buffer {
+                    Some(index_buffer) => Some(replayed(&self.buffers, *index_buffer)?),
+                    None => None,
+                };
+                let samplers = samplers
+                    .iter()
+                    .map(|sampler| match sampler {
+                        Some(sampler) => replayed(&self.samplers, *sampler).map(Some),
+                        None => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                ctx.apply_bindings_with_samplers(
+                    &self.buffers(vertex_buffers)?,
+                    index_buffer,
+                    &self.textures(textures)?,
+                    &samplers,
+                )
+            }
+            Command::ApplyUniforms { bytes } => {
+                ctx.apply_uniforms_from_bytes(bytes.as_ptr(), bytes.len())
+            }
+            Command::ApplyUniformBlock { block_index, bytes } => {
+                ctx.apply_uniform_block_from_bytes(*block_index, bytes.as_ptr(), bytes.len())
+            }
+            Command::Clear {
+                color,
+                depth,
+                stencil,
+            } => ctx.clear(*color, *depth, *stencil),
+            Command::BeginDefaultPass { action } => ctx.begin_default_pass(copy_action(action)),
+            Command::BeginPass { pass, action } => {
+                let pass = match pass {
+                    Some(pass) => Some(replayed(&self.passes, *pass)?),
+                    None => None,
+                };
+                ctx.begin_pass(pass, copy_action(action))
+            }
+            Command::EndRenderPass {} => ctx.end_render_pass(),
+            Command::CommitFrame {} => ctx.commit_frame(),
+            Command::Draw {
+                base_element,
+                num_elements,
+                num_instances,
+            } => ctx.draw(*base_element, *num_elements, *num_instances),
+            Command::DrawArrays {
+                first_vertex,
+                num_vertices,
+                num_instances,
+            } => ctx.draw_arrays(*first_vertex, *num_vertices, *num_instances),
+            Command::MultiDraw { draws } => ctx.multi_draw(draws),
+            Command::MultiDrawIndirect {
+                indirect_buffer,
+                offset,
+                draw_count,
+            } => ctx.multi_draw_indirect(
+                replayed(&self.buffers, *indirect_buffer)?,
+                *offset,
+                *draw_count,
+            ),
+            Command::ApplyComputeBindings {
+                storage_buffers,
+                storage_images,
+                images,
+            } => {
+                let storage_images = storage_images
+                    .iter()
+                    .map(|image| {
+                        Ok(StorageImage {
+                            texture: self.texture(image.texture)?,
+                            ..*image
+                        })
+                    })
+                    .collect::<Result<_, CaptureError>>()?;
+                ctx.apply_compute_bindings(&ComputeBindings {
+                    storage_buffers: self.buffers(storage_buffers)?,
+                    storage_images,
+                    images: self.textures(images)?,
+                })
+            }
+            Command::Dispatch {
+                groups_x,
+                groups_y,
+                groups_z,
+            } => ctx.dispatch(*groups_x, *groups_y, *groups_z),
+            Command::MemoryBarrier { barriers } => ctx.memory_barrier(barriers),
+        }
+        Ok(())
+    }
+}
+
+// PassAction is neither Clone nor Copy
+fn copy_action(action: &PassAction) -> PassAction {
+    match *action {
+        PassAction::Nothing => PassAction::Nothing,
+        PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        } => PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        },
+    }
+}
+
+#[test]
+fn test_capture_header() {
+    assert!(matches!(
+        Replayer::new(b"not a capture"),
+        Err(CaptureError::NotACapture)
+    ));
+
+    let mut capture = MAGIC.to_vec();
+    VERSION.write(&mut capture);
+    Command::ApplyViewport {
+        x: 0,
+        y: 0,
+        w: 640,
+        h: 480,
+    }
+    .write(&mut capture);
+    Command::CommitFrame {}.write(&mut capture);
+    let replayer = Replayer::new(&capture).unwrap();
+    assert_eq!(replayer.frame_count(), 1);
+
+    assert!(matches!(
+        Replayer::new(&capture[..capture.len() - 4]),
+        Err(CaptureError::Truncated)
+    ));
+}
+
+#[test]
+fn test_capture_round_trip() {
+    let path = std::env::temp_dir().join(format!("miniquad_capture_{}.mqcap", std::process::id()));
+
+    // draws and resources, replayed against a NullContext
+    let mut recording = RecordingBackend::new(Box::new(NullContext::new()));
+    let glsl = ShaderSource::Glsl {
+        vertex: "",
+        fragment: "",
+    };
+    let (_, pipeline, vertex_buffer) =
+        new_fullscreen_triangle(&mut recording, glsl, ShaderMeta::default());
+    for _ in 0..2 {
+        recording.begin_default_pass(PassAction::clear_color(0., 0., 0., 1.));
+        recording.apply_pipeline(&pipeline);
+        recording.apply_bindings_from_slice(&[vertex_buffer], None, &[]);
+        recording.draw_arrays(0, 3, 1);
+        recording.end_render_pass();
+        recording.commit_frame();
+    }
+    recording.save(&path).unwrap();
+    let recorded = recording.into_inner();
+
+    let mut replayer = Replayer::new(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(replayer.frame_count(), 2);
+    let mut ctx = NullContext::new();
+    assert!(replayer.replay_frame(&mut ctx).unwrap());
+    assert_eq!((ctx.frames(), ctx.draw_calls()), (1, 1));
+    assert!(replayer.replay_frame(&mut ctx).unwrap());
+    assert!(!replayer.replay_frame(&mut ctx).unwrap());
+    assert_eq!(replayer.frame(), 2);
+    assert_eq!((ctx.frames(), ctx.draw_calls()), (2, 2));
+    assert_eq!(ctx.resource_counts(), recorded.resource_counts());
+
+    // texture data and render passes, replayed against a SoftwareContext
+    let mut recording = RecordingBackend::new(Box::new(SoftwareContext::new(1, 1)));
+    let texture = recording.new_texture_from_rgba8(1, 1, &[1, 2, 3, 4]);
+    let color = recording.new_render_texture(TextureParams {
+        width: 2,
+        height: 2,
+        ..Default::default()
+    });
+    let pass = recording.new_render_pass(color, None);
+    recording.begin_pass(Some(pass), PassAction::clear_color(1., 0., 1., 1.));
+    recording.end_render_pass();
+    recording.commit_frame();
+    recording.save(&path).unwrap();
+    let mut recorded = recording.into_inner();
+    let capture = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut replayer = Replayer::new(&capture).unwrap();
+    let mut ctx = SoftwareContext::new(1, 1);
+    assert!(replayer.replay_frame(&mut ctx).unwrap());
+    let passes = replayer.render_passes();
+    assert_eq!(passes.len(), 1);
+    let mut pixels = [0; 16];
+    ctx.texture_read_pixels(ctx.render_pass_texture(passes[0]), &mut pixels);
+    assert_eq!(pixels, [255, 0, 255, 255].repeat(4)[..]);
+    let mut recorded_pixels = [0; 16];
+    recorded.texture_read_pixels(color, &mut recorded_pixels);
+    assert_eq!(pixels, recorded_pixels);
+    let replayed_texture = replayer.ids.texture(texture).unwrap();
+    let mut texel = [0; 4];
+    ctx.texture_read_pixels(replayed_texture, &mut texel);
+    assert_eq!(texel, [1, 2, 3, 4]);
+}
@@ -1,11 +1,13 @@
-use std::ffi::CString;
+use std::{collections::HashMap, ffi::CString};
 
 use crate::{window, ResourceManager};
 
 mod cache;
+mod program_cache;
 
 use super::*;
 use cache::*;
+use program_cache::ProgramCache;
 
 /// Raw OpenGL bindings
 /// Highly unsafe, some of the functions could be missing due to incompatible GL version
@@ -21,7 +23,7 @@ pub mod raw_gl {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 struct Buffer {
     gl_buf: GLuint,
     buffer_type: BufferType,
@@ -30,6 +32,34 @@ struct Buffer {
     // used only as a type argument for glDrawElements and can be
     // 1, 2 or 4
     index_type: Option<u32>,
+    usage: BufferUsage,
+    // Contents of an IndirectBuffer on contexts without GL_DRAW_INDIRECT_BUFFER,
+    // draw arguments are read on the CPU instead
+    cpu_copy: Option<Vec<u8>>,
+}
+
+struct Readback {
+    pbo: GLuint,
+    size: usize,
+    // null for synchronous readbacks, which are done right away
+    fence: GLsync,
+    data: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Query {
+    gl_query: GLuint,
+    kind: QueryKind,
+}
+
+fn gl_query_target(kind: QueryKind) -> GLenum {
+    match kind {
+        QueryKind::TimeElapsed => GL_TIME_ELAPSED,
+        QueryKind::Timestamp => GL_TIMESTAMP,
+        QueryKind::AnySamplesPassed => GL_ANY_SAMPLES_PASSED,
+        QueryKind::SamplesPassed => GL_SAMPLES_PASSED,
+        QueryKind::PrimitivesGenerated => GL_PRIMITIVES_GENERATED,
+    }
 }
 
 #[derive(Debug)]
@@ -39,10 +69,161 @@ struct ShaderUniform {
     array_count: i32,
 }
 
+impl ShaderUniform {
+    unsafe fn apply(&self, data: *const u8) {
+        use UniformType::*;
+
+        let data_int = data as *const i32;
+        let data = data as *const f32;
+
+        if let Some(gl_loc) = self.gl_loc {
+            match self.uniform_type {
+                Float1 => {
+                    glUniform1fv(gl_loc, self.array_count, data);
+                }
+                Float2 => {
+                    glUniform2fv(gl_loc, self.array_count, data);
+                }
+                Float3 => {
+                    glUniform3fv(gl_loc, self.array_count, data);
+                }
+                Float4 => {
+                    glUniform4fv(gl_loc, self.array_count, data);
+                }
+                Int1 => {
+                    glUniform1iv(gl_loc, self.array_count, data_int);
+                }
+                Int2 => {
+                    glUniform2iv(gl_loc, self.array_count, data_int);
+                }
+                Int3 => {
+                    glUniform3iv(gl_loc, self.array_count, data_int);
+                }
+                Int4 => {
+                    glUniform4iv(gl_loc, self.array_count, data_int);
+                }
+                Mat4 => {
+                    glUniformMatrix4fv(gl_loc, self.array_count, 0, data);
+                }
+            }
+        }
+    }
+}
+
+struct ShaderUniformBlock {
+    // std140 offset of each field, used for per-field glUniform calls
+    // when uniform buffers are not available
+    uniforms: Vec<(usize, ShaderUniform)>,
+    size: usize,
+}
+
+impl ShaderUniformBlock {
+    /// GL2/WebGL1 fallback: no uniform buffers, set each field separately.
+    unsafe fn apply_fields(&self, data: &[u8]) {
+        for (offset, uniform) in &self.uniforms {
+            let size = uniform.uniform_type.size();
+            let count = uniform.array_count.max(1) as usize;
+            // std140 pads array elements to vec4
+            let padded = count > 1 && size % 16 != 0;
+            let stride = if padded { 16 } else { size };
+            assert!(
+                offset + stride * (count - 1) + size <= data.len(),
+                "Uniform block data does not match its layout"
+            );
+            if padded {
+                // glUniform*v wants the elements packed
+                let mut packed = Vec::with_capacity(size * count);
+                for i in 0..count {
+                    let element = offset + i * 16;
+                    packed.extend_from_slice(&data[element..element + size]);
+                }
+                uniform.apply(packed.as_ptr());
+            } else {
+                uniform.apply(data[*offset..].as_ptr());
+            }
+        }
+    }
+}
+
 struct ShaderInternal {
     program: GLuint,
     images: Vec<ShaderImage>,
     uniforms: Vec<ShaderUniform>,
+    uniform_blocks: Vec<ShaderUniformBlock>,
+    reflection: ShaderReflection,
+}
+
+const UNIFORM_RING_SIZE: usize = 3;
+const UNIFORM_RING_MIN_CAPACITY: usize = 64 * 1024;
+
+/// Uniform buffers backing `apply_uniform_block`, one per frame in flight.
+/// Each block upload takes the next aligned range of the current buffer,
+/// so the ranges used by earlier draws are never overwritten during the frame.
+struct UniformRing {
+    buffers: [GLuint; UNIFORM_RING_SIZE],
+    capacities: [usize; UNIFORM_RING_SIZE],
+    current: usize,
+    offset: usize,
+    alignment: usize,
+    // outgrown buffers, ranges from those could still be bound until the end of the frame
+    retired: Vec<GLuint>,
+}
+
+impl UniformRing {
+    fn new() -> UniformRing {
+        let mut alignment = 0;
+        unsafe {
+            glGetIntegerv(GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut alignment);
+        }
+        UniformRing {
+            buffers: [0; UNIFORM_RING_SIZE],
+            capacities: [0; UNIFORM_RING_SIZE],
+            current: 0,
+            offset: 0,
+            alignment: (alignment as usize).max(1),
+            retired: vec![],
+        }
+    }
+
+    /// Copy `size` bytes into the ring, reserving `reserve` bytes for them.
+    /// Returns the buffer and the offset of the data.
+    unsafe fn push(&mut self, data: *const u8, size: usize, reserve: usize) -> (GLuint, usize) {
+        let mut offset = (self.offset + self.alignment - 1) / self.alignment * self.alignment;
+        if offset + reserve > self.capacities[self.current] {
+            if self.buffers[self.current] != 0 {
+                self.retired.push(self.buffers[self.current]);
+            }
+            let capacity = (self.capacities[self.current] * 2)
+                .max(reserve.next_power_of_two())
+                .max(UNIFORM_RING_MIN_CAPACITY);
+            let mut buffer = 0;
+            glGenBuffers(1, &mut buffer);
+            glBindBuffer(GL_UNIFORM_BUFFER, buffer);
+            glBufferData(
+                GL_UNIFORM_BUFFER,
+                capacity as _,
+                std::ptr::null(),
+                GL_STREAM_DRAW,
+            );
+            self.buffers[self.current] = buffer;
+            self.capacities[self.current] = capacity;
+            offset = 0;
+        }
+
+        let buffer = self.buffers[self.current];
+        glBindBuffer(GL_UNIFORM_BUFFER, buffer);
+        glBufferSubData(GL_UNIFORM_BUFFER, offset as _, size as _, data as _);
+        self.offset = offset + reserve;
+        (buffer, offset)
+    }
+
+    fn next_frame(&mut self) {
+        for buffer in self.retired.drain(..) {
+            unsafe { glDeleteBuffers(1, &buffer) };
+        }
+        self.current = (self.current + 1) % UNIFORM_RING_SIZE;
+        self.offset = 0;
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -57,18 +238,121 @@ impl TextureOrRenderbuffer {
             _ => None,
         }
     }
-    fn renderbuffer(&self) -> Option<GLuint> {
-        match self {
-            TextureOrRenderbuffer::Renderbuffer(id) => Some(*id),
-            _ => None,
-        }
-    }
 }
 
 #[derive(Clone, Copy, Debug)]
 struct Texture {
     raw: TextureOrRenderbuffer,
     params: TextureParams,
+    access: TextureAccess,
+    // own sampling state, from `params` and texture_set_*
+    sampler: SamplerParams,
+}
+
+struct Sampler {
+    // 0 without features.sampler_objects
+    gl_sampler: GLuint,
+    params: SamplerParams,
+}
+
+/// Where `SamplerParams` are set: the texture bound to a target or a sampler object.
+#[derive(Clone, Copy)]
+enum SamplerTarget {
+    Texture(GLenum),
+    Sampler(GLuint),
+}
+
+impl SamplerTarget {
+    /// Sampling state of a new texture or sampler object.
+    fn gl_defaults() -> SamplerParams {
+        SamplerParams {
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Linear,
+            mipmap_filter: MipmapFilterMode::Linear,
+            wrap_x: TextureWrap::Repeat,
+            wrap_y: TextureWrap::Repeat,
+            wrap_z: TextureWrap::Repeat,
+            ..Default::default()
+        }
+    }
+
+    unsafe fn parameteri(self, pname: GLenum, param: GLint) {
+        match self {
+            SamplerTarget::Texture(target) => glTexParameteri(target, pname, param),
+            SamplerTarget::Sampler(sampler) => glSamplerParameteri(sampler, pname, param),
+        }
+    }
+
+    unsafe fn parameterf(self, pname: GLenum, param: GLfloat) {
+        match self {
+            SamplerTarget::Texture(target) => glTexParameterf(target, pname, param),
+            SamplerTarget::Sampler(sampler) => glSamplerParameterf(sampler, pname, param),
+        }
+    }
+
+    unsafe fn parameterfv(self, pname: GLenum, params: &[f32; 4]) {
+        match self {
+            SamplerTarget::Texture(target) => glTexParameterfv(target, pname, params.as_ptr()),
+            SamplerTarget::Sampler(sampler) => {
+                glSamplerParameterfv(sampler, pname, params.as_ptr())
+            }
+        }
+    }
+
+    /// Only sets what differs from `old`.
+    /// The parts the context could not do are left out, see `SamplerParams` docs.
+    unsafe fn set_params(self, info: &ContextInfo, params: &SamplerParams, old: &SamplerParams) {
+        let features = &info.features;
+        let desktop =
+            cfg!(not(target_arch = "wasm32")) && !info.gl_version_string.contains("OpenGL ES");
+
+        if params.wrap_x != old.wrap_x {
+            self.parameteri(GL_TEXTURE_WRAP_S, GLenum::from(params.wrap_x) as _);
+        }
+        if params.wrap_y != old.wrap_y {
+            self.parameteri(GL_TEXTURE_WRAP_T, GLenum::from(params.wrap_y) as _);
+        }
+        // GL_TEXTURE_WRAP_R is missing on GL2 and WebGL1, together with 3D textures
+        if features.texture_3d && params.wrap_z != old.wrap_z {
+            self.parameteri(GL_TEXTURE_WRAP_R, GLenum::from(params.wrap_z) as _);
+        }
+        if (params.min_filter, params.mipmap_filter) != (old.min_filter, old.mipmap_filter) {
+            let filter = Texture::gl_filter(params.min_filter, params.mipmap_filter);
+            self.parameteri(GL_TEXTURE_MIN_FILTER, filter as _);
+        }
+        if params.mag_filter != old.mag_filter {
+            let filter = match params.mag_filter {
+                FilterMode::Nearest => GL_NEAREST,
+                FilterMode::Linear => GL_LINEAR,
+            };
+            self.parameteri(GL_TEXTURE_MAG_FILTER, filter as _);
+        }
+        if features.clamp_to_border && params.border_color != old.border_color {
+            self.parameterfv(GL_TEXTURE_BORDER_COLOR, &params.border_color);
+        }
+        if features.texture_3d && params.min_lod != old.min_lod {
+            self.parameterf(GL_TEXTURE_MIN_LOD, params.min_lod);
+        }
+        if features.texture_3d && params.max_lod != old.max_lod {
+            self.parameterf(GL_TEXTURE_MAX_LOD, params.max_lod);
+        }
+        if desktop && params.lod_bias != old.lod_bias {
+            self.parameterf(GL_TEXTURE_LOD_BIAS, params.lod_bias);
+        }
+        if info.max_anisotropy > 1. && params.max_anisotropy != old.max_anisotropy {
+            let anisotropy = params.max_anisotropy.max(1.).min(info.max_anisotropy);
+            self.parameterf(GL_TEXTURE_MAX_ANISOTROPY, anisotropy);
+        }
+        if features.sampler_compare && params.compare != old.compare {
+            match params.compare {
+                Some(compare) => {
+                    self.parameteri(GL_TEXTURE_COMPARE_MODE, GL_COMPARE_REF_TO_TEXTURE as _);
+                    self.parameteri(GL_TEXTURE_COMPARE_FUNC, GLenum::from(compare) as _);
+                }
+                None => self.parameteri(GL_TEXTURE_COMPARE_MODE, GL_NONE as _),
+            }
+        }
+    }
 }
 
 impl TextureFormat {
@@ -83,6 +367,33 @@ impl TextureFormat {
             TextureFormat::Alpha => GL_ALPHA,
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => GL_R8,
+            TextureFormat::R8 => GL_R8,
+            TextureFormat::RG8 => GL_RG8,
+            TextureFormat::R16F => GL_R16F,
+            TextureFormat::RG16F => GL_RG16F,
+            TextureFormat::R32F => GL_R32F,
+            TextureFormat::RGBA32F => GL_RGBA32F,
+            TextureFormat::R32UI => GL_R32UI,
+            TextureFormat::SRGB8A8 => GL_SRGB8_ALPHA8,
+            TextureFormat::RGB10A2 => GL_RGB10_A2,
+            TextureFormat::Depth24Stencil8 => GL_DEPTH24_STENCIL8,
+            TextureFormat::BC1 => GL_COMPRESSED_RGBA_S3TC_DXT1_EXT,
+            TextureFormat::BC2 => GL_COMPRESSED_RGBA_S3TC_DXT3_EXT,
+            TextureFormat::BC3 => GL_COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            TextureFormat::BC4 => GL_COMPRESSED_RED_RGTC1,
+            TextureFormat::BC5 => GL_COMPRESSED_RG_RGTC2,
+            TextureFormat::BC6H => GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            TextureFormat::BC7 => GL_COMPRESSED_RGBA_BPTC_UNORM,
+            TextureFormat::ETC2RGB8 => GL_COMPRESSED_RGB8_ETC2,
+            TextureFormat::ETC2RGBA8 => GL_COMPRESSED_RGBA8_ETC2_EAC,
+            TextureFormat::EACR11 => GL_COMPRESSED_R11_EAC,
+            TextureFormat::EACRG11 => GL_COMPRESSED_RG11_EAC,
+            TextureFormat::ASTC4x4 => GL_COMPRESSED_RGBA_ASTC_4x4_KHR,
+            TextureFormat::ASTC5x5 => GL_COMPRESSED_RGBA_ASTC_5x5_KHR,
+            TextureFormat::ASTC6x6 => GL_COMPRESSED_RGBA_ASTC_6x6_KHR,
+            TextureFormat::ASTC8x8 => GL_COMPRESSED_RGBA_ASTC_8x8_KHR,
+            TextureFormat::ASTC10x10 => GL_COMPRESSED_RGBA_ASTC_10x10_KHR,
+            TextureFormat::ASTC12x12 => GL_COMPRESSED_RGBA_ASTC_12x12_KHR,
         }
     }
 }
@@ -100,6 +411,21 @@ impl From<TextureFormat> for (GLenum, GLenum, GLenum) {
             TextureFormat::Alpha => (GL_ALPHA, GL_ALPHA, GL_UNSIGNED_BYTE),
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => (GL_R8, GL_RED, GL_UNSIGNED_BYTE), // texture updates will swizzle Red -> Alpha to match WASM
+            TextureFormat::R8 => (GL_R8, GL_RED, GL_UNSIGNED_BYTE),
+            TextureFormat::RG8 => (GL_RG8, GL_RG, GL_UNSIGNED_BYTE),
+            TextureFormat::R16F => (GL_R16F, GL_RED, GL_HALF_FLOAT),
+            TextureFormat::RG16F => (GL_RG16F, GL_RG, GL_HALF_FLOAT),
+            TextureFormat::R32F => (GL_R32F, GL_RED, GL_FLOAT),
+            TextureFormat::RGBA32F => (GL_RGBA32F, GL_RGBA, GL_FLOAT),
+            TextureFormat::R32UI => (GL_R32UI, GL_RED_INTEGER, GL_UNSIGNED_INT),
+            TextureFormat::SRGB8A8 => (GL_SRGB8_ALPHA8, GL_RGBA, GL_UNSIGNED_BYTE),
+            TextureFormat::RGB10A2 => (GL_RGB10_A2, GL_RGBA, GL_UNSIGNED_INT_2_10_10_10_REV),
+            TextureFormat::Depth24Stencil8 => {
+                (GL_DEPTH24_STENCIL8, GL_DEPTH_STENCIL, GL_UNSIGNED_INT_24_8)
+            }
+            // compressed data is uploaded with glCompressedTexImage2D,
+            // that only takes the internal format
+            _ => (format.sized_internal_format(), 0, 0),
         }
     }
 }
@@ -109,9 +435,17 @@ impl From<TextureKind> for GLuint {
         match kind {
             TextureKind::Texture2D => GL_TEXTURE_2D,
             TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP,
+            TextureKind::Texture2DArray => GL_TEXTURE_2D_ARRAY,
+            TextureKind::Texture3D => GL_TEXTURE_3D,
         }
     }
 }
+
+impl TextureKind {
+    fn is_layered(self) -> bool {
+        matches!(self, TextureKind::Texture2DArray | TextureKind::Texture3D)
+    }
+}
 impl From<Equation> for GLenum {
     fn from(eq: Equation) -> Self {
         match eq {
@@ -155,6 +489,18 @@ impl From<StencilOp> for GLenum {
     }
 }
 
+impl From<TextureWrap> for GLenum {
+    fn from(wrap: TextureWrap) -> Self {
+        match wrap {
+            TextureWrap::Repeat => GL_REPEAT,
+            TextureWrap::Mirror => GL_MIRRORED_REPEAT,
+            TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
+            TextureWrap::ClampToBorder => GL_CLAMP_TO_BORDER,
+            TextureWrap::MirrorClampToEdge => GL_MIRROR_CLAMP_TO_EDGE,
+        }
+    }
+}
+
 impl From<CompareFunc> for GLenum {
     fn from(cf: CompareFunc) -> Self {
         match cf {
@@ -179,16 +525,53 @@ impl Texture {
     ) -> Texture {
         if let TextureSource::Bytes(bytes_data) = source {
             assert_eq!(
-                params.format.size(params.width, params.height) as usize,
+                params.format.size(params.width, params.height) as usize * params.depth as usize,
                 bytes_data.len()
             );
         }
+        if params.kind.is_layered() {
+            assert!(
+                ctx.info.features.texture_3d,
+                "Texture arrays and 3D textures are not supported by the GPU"
+            );
+            assert!(
+                params.sample_count <= 1,
+                "Multisampled texture arrays and 3D textures are not supported"
+            );
+        } else {
+            assert!(params.depth == 1, "depth is only used by layered textures");
+        }
         if access != TextureAccess::RenderTarget {
             assert!(
                 params.sample_count <= 1,
                 "Multisampling is only supported for render textures"
             );
         }
+        if access == TextureAccess::Storage {
+            assert!(
+                ctx.info.features.compute,
+                "Storage textures are not supported by the GPU"
+            );
+        }
+        let caps = ctx.info.texture_format_caps(params.format);
+        assert!(
+            caps.sample,
+            "{:?} textures are not supported by the GPU",
+            params.format
+        );
+        if access != TextureAccess::Static {
+            assert!(
+                caps.render,
+                "{:?} textures could not be rendered to on this GPU",
+                params.format
+            );
+        }
+        if params.format.is_compressed() {
+            assert!(
+                !params.kind.is_layered(),
+                "Compressed texture arrays and 3D textures are not supported"
+            );
+        }
         let (internal_format, format, pixel_type) = params.format.into();
 
         if access == TextureAccess::RenderTarget && params.sample_count > 1 {
@@ -208,12 +591,15 @@ impl Texture {
             return Texture {
                 raw: TextureOrRenderbuffer::Renderbuffer(renderbuffer),
                 params,
+                access,
+                sampler: SamplerParams::from_texture_params(&params),
             };
         }
 
         ctx.cache.store_texture_binding(0);
 
         let mut texture: GLuint = 0;
+        let sampler = SamplerParams::from_texture_params(&params);
 
         unsafe {
             glGenTextures(1, &mut texture as *mut _);
@@ -233,34 +619,28 @@ impl Texture {
             }
 
             match source {
-                TextureSource::Empty => {
+                _ if access == TextureAccess::Storage => {
+                    Self::new_storage(params, source);
+                }
+                _ if params.format.is_compressed() => {
+                    Self::new_compressed(params, source);
+                }
+                TextureSource::Empty | TextureSource::Bytes(_) => {
                     // not quite sure if glTexImage2D(null) is really a requirement
                     // but it was like this for quite a while and apparantly it works?
-                    glTexImage2D(
-                        GL_TEXTURE_2D,
-                        0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
-                        0,
-                        format,
-                        pixel_type,
-                        std::ptr::null() as _,
-                    );
-                }
-                TextureSource::Bytes(source) => {
-                    assert!(params.kind == TextureKind::Texture2D, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
-                    glTexImage2D(
-                        GL_TEXTURE_2D,
-                        0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
-                        0,
-                        format,
-                        pixel_type,
-                        source.as_ptr() as *const _,
-                    );
+                    let data = match source {
+                        TextureSource::Bytes(bytes) => {
+                            assert!(params.kind != TextureKind::CubeMap, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
+                            bytes.as_ptr()
+                        }
+                        _ => std::ptr::null(),
+                    };
+                    // all the levels are allocated up front,
+                    // so each of them could be updated or rendered to
+                    for level in 0..params.mip_levels() {
+                        let data = if level == 0 { data } else { std::ptr::null() };
+                        Self::tex_image(params, level, data);
+                    }
                 }
                 TextureSource::Array(array) => {
                     if params.kind == TextureKind::CubeMap {
@@ -269,24 +649,49 @@ impl Texture {
                             "Cubemaps require TextureSource::Array of 6 textures."
                         );
                     }
+                    if params.kind.is_layered() {
+                        assert!(
+                            array.len() == 1,
+                            "Layered textures require TextureSource::Array of 1 element."
+                        );
+                    }
                     for (cubemap_face, mipmaps) in array.iter().enumerate() {
                         if mipmaps.len() != 1 {
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
-                            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, array.len() as _);
+                            glTexParameteri(params.kind.into(), GL_TEXTURE_BASE_LEVEL, 0);
+                            glTexParameteri(
+                                params.kind.into(),
+                                GL_TEXTURE_MAX_LEVEL,
+                                mipmaps.len() as i32 - 1,
+                            );
                         }
                         for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
+                            if params.kind.is_layered() {
+                                glTexImage3D(
+                                    params.kind.into(),
+                                    mipmap_level as _,
+                                    internal_format as i32,
+                                    (params.width >> mipmap_level).max(1) as i32,
+                                    (params.height >> mipmap_level).max(1) as i32,
+                                    params.mip_layers(mipmap_level as _) as i32,
+                                    0,
+                                    format,
+                                    pixel_type,
+                                    bytes.as_ptr() as *const _,
+                                );
+                                continue;
+                            }
                             let target = match params.kind {
-                                TextureKind::Texture2D => GL_TEXTURE_2D,
                                 TextureKind::CubeMap => {
                                     GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
                                 }
+                                _ => GL_TEXTURE_2D,
                             };
                             glTexImage2D(
                                 target,
                                 mipmap_level as _,
                                 internal_format as i32,
-                                params.width as i32,
-                                params.height as i32,
+                                (params.width >> mipmap_level).max(1) as i32,
+                                (params.height >> mipmap_level).max(1) as i32,
                                 0,
                                 format,
                                 pixel_type,
@@ -297,28 +702,161 @@ impl Texture {
                 }
             }
 
-            let wrap = match params.wrap {
-                TextureWrap::Repeat => GL_REPEAT,
-                TextureWrap::Mirror => GL_MIRRORED_REPEAT,
-                TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
-            };
-
-            let min_filter = Self::gl_filter(params.min_filter, params.mipmap_filter);
-            let mag_filter = match params.mag_filter {
-                FilterMode::Nearest => GL_NEAREST,
-                FilterMode::Linear => GL_LINEAR,
-            };
-
-            glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_S, wrap as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_T, wrap as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_MIN_FILTER, min_filter as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_MAG_FILTER, mag_filter as i32);
+            SamplerTarget::Texture(params.kind.into()).set_params(
+                &ctx.info,
+                &sampler,
+                &SamplerTarget::gl_defaults(),
+            );
         }
         ctx.cache.restore_texture_binding(0);
 
         Texture {
             raw: TextureOrRenderbuffer::Texture(texture),
             params,
+            access,
+            sampler,
+        }
+    }
+
+    /// Immutable storage is required for image load/store on GLES.
+    /// Expects the texture to be bound.
+    unsafe fn new_storage(params: TextureParams, source: TextureSource) {
+        let (_, format, pixel_type) = params.format.into();
+        let target = params.kind.into();
+        let levels = match source {
+            TextureSource::Array(array) => array.iter().map(|mips| mips.len()).max().unwrap_or(1),
+            _ => params.mip_levels() as usize,
+        };
+
+        if params.kind.is_layered() {
+            glTexStorage3D(
+                target,
+                levels as _,
+                params.format.sized_internal_format(),
+                params.width as _,
+                params.height as _,
+                params.depth as _,
+            );
+        } else {
+            glTexStorage2D(
+                target,
+                levels as _,
+                params.format.sized_internal_format(),
+                params.width as _,
+                params.height as _,
+            );
+        }
+
+        let upload = |target: GLenum, level: usize, bytes: &[u8]| {
+            let width = (params.width >> level).max(1) as _;
+            let height = (params.height >> level).max(1) as _;
+            if params.kind.is_layered() {
+                glTexSubImage3D(
+                    target,
+                    level as _,
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                    params.mip_layers(level as _) as _,
+                    format,
+                    pixel_type,
+                    bytes.as_ptr() as *const _,
+                );
+            } else {
+                glTexSubImage2D(
+                    target,
+                    level as _,
+                    0,
+                    0,
+                    width,
+                    height,
+                    format,
+                    pixel_type,
+                    bytes.as_ptr() as *const _,
+                );
+            }
+        };
+        match source {
+            TextureSource::Empty => {}
+            TextureSource::Bytes(bytes) => {
+                assert!(params.kind != TextureKind::CubeMap, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
+                upload(target, 0, bytes);
+            }
+            TextureSource::Array(array) => {
+                for (cubemap_face, mipmaps) in array.iter().enumerate() {
+                    let target = match params.kind {
+                        TextureKind::CubeMap => {
+                            GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
+                        }
+                        _ => target,
+                    };
+                    for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
+                        upload(target, mipmap_level, bytes);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Block compressed data could only be uploaded with glCompressedTexImage2D.
+    /// Expects the texture to be bound.
+    unsafe fn new_compressed(params: TextureParams, source: TextureSource) {
+        let upload = |target: GLenum, level: usize, bytes: Option<&[u8]>| {
+            let width = (params.width >> level).max(1);
+            let height = (params.height >> level).max(1);
+            let size = params.format.size(width, height);
+            if let Some(bytes) = bytes {
+                assert_eq!(size as usize, bytes.len());
+            }
+            glCompressedTexImage2D(
+                target,
+                level as _,
+                params.format.sized_internal_format(),
+                width as _,
+                height as _,
+                0,
+                size as _,
+                bytes.map_or(std::ptr::null(), |bytes| bytes.as_ptr()) as *const _,
+            );
+        };
+        let face_target = |face: usize| match params.kind {
+            TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+            _ => GL_TEXTURE_2D,
+        };
+        let faces = if params.kind == TextureKind::CubeMap {
+            6
+        } else {
+            1
+        };
+
+        match source {
+            TextureSource::Empty => {
+                for level in 0..params.mip_levels() as usize {
+                    for face in 0..faces {
+                        upload(face_target(face), level, None);
+                    }
+                }
+            }
+            TextureSource::Bytes(bytes) => {
+                assert!(params.kind == TextureKind::Texture2D, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
+                upload(GL_TEXTURE_2D, 0, Some(bytes));
+            }
+            TextureSource::Array(array) => {
+                assert!(
+                    array.len() == faces,
+                    "Cubemaps require TextureSource::Array of 6 textures."
+                );
+                let levels = array.iter().map(|mips| mips.len()).max().unwrap_or(1);
+                glTexParameteri(params.kind.into(), GL_TEXTURE_BASE_LEVEL, 0);
+                glTexParameteri(params.kind.into(), GL_TEXTURE_MAX_LEVEL, levels as i32 - 1);
+                for (face, mipmaps) in array.iter().enumerate() {
+                    for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
+                        upload(face_target(face), mipmap_level, Some(bytes));
+                    }
+                }
+            }
         }
     }
 
@@ -327,6 +865,10 @@ impl Texture {
             .raw
             .texture()
             .expect("Resize not yet implemented for RenderBuffer(multisampled) textures");
+        assert!(
+            self.access != TextureAccess::Storage,
+            "TextureAccess::Storage textures could not be resized"
+        );
         ctx.cache.store_texture_binding(0);
         ctx.cache.bind_texture(0, self.params.kind.into(), raw);
 
@@ -336,6 +878,34 @@ impl Texture {
         self.params.height = height;
 
         unsafe {
+            if self.params.format.is_compressed() {
+                let source = match source {
+                    Some(source) => TextureSource::Bytes(source),
+                    Option::None => TextureSource::Empty,
+                };
+                Self::new_compressed(self.params, source);
+                ctx.cache.restore_texture_binding(0);
+                return;
+            }
+            if self.params.kind.is_layered() {
+                glTexImage3D(
+                    self.params.kind.into(),
+                    0,
+                    internal_format as i32,
+                    self.params.width as i32,
+                    self.params.height as i32,
+                    self.params.depth as i32,
+                    0,
+                    format,
+                    pixel_type,
+                    match source {
+                        Some(source) => source.as_ptr() as *const _,
+                        Option::None => std::ptr::null(),
+                    },
+                );
+                ctx.cache.restore_texture_binding(0);
+                return;
+            }
             glTexImage2D(
                 GL_TEXTURE_2D,
                 0,
@@ -355,18 +925,24 @@ impl Texture {
         ctx.cache.restore_texture_binding(0);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update_texture_part(
         &self,
         ctx: &mut GlContext,
+        mip_level: u32,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
         height: i32,
         source: &[u8],
     ) {
+        let (level_width, level_height) = self.params.mip_size(mip_level);
         assert_eq!(self.size(width as _, height as _), source.len());
-        assert!(x_offset + width <= self.params.width as _);
-        assert!(y_offset + height <= self.params.height as _);
+        assert!(x_offset + width <= level_width as _);
+        assert!(y_offset + height <= level_height as _);
+        assert!(mip_level < self.params.mip_levels());
+        assert!(layer < self.params.mip_layers(mip_level));
         let raw = self.raw.texture().expect(
             "update_texture_part not yet implemented for RenderBuffer(multisampled) textures",
         );
@@ -384,16 +960,49 @@ impl Texture {
                 if self.params.format == TextureFormat::Alpha {
                     // if alpha miniquad texture, the value on non-WASM is stored in red channel
                     // swizzle red -> alpha
-                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_A, GL_RED as _);
+                    glTexParameteri(self.params.kind.into(), GL_TEXTURE_SWIZZLE_A, GL_RED as _);
                 } else {
                     // keep alpha -> alpha
-                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_A, GL_ALPHA as _);
+                    glTexParameteri(self.params.kind.into(), GL_TEXTURE_SWIZZLE_A, GL_ALPHA as _);
                 }
             }
 
+            if self.params.format.is_compressed() {
+                glCompressedTexSubImage2D(
+                    Self::face_target(self.params, layer),
+                    mip_level as _,
+                    x_offset as _,
+                    y_offset as _,
+                    width as _,
+                    height as _,
+                    self.params.format.sized_internal_format(),
+                    source.len() as _,
+                    source.as_ptr() as *const _,
+                );
+                ctx.cache.restore_texture_binding(0);
+                return;
+            }
+            if self.params.kind.is_layered() {
+                glTexSubImage3D(
+                    self.params.kind.into(),
+                    mip_level as _,
+                    x_offset as _,
+                    y_offset as _,
+                    layer as _,
+                    width as _,
+                    height as _,
+                    1,
+                    format,
+                    pixel_type,
+                    source.as_ptr() as *const _,
+                );
+                ctx.cache.restore_texture_binding(0);
+                return;
+            }
+
             glTexSubImage2D(
-                GL_TEXTURE_2D,
-                0,
+                Self::face_target(self.params, layer),
+                mip_level as _,
                 x_offset as _,
                 y_offset as _,
                 width as _,
@@ -408,46 +1017,134 @@ impl Texture {
     }
 
     /// Read texture data into CPU memory
-    pub fn read_pixels(&self, bytes: &mut [u8]) {
-        let raw = self
-            .raw
-            .texture()
-            .expect("read_pixels not yet implemented for RenderBuffer(multisampled) textures");
+    pub fn read_pixels(&self, mip_level: u32, layer: u32, bytes: &mut [u8]) {
+        let (width, height) = self.params.mip_size(mip_level);
+        assert!(bytes.len() >= self.size(width, height));
+        let rect = (0, 0, width, height);
+        unsafe { self.read_pixels_rect(mip_level, layer, rect, bytes.as_mut_ptr() as _) };
+    }
+
+    /// glReadPixels of the given rect of a mip level and layer into `pixels`, which is either
+    /// a pointer to at least `format.size(width, height)` bytes or an offset into the bound
+    /// GL_PIXEL_PACK_BUFFER.
+    unsafe fn read_pixels_rect(
+        &self,
+        mip_level: u32,
+        layer: u32,
+        rect: (u32, u32, u32, u32),
+        pixels: *mut std::ffi::c_void,
+    ) {
+        let (x, y, width, height) = rect;
+        let (level_width, level_height) = self.params.mip_size(mip_level);
+        assert!(mip_level < self.params.mip_levels());
+        assert!(layer < self.params.mip_layers(mip_level));
+        assert!(x + width <= level_width && y + height <= level_height);
+        assert!(
+            !self.params.format.is_compressed(),
+            "Compressed textures could not be read back"
+        );
+        assert!(
+            self.raw.texture().is_some(),
+            "read_pixels not yet implemented for RenderBuffer(multisampled) textures"
+        );
 
         let (_, format, pixel_type) = self.params.format.into();
 
         let mut fbo = 0;
-        unsafe {
-            let mut binded_fbo: i32 = 0;
-            glGetIntegerv(gl::GL_DRAW_FRAMEBUFFER_BINDING, &mut binded_fbo);
-            glGenFramebuffers(1, &mut fbo);
-            glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
-            glFramebufferTexture2D(
-                gl::GL_FRAMEBUFFER,
-                gl::GL_COLOR_ATTACHMENT0,
-                gl::GL_TEXTURE_2D,
-                raw,
-                0,
-            );
+        let mut binded_fbo: i32 = 0;
+        glGetIntegerv(gl::GL_DRAW_FRAMEBUFFER_BINDING, &mut binded_fbo);
+        glGenFramebuffers(1, &mut fbo);
+        glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
+        self.attach(gl::GL_COLOR_ATTACHMENT0, mip_level, layer);
+
+        glPixelStorei(GL_PACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
+        glReadPixels(
+            x as _,
+            y as _,
+            width as _,
+            height as _,
+            format,
+            pixel_type,
+            pixels,
+        );
+
+        glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
+        glDeleteFramebuffers(1, &fbo);
+    }
+
+    #[inline]
+    fn size(&self, width: u32, height: u32) -> usize {
+        self.params.format.size(width, height) as usize
+    }
 
-            glReadPixels(
+    /// Allocate a whole mip level, every cubemap face gets the same `data`.
+    /// Expects the texture to be bound.
+    unsafe fn tex_image(params: TextureParams, level: u32, data: *const u8) {
+        let (internal_format, format, pixel_type) = params.format.into();
+        let (width, height) = params.mip_size(level);
+        if params.kind.is_layered() {
+            glTexImage3D(
+                params.kind.into(),
+                level as _,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                params.mip_layers(level) as i32,
                 0,
+                format,
+                pixel_type,
+                data as *const _,
+            );
+            return;
+        }
+        for face in 0..params.mip_layers(level) {
+            glTexImage2D(
+                Self::face_target(params, face),
+                level as _,
+                internal_format as i32,
+                width as i32,
+                height as i32,
                 0,
-                self.params.width as _,
-                self.params.height as _,
                 format,
                 pixel_type,
-                bytes.as_mut_ptr() as _,
+                data as *const _,
             );
+        }
+    }
 
-            glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
-            glDeleteFramebuffers(1, &fbo);
+    /// glTexImage2D target of a cubemap face, GL_TEXTURE_2D for other kinds.
+    fn face_target(params: TextureParams, face: u32) -> GLenum {
+        match params.kind {
+            TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP_POSITIVE_X + face,
+            _ => GL_TEXTURE_2D,
         }
     }
 
-    #[inline]
-    fn size(&self, width: u32, height: u32) -> usize {
-        self.params.format.size(width, height) as usize
+    /// Attach a mip level and layer of the texture to the bound GL_FRAMEBUFFER.
+    unsafe fn attach(&self, attachment: GLenum, mip_level: u32, layer: u32) {
+        match self.raw {
+            TextureOrRenderbuffer::Renderbuffer(raw) => {
+                glFramebufferRenderbuffer(GL_FRAMEBUFFER, attachment, GL_RENDERBUFFER, raw);
+            }
+            TextureOrRenderbuffer::Texture(raw) if self.params.kind.is_layered() => {
+                glFramebufferTextureLayer(
+                    GL_FRAMEBUFFER,
+                    attachment,
+                    raw,
+                    mip_level as _,
+                    layer as _,
+                );
+            }
+            TextureOrRenderbuffer::Texture(raw) => {
+                glFramebufferTexture2D(
+                    GL_FRAMEBUFFER,
+                    attachment,
+                    Self::face_target(self.params, layer),
+                    raw,
+                    mip_level as _,
+                );
+            }
+        }
     }
 
     fn gl_filter(filter: FilterMode, mipmap_filter: MipmapFilterMode) -> GLenum {
@@ -468,8 +1165,12 @@ impl Texture {
 
 pub(crate) struct PipelineInternal {
     layout: Vec<Option<VertexAttributeInternal>>,
+    // to resolve `layout` again on shader_replace
+    buffer_layout: Vec<BufferLayout>,
+    attributes: Vec<VertexAttribute>,
     shader: ShaderId,
     params: PipelineParams,
+    compute: bool,
 }
 
 type UniformLocation = Option<GLint>;
@@ -494,19 +1195,30 @@ pub(crate) struct RenderPassInternal {
     color_textures: Vec<TextureId>,
     resolves: Option<Vec<(u32, TextureId)>>,
     depth_texture: Option<TextureId>,
+    // of the first attachment, the pass is sized after it
+    mip_level: u32,
 }
 
-struct Textures(Vec<Texture>);
+struct Textures(ResourceManager<Texture>);
 impl Textures {
     fn get(&self, texture: TextureId) -> Texture {
         match texture.0 {
             TextureIdInner::Raw(RawId::OpenGl(texture)) => Texture {
                 raw: TextureOrRenderbuffer::Texture(texture),
                 params: Default::default(),
+                access: TextureAccess::Static,
+                sampler: SamplerParams::from_texture_params(&Default::default()),
             },
             #[cfg(target_vendor = "apple")]
             TextureIdInner::Raw(RawId::Metal(..)) => panic!("Metal texture in OpenGL context!"),
-            TextureIdInner::Managed(texture) => self.0[texture],
+            TextureIdInner::Managed(id) => self.0[id],
+        }
+    }
+
+    fn contains(&self, texture: TextureId) -> bool {
+        match texture.0 {
+            TextureIdInner::Managed(id) => self.0.contains(id),
+            TextureIdInner::Raw(_) => true,
         }
     }
 }
@@ -515,8 +1227,18 @@ pub struct GlContext {
     pipelines: ResourceManager<PipelineInternal>,
     passes: ResourceManager<RenderPassInternal>,
     buffers: ResourceManager<Buffer>,
+    queries: ResourceManager<Query>,
+    readbacks: ResourceManager<Readback>,
+    fences: ResourceManager<GLsync>,
     textures: Textures,
+    samplers: ResourceManager<Sampler>,
+    // textures currently sampled with another sampler's state, without sampler objects
+    emulated_samplers: HashMap<GLuint, SamplerParams>,
     default_framebuffer: GLuint,
+    uniform_ring: Option<UniformRing>,
+    // applied uniform blocks, for the glUniform fallback to re-apply on pipeline change
+    uniform_block_data: [Option<Vec<u8>>; MAX_UNIFORM_BLOCKS],
+    program_cache: Option<ProgramCache>,
     pub(crate) cache: GlCache,
     pub(crate) info: ContextInfo,
 }
@@ -540,13 +1262,27 @@ impl GlContext {
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
             let info = gl_info();
+            let program_cache = crate::program_cache_dir()
+                .and_then(|dir| ProgramCache::new(dir, &info.gl_version_string));
             GlContext {
                 default_framebuffer,
+                uniform_ring: if info.features.uniform_buffers {
+                    Some(UniformRing::new())
+                } else {
+                    None
+                },
+                uniform_block_data: Default::default(),
                 shaders: ResourceManager::default(),
                 pipelines: ResourceManager::default(),
                 passes: ResourceManager::default(),
                 buffers: ResourceManager::default(),
-                textures: Textures(vec![]),
+                queries: ResourceManager::default(),
+                readbacks: ResourceManager::default(),
+                fences: ResourceManager::default(),
+                textures: Textures(ResourceManager::default()),
+                samplers: ResourceManager::default(),
+                emulated_samplers: HashMap::new(),
+                program_cache,
                 info,
                 cache: GlCache {
                     stored_index_buffer: 0,
@@ -561,13 +1297,16 @@ impl GlContext {
                     alpha_blend: None,
                     stencil: None,
                     color_write: (true, true, true, true),
+                    attachment_blend: None,
                     cull_face: CullFace::Nothing,
+                    primitive_restart: false,
                     stored_texture: 0,
                     stored_target: 0,
                     textures: [CachedTexture {
                         target: 0,
                         texture: 0,
                     }; MAX_SHADERSTAGE_IMAGES],
+                    samplers: [0; MAX_SHADERSTAGE_IMAGES],
                     attributes: [None; MAX_VERTEX_ATTRIBUTES],
                 },
             }
@@ -577,26 +1316,117 @@ impl GlContext {
     pub fn features(&self) -> &Features {
         &self.info.features
     }
+
+    /// With `meta: None` the meta is taken from the program reflection.
+    fn load_shader_source(
+        &self,
+        shader: ShaderSource,
+        meta: Option<ShaderMeta>,
+    ) -> Result<ShaderInternal, ShaderError> {
+        match shader {
+            ShaderSource::Glsl { fragment, vertex } => load_shader_internal(
+                vertex,
+                fragment,
+                meta,
+                self.info.features.uniform_buffers,
+                self.program_cache.as_ref(),
+            ),
+            ShaderSource::GlslCompute { compute } => {
+                assert!(
+                    self.info.features.compute,
+                    "Compute shaders are not supported by the GPU"
+                );
+                load_compute_shader_internal(compute, meta, self.program_cache.as_ref())
+            }
+            ShaderSource::Msl { .. } => panic!("Metal source on OpenGl context"),
+            ShaderSource::Native(_) => panic!("Native shaders require SoftwareContext"),
+        }
+    }
+
+    /// Change the texture's own sampling state, `TextureParams` stay as they were created.
+    fn set_texture_sampler(&mut self, texture: TextureId, params: SamplerParams, method: &str) {
+        let t = self.textures.get(texture);
+        let raw = t.raw.texture().unwrap_or_else(|| {
+            panic!(
+                "{} not yet implemented for RenderBuffer(multisampled) textures",
+                method
+            )
+        });
+        let current = self.emulated_samplers.remove(&raw).unwrap_or(t.sampler);
+
+        self.cache.store_texture_binding(0);
+        self.cache.bind_texture(0, t.params.kind.into(), raw);
+        unsafe {
+            SamplerTarget::Texture(t.params.kind.into()).set_params(&self.info, &params, &current)
+        };
+        self.cache.restore_texture_binding(0);
+
+        if let TextureIdInner::Managed(id) = texture.0 {
+            self.textures.0[id].sampler = params;
+        }
+    }
 }
 
 fn load_shader_internal(
     vertex_shader: &str,
     fragment_shader: &str,
-    meta: ShaderMeta,
+    meta: Option<ShaderMeta>,
+    uniform_buffers: bool,
+    program_cache: Option<&ProgramCache>,
 ) -> Result<ShaderInternal, ShaderError> {
-    unsafe {
-        let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
-        let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader)?;
+    let key = program_cache.map(|cache| (cache, cache.key(&[vertex_shader, fragment_shader])));
+    if let Some(program) = key.and_then(|(cache, key)| cache.load(key)) {
+        return program_internal(program, meta, uniform_buffers);
+    }
 
+    let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
+    let fragment_shader = match load_shader(GL_FRAGMENT_SHADER, fragment_shader) {
+        Ok(shader) => shader,
+        Err(err) => {
+            unsafe { glDeleteShader(vertex_shader) };
+            return Err(err);
+        }
+    };
+    let program = link_program(&[vertex_shader, fragment_shader], key)?;
+    program_internal(program, meta, uniform_buffers)
+}
+
+fn load_compute_shader_internal(
+    compute_shader: &str,
+    meta: Option<ShaderMeta>,
+    program_cache: Option<&ProgramCache>,
+) -> Result<ShaderInternal, ShaderError> {
+    // compute shaders are GL4.3+ only, uniform buffers are always there
+    let key = program_cache.map(|cache| (cache, cache.key(&[compute_shader])));
+    if let Some(program) = key.and_then(|(cache, key)| cache.load(key)) {
+        return program_internal(program, meta, true);
+    }
+
+    let compute_shader = load_shader(GL_COMPUTE_SHADER, compute_shader)?;
+    let program = link_program(&[compute_shader], key)?;
+    program_internal(program, meta, true)
+}
+
+/// Links and deletes `shaders`. With `cache` the linked binary is stored under the key.
+fn link_program(
+    shaders: &[GLuint],
+    cache: Option<(&ProgramCache, u64)>,
+) -> Result<GLuint, ShaderError> {
+    unsafe {
         let program = glCreateProgram();
-        glAttachShader(program, vertex_shader);
-        glAttachShader(program, fragment_shader);
+        for shader in shaders {
+            glAttachShader(program, *shader);
+        }
+        if cache.is_some() {
+            glProgramParameteri(program, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, GL_TRUE as _);
+        }
         glLinkProgram(program);
 
         // delete no longer used shaders
-        glDetachShader(program, vertex_shader);
-        glDeleteShader(vertex_shader);
-        glDeleteShader(fragment_shader);
+        for shader in shaders {
+            glDetachShader(program, *shader);
+            glDeleteShader(*shader);
+        }
 
         let mut link_status = 0;
         glGetProgramiv(program, GL_LINK_STATUS, &mut link_status as *mut _);
@@ -614,9 +1444,47 @@ fn load_shader_internal(
             assert!(max_length >= 1);
             let error_message =
                 std::string::String::from_utf8_lossy(&error_message[0..max_length as usize - 1]);
+            glDeleteProgram(program);
             return Err(ShaderError::LinkError(error_message.to_string()));
         }
 
+        if let Some((cache, key)) = cache {
+            cache.store(key, program);
+        }
+        Ok(program)
+    }
+}
+
+/// With `meta: None` the meta is taken from the program reflection.
+/// The linked `program` is deleted on error.
+fn program_internal(
+    program: GLuint,
+    meta: Option<ShaderMeta>,
+    uniform_buffers: bool,
+) -> Result<ShaderInternal, ShaderError> {
+    let res = program_layout(program, meta, uniform_buffers);
+    if res.is_err() {
+        unsafe { glDeleteProgram(program) };
+    }
+    res
+}
+
+fn program_layout(
+    program: GLuint,
+    meta: Option<ShaderMeta>,
+    uniform_buffers: bool,
+) -> Result<ShaderInternal, ShaderError> {
+    unsafe {
+        let reflection = reflect_program(program, uniform_buffers);
+        let meta = meta.unwrap_or_else(|| ShaderMeta::reflect(&reflection));
+        if meta.uniform_blocks.len() > MAX_UNIFORM_BLOCKS {
+            return Err(ShaderError::LayoutMismatch(vec![format!(
+                "{} uniform blocks, MAX_UNIFORM_BLOCKS is {}",
+                meta.uniform_blocks.len(),
+                MAX_UNIFORM_BLOCKS
+            )]));
+        }
+
         glUseProgram(program);
 
         #[rustfmt::skip]
@@ -635,14 +1503,283 @@ fn load_shader_internal(
             Some(res)
         }).collect();
 
+        let mut uniform_blocks = Vec::with_capacity(meta.uniform_blocks.len());
+        for (binding, block) in meta.uniform_blocks.iter().enumerate() {
+            let (offsets, size) = block.layout.std140();
+            let mut uniforms = vec![];
+            if uniform_buffers {
+                let name = CString::new(block.name.as_str())?;
+                let index = glGetUniformBlockIndex(program, name.as_ptr());
+                // unused blocks are optimized out
+                if index != GL_INVALID_INDEX {
+                    glUniformBlockBinding(program, index, binding as _);
+                }
+            } else {
+                for (uniform, offset) in block.layout.uniforms.iter().zip(offsets) {
+                    let uniform_desc = ShaderUniform {
+                        gl_loc: get_uniform_location(program, &uniform.name),
+                        uniform_type: uniform.uniform_type,
+                        array_count: uniform.array_count as _,
+                    };
+                    uniforms.push((offset, uniform_desc));
+                }
+            }
+            uniform_blocks.push(ShaderUniformBlock { uniforms, size });
+        }
+
         Ok(ShaderInternal {
             program,
             images,
             uniforms,
+            uniform_blocks,
+            reflection,
         })
     }
 }
 
+/// Attribute locations of `program` for `new_pipeline`.
+fn vertex_layout(
+    program: GLuint,
+    buffer_layout: &[BufferLayout],
+    attributes: &[VertexAttribute],
+) -> Vec<Option<VertexAttributeInternal>> {
+    #[derive(Clone, Copy, Default)]
+    struct BufferCacheData {
+        stride: i32,
+        offset: i64,
+    }
+
+    let mut buffer_cache: Vec<BufferCacheData> =
+        vec![BufferCacheData::default(); buffer_layout.len()];
+
+    for VertexAttribute {
+        format,
+        buffer_index,
+        ..
+    } in attributes
+    {
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+        let cache = buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+
+        if layout.stride == 0 {
+            cache.stride += format.size_bytes();
+        } else {
+            cache.stride = layout.stride;
+        }
+        // WebGL 1 limitation
+        assert!(cache.stride <= 255);
+    }
+
+    let attributes_len = attributes
+        .iter()
+        .map(|layout| match layout.format {
+            VertexFormat::Mat4 => 4,
+            _ => 1,
+        })
+        .sum();
+
+    let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
+
+    for VertexAttribute {
+        name,
+        format,
+        buffer_index,
+        gl_pass_as_float,
+    } in attributes
+    {
+        let buffer_data = &mut buffer_cache
+            .get_mut(*buffer_index)
+            .unwrap_or_else(|| panic!());
+        let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
+
+        let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
+        let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
+        let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
+        let divisor = if layout.step_func == VertexStep::PerVertex {
+            0
+        } else {
+            layout.step_rate
+        };
+
+        let mut attributes_count: usize = 1;
+        let mut format = *format;
+
+        if format == VertexFormat::Mat4 {
+            format = VertexFormat::Float4;
+            attributes_count = 4;
+        }
+        for i in 0..attributes_count {
+            if let Some(attr_loc) = attr_loc {
+                let attr_loc = attr_loc as GLuint + i as GLuint;
+
+                let attr = VertexAttributeInternal {
+                    attr_loc,
+                    size: format.components(),
+                    type_: format.type_(),
+                    offset: buffer_data.offset,
+                    stride: buffer_data.stride,
+                    buffer_index: *buffer_index,
+                    divisor,
+                    gl_pass_as_float: *gl_pass_as_float,
+                };
+
+                assert!(
+                    attr_loc < vertex_layout.len() as u32,
+                    "attribute: {} outside of allocated attributes array len: {}",
+                    name,
+                    vertex_layout.len()
+                );
+                vertex_layout[attr_loc as usize] = Some(attr);
+            }
+            buffer_data.offset += format.size_bytes() as i64
+        }
+    }
+
+    vertex_layout
+}
+
+fn reflected_uniform_type(gl_type: GLenum) -> Option<UniformType> {
+    match gl_type {
+        GL_FLOAT => Some(UniformType::Float1),
+        GL_FLOAT_VEC2 => Some(UniformType::Float2),
+        GL_FLOAT_VEC3 => Some(UniformType::Float3),
+        GL_FLOAT_VEC4 => Some(UniformType::Float4),
+        GL_INT | GL_UNSIGNED_INT | GL_BOOL => Some(UniformType::Int1),
+        GL_INT_VEC2 | GL_UNSIGNED_INT_VEC2 | GL_BOOL_VEC2 => Some(UniformType::Int2),
+        GL_INT_VEC3 | GL_UNSIGNED_INT_VEC3 | GL_BOOL_VEC3 => Some(UniformType::Int3),
+        GL_INT_VEC4 | GL_UNSIGNED_INT_VEC4 | GL_BOOL_VEC4 => Some(UniformType::Int4),
+        GL_FLOAT_MAT4 => Some(UniformType::Mat4),
+        _ => None,
+    }
+}
+
+fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        GL_SAMPLER_2D
+            | GL_SAMPLER_3D
+            | GL_SAMPLER_CUBE
+            | GL_SAMPLER_2D_SHADOW
+            | GL_SAMPLER_2D_ARRAY
+            | GL_SAMPLER_2D_ARRAY_SHADOW
+            | GL_SAMPLER_CUBE_SHADOW
+            | GL_INT_SAMPLER_2D
+            | GL_INT_SAMPLER_3D
+            | GL_INT_SAMPLER_CUBE
+            | GL_INT_SAMPLER_2D_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_2D
+            | GL_UNSIGNED_INT_SAMPLER_3D
+            | GL_UNSIGNED_INT_SAMPLER_CUBE
+            | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY
+    )
+}
+
+const MAX_REFLECTED_NAME: usize = 256;
+
+/// Name, array size and type of an active uniform or attribute.
+unsafe fn active_info(
+    get_active: unsafe fn(
+        GLuint,
+        GLuint,
+        GLsizei,
+        *mut GLsizei,
+        *mut GLint,
+        *mut GLenum,
+        *mut GLchar,
+    ),
+    program: GLuint,
+    index: GLuint,
+) -> (String, i32, GLenum) {
+    let mut name = [0u8; MAX_REFLECTED_NAME];
+    let mut length = 0;
+    let mut size = 0;
+    let mut gl_type = 0;
+    get_active(
+        program,
+        index,
+        name.len() as _,
+        &mut length,
+        &mut size,
+        &mut gl_type,
+        name.as_mut_ptr() as _,
+    );
+    let name = String::from_utf8_lossy(&name[..length as usize]);
+    // arrays are reported as "name[0]"
+    let name = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+    (name, size, gl_type)
+}
+
+unsafe fn reflect_program(program: GLuint, uniform_buffers: bool) -> ShaderReflection {
+    let mut reflection = ShaderReflection::default();
+
+    let mut count = 0;
+    glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &mut count);
+    for i in 0..count as GLuint {
+        let (name, _, _) = active_info(glGetActiveAttrib, program, i);
+        // gl_VertexID and friends
+        if !name.starts_with("gl_") {
+            reflection.attributes.push(name);
+        }
+    }
+
+    let mut block_count = 0;
+    if uniform_buffers {
+        glGetProgramiv(program, GL_ACTIVE_UNIFORM_BLOCKS, &mut block_count);
+    }
+    // (offset, uniform) of each block
+    let mut blocks: Vec<Vec<(usize, UniformDesc)>> = vec![vec![]; block_count as usize];
+
+    glGetProgramiv(program, GL_ACTIVE_UNIFORMS, &mut count);
+    for i in 0..count as GLuint {
+        let (name, size, gl_type) = active_info(glGetActiveUniform, program, i);
+        if is_sampler(gl_type) {
+            reflection.images.push(name);
+            continue;
+        }
+        let Some(uniform_type) = reflected_uniform_type(gl_type) else {
+            continue;
+        };
+        let mut block_index = -1;
+        if uniform_buffers {
+            glGetActiveUniformsiv(program, 1, &i, GL_UNIFORM_BLOCK_INDEX, &mut block_index);
+        }
+        if block_index == -1 {
+            let uniform = UniformDesc::new(&name, uniform_type).array(size as _);
+            reflection.uniforms.push(uniform);
+        } else {
+            let mut offset = 0;
+            glGetActiveUniformsiv(program, 1, &i, GL_UNIFORM_OFFSET, &mut offset);
+            // members of blocks with an instance name are "Block.member"
+            let name = name.rsplit('.').next().unwrap();
+            let uniform = UniformDesc::new(name, uniform_type).array(size as _);
+            blocks[block_index as usize].push((offset as usize, uniform));
+        }
+    }
+
+    for (i, mut members) in blocks.into_iter().enumerate() {
+        let mut name = [0u8; MAX_REFLECTED_NAME];
+        let mut length = 0;
+        glGetActiveUniformBlockName(
+            program,
+            i as _,
+            name.len() as _,
+            &mut length,
+            name.as_mut_ptr() as _,
+        );
+        let name = String::from_utf8_lossy(&name[..length as usize]);
+        members.sort_by_key(|(offset, _)| *offset);
+        let (offsets, uniforms) = members.into_iter().unzip();
+        reflection.uniform_blocks.push(UniformBlockReflection {
+            block: UniformBlockDesc::new(&name, uniforms),
+            offsets,
+        });
+    }
+
+    reflection
+}
+
 pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderError> {
     unsafe {
         let shader = glCreateShader(shader_type);
@@ -681,6 +1818,7 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
                 shader_type: match shader_type {
                     GL_VERTEX_SHADER => ShaderType::Vertex,
                     GL_FRAGMENT_SHADER => ShaderType::Fragment,
+                    GL_COMPUTE_SHADER => ShaderType::Compute,
                     _ => unreachable!(),
                 },
                 error_message,
@@ -692,6 +1830,82 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
 }
 
 impl GlContext {
+    /// Release the pass framebuffers and, with `delete_attachments`, its textures.
+    fn delete_framebuffers(&mut self, render_pass: RenderPass, delete_attachments: bool) {
+        let Some(render_pass) = self.passes.remove(render_pass.0) else {
+            return;
+        };
+
+        unsafe { glDeleteFramebuffers(1, &render_pass.gl_fb as *const _) }
+        for (fb, _) in render_pass.resolves.iter().flatten() {
+            unsafe { glDeleteFramebuffers(1, fb as *const _) }
+        }
+        if !delete_attachments {
+            return;
+        }
+
+        let resolve_textures = render_pass.resolves.iter().flatten().map(|(_, t)| t);
+        let attachments = render_pass
+            .color_textures
+            .iter()
+            .chain(resolve_textures)
+            .chain(render_pass.depth_texture.iter());
+        for texture in attachments {
+            // could be shared with another pass, e.g. a mip level target
+            let _ = self.try_delete_texture(*texture);
+        }
+    }
+
+    fn bind_shader_images(&mut self, textures: &[TextureId], samplers: &[Option<SamplerId>]) {
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let shader = &self.shaders[pip.shader.0];
+        let created_samplers = &self.samplers;
+
+        for (n, shader_image) in shader.images.iter().enumerate() {
+            let bindings_image = textures
+                .get(n)
+                .unwrap_or_else(|| panic!("Image count in bindings and shader did not match!"));
+            if let Some(gl_loc) = shader_image.gl_loc {
+                let texture = self.textures.get(*bindings_image);
+                let raw = match texture.raw {
+                    TextureOrRenderbuffer::Texture(id) => id,
+                    TextureOrRenderbuffer::Renderbuffer(id) => id,
+                };
+                let sampler = samplers
+                    .get(n)
+                    .copied()
+                    .flatten()
+                    .map(|sampler| &created_samplers[sampler.0]);
+                unsafe {
+                    self.cache.bind_texture(n, texture.params.kind.into(), raw);
+                    glUniform1i(gl_loc, n as i32);
+
+                    if self.info.features.sampler_objects {
+                        self.cache
+                            .bind_sampler(n, sampler.map_or(0, |sampler| sampler.gl_sampler));
+                    } else if let TextureOrRenderbuffer::Texture(raw) = texture.raw {
+                        // the bound texture keeps the sampler's state until used with another one
+                        let params = sampler.map_or(texture.sampler, |sampler| sampler.params);
+                        let current = self
+                            .emulated_samplers
+                            .get(&raw)
+                            .copied()
+                            .unwrap_or(texture.sampler);
+                        if params != current {
+                            SamplerTarget::Texture(texture.params.kind.into())
+                                .set_params(&self.info, &params, &current);
+                            if params == texture.sampler {
+                                self.emulated_samplers.remove(&raw);
+                            } else {
+                                self.emulated_samplers.insert(raw, params);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
         if color_blend.is_none() && alpha_blend.is_some() {
             panic!("AlphaBlend without ColorBlend");
@@ -806,6 +2020,65 @@ impl GlContext {
         self.cache.cull_face = cull_face;
     }
 
+    fn set_attachment_blend(
+        &mut self,
+        attachment_blend: Option<[AttachmentBlend; MAX_COLOR_ATTACHMENTS]>,
+    ) {
+        if self.cache.attachment_blend == attachment_blend {
+            return;
+        }
+        unsafe {
+            if let Some(attachments) = attachment_blend {
+                for (i, attachment) in attachments.iter().enumerate() {
+                    let i = i as GLuint;
+                    if let Some(color_blend) = attachment.color_blend {
+                        let alpha_blend = attachment.alpha_blend.unwrap_or(color_blend);
+                        glEnablei(GL_BLEND, i);
+                        glBlendFuncSeparatei(
+                            i,
+                            color_blend.sfactor.into(),
+                            color_blend.dfactor.into(),
+                            alpha_blend.sfactor.into(),
+                            alpha_blend.dfactor.into(),
+                        );
+                        glBlendEquationSeparatei(
+                            i,
+                            color_blend.equation.into(),
+                            alpha_blend.equation.into(),
+                        );
+                    } else {
+                        glDisablei(GL_BLEND, i);
+                    }
+                    let (r, g, b, a) = attachment.color_write;
+                    glColorMaski(i, r as _, g as _, b as _, a as _);
+                }
+            } else {
+                // non-indexed glDisable/glColorMask reset the state of all the attachments
+                glDisable(GL_BLEND);
+                let (r, g, b, a) = self.cache.color_write;
+                glColorMask(r as _, g as _, b as _, a as _);
+                self.cache.color_blend = None;
+                self.cache.alpha_blend = None;
+            }
+        }
+        self.cache.attachment_blend = attachment_blend;
+    }
+
+    fn set_primitive_restart(&mut self, primitive_restart: bool) {
+        // WebGL2 always restarts on the maximum index and has no enum to toggle it
+        if cfg!(target_arch = "wasm32") || self.cache.primitive_restart == primitive_restart {
+            return;
+        }
+        unsafe {
+            if primitive_restart {
+                glEnable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+            } else {
+                glDisable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+            }
+        }
+        self.cache.primitive_restart = primitive_restart;
+    }
+
     fn set_color_write(&mut self, color_write: ColorMask) {
         if self.cache.color_write == color_write {
             return;
@@ -814,10 +2087,168 @@ impl GlContext {
         unsafe { glColorMask(r as _, g as _, b as _, a as _) }
         self.cache.color_write = color_write;
     }
+
+    /// Primitive type, GL index type and index size in bytes
+    /// for an indexed draw with the currently applied pipeline and bindings.
+    fn indexed_draw_types(&self) -> (GLenum, GLenum, i32) {
+        let pip = &self.pipelines[self
+            .cache
+            .cur_pipeline
+            .expect("Drawing without any binded pipeline")
+            .0];
+        assert!(!pip.compute, "Drawing with a compute pipeline");
+        let index_size = self
+            .cache
+            .index_type
+            .expect("Unset index buffer type, use draw_arrays to draw without an index buffer");
+        let index_type = match index_size {
+            1 => GL_UNSIGNED_BYTE,
+            2 => GL_UNSIGNED_SHORT,
+            4 => GL_UNSIGNED_INT,
+            _ => panic!("Unsupported index buffer type!"),
+        };
+
+        (
+            pip.params.primitive_type.into(),
+            index_type,
+            index_size as i32,
+        )
+    }
+
+    /// Offset per-vertex attributes of the applied bindings by `base_vertex` vertices,
+    /// emulates base vertex draws on contexts without glDrawElementsInstancedBaseVertex.
+    fn offset_vertex_attributes(&self, base_vertex: i32) {
+        for (attr_index, cached_attr) in self.cache.attributes.iter().enumerate() {
+            let Some(CachedAttribute { attribute, gl_vbuf }) = cached_attr else {
+                continue;
+            };
+            if attribute.divisor != 0 {
+                continue;
+            }
+            let offset = attribute.offset + base_vertex as i64 * attribute.stride as i64;
+            assert!(
+                offset >= 0,
+                "base_vertex points before the vertex buffer start"
+            );
+            unsafe {
+                glBindBuffer(GL_ARRAY_BUFFER, *gl_vbuf);
+                vertex_attrib_pointer(attr_index as GLuint, attribute, offset);
+            }
+        }
+        unsafe {
+            glBindBuffer(GL_ARRAY_BUFFER, self.cache.vertex_buffer);
+        }
+    }
+}
+
+/// Null syncs are used when there are no sync objects and are always signaled.
+unsafe fn sync_signaled(sync: GLsync) -> bool {
+    if sync.is_null() {
+        return true;
+    }
+    let mut status = 0;
+    glGetSynciv(sync, GL_SYNC_STATUS, 1, std::ptr::null_mut(), &mut status);
+    status as GLenum == GL_SIGNALED
+}
+
+/// Reads all the pending GL errors, true if one of them was GL_OUT_OF_MEMORY.
+/// Called once before a checked allocation to drop the errors left by earlier calls.
+unsafe fn take_out_of_memory() -> bool {
+    let mut out_of_memory = false;
+    // without a current context some drivers keep reporting an error forever
+    for _ in 0..16 {
+        match glGetError() {
+            GL_NO_ERROR => break,
+            error => out_of_memory |= error == GL_OUT_OF_MEMORY,
+        }
+    }
+    out_of_memory
+}
+
+fn framebuffer_status_name(status: GLenum) -> String {
+    match status {
+        GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT".to_string(),
+        GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+            "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT".to_string()
+        }
+        GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS => "GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS".to_string(),
+        GL_FRAMEBUFFER_UNSUPPORTED => "GL_FRAMEBUFFER_UNSUPPORTED".to_string(),
+        GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => {
+            "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE".to_string()
+        }
+        status => format!("glCheckFramebufferStatus returned 0x{:X}", status),
+    }
+}
+
+unsafe fn vertex_attrib_pointer(index: GLuint, attribute: &VertexAttributeInternal, offset: i64) {
+    match attribute.type_ {
+        GL_INT | GL_UNSIGNED_INT | GL_SHORT | GL_UNSIGNED_SHORT | GL_UNSIGNED_BYTE | GL_BYTE
+            if !attribute.gl_pass_as_float =>
+        {
+            glVertexAttribIPointer(
+                index,
+                attribute.size,
+                attribute.type_,
+                attribute.stride,
+                offset as *mut _,
+            )
+        }
+        _ => glVertexAttribPointer(
+            index,
+            attribute.size,
+            attribute.type_,
+            GL_FALSE as u8,
+            attribute.stride,
+            offset as *mut _,
+        ),
+    }
+}
+
+/// (major, minor) out of GL_VERSION_STRING,
+/// "4.6 (Core Profile) Mesa 22.3.6" or "OpenGL ES 3.2 Mesa 22.3.6" alike.
+fn gl_version(version_string: &str) -> (u32, u32) {
+    let version = version_string
+        .split_whitespace()
+        .find(|s| s.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or("");
+    let mut numbers = version.split('.').map(|n| n.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+/// Extension names without the "GL_" prefix, the way WebGL reports them.
+///
+/// glGetString(GL_EXTENSIONS) is not available in core profiles,
+/// GL3+ extensions are queried one by one.
+fn gl_extensions(gl2: bool) -> Vec<String> {
+    unsafe {
+        if gl2 || cfg!(target_arch = "wasm32") {
+            let extensions = glGetString(GL_EXTENSIONS);
+            if extensions.is_null() {
+                return vec![];
+            }
+            std::ffi::CStr::from_ptr(extensions as _)
+                .to_string_lossy()
+                .split_whitespace()
+                .map(|extension| extension.trim_start_matches("GL_").to_owned())
+                .collect()
+        } else {
+            let mut count = 0;
+            glGetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+            (0..count)
+                .filter_map(|i| {
+                    let extension = glGetStringi(GL_EXTENSIONS, i as _);
+                    (!extension.is_null()).then(|| {
+                        let extension = std::ffi::CStr::from_ptr(extension as _).to_string_lossy();
+                        extension.trim_start_matches("GL_").to_owned()
+                    })
+                })
+                .collect()
+        }
+    }
 }
 
 #[allow(clippy::field_reassign_with_default)]
-fn gl_info() -> ContextInfo {
+pub(crate) fn gl_info() -> ContextInfo {
     let version_string = unsafe { glGetString(super::gl::GL_VERSION) };
     let gl_version_string = unsafe { std::ffi::CStr::from_ptr(version_string as _) }
         .to_str()
@@ -829,18 +2260,96 @@ fn gl_info() -> ContextInfo {
         || gl_version_string.starts_with("2")
         || gl_version_string.starts_with("OpenGL ES 2");
     let webgl1 = gl_version_string == "WebGL 1.0";
+    let webgl2 = gl_version_string.contains("WebGL 2.0");
+    let gles = gl_version_string.contains("OpenGL ES");
+    let version = gl_version(&gl_version_string);
+    let desktop = !gles && cfg!(not(target_arch = "wasm32"));
+    let extensions = gl_extensions(gl2);
+    let has_extension = |name: &str| extensions.iter().any(|extension| extension == name);
 
     let features = Features {
         instancing: !gl2,
         resolve_attachments: !webgl1 && !gl2,
+        compute: cfg!(not(target_arch = "wasm32"))
+            && if gles {
+                version >= (3, 1)
+            } else {
+                version >= (4, 3)
+            },
+        uniform_buffers: webgl2 || version >= if gles { (3, 0) } else { (3, 1) },
+        texture_3d: !webgl1 && !gl2,
+        sized_texture_formats: !webgl1 && !gl2,
+        float_render_targets: desktop || has_extension("EXT_color_buffer_float"),
+        float32_filtering: desktop || has_extension("OES_texture_float_linear"),
+        texture_compression_s3tc: has_extension("EXT_texture_compression_s3tc")
+            || has_extension("WEBGL_compressed_texture_s3tc"),
+        texture_compression_rgtc: desktop && version >= (3, 0)
+            || has_extension("EXT_texture_compression_rgtc")
+            || has_extension("ARB_texture_compression_rgtc"),
+        texture_compression_bptc: desktop && version >= (4, 2)
+            || has_extension("EXT_texture_compression_bptc")
+            || has_extension("ARB_texture_compression_bptc"),
+        texture_compression_etc2: gles && version >= (3, 0)
+            || desktop && version >= (4, 3)
+            || has_extension("ARB_ES3_compatibility")
+            || has_extension("WEBGL_compressed_texture_etc"),
+        texture_compression_astc: has_extension("KHR_texture_compression_astc_ldr")
+            || has_extension("WEBGL_compressed_texture_astc"),
+        // extension entry points are only loaded without a suffix,
+        // so OES/EXT variants are not taken into account
+        base_vertex: desktop
+            && (version >= (3, 2) || has_extension("ARB_draw_elements_base_vertex"))
+            || gles && version >= (3, 2),
+        draw_indirect: desktop && (version >= (4, 0) || has_extension("ARB_draw_indirect"))
+            || gles && version >= (3, 1),
+        multi_draw_indirect: desktop
+            && (version >= (4, 3) || has_extension("ARB_multi_draw_indirect")),
+        buffer_read: desktop || gles && version >= (3, 0) || webgl2,
+        query_time_elapsed: desktop && (version >= (3, 3) || has_extension("ARB_timer_query"))
+            || has_extension("EXT_disjoint_timer_query") && (webgl1 || gles && version >= (3, 0))
+            || has_extension("EXT_disjoint_timer_query_webgl2"),
+        // glQueryCounterEXT is not loaded on GLES
+        query_timestamp: desktop && (version >= (3, 3) || has_extension("ARB_timer_query"))
+            || has_extension("EXT_disjoint_timer_query") && webgl1
+            || has_extension("EXT_disjoint_timer_query_webgl2"),
+        query_any_samples_passed: desktop
+            && (version >= (3, 3) || has_extension("ARB_occlusion_query2"))
+            || gles && version >= (3, 0)
+            || webgl2,
+        query_samples_passed: desktop,
+        query_primitives_generated: desktop && version >= (3, 0) || gles && version >= (3, 2),
+        async_readback: desktop && (version >= (3, 2) || has_extension("ARB_sync"))
+            || gles && version >= (3, 0)
+            || webgl2,
+        line_loop_triangle_fan: true,
+        primitive_restart: desktop && version >= (4, 3) || gles && version >= (3, 0) || webgl2,
+        independent_blend: desktop && version >= (4, 0)
+            || gles && version >= (3, 2)
+            || webgl2 && has_extension("OES_draw_buffers_indexed"),
+        buffer_map: desktop && (version >= (3, 0) || has_extension("ARB_map_buffer_range"))
+            || gles && version >= (3, 0),
+        sampler_objects: desktop && (version >= (3, 3) || has_extension("ARB_sampler_objects"))
+            || gles && version >= (3, 0)
+            || webgl2,
+        clamp_to_border: desktop
+            || gles && version >= (3, 2)
+            || has_extension("EXT_texture_border_clamp")
+            || has_extension("OES_texture_border_clamp"),
+        mirror_clamp_to_edge: desktop
+            && (version >= (4, 4)
+                || has_extension("ARB_texture_mirror_clamp_to_edge")
+                || has_extension("EXT_texture_mirror_clamp"))
+            || has_extension("EXT_texture_mirror_clamp_to_edge"),
+        sampler_compare: !webgl1 && !gl2,
     };
 
-    let mut glsl_support = GlslSupport::default();
-
-    // this is not quite documented,
-    // but somehow even GL2.1 usually have all the compatibility extensions to support glsl100
-    // It was tested on really old windows machines, virtual machines etc. glsl100 always works!
-    glsl_support.v100 = true;
+    let mut glsl_support = GlslSupport {
+        // this is not quite documented,
+        // but somehow even GL2.1 usually have all the compatibility extensions to support glsl100
+        // It was tested on really old windows machines, virtual machines etc. glsl100 always works!
+        v100: true,
+        ..Default::default()
+    };
 
     // on wasm miniquad always creates webgl1 context, with the only glsl available being version 100
     #[cfg(target_arch = "wasm32")]
@@ -873,11 +2382,24 @@ fn gl_info() -> ContextInfo {
         glsl_support.v130 = true;
     }
 
+    let mut max_texture_size = 0;
+    unsafe { glGetIntegerv(GL_MAX_TEXTURE_SIZE, &mut max_texture_size) };
+
+    let mut max_anisotropy = 1.;
+    if desktop && version >= (4, 6)
+        || has_extension("EXT_texture_filter_anisotropic")
+        || has_extension("ARB_texture_filter_anisotropic")
+    {
+        unsafe { glGetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy) };
+    }
+
     ContextInfo {
         backend: Backend::OpenGl,
         gl_version_string,
         glsl_support,
         features,
+        max_texture_size: max_texture_size as u32,
+        max_anisotropy,
     }
 }
 
@@ -891,14 +2413,44 @@ impl RenderingBackend for GlContext {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError> {
-        let (fragment, vertex) = match shader {
-            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
-            _ => panic!("Metal source on OpenGl context"),
-        };
-        let shader = load_shader_internal(vertex, fragment, meta)?;
+        let shader = self.load_shader_source(shader, Some(meta))?;
         Ok(ShaderId(self.shaders.add(shader)))
     }
 
+    fn new_shader_reflected(
+        &mut self,
+        shader: ShaderSource,
+    ) -> Result<(ShaderId, ShaderMeta), ShaderError> {
+        let shader = self.load_shader_source(shader, None)?;
+        let meta = ShaderMeta::reflect(&shader.reflection);
+        Ok((ShaderId(self.shaders.add(shader)), meta))
+    }
+
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        let new = self.load_shader_source(source, Some(meta))?;
+        let old = std::mem::replace(&mut self.shaders[shader.0], new);
+        unsafe { glDeleteProgram(old.program) };
+
+        let program = self.shaders[shader.0].program;
+        for pipeline in self.pipelines.iter_mut() {
+            if pipeline.shader == shader && !pipeline.compute {
+                pipeline.layout =
+                    vertex_layout(program, &pipeline.buffer_layout, &pipeline.attributes);
+            }
+        }
+        self.cache.cur_pipeline = None;
+        Ok(())
+    }
+
+    fn shader_reflection(&self, shader: ShaderId) -> Option<&ShaderReflection> {
+        Some(&self.shaders[shader.0].reflection)
+    }
+
     fn new_texture(
         &mut self,
         access: TextureAccess,
@@ -906,60 +2458,129 @@ impl RenderingBackend for GlContext {
         params: TextureParams,
     ) -> TextureId {
         let texture = Texture::new(self, access, source, params);
-        self.textures.0.push(texture);
-        TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+        TextureId(TextureIdInner::Managed(self.textures.0.add(texture)))
+    }
+
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, GraphicsError> {
+        self.info.check_texture_params(access, &source, &params)?;
+        unsafe { take_out_of_memory() };
+        let texture = self.new_texture(access, source, params);
+        if unsafe { take_out_of_memory() } {
+            self.delete_texture(texture);
+            return Err(GraphicsError::OutOfMemory);
+        }
+        Ok(texture)
     }
 
     fn delete_texture(&mut self, texture: TextureId) {
+        self.try_delete_texture(texture)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
         //self.cache.clear_texture_bindings();
 
-        let t = self.textures.get(texture);
+        let t = match texture.0 {
+            TextureIdInner::Managed(id) => self
+                .textures
+                .0
+                .remove(id)
+                .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", texture)))?,
+            TextureIdInner::Raw(_) => self.textures.get(texture),
+        };
         match &t.raw {
             TextureOrRenderbuffer::Texture(raw) => unsafe {
+                self.emulated_samplers.remove(raw);
                 glDeleteTextures(1, raw as *const _);
             },
             TextureOrRenderbuffer::Renderbuffer(raw) => unsafe {
                 glDeleteRenderbuffers(1, raw as *const _);
             },
         }
+        Ok(())
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        self.try_delete_sampler(sampler)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        let sampler = self
+            .samplers
+            .remove(sampler.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", sampler)))?;
+        if sampler.gl_sampler != 0 {
+            // deleting a bound sampler object reverts the unit to the texture's own state
+            for bound in self.cache.samplers.iter_mut() {
+                if *bound == sampler.gl_sampler {
+                    *bound = 0;
+                }
+            }
+            unsafe { glDeleteSamplers(1, &sampler.gl_sampler) };
+        }
+        Ok(())
     }
 
     fn delete_shader(&mut self, program: ShaderId) {
-        unsafe { glDeleteProgram(self.shaders[program.0].program) };
-        self.shaders.remove(program.0);
+        self.try_delete_shader(program)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        let shader = self
+            .shaders
+            .remove(program.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", program)))?;
+        unsafe { glDeleteProgram(shader.program) };
         self.cache.cur_pipeline = None;
+        Ok(())
     }
 
     fn delete_pipeline(&mut self, pipeline: Pipeline) {
-        self.pipelines.remove(pipeline.0);
+        self.try_delete_pipeline(pipeline)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
-    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
-        let t = self.textures.get(texture);
-        let raw = t
-            .raw
-            .texture()
-            .expect("texture_set_wrap not yet implemented for RenderBuffer(multisampled) textures");
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.pipelines
+            .remove(pipeline.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", pipeline)))?;
+        if self.cache.cur_pipeline == Some(pipeline) {
+            self.cache.cur_pipeline = None;
+        }
+        Ok(())
+    }
 
-        self.cache.store_texture_binding(0);
-        self.cache.bind_texture(0, t.params.kind.into(), raw);
-        let wrap_x = match wrap_x {
-            TextureWrap::Repeat => GL_REPEAT,
-            TextureWrap::Mirror => GL_MIRRORED_REPEAT,
-            TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
-        };
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        let mut params = self.textures.get(texture).sampler;
+        params.wrap_x = wrap_x;
+        params.wrap_y = wrap_y;
+        self.set_texture_sampler(texture, params, "texture_set_wrap");
+    }
 
-        let wrap_y = match wrap_y {
-            TextureWrap::Repeat => GL_REPEAT,
-            TextureWrap::Mirror => GL_MIRRORED_REPEAT,
-            TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
-        };
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId {
+        self.info
+            .check_sampler_params(&params)
+            .unwrap_or_else(|e| panic!("{}", e));
 
-        unsafe {
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, wrap_x as i32);
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, wrap_y as i32);
+        let mut gl_sampler = 0;
+        if self.info.features.sampler_objects {
+            unsafe {
+                glGenSamplers(1, &mut gl_sampler);
+                SamplerTarget::Sampler(gl_sampler).set_params(
+                    &self.info,
+                    &params,
+                    &SamplerTarget::gl_defaults(),
+                );
+            }
         }
-        self.cache.restore_texture_binding(0);
+        SamplerId(self.samplers.add(Sampler { gl_sampler, params }))
     }
 
     fn texture_set_min_filter(
@@ -968,38 +2589,15 @@ impl RenderingBackend for GlContext {
         filter: FilterMode,
         mipmap_filter: MipmapFilterMode,
     ) {
-        let t = self.textures.get(texture);
-        let raw = t.raw.texture().expect(
-            "texture_set_min_filter not yet implemented for RenderBuffer(multisampled) textures",
-        );
-
-        self.cache.store_texture_binding(0);
-        self.cache.bind_texture(0, t.params.kind.into(), raw);
-
-        let filter = Texture::gl_filter(filter, mipmap_filter);
-        unsafe {
-            glTexParameteri(t.params.kind.into(), GL_TEXTURE_MIN_FILTER, filter as i32);
-        }
-        self.cache.restore_texture_binding(0);
+        let mut params = self.textures.get(texture).sampler;
+        params.min_filter = filter;
+        params.mipmap_filter = mipmap_filter;
+        self.set_texture_sampler(texture, params, "texture_set_min_filter");
     }
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
-        let t = self.textures.get(texture);
-        let raw = t
-            .raw
-            .texture()
-            .expect("texture_set_wrap not yet implemented for RenderBuffer(multisampled) textures");
-
-        self.cache.store_texture_binding(0);
-        self.cache.bind_texture(0, t.params.kind.into(), raw);
-
-        let filter = match filter {
-            FilterMode::Nearest => GL_NEAREST,
-            FilterMode::Linear => GL_LINEAR,
-        };
-        unsafe {
-            glTexParameteri(t.params.kind.into(), GL_TEXTURE_MAG_FILTER, filter as i32);
-        }
-        self.cache.restore_texture_binding(0);
+        let mut params = self.textures.get(texture).sampler;
+        params.mag_filter = filter;
+        self.set_texture_sampler(texture, params, "texture_set_mag_filter");
     }
     fn texture_resize(
         &mut self,
@@ -1011,18 +2609,112 @@ impl RenderingBackend for GlContext {
         let mut t = self.textures.get(texture);
         t.resize(self, width, height, source);
         if let TextureIdInner::Managed(tex_id) = texture.0 {
-            self.textures.0[tex_id].params = t.params;
+            if let Some(texture) = self.textures.0.get_mut(tex_id) {
+                texture.params = t.params;
+            }
         };
     }
-    fn texture_read_pixels(&mut self, texture: TextureId, source: &mut [u8]) {
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        source: &mut [u8],
+    ) {
+        let t = self.textures.get(texture);
+        t.read_pixels(mip_level, layer, source);
+    }
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
         let t = self.textures.get(texture);
-        t.read_pixels(source);
+        let (_, _, width, height) = rect;
+        let size = t.size(width, height);
+
+        if !self.info.features.async_readback {
+            let mut data = vec![0; size];
+            unsafe { t.read_pixels_rect(0, 0, rect, data.as_mut_ptr() as _) };
+            return ReadbackId(self.readbacks.add(Readback {
+                pbo: 0,
+                size,
+                fence: std::ptr::null_mut(),
+                data: Some(data),
+            }));
+        }
+
+        let mut pbo = 0;
+        let fence = unsafe {
+            glGenBuffers(1, &mut pbo);
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, pbo);
+            glBufferData(
+                GL_PIXEL_PACK_BUFFER,
+                size as _,
+                std::ptr::null(),
+                GL_STREAM_READ,
+            );
+            t.read_pixels_rect(0, 0, rect, std::ptr::null_mut());
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+
+            let fence = glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+            glFlush();
+            fence
+        };
+        ReadbackId(self.readbacks.add(Readback {
+            pbo,
+            size,
+            fence,
+            data: None,
+        }))
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        let readback = &self.readbacks[readback.0];
+        readback.data.is_some() || unsafe { sync_signaled(readback.fence) }
     }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        if !self.poll_readback(readback) {
+            return None;
+        }
+        let Readback {
+            pbo,
+            size,
+            fence,
+            data,
+        } = self.readbacks.remove(readback.0)?;
+        if data.is_some() {
+            return data;
+        }
+
+        let mut data = vec![0; size];
+        unsafe {
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, pbo);
+            if self.info.features.buffer_map {
+                let ptr = glMapBufferRange(GL_PIXEL_PACK_BUFFER, 0, size as _, GL_MAP_READ_BIT);
+                assert!(!ptr.is_null(), "Failed to map the pixel buffer");
+                std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr(), size);
+                glUnmapBuffer(GL_PIXEL_PACK_BUFFER);
+            } else {
+                glGetBufferSubData(GL_PIXEL_PACK_BUFFER, 0, size as _, data.as_mut_ptr() as _);
+            }
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+            glDeleteBuffers(1, &pbo);
+            glDeleteSync(fence);
+        }
+        Some(data)
+    }
+
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         let t = self.textures.get(texture);
         let raw = t.raw.texture().expect(
             "texture_generate_mipmaps not yet implemented for RenderBuffer(multisampled) textures",
         );
+        assert!(
+            !t.params.format.is_compressed(),
+            "Mipmaps could not be generated for compressed textures"
+        );
 
         self.cache.store_texture_binding(0);
         self.cache.bind_texture(0, t.params.kind.into(), raw);
@@ -1031,9 +2723,11 @@ impl RenderingBackend for GlContext {
         }
         self.cache.restore_texture_binding(0);
     }
-    fn texture_update_part(
+    fn texture_update_mip_part(
         &mut self,
         texture: TextureId,
+        mip_level: u32,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
@@ -1041,7 +2735,9 @@ impl RenderingBackend for GlContext {
         source: &[u8],
     ) {
         let t = self.textures.get(texture);
-        t.update_texture_part(self, x_offset, y_offset, width, height, source);
+        t.update_texture_part(
+            self, mip_level, layer, x_offset, y_offset, width, height, source,
+        );
     }
     fn texture_params(&self, texture: TextureId) -> TextureParams {
         let texture = self.textures.get(texture);
@@ -1057,11 +2753,11 @@ impl RenderingBackend for GlContext {
         RawId::OpenGl(raw)
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_targets(
         &mut self,
-        color_img: &[TextureId],
+        color_img: &[RenderTarget],
         resolve_img: Option<&[TextureId]>,
-        depth_img: Option<TextureId>,
+        depth_img: Option<RenderTarget>,
     ) -> RenderPass {
         if color_img.is_empty() && depth_img.is_none() {
             panic!("Render pass should have at least one non-none target");
@@ -1072,47 +2768,22 @@ impl RenderingBackend for GlContext {
         unsafe {
             glGenFramebuffers(1, &mut gl_fb as *mut _);
             glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
-            for (i, color_img) in color_img.iter().enumerate() {
-                let texture = self.textures.get(*color_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
-                } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+            for (i, target) in color_img.iter().enumerate() {
+                let texture = self.textures.get(target.texture);
+                texture.attach(
+                    GL_COLOR_ATTACHMENT0 + i as u32,
+                    target.mip_level,
+                    target.layer,
+                );
             }
-            if let Some(depth_img) = depth_img {
-                let texture = self.textures.get(depth_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
+            if let Some(target) = depth_img {
+                let texture = self.textures.get(target.texture);
+                let attachment = if texture.params.format.has_stencil() {
+                    GL_DEPTH_STENCIL_ATTACHMENT
                 } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+                    GL_DEPTH_ATTACHMENT
+                };
+                texture.attach(attachment, target.mip_level, target.layer);
             }
             let mut attachments = vec![];
             for i in 0..color_img.len() {
@@ -1149,9 +2820,10 @@ impl RenderingBackend for GlContext {
         }
         let pass = RenderPassInternal {
             gl_fb,
-            color_textures: color_img.to_vec(),
+            color_textures: color_img.iter().map(|target| target.texture).collect(),
             resolves,
-            depth_texture: depth_img,
+            depth_texture: depth_img.map(|target| target.texture),
+            mip_level: color_img.first().copied().or(depth_img).unwrap().mip_level,
         };
 
         RenderPass(self.passes.add(pass))
@@ -1159,25 +2831,61 @@ impl RenderingBackend for GlContext {
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         &self.passes[render_pass.0].color_textures
     }
-    fn delete_render_pass(&mut self, render_pass: RenderPass) {
-        let pass_id = render_pass.0;
-
-        let render_pass = self.passes.remove(pass_id);
-
-        unsafe { glDeleteFramebuffers(1, &render_pass.gl_fb as *const _) }
-
-        for color_texture in &render_pass.color_textures {
-            self.delete_texture(*color_texture);
+    fn try_new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> Result<RenderPass, GraphicsError> {
+        let attachments = color_img
+            .iter()
+            .chain(depth_img.iter())
+            .map(|target| target.texture)
+            .chain(resolve_img.into_iter().flatten().copied());
+        for texture in attachments {
+            if !self.textures.contains(texture) {
+                return Err(GraphicsError::InvalidId(format!("{:?}", texture)));
+            }
         }
-        if let Some(resolves) = render_pass.resolves {
-            for (fb, texture) in resolves {
-                unsafe { glDeleteFramebuffers(1, &fb as *const _) }
-                self.delete_texture(texture);
+        check_render_pass_attachments(self, color_img, resolve_img, depth_img)?;
+
+        let pass = self.new_render_pass_targets(color_img, resolve_img, depth_img);
+        let internal = &self.passes[pass.0];
+        let framebuffers = std::iter::once(internal.gl_fb)
+            .chain(internal.resolves.iter().flatten().map(|(fb, _)| *fb));
+        let mut status = GL_FRAMEBUFFER_COMPLETE;
+        for fb in framebuffers {
+            unsafe {
+                glBindFramebuffer(GL_FRAMEBUFFER, fb);
+                status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
+            }
+            if status != GL_FRAMEBUFFER_COMPLETE {
+                break;
             }
         }
-        if let Some(depth_texture) = render_pass.depth_texture {
-            self.delete_texture(depth_texture);
+        unsafe { glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer) };
+
+        if status != GL_FRAMEBUFFER_COMPLETE {
+            // the attachments are still owned by the caller
+            self.delete_framebuffers(pass, false);
+            return Err(GraphicsError::IncompleteFramebuffer(
+                framebuffer_status_name(status),
+            ));
+        }
+        Ok(pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.try_delete_render_pass(render_pass)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        if self.passes.get(render_pass.0).is_none() {
+            return Err(GraphicsError::InvalidId(format!("{:?}", render_pass)));
         }
+        self.delete_framebuffers(render_pass, true);
+        Ok(())
     }
 
     fn new_pipeline(
@@ -1185,108 +2893,68 @@ impl RenderingBackend for GlContext {
         buffer_layout: &[BufferLayout],
         attributes: &[VertexAttribute],
         shader: ShaderId,
-        params: PipelineParams,
+        mut params: PipelineParams,
     ) -> Pipeline {
-        #[derive(Clone, Copy, Default)]
-        struct BufferCacheData {
-            stride: i32,
-            offset: i64,
-        }
-
-        let mut buffer_cache: Vec<BufferCacheData> =
-            vec![BufferCacheData::default(); buffer_layout.len()];
-
-        for VertexAttribute {
-            format,
-            buffer_index,
-            ..
-        } in attributes
-        {
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-            let cache = buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-
-            if layout.stride == 0 {
-                cache.stride += format.size_bytes();
+        assert!(
+            !params.primitive_restart || self.info.features.primitive_restart,
+            "primitive_restart is not supported by the GPU"
+        );
+        if let Some(attachments) = params.attachment_blend {
+            if attachments
+                .iter()
+                .any(|a| a.color_blend.is_none() && a.alpha_blend.is_some())
+            {
+                panic!("AlphaBlend without ColorBlend");
+            }
+            // the same state for all the attachments does not need indexed blending
+            if attachments.iter().all(|a| *a == attachments[0]) {
+                params.color_blend = attachments[0].color_blend;
+                params.alpha_blend = attachments[0].alpha_blend;
+                params.color_write = attachments[0].color_write;
+                params.attachment_blend = None;
             } else {
-                cache.stride = layout.stride;
+                assert!(
+                    self.info.features.independent_blend,
+                    "Different attachment_blend states require features.independent_blend"
+                );
             }
-            // WebGL 1 limitation
-            assert!(cache.stride <= 255);
         }
 
         let program = self.shaders[shader.0].program;
+        let pipeline = PipelineInternal {
+            layout: vertex_layout(program, buffer_layout, attributes),
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
+            shader,
+            params,
+            compute: false,
+        };
 
-        let attributes_len = attributes
-            .iter()
-            .map(|layout| match layout.format {
-                VertexFormat::Mat4 => 4,
-                _ => 1,
-            })
-            .sum();
-
-        let mut vertex_layout: Vec<Option<VertexAttributeInternal>> = vec![None; attributes_len];
-
-        for VertexAttribute {
-            name,
-            format,
-            buffer_index,
-            gl_pass_as_float,
-        } in attributes
-        {
-            let buffer_data = &mut buffer_cache
-                .get_mut(*buffer_index)
-                .unwrap_or_else(|| panic!());
-            let layout = buffer_layout.get(*buffer_index).unwrap_or_else(|| panic!());
-
-            let cname = CString::new(*name).unwrap_or_else(|e| panic!("{}", e));
-            let attr_loc = unsafe { glGetAttribLocation(program, cname.as_ptr() as *const _) };
-            let attr_loc = if attr_loc == -1 { None } else { Some(attr_loc) };
-            let divisor = if layout.step_func == VertexStep::PerVertex {
-                0
-            } else {
-                layout.step_rate
-            };
-
-            let mut attributes_count: usize = 1;
-            let mut format = *format;
-
-            if format == VertexFormat::Mat4 {
-                format = VertexFormat::Float4;
-                attributes_count = 4;
-            }
-            for i in 0..attributes_count {
-                if let Some(attr_loc) = attr_loc {
-                    let attr_loc = attr_loc as GLuint + i as GLuint;
-
-                    let attr = VertexAttributeInternal {
-                        attr_loc,
-                        size: format.components(),
-                        type_: format.type_(),
-                        offset: buffer_data.offset,
-                        stride: buffer_data.stride,
-                        buffer_index: *buffer_index,
-                        divisor,
-                        gl_pass_as_float: *gl_pass_as_float,
-                    };
+        Pipeline(self.pipelines.add(pipeline))
+    }
 
-                    assert!(
-                        attr_loc < vertex_layout.len() as u32,
-                        "attribute: {} outside of allocated attributes array len: {}",
-                        name,
-                        vertex_layout.len()
-                    );
-                    vertex_layout[attr_loc as usize] = Some(attr);
-                }
-                buffer_data.offset += format.size_bytes() as i64
-            }
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, GraphicsError> {
+        self.info.check_pipeline_params(&params)?;
+        if self.shaders.get(shader.0).is_none() {
+            return Err(GraphicsError::InvalidId(format!("{:?}", shader)));
         }
+        Ok(self.new_pipeline(buffer_layout, attributes, shader, params))
+    }
 
+    fn new_compute_pipeline(&mut self, shader: ShaderId) -> Pipeline {
         let pipeline = PipelineInternal {
-            layout: vertex_layout,
+            layout: vec![],
+            buffer_layout: vec![],
+            attributes: vec![],
             shader,
-            params,
+            params: Default::default(),
+            compute: true,
         };
 
         Pipeline(self.pipelines.add(pipeline))
@@ -1295,11 +2963,26 @@ impl RenderingBackend for GlContext {
     fn apply_pipeline(&mut self, pipeline: &Pipeline) {
         self.cache.cur_pipeline = Some(*pipeline);
 
-        {
-            let pipeline = &self.pipelines[pipeline.0];
-            let shader = &self.shaders[pipeline.shader.0];
-            unsafe {
-                glUseProgram(shader.program);
+        {
+            let pipeline = &self.pipelines[pipeline.0];
+            let shader = &self.shaders[pipeline.shader.0];
+            unsafe {
+                glUseProgram(shader.program);
+            }
+
+            // without uniform buffers, block values are a part of program state
+            // and are lost on program change
+            if self.uniform_ring.is_none() {
+                for (block, data) in shader.uniform_blocks.iter().zip(&self.uniform_block_data) {
+                    if let Some(data) = data {
+                        unsafe { block.apply_fields(data) };
+                    }
+                }
+            }
+
+            // fixed function state is irrelevant for dispatches
+            if pipeline.compute {
+                return;
             }
 
             unsafe {
@@ -1328,13 +3011,17 @@ impl RenderingBackend for GlContext {
         }
 
         self.set_cull_face(self.pipelines[pipeline.0].params.cull_face);
-        self.set_blend(
-            self.pipelines[pipeline.0].params.color_blend,
-            self.pipelines[pipeline.0].params.alpha_blend,
-        );
+        self.set_attachment_blend(self.pipelines[pipeline.0].params.attachment_blend);
+        if self.cache.attachment_blend.is_none() {
+            self.set_blend(
+                self.pipelines[pipeline.0].params.color_blend,
+                self.pipelines[pipeline.0].params.alpha_blend,
+            );
+            self.set_color_write(self.pipelines[pipeline.0].params.color_write);
+        }
 
         self.set_stencil(self.pipelines[pipeline.0].params.stencil_test);
-        self.set_color_write(self.pipelines[pipeline.0].params.color_write);
+        self.set_primitive_restart(self.pipelines[pipeline.0].params.primitive_restart);
     }
 
     fn new_buffer(
@@ -1356,8 +3043,35 @@ impl RenderingBackend for GlContext {
                 Some(element_size as u32)
             }
             BufferType::IndexBuffer => panic!("unsupported index buffer dimension"),
-            BufferType::VertexBuffer => None,
+            BufferType::StorageBuffer => {
+                assert!(
+                    self.info.features.compute,
+                    "Storage buffers are not supported by the GPU"
+                );
+                None
+            }
+            BufferType::VertexBuffer | BufferType::IndirectBuffer => None,
         };
+
+        if type_ == BufferType::IndirectBuffer && !self.info.features.draw_indirect {
+            let cpu_copy = match data {
+                BufferSource::Slice(data) => {
+                    debug_assert!(data.is_slice);
+                    unsafe { std::slice::from_raw_parts(data.ptr as *const u8, size) }.to_vec()
+                }
+                BufferSource::Empty { .. } => vec![0; size],
+            };
+            let buffer = Buffer {
+                gl_buf: 0,
+                buffer_type: type_,
+                size,
+                index_type,
+                usage,
+                cpu_copy: Some(cpu_copy),
+            };
+            return BufferId(self.buffers.add(buffer));
+        }
+
         let mut gl_buf: u32 = 0;
 
         unsafe {
@@ -1378,18 +3092,38 @@ impl RenderingBackend for GlContext {
             buffer_type: type_,
             size,
             index_type,
+            usage,
+            cpu_copy: None,
         };
 
         BufferId(self.buffers.add(buffer))
     }
 
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, GraphicsError> {
+        if type_ == BufferType::StorageBuffer && !self.info.features.compute {
+            return Err(GraphicsError::UnsupportedFeature("compute"));
+        }
+        unsafe { take_out_of_memory() };
+        let buffer = self.new_buffer(type_, usage, data);
+        if unsafe { take_out_of_memory() } {
+            self.delete_buffer(buffer);
+            return Err(GraphicsError::OutOfMemory);
+        }
+        Ok(buffer)
+    }
+
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
         let data = match data {
             BufferSource::Slice(data) => data,
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
         debug_assert!(data.is_slice);
-        let buffer = &self.buffers[buffer.0];
+        let buffer = &mut self.buffers[buffer.0];
 
         if matches!(buffer.buffer_type, BufferType::IndexBuffer) {
             assert!(buffer.index_type.is_some());
@@ -1398,13 +3132,119 @@ impl RenderingBackend for GlContext {
 
         let size = data.size;
 
-        assert!(size <= buffer.size);
+        assert!(byte_offset + size <= buffer.size);
+
+        if let Some(cpu_copy) = &mut buffer.cpu_copy {
+            let data = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, size) };
+            cpu_copy[byte_offset..byte_offset + size].copy_from_slice(data);
+            return;
+        }
+
+        let gl_target = gl_buffer_target(&buffer.buffer_type);
+        self.cache.store_buffer_binding(gl_target);
+        self.cache
+            .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
+        unsafe { glBufferSubData(gl_target, byte_offset as _, size as _, data.ptr as _) };
+        self.cache.restore_buffer_binding(gl_target);
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        assert!(
+            self.info.features.buffer_read,
+            "Buffer readback is not supported by the GPU"
+        );
+        let buffer = &self.buffers[buffer.0];
+        let size = data.len();
+        assert!(byte_offset + size <= buffer.size);
+
+        if let Some(cpu_copy) = &buffer.cpu_copy {
+            data.copy_from_slice(&cpu_copy[byte_offset..byte_offset + size]);
+            return;
+        }
+        if size == 0 {
+            return;
+        }
+
+        let gl_target = gl_buffer_target(&buffer.buffer_type);
+        self.cache.store_buffer_binding(gl_target);
+        self.cache
+            .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
+        unsafe {
+            if self.info.features.buffer_map {
+                let ptr = glMapBufferRange(gl_target, byte_offset as _, size as _, GL_MAP_READ_BIT);
+                assert!(!ptr.is_null(), "Failed to map the buffer");
+                std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr(), size);
+                glUnmapBuffer(gl_target);
+            } else {
+                glGetBufferSubData(
+                    gl_target,
+                    byte_offset as _,
+                    size as _,
+                    data.as_mut_ptr() as _,
+                );
+            }
+        }
+        self.cache.restore_buffer_binding(gl_target);
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        let buffer = &mut self.buffers[buffer.0];
+        assert!(byte_offset + size <= buffer.size);
+
+        if let Some(cpu_copy) = &mut buffer.cpu_copy {
+            f(&mut cpu_copy[byte_offset..byte_offset + size]);
+            return;
+        }
+        if size == 0 {
+            return;
+        }
 
+        let orphan = buffer.usage == BufferUsage::Stream;
         let gl_target = gl_buffer_target(&buffer.buffer_type);
         self.cache.store_buffer_binding(gl_target);
         self.cache
             .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
-        unsafe { glBufferSubData(gl_target, 0, size as _, data.ptr as _) };
+        unsafe {
+            if self.info.features.buffer_map {
+                let invalidate = if orphan {
+                    GL_MAP_INVALIDATE_BUFFER_BIT
+                } else {
+                    GL_MAP_INVALIDATE_RANGE_BIT
+                };
+                let ptr = glMapBufferRange(
+                    gl_target,
+                    byte_offset as _,
+                    size as _,
+                    GL_MAP_WRITE_BIT | invalidate,
+                );
+                assert!(!ptr.is_null(), "Failed to map the buffer");
+                f(std::slice::from_raw_parts_mut(ptr as *mut u8, size));
+                glUnmapBuffer(gl_target);
+            } else {
+                let mut staging = vec![0; size];
+                f(&mut staging);
+                if orphan {
+                    glBufferData(
+                        gl_target,
+                        buffer.size as _,
+                        std::ptr::null(),
+                        gl_usage(&buffer.usage),
+                    );
+                }
+                glBufferSubData(
+                    gl_target,
+                    byte_offset as _,
+                    size as _,
+                    staging.as_ptr() as _,
+                );
+            }
+        }
         self.cache.restore_buffer_binding(gl_target);
     }
 
@@ -1421,10 +3261,111 @@ impl RenderingBackend for GlContext {
     /// There is no protection against using deleted textures later. However its not an UB in OpenGl and thats why
     /// this function is not marked as unsafe
     fn delete_buffer(&mut self, buffer: BufferId) {
-        unsafe { glDeleteBuffers(1, &self.buffers[buffer.0].gl_buf as *const _) }
+        self.try_delete_buffer(buffer)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        let internal = self
+            .buffers
+            .remove(buffer.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", buffer)))?;
+        unsafe { glDeleteBuffers(1, &internal.gl_buf as *const _) }
         self.cache.clear_buffer_bindings();
         self.cache.clear_vertex_attributes();
-        self.buffers.remove(buffer.0);
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        assert!(
+            self.info.query_supported(kind),
+            "{:?} queries are not supported by the GPU",
+            kind
+        );
+        let mut gl_query = 0;
+        unsafe { glGenQueries(1, &mut gl_query) };
+        QueryId(self.queries.add(Query { gl_query, kind }))
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        let query = self.queries[query.0];
+        assert!(
+            query.kind != QueryKind::Timestamp,
+            "Timestamp queries are recorded with end_query alone"
+        );
+        unsafe { glBeginQuery(gl_query_target(query.kind), query.gl_query) };
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        let query = self.queries[query.0];
+        unsafe {
+            if query.kind == QueryKind::Timestamp {
+                glQueryCounter(query.gl_query, GL_TIMESTAMP);
+            } else {
+                glEndQuery(gl_query_target(query.kind));
+            }
+        }
+    }
+
+    fn query_result(&mut self, query: QueryId) -> Option<u64> {
+        let query = self.queries[query.0];
+        let mut available: GLuint = 0;
+        unsafe { glGetQueryObjectuiv(query.gl_query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+        if available == 0 {
+            return None;
+        }
+
+        // glGetQueryObjectui64v is not a part of GLES, but 32 bits are enough
+        // for anything except timestamps, which are not supported there
+        let gles =
+            cfg!(not(target_arch = "wasm32")) && self.info.gl_version_string.contains("OpenGL ES");
+        unsafe {
+            if gles {
+                let mut result: GLuint = 0;
+                glGetQueryObjectuiv(query.gl_query, GL_QUERY_RESULT, &mut result);
+                Some(result as u64)
+            } else {
+                let mut result: GLuint64 = 0;
+                glGetQueryObjectui64v(query.gl_query, GL_QUERY_RESULT, &mut result);
+                Some(result)
+            }
+        }
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        let query = self
+            .queries
+            .remove(query.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", query))));
+        unsafe { glDeleteQueries(1, &query.gl_query) };
+    }
+
+    fn insert_fence(&mut self) -> FenceId {
+        let fence = unsafe {
+            if self.info.features.async_readback {
+                let fence = glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+                glFlush();
+                fence
+            } else {
+                glFinish();
+                std::ptr::null_mut()
+            }
+        };
+        FenceId(self.fences.add(fence))
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        unsafe { sync_signaled(self.fences[fence.0]) }
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        let sync = self
+            .fences
+            .remove(fence.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", fence))));
+        if !sync.is_null() {
+            unsafe { glDeleteSync(sync) }
+        }
     }
 
     /// Set a new viewport rectangle.
@@ -1443,38 +3384,24 @@ impl RenderingBackend for GlContext {
         }
     }
 
-    fn apply_bindings_from_slice(
+    fn apply_bindings_with_samplers(
         &mut self,
         vertex_buffers: &[BufferId],
-        index_buffer: BufferId,
+        index_buffer: Option<BufferId>,
         textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
     ) {
-        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
-        let shader = &self.shaders[pip.shader.0];
-
-        for (n, shader_image) in shader.images.iter().enumerate() {
-            let bindings_image = textures
-                .get(n)
-                .unwrap_or_else(|| panic!("Image count in bindings and shader did not match!"));
-            if let Some(gl_loc) = shader_image.gl_loc {
-                let texture = self.textures.get(*bindings_image);
-                let raw = match texture.raw {
-                    TextureOrRenderbuffer::Texture(id) => id,
-                    TextureOrRenderbuffer::Renderbuffer(id) => id,
-                };
-                unsafe {
-                    self.cache.bind_texture(n, texture.params.kind.into(), raw);
-                    glUniform1i(gl_loc, n as i32);
-                }
-            }
+        self.bind_shader_images(textures, samplers);
+
+        match index_buffer {
+            Some(index_buffer) => self.cache.bind_buffer(
+                GL_ELEMENT_ARRAY_BUFFER,
+                self.buffers[index_buffer.0].gl_buf,
+                self.buffers[index_buffer.0].index_type,
+            ),
+            None => self.cache.bind_buffer(GL_ELEMENT_ARRAY_BUFFER, 0, None),
         }
 
-        self.cache.bind_buffer(
-            GL_ELEMENT_ARRAY_BUFFER,
-            self.buffers[index_buffer.0].gl_buf,
-            self.buffers[index_buffer.0].index_type,
-        );
-
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
 
         for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
@@ -1488,7 +3415,7 @@ impl RenderingBackend for GlContext {
                     "Attribute index outside of vertex_buffers length"
                 );
                 let vb = vertex_buffers[attribute.buffer_index];
-                let vb = self.buffers[vb.0];
+                let vb = &self.buffers[vb.0];
 
                 if cached_attr.map_or(true, |cached_attr| {
                     attribute != cached_attr.attribute || cached_attr.gl_vbuf != vb.gl_buf
@@ -1497,28 +3424,7 @@ impl RenderingBackend for GlContext {
                         .bind_buffer(GL_ARRAY_BUFFER, vb.gl_buf, vb.index_type);
 
                     unsafe {
-                        match attribute.type_ {
-                            GL_INT | GL_UNSIGNED_INT | GL_SHORT | GL_UNSIGNED_SHORT
-                            | GL_UNSIGNED_BYTE | GL_BYTE
-                                if !attribute.gl_pass_as_float =>
-                            {
-                                glVertexAttribIPointer(
-                                    attr_index as GLuint,
-                                    attribute.size,
-                                    attribute.type_,
-                                    attribute.stride,
-                                    attribute.offset as *mut _,
-                                )
-                            }
-                            _ => glVertexAttribPointer(
-                                attr_index as GLuint,
-                                attribute.size,
-                                attribute.type_,
-                                GL_FALSE as u8,
-                                attribute.stride,
-                                attribute.offset as *mut _,
-                            ),
-                        }
+                        vertex_attrib_pointer(attr_index as GLuint, &attribute, attribute.offset);
                         if self.info.features.instancing {
                             glVertexAttribDivisor(attr_index as GLuint, attribute.divisor as u32);
                         }
@@ -1547,53 +3453,58 @@ impl RenderingBackend for GlContext {
         let mut offset = 0;
 
         for uniform in shader.uniforms.iter() {
-            use UniformType::*;
-
             assert!(
                 offset as i32 <= size as i32 - uniform.uniform_type.size() as i32 / 4,
                 "Uniforms struct does not match shader uniforms layout"
             );
 
             unsafe {
-                let data = (uniform_ptr as *const f32).add(offset);
-                let data_int = (uniform_ptr as *const i32).add(offset);
-
-                if let Some(gl_loc) = uniform.gl_loc {
-                    match uniform.uniform_type {
-                        Float1 => {
-                            glUniform1fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float2 => {
-                            glUniform2fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float3 => {
-                            glUniform3fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float4 => {
-                            glUniform4fv(gl_loc, uniform.array_count, data);
-                        }
-                        Int1 => {
-                            glUniform1iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int2 => {
-                            glUniform2iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int3 => {
-                            glUniform3iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int4 => {
-                            glUniform4iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Mat4 => {
-                            glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
-                        }
-                    }
-                }
+                uniform.apply((uniform_ptr as *const f32).add(offset) as _);
             }
             offset += uniform.uniform_type.size() / 4 * uniform.array_count as usize;
         }
     }
 
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        let pip = &self.pipelines[self
+            .cache
+            .cur_pipeline
+            .expect("apply_uniform_block before apply_pipeline")
+            .0];
+        let shader = &self.shaders[pip.shader.0];
+        let block = shader
+            .uniform_blocks
+            .get(block_index)
+            .unwrap_or_else(|| panic!("Uniform block {} is not in ShaderMeta", block_index));
+
+        match &mut self.uniform_ring {
+            Some(ring) => unsafe {
+                // glsl always reads the whole std140 block, even if
+                // the data ends with a smaller, not padded, field
+                let reserve = size.max(block.size);
+                let (buffer, offset) = ring.push(uniform_ptr, size, reserve);
+                glBindBufferRange(
+                    GL_UNIFORM_BUFFER,
+                    block_index as _,
+                    buffer,
+                    offset as _,
+                    reserve as _,
+                );
+            },
+            None => {
+                let data = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+                unsafe { block.apply_fields(data) };
+                self.uniform_block_data[block_index] = Some(data.to_vec());
+            }
+        }
+    }
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1619,6 +3530,8 @@ impl RenderingBackend for GlContext {
             bits |= GL_STENCIL_BUFFER_BIT;
             unsafe {
                 glClearStencil(v);
+                // glClear respects the stencil write mask left by the last pipeline
+                glStencilMask(!0);
             }
         }
 
@@ -1627,6 +3540,13 @@ impl RenderingBackend for GlContext {
                 glClear(bits);
             }
         }
+
+        if let (Some(_), Some(cached)) = (stencil, self.cache.stencil) {
+            unsafe {
+                glStencilMaskSeparate(GL_FRONT, cached.front.write_mask);
+                glStencilMaskSeparate(GL_BACK, cached.back.write_mask);
+            }
+        }
     }
 
     fn begin_default_pass(&mut self, action: PassAction) {
@@ -1655,11 +3575,8 @@ impl RenderingBackend for GlContext {
                     .copied()
                     .or(pass.depth_texture)
                     .unwrap();
-                (
-                    pass.gl_fb,
-                    self.textures.get(texture).params.width as i32,
-                    self.textures.get(texture).params.height as i32,
-                )
+                let (width, height) = self.textures.get(texture).params.mip_size(pass.mip_level);
+                (pass.gl_fb, width as i32, height as i32)
             }
         };
         unsafe {
@@ -1720,9 +3637,122 @@ impl RenderingBackend for GlContext {
     fn commit_frame(&mut self) {
         self.cache.clear_buffer_bindings();
         self.cache.clear_texture_bindings();
+        if let Some(ring) = &mut self.uniform_ring {
+            ring.next_frame();
+        }
+        self.uniform_block_data = Default::default();
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.multi_draw(&[DrawRange {
+            base_element,
+            num_elements,
+            base_vertex: 0,
+            num_instances,
+        }]);
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        if !self.info.features.instancing && draws.iter().any(|draw| draw.num_instances != 1) {
+            eprintln!("Instanced rendering is not supported by the GPU");
+            eprintln!("Ignoring this draw call");
+            return;
+        }
+
+        let (primitive_type, index_type, index_size) = self.indexed_draw_types();
+        let emulate_base_vertex = !self.info.features.base_vertex;
+        let mut base_vertex = 0;
+
+        for draw in draws {
+            if emulate_base_vertex && draw.base_vertex != base_vertex {
+                base_vertex = draw.base_vertex;
+                self.offset_vertex_attributes(base_vertex);
+            }
+            let indices = (index_size * draw.base_element) as *mut _;
+
+            unsafe {
+                if draw.base_vertex != 0 && !emulate_base_vertex {
+                    glDrawElementsInstancedBaseVertex(
+                        primitive_type,
+                        draw.num_elements,
+                        index_type,
+                        indices,
+                        draw.num_instances,
+                        draw.base_vertex,
+                    );
+                } else {
+                    glDrawElementsInstanced(
+                        primitive_type,
+                        draw.num_elements,
+                        index_type,
+                        indices,
+                        draw.num_instances,
+                    );
+                }
+            }
+        }
+
+        if base_vertex != 0 {
+            self.offset_vertex_attributes(0);
+        }
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize) {
+        let buffer = &self.buffers[indirect_buffer.0];
+        assert!(
+            buffer.buffer_type == BufferType::IndirectBuffer,
+            "Draw arguments should be in a BufferType::IndirectBuffer"
+        );
+        let stride = std::mem::size_of::<DrawIndirectArgs>();
+        assert!(
+            offset + draw_count * stride <= buffer.size,
+            "Indirect draw arguments out of the buffer bounds"
+        );
+
+        if let Some(cpu_copy) = &buffer.cpu_copy {
+            let draws = cpu_copy[offset..offset + draw_count * stride]
+                .chunks_exact(stride)
+                .map(|args| {
+                    let args: DrawIndirectArgs =
+                        unsafe { std::ptr::read_unaligned(args.as_ptr() as *const _) };
+                    assert!(args.base_instance == 0, "Unsupported base_instance");
+                    DrawRange {
+                        base_element: args.base_element as i32,
+                        num_elements: args.num_elements as i32,
+                        base_vertex: args.base_vertex,
+                        num_instances: args.num_instances as i32,
+                    }
+                })
+                .collect::<Vec<_>>();
+            self.multi_draw(&draws);
+            return;
+        }
+
+        let (primitive_type, index_type, _) = self.indexed_draw_types();
+        unsafe {
+            glBindBuffer(GL_DRAW_INDIRECT_BUFFER, buffer.gl_buf);
+            if self.info.features.multi_draw_indirect {
+                glMultiDrawElementsIndirect(
+                    primitive_type,
+                    index_type,
+                    offset as *const _,
+                    draw_count as _,
+                    0,
+                );
+            } else {
+                for n in 0..draw_count {
+                    glDrawElementsIndirect(
+                        primitive_type,
+                        index_type,
+                        (offset + n * stride) as *const _,
+                    );
+                }
+            }
+            glBindBuffer(GL_DRAW_INDIRECT_BUFFER, 0);
+        }
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
         assert!(
             self.cache.cur_pipeline.is_some(),
             "Drawing without any binded pipeline"
@@ -1735,22 +3765,257 @@ impl RenderingBackend for GlContext {
         }
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        assert!(!pip.compute, "Drawing with a compute pipeline");
         let primitive_type = pip.params.primitive_type.into();
-        let index_type = self.cache.index_type.expect("Unset index buffer type");
 
         unsafe {
-            glDrawElementsInstanced(
-                primitive_type,
-                num_elements,
-                match index_type {
-                    1 => GL_UNSIGNED_BYTE,
-                    2 => GL_UNSIGNED_SHORT,
-                    4 => GL_UNSIGNED_INT,
-                    _ => panic!("Unsupported index buffer type!"),
-                },
-                (index_type as i32 * base_element) as *mut _,
-                num_instances,
+            if num_instances == 1 {
+                glDrawArrays(primitive_type, first_vertex, num_vertices);
+            } else {
+                glDrawArraysInstanced(primitive_type, first_vertex, num_vertices, num_instances);
+            }
+        }
+    }
+
+    fn apply_compute_bindings(&mut self, bindings: &ComputeBindings) {
+        let pip = &self.pipelines[self
+            .cache
+            .cur_pipeline
+            .expect("No compute pipeline applied")
+            .0];
+        assert!(pip.compute, "apply_compute_bindings with a render pipeline");
+
+        self.bind_shader_images(&bindings.images, &[]);
+
+        for (n, buffer) in bindings.storage_buffers.iter().enumerate() {
+            let buffer = &self.buffers[buffer.0];
+            assert!(
+                buffer.buffer_type == BufferType::StorageBuffer,
+                "Only BufferType::StorageBuffer could be bound as a storage buffer"
             );
+            unsafe {
+                glBindBufferBase(GL_SHADER_STORAGE_BUFFER, n as _, buffer.gl_buf);
+            }
+        }
+
+        for (n, image) in bindings.storage_images.iter().enumerate() {
+            let texture = self.textures.get(image.texture);
+            let raw = texture
+                .raw
+                .texture()
+                .expect("Multisampled textures could not be used as storage images");
+            let access = match image.access {
+                ImageAccess::ReadOnly => GL_READ_ONLY,
+                ImageAccess::WriteOnly => GL_WRITE_ONLY,
+                ImageAccess::ReadWrite => GL_READ_WRITE,
+            };
+            // whole cubemap is bound, faces are addressed by the z coordinate
+            let layered = texture.params.kind != TextureKind::Texture2D;
+            unsafe {
+                glBindImageTexture(
+                    n as _,
+                    raw,
+                    image.level as _,
+                    layered as _,
+                    0,
+                    access,
+                    texture.params.format.sized_internal_format(),
+                );
+            }
+        }
+    }
+
+    fn dispatch(&mut self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        let pip = &self.pipelines[self
+            .cache
+            .cur_pipeline
+            .expect("No compute pipeline applied")
+            .0];
+        assert!(pip.compute, "Dispatching a render pipeline");
+
+        unsafe {
+            glDispatchCompute(groups_x, groups_y, groups_z);
         }
     }
+
+    fn memory_barrier(&mut self, barriers: &[MemoryBarrier]) {
+        let bits = barriers
+            .iter()
+            .fold(0, |bits, barrier| bits | GLbitfield::from(*barrier));
+        if bits != 0 {
+            unsafe {
+                glMemoryBarrier(bits);
+            }
+        }
+    }
+}
+
+/// Run `f` once with a `GlContext` on a headless EGL surface.
+/// Returns `false` when there is no EGL on this machine.
+#[cfg(all(test, target_os = "linux"))]
+fn with_headless_gl_context(f: impl FnOnce(&mut GlContext) + 'static) -> bool {
+    struct Stage(Option<Box<dyn FnOnce(&mut GlContext)>>);
+    impl crate::EventHandler for Stage {
+        fn update(&mut self) {}
+        fn draw(&mut self) {
+            if let Some(f) = self.0.take() {
+                f(&mut GlContext::new());
+            }
+        }
+    }
+
+    let mut conf = crate::conf::Conf {
+        window_width: 4,
+        window_height: 4,
+        ..Default::default()
+    };
+    conf.platform.linux_backend = crate::conf::LinuxBackend::Headless;
+    conf.platform.headless_frames = Some(1);
+    let f: Box<dyn FnOnce(&mut GlContext)> = Box::new(f);
+    let mut stage = Some(move || Box::new(Stage(Some(f))) as Box<dyn crate::EventHandler>);
+    crate::native::linux_headless::run(&conf, &mut stage).is_ok()
+}
+
+// One test for all the GL paths, a process could only have a single display.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gl_context_headless() {
+    let ran = with_headless_gl_context(|ctx| {
+        let target = ctx.new_render_texture(TextureParams {
+            width: 1,
+            height: 1,
+            ..Default::default()
+        });
+        let pass = ctx.new_render_pass(target, None);
+        let draw = |ctx: &mut GlContext, pipeline, bindings: &Bindings| {
+            ctx.begin_pass(Some(pass), PassAction::clear_color(0., 0., 0., 0.));
+            ctx.apply_pipeline(&pipeline);
+            ctx.apply_bindings(bindings);
+            ctx.draw_arrays(0, 3, 1);
+            ctx.end_render_pass();
+            let mut pixel = [0; 4];
+            ctx.texture_read_pixels(target, &mut pixel);
+            pixel
+        };
+        let solid = |color: &str| {
+            format!(
+                "#version 100
+                void main() {{ gl_FragColor = vec4({}); }}",
+                color
+            )
+        };
+        fn glsl(fragment: &str) -> ShaderSource {
+            let vertex = "#version 100
+                attribute vec2 in_pos;
+                void main() { gl_Position = vec4(in_pos, 0.0, 1.0); }";
+            ShaderSource::Glsl { vertex, fragment }
+        }
+
+        // shader_replace keeps the pipeline, and the old program on errors
+        let red = solid("1.0, 0.0, 0.0, 1.0");
+        let (shader, pipeline, vertex_buffer) =
+            new_fullscreen_triangle(ctx, glsl(&red), ShaderMeta::default());
+        let bindings = Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer: None,
+            images: vec![],
+            samplers: vec![],
+        };
+        assert_eq!(draw(ctx, pipeline, &bindings), [255, 0, 0, 255]);
+        let broken = ctx.shader_replace(shader, glsl("void main() {"), ShaderMeta::default());
+        assert!(matches!(broken, Err(ShaderError::CompilationError { .. })));
+        assert_eq!(draw(ctx, pipeline, &bindings), [255, 0, 0, 255]);
+        let green = solid("0.0, 1.0, 0.0, 1.0");
+        ctx.shader_replace(shader, glsl(&green), ShaderMeta::default())
+            .unwrap();
+        assert_eq!(draw(ctx, pipeline, &bindings), [0, 255, 0, 255]);
+
+        // samplers override the texture's own state
+        let meta = ShaderMeta {
+            images: vec!["tex".to_string()],
+            ..Default::default()
+        };
+        let sample = "#version 100
+            uniform sampler2D tex;
+            void main() { gl_FragColor = texture2D(tex, vec2(1.25, 0.5)); }";
+        let (_, pipeline, vertex_buffer) = new_fullscreen_triangle(ctx, glsl(sample), meta);
+        let texture = ctx.new_texture_from_rgba8(2, 1, &[255, 0, 0, 255, 0, 255, 0, 255]);
+        ctx.texture_set_filter(texture, FilterMode::Nearest, MipmapFilterMode::None);
+        let nearest = SamplerParams {
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        let repeat = ctx.new_sampler(SamplerParams {
+            wrap_x: TextureWrap::Repeat,
+            ..nearest
+        });
+        let mut bindings = Bindings {
+            vertex_buffers: vec![vertex_buffer],
+            index_buffer: None,
+            images: vec![texture],
+            samplers: vec![None],
+        };
+        assert_eq!(draw(ctx, pipeline, &bindings), [0, 255, 0, 255]);
+        bindings.samplers = vec![Some(repeat)];
+        assert_eq!(draw(ctx, pipeline, &bindings), [255, 0, 0, 255]);
+        if ctx.info().features.clamp_to_border {
+            let border = ctx.new_sampler(SamplerParams {
+                wrap_x: TextureWrap::ClampToBorder,
+                border_color: [0., 0., 1., 1.],
+                ..nearest
+            });
+            bindings.samplers = vec![Some(border)];
+            assert_eq!(draw(ctx, pipeline, &bindings), [0, 0, 255, 255]);
+        }
+
+        // mip chains are uploaded, updated and read level by level
+        let params = TextureParams {
+            width: 4,
+            height: 2,
+            ..Default::default()
+        };
+        let texture = ctx.new_texture_from_mips(&[&[1; 32], &[2; 8], &[3; 4]], params);
+        assert_eq!(ctx.texture_params(texture).mip_levels(), 3);
+        ctx.texture_update_mip_part(texture, 1, 0, 1, 0, 1, 1, &[4; 4]);
+        let mut pixels = [0; 8];
+        ctx.texture_read_mip_pixels(texture, 1, 0, &mut pixels);
+        assert_eq!(pixels, [2, 2, 2, 2, 4, 4, 4, 4]);
+        let mut pixels = [0; 4];
+        ctx.texture_read_mip_pixels(texture, 2, 0, &mut pixels);
+        assert_eq!(pixels, [3; 4]);
+
+        // and rendered to one level and one cubemap face at a time
+        let cubemap = ctx.new_render_texture(TextureParams {
+            kind: TextureKind::CubeMap,
+            width: 2,
+            height: 2,
+            allocate_mipmaps: true,
+            ..Default::default()
+        });
+        let face = RenderTarget {
+            texture: cubemap,
+            mip_level: 1,
+            layer: 3,
+        };
+        let pass = ctx.new_render_pass_targets(&[face], None, None);
+        ctx.begin_pass(Some(pass), PassAction::clear_color(1., 0., 0., 1.));
+        ctx.end_render_pass();
+        let mut pixels = [0; 4];
+        for layer in 0..6 {
+            ctx.texture_read_mip_pixels(cubemap, 1, layer, &mut pixels);
+            let expected = if layer == 3 { [255, 0, 0, 255] } else { [0; 4] };
+            assert_eq!(pixels, expected);
+        }
+        let out_of_range = RenderTarget {
+            mip_level: 2,
+            ..face
+        };
+        assert!(ctx
+            .try_new_render_pass_targets(&[out_of_range], None, None)
+            .is_err());
+    });
+    if !ran {
+        eprintln!("test_gl_context_headless: no EGL, skipped");
+    }
 }
@@ -35,13 +35,17 @@ pub struct GlCache {
     pub index_type: Option<u32>,
     pub vertex_buffer: GLuint,
     pub textures: [CachedTexture; MAX_SHADERSTAGE_IMAGES],
+    // sampler objects, 0 for the texture's own sampling state
+    pub samplers: [GLuint; MAX_SHADERSTAGE_IMAGES],
     pub cur_pipeline: Option<Pipeline>,
     pub cur_pass: Option<RenderPass>,
     pub color_blend: Option<BlendState>,
     pub alpha_blend: Option<BlendState>,
     pub stencil: Option<StencilState>,
     pub color_write: ColorMask,
+    pub attachment_blend: Option<[AttachmentBlend; MAX_COLOR_ATTACHMENTS]>,
     pub cull_face: CullFace,
+    pub primitive_restart: bool,
     pub attributes: [Option<CachedAttribute>; MAX_VERTEX_ATTRIBUTES],
 }
 
@@ -54,7 +58,7 @@ impl GlCache {
                     glBindBuffer(target, buffer);
                 }
             }
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             if self.index_buffer != buffer {
                 self.index_buffer = buffer;
                 unsafe {
@@ -62,13 +66,18 @@ impl GlCache {
                 }
             }
             self.index_type = index_type;
+        } else {
+            // other targets are only bound for uploads, no need to cache them
+            unsafe {
+                glBindBuffer(target, buffer);
+            }
         }
     }
 
     pub fn store_buffer_binding(&mut self, target: GLenum) {
         if target == GL_ARRAY_BUFFER {
             self.stored_vertex_buffer = self.vertex_buffer;
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             self.stored_index_buffer = self.index_buffer;
             self.stored_index_type = self.index_type;
         }
@@ -80,7 +89,7 @@ impl GlCache {
                 self.bind_buffer(target, self.stored_vertex_buffer, None);
                 self.stored_vertex_buffer = 0;
             }
-        } else if self.stored_index_buffer != 0 {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER && self.stored_index_buffer != 0 {
             self.bind_buffer(target, self.stored_index_buffer, self.stored_index_type);
             self.stored_index_buffer = 0;
         }
@@ -99,6 +108,15 @@ impl GlCache {
         }
     }
 
+    pub fn bind_sampler(&mut self, slot_index: usize, sampler: GLuint) {
+        if self.samplers[slot_index] != sampler {
+            self.samplers[slot_index] = sampler;
+            unsafe {
+                glBindSampler(slot_index as GLuint, sampler);
+            }
+        }
+    }
+
     pub fn store_texture_binding(&mut self, slot_index: usize) {
         self.stored_target = self.textures[slot_index].target;
         self.stored_texture = self.textures[slot_index].texture;
@@ -0,0 +1,167 @@
+//! On-disk cache of linked program binaries, see `Conf::program_cache_dir`.
+
+use std::{fs, path::PathBuf};
+
+use super::*;
+
+pub(super) struct ProgramCache {
+    dir: PathBuf,
+    gl_version_string: String,
+    renderer: String,
+    vendor: String,
+}
+
+fn gl_string(name: GLenum) -> String {
+    let string = unsafe { glGetString(name) };
+    if string.is_null() {
+        return String::new();
+    }
+    unsafe { std::ffi::CStr::from_ptr(string as _) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl ProgramCache {
+    /// None when the driver can not give program binaries back.
+    pub fn new(dir: PathBuf, gl_version_string: &str) -> Option<ProgramCache> {
+        if cfg!(target_arch = "wasm32") {
+            return None;
+        }
+        let gl2 = gl_version_string.is_empty() || gl_version_string.starts_with("2");
+        let gles = gl_version_string.contains("OpenGL ES");
+        let version = gl_version(gl_version_string);
+        let supported = if gles {
+            version >= (3, 0)
+        } else {
+            version >= (4, 1)
+                || gl_extensions(gl2)
+                    .iter()
+                    .any(|extension| extension == "ARB_get_program_binary")
+        };
+        if !supported {
+            return None;
+        }
+
+        // some drivers expose the entry points, but no binary formats at all
+        let mut formats: GLint = 0;
+        unsafe { glGetIntegerv(GL_NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+        if formats == 0 {
+            return None;
+        }
+
+        Some(ProgramCache {
+            dir,
+            gl_version_string: gl_version_string.to_owned(),
+            renderer: gl_string(GL_RENDERER),
+            vendor: gl_string(GL_VENDOR),
+        })
+    }
+
+    /// FNV-1a over the driver version, the GPU and the sources. Stable between runs and
+    /// rust versions, unlike `DefaultHasher`. Binaries of another GPU reporting the same
+    /// version string are never even tried.
+    pub fn key(&self, sources: &[&str]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut write = |bytes: &[u8]| {
+            for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        write(self.gl_version_string.as_bytes());
+        write(self.renderer.as_bytes());
+        write(self.vendor.as_bytes());
+        for source in sources {
+            write(source.as_bytes());
+        }
+        hash
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.glbin", key))
+    }
+
+    /// Linked program, or None if there is no binary or the driver rejected it.
+    pub fn load(&self, key: u64) -> Option<GLuint> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        if data.len() <= 4 {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let binary = &data[4..];
+
+        unsafe {
+            let program = glCreateProgram();
+            glProgramBinary(program, format, binary.as_ptr() as _, binary.len() as _);
+            let mut link_status = 0;
+            glGetProgramiv(program, GL_LINK_STATUS, &mut link_status);
+            if link_status == 0 {
+                // driver update or a corrupted file
+                glDeleteProgram(program);
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+            Some(program)
+        }
+    }
+
+    /// `program` should be linked with GL_PROGRAM_BINARY_RETRIEVABLE_HINT set.
+    pub fn store(&self, key: u64, program: GLuint) {
+        let mut length = 0;
+        unsafe { glGetProgramiv(program, GL_PROGRAM_BINARY_LENGTH, &mut length) };
+        if length <= 0 {
+            return;
+        }
+        let mut data = vec![0u8; 4 + length as usize];
+        let mut format = 0;
+        unsafe {
+            glGetProgramBinary(
+                program,
+                length,
+                &mut length,
+                &mut format,
+                data[4..].as_mut_ptr() as _,
+            )
+        };
+        data[..4].copy_from_slice(&format.to_le_bytes());
+        data.truncate(4 + length.max(0) as usize);
+
+        // write and rename, so a crash would not leave a half written binary behind
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        let res = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp, &data))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(err) = res {
+            eprintln!("Failed to store program binary {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[test]
+fn test_program_cache_key() {
+    let cache = |renderer: &str| ProgramCache {
+        dir: PathBuf::new(),
+        gl_version_string: "4.6.0 NVIDIA 550.54.14".to_string(),
+        renderer: renderer.to_string(),
+        vendor: "NVIDIA Corporation".to_string(),
+    };
+    let sources = [
+        "void main() {}",
+        "void main() { gl_FragColor = vec4(1.0); }",
+    ];
+
+    // the key names files on disk, it should never change between builds
+    let key = cache("NVIDIA GeForce RTX 3060").key(&sources);
+    assert_eq!(key, 0x3441e5463c61d1a5);
+    assert_ne!(cache("NVIDIA GeForce GTX 1050").key(&sources), key);
+    assert_ne!(cache("NVIDIA GeForce RTX 3060").key(&sources[..1]), key);
+    // sources are length prefixed, moving text between them changes the key
+    let moved = [
+        "void main() {} void",
+        " main() { gl_FragColor = vec4(1.0); }",
+    ];
+    assert_ne!(cache("NVIDIA GeForce RTX 3060").key(&moved), key);
+}
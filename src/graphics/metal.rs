@@ -6,6 +6,7 @@ use crate::native::apple::{
 };
 
 use super::*;
+use crate::ResourceManager;
 
 // https://developer.apple.com/metal/Metal-Feature-Set-Tables.pdf
 const MAX_UNIFORM_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
@@ -70,6 +71,18 @@ impl From<Comparison> for MTLCompareFunction {
     }
 }
 
+impl From<TextureWrap> for MTLSamplerAddressMode {
+    fn from(wrap: TextureWrap) -> Self {
+        match wrap {
+            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
+            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
+            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
+            TextureWrap::ClampToBorder => MTLSamplerAddressMode::ClampToBorderColor,
+            TextureWrap::MirrorClampToEdge => MTLSamplerAddressMode::MirrorClampToEdge,
+        }
+    }
+}
+
 impl From<BlendFactor> for MTLBlendFactor {
     fn from(factor: BlendFactor) -> Self {
         match factor {
@@ -96,20 +109,20 @@ impl From<BlendFactor> for MTLBlendFactor {
     }
 }
 
-// impl From<StencilOp> for MTLStencilOperation {
-//     fn from(op: StencilOp) -> Self {
-//         match op {
-//             StencilOp::Keep => MTLStencilOperation::Keep,
-//             StencilOp::Zero => MTLStencilOperation::Zero,
-//             StencilOp::Replace => MTLStencilOperation::Replace,
-//             StencilOp::IncrementClamp => MTLStencilOperation::IncrementClamp,
-//             StencilOp::DecrementClamp => MTLStencilOperation::DecrementClamp,
-//             StencilOp::Invert => MTLStencilOperation::Invert,
-//             StencilOp::IncrementWrap => MTLStencilOperation::IncrementWrap,
-//             StencilOp::DecrementWrap => MTLStencilOperation::DecrementWrap,
-//         }
-//     }
-// }
+impl From<StencilOp> for MTLStencilOperation {
+    fn from(op: StencilOp) -> Self {
+        match op {
+            StencilOp::Keep => MTLStencilOperation::Keep,
+            StencilOp::Zero => MTLStencilOperation::Zero,
+            StencilOp::Replace => MTLStencilOperation::Replace,
+            StencilOp::IncrementClamp => MTLStencilOperation::IncrementClamp,
+            StencilOp::DecrementClamp => MTLStencilOperation::DecrementClamp,
+            StencilOp::Invert => MTLStencilOperation::Invert,
+            StencilOp::IncrementWrap => MTLStencilOperation::IncrementWrap,
+            StencilOp::DecrementWrap => MTLStencilOperation::DecrementWrap,
+        }
+    }
+}
 
 impl From<Equation> for MTLBlendOperation {
     fn from(cf: Equation) -> Self {
@@ -151,6 +164,11 @@ impl From<PrimitiveType> for MTLPrimitiveType {
             PrimitiveType::Triangles => MTLPrimitiveType::Triangle,
             PrimitiveType::Lines => MTLPrimitiveType::Line,
             PrimitiveType::Points => MTLPrimitiveType::Point,
+            PrimitiveType::TriangleStrip => MTLPrimitiveType::TriangleStrip,
+            PrimitiveType::LineStrip => MTLPrimitiveType::LineStrip,
+            PrimitiveType::LineLoop | PrimitiveType::TriangleFan => {
+                panic!("{:?} is not supported on Metal", primitive_type)
+            }
         }
     }
 }
@@ -162,6 +180,34 @@ impl From<TextureFormat> for MTLPixelFormat {
             //TODO: Depth16Unorm ?
             TextureFormat::Depth => MTLPixelFormat::Depth32Float_Stencil8,
             TextureFormat::RGBA16F => MTLPixelFormat::RGBA16Float,
+            TextureFormat::R8 => MTLPixelFormat::R8Unorm,
+            TextureFormat::RG8 => MTLPixelFormat::RG8Unorm,
+            TextureFormat::R16F => MTLPixelFormat::R16Float,
+            TextureFormat::RG16F => MTLPixelFormat::RG16Float,
+            TextureFormat::R32F => MTLPixelFormat::R32Float,
+            TextureFormat::RGBA32F => MTLPixelFormat::RGBA32Float,
+            TextureFormat::R32UI => MTLPixelFormat::R32Uint,
+            TextureFormat::SRGB8A8 => MTLPixelFormat::RGBA8Unorm_sRGB,
+            TextureFormat::RGB10A2 => MTLPixelFormat::RGB10A2Unorm,
+            // Depth24Unorm_Stencil8 is not available on iOS
+            TextureFormat::Depth24Stencil8 => MTLPixelFormat::Depth32Float_Stencil8,
+            TextureFormat::BC1 => MTLPixelFormat::BC1_RGBA,
+            TextureFormat::BC2 => MTLPixelFormat::BC2_RGBA,
+            TextureFormat::BC3 => MTLPixelFormat::BC3_RGBA,
+            TextureFormat::BC4 => MTLPixelFormat::BC4_RUnorm,
+            TextureFormat::BC5 => MTLPixelFormat::BC5_RGUnorm,
+            TextureFormat::BC6H => MTLPixelFormat::BC6H_RGBUfloat,
+            TextureFormat::BC7 => MTLPixelFormat::BC7_RGBAUnorm,
+            TextureFormat::ETC2RGB8 => MTLPixelFormat::ETC2_RGB8,
+            TextureFormat::ETC2RGBA8 => MTLPixelFormat::EAC_RGBA8,
+            TextureFormat::EACR11 => MTLPixelFormat::EAC_R11Unorm,
+            TextureFormat::EACRG11 => MTLPixelFormat::EAC_RG11Unorm,
+            TextureFormat::ASTC4x4 => MTLPixelFormat::ASTC_4x4_LDR,
+            TextureFormat::ASTC5x5 => MTLPixelFormat::ASTC_5x5_LDR,
+            TextureFormat::ASTC6x6 => MTLPixelFormat::ASTC_6x6_LDR,
+            TextureFormat::ASTC8x8 => MTLPixelFormat::ASTC_8x8_LDR,
+            TextureFormat::ASTC10x10 => MTLPixelFormat::ASTC_10x10_LDR,
+            TextureFormat::ASTC12x12 => MTLPixelFormat::ASTC_12x12_LDR,
             _ => todo!(),
         }
     }
@@ -199,17 +245,37 @@ fn roundup_ub_buffer(current_buffer: u64) -> u64 {
 // There is no way to flush the buffer and makes metal's "update" function to update GPU memory right away.
 // Thus miniquad keeps a lot of buffer's copies...
 const BUFFERS_IN_ROTATION: usize = 30;
+// each query owns an u64 slot in the visibility result buffer
+const MAX_QUERIES: usize = 4096;
+
+// uniforms are at buffer(0), vertex buffers at buffer(1..=MAX_VERTEX_ATTRIBUTES)
+const UNIFORM_BLOCKS_BUFFER_INDEX: usize = MAX_VERTEX_ATTRIBUTES + 1;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Buffer {
     raw: [ObjcId; BUFFERS_IN_ROTATION],
     //buffer_type: BufferType,
     size: usize,
+    usage: BufferUsage,
     //index_type: Option<IndexType>,
     value: usize,
     next_value: usize,
 }
 
+impl Buffer {
+    /// Contents of the buffer the next update goes into.
+    /// With `keep_contents`, the current contents are copied over
+    /// if the next buffer in rotation is not the current one.
+    unsafe fn next_contents(&self, keep_contents: bool) -> *mut u8 {
+        let dest: *mut u8 = msg_send![self.raw[self.next_value], contents];
+        if keep_contents && self.next_value != self.value {
+            let src: *const u8 = msg_send![self.raw[self.value], contents];
+            std::ptr::copy_nonoverlapping(src, dest, self.size);
+        }
+        dest
+    }
+}
+
 #[derive(Debug)]
 struct ShaderInternal {
     vertex_function: ObjcId,
@@ -222,19 +288,35 @@ struct ShaderInternal {
 struct RenderPassInternal {
     render_pass_desc: ObjcId,
     texture: Vec<TextureId>,
-    _depth_texture: Option<TextureId>,
+    depth_texture: Option<TextureId>,
 }
 
 #[derive(Clone, Debug)]
 struct PipelineInternal {
     pipeline_state: ObjcId,
+    // MTLRenderPipelineDescriptor, to make a new pipeline_state on shader_replace
+    descriptor: ObjcId,
     depth_stencil_state: ObjcId,
     //layout: Vec<BufferLayout>,
     //attributes: Vec<VertexAttributeInternal>,
-    _shader: ShaderId,
+    shader: ShaderId,
+    primitive_type: MTLPrimitiveType,
+    // front and back stencil reference values
+    stencil_ref: Option<(u32, u32)>,
     //params: PipelineParams,
 }
 
+unsafe fn new_stencil_descriptor(face: &StencilFaceState) -> ObjcId {
+    let desc = msg_send_![class!(MTLStencilDescriptor), new];
+    msg_send_![desc, setStencilCompareFunction: MTLCompareFunction::from(face.test_func)];
+    msg_send_![desc, setStencilFailureOperation: MTLStencilOperation::from(face.fail_op)];
+    msg_send_![desc, setDepthFailureOperation: MTLStencilOperation::from(face.depth_fail_op)];
+    msg_send_![desc, setDepthStencilPassOperation: MTLStencilOperation::from(face.pass_op)];
+    msg_send_![desc, setReadMask: face.test_mask];
+    msg_send_![desc, setWriteMask: face.write_mask];
+    desc
+}
+
 #[derive(Clone, Copy)]
 struct Texture {
     texture: ObjcId,
@@ -242,7 +324,7 @@ struct Texture {
     sampler_descriptor: ObjcId,
     params: TextureParams,
 }
-struct Textures(Vec<Texture>);
+struct Textures(ResourceManager<Texture>);
 
 impl Textures {
     fn get(&self, texture: TextureId) -> Texture {
@@ -262,11 +344,12 @@ impl Textures {
     }
 }
 pub struct MetalContext {
-    buffers: Vec<Buffer>,
-    shaders: Vec<ShaderInternal>,
-    pipelines: Vec<PipelineInternal>,
+    buffers: ResourceManager<Buffer>,
+    shaders: ResourceManager<ShaderInternal>,
+    pipelines: ResourceManager<PipelineInternal>,
     textures: Textures,
-    passes: Vec<RenderPassInternal>,
+    samplers: ResourceManager<ObjcId>,
+    passes: ResourceManager<RenderPassInternal>,
     command_queue: ObjcId,
     command_buffer: Option<ObjcId>,
     render_encoder: Option<ObjcId>,
@@ -279,6 +362,29 @@ pub struct MetalContext {
     // cached pipeline from apply_pipeline
     current_pipeline: Option<Pipeline>,
     current_ub_offset: u64,
+    queries: ResourceManager<Query>,
+    visibility_buffer: ObjcId,
+    readbacks: ResourceManager<Readback>,
+    // frame each fence was inserted in
+    fences: ResourceManager<u64>,
+    // number of committed frames, commit_frame waits for the GPU
+    // so everything encoded before is finished
+    frame_counter: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Query {
+    kind: QueryKind,
+    // frame the query was ended in, results are ready once the frame is committed
+    end_frame: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Readback {
+    buffer: ObjcId,
+    size: usize,
+    // frame the blit was encoded in
+    frame: u64,
 }
 
 impl Default for MetalContext {
@@ -357,27 +463,112 @@ impl MetalContext {
                           options:options],
             ];
 
+            let visibility_buffer: ObjcId = msg_send![device,
+                                              newBufferWithLength:(MAX_QUERIES * 8) as u64
+                                              options:MTLResourceOptions::StorageModeShared];
+
             MetalContext {
                 command_queue,
                 command_buffer: None,
                 render_encoder: None,
                 view,
                 device,
-                buffers: vec![],
-                shaders: vec![],
-                pipelines: vec![],
-                textures: Textures(Vec::new()),
-                passes: vec![],
+                buffers: ResourceManager::default(),
+                shaders: ResourceManager::default(),
+                pipelines: ResourceManager::default(),
+                textures: Textures(ResourceManager::default()),
+                samplers: ResourceManager::default(),
+                passes: ResourceManager::default(),
                 index_buffer: None,
                 current_pipeline: None,
                 uniform_buffers,
                 current_frame_index: 1,
                 current_ub_offset: 0,
+                queries: ResourceManager::default(),
+                visibility_buffer,
+                readbacks: ResourceManager::default(),
+                fences: ResourceManager::default(),
+                frame_counter: 0,
             }
         }
     }
 }
 
+impl MetalContext {
+    unsafe fn load_library(&self, program: &str) -> Result<ShaderInternal, String> {
+        let shader = apple_util::str_to_nsstring(program);
+        let mut error: ObjcId = nil;
+        let library: ObjcId = msg_send![
+            self.device,
+            newLibraryWithSource: shader
+            options:nil
+            error: &mut error
+        ];
+        if library.is_null() {
+            let description: ObjcId = msg_send![error, localizedDescription];
+            return Err(apple_util::nsstring_to_string(description));
+        }
+
+        let vertex_function: ObjcId =
+            msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("vertexShader")];
+        if vertex_function.is_null() {
+            return Err("vertexShader function is missing".to_string());
+        }
+        let fragment_function: ObjcId =
+            msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("fragmentShader")];
+        if fragment_function.is_null() {
+            msg_send_![vertex_function, release];
+            return Err("fragmentShader function is missing".to_string());
+        }
+        Ok(ShaderInternal {
+            vertex_function,
+            fragment_function,
+        })
+    }
+
+    fn push_uniforms(&mut self, uniform_ptr: *const u8, size: usize, index: u64) {
+        assert!(
+            self.current_pipeline.is_some(),
+            "apply_uniforms before apply_pipeline"
+        );
+        assert!(
+            self.render_encoder.is_some(),
+            "apply_uniforms before begin_pass"
+        );
+
+        let render_encoder = self.render_encoder.unwrap();
+
+        self.current_frame_index = (self.current_frame_index + 1) % NUM_INFLIGHT_FRAMES;
+
+        assert!(size < MAX_UNIFORM_BUFFER_SIZE as usize);
+
+        assert!(self.current_ub_offset < MAX_UNIFORM_BUFFER_SIZE);
+
+        let buffer = self.uniform_buffers[self.current_frame_index];
+        unsafe {
+            let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
+            std::ptr::copy(
+                uniform_ptr as _,
+                dest.add(self.current_ub_offset as usize),
+                size,
+            );
+
+            #[cfg(target_os = "macos")]
+            msg_send_![buffer, didModifyRange:NSRange::new(0, size as u64)];
+
+            msg_send_![render_encoder,
+                       setVertexBuffer:buffer
+                       offset:self.current_ub_offset
+                       atIndex:index];
+            msg_send_![render_encoder,
+                       setFragmentBuffer:buffer
+                       offset:self.current_ub_offset
+                       atIndex:index];
+        }
+        self.current_ub_offset = roundup_ub_buffer(self.current_ub_offset + size as u64);
+    }
+}
+
 impl RenderingBackend for MetalContext {
     fn info(&self) -> ContextInfo {
         ContextInfo {
@@ -387,7 +578,43 @@ impl RenderingBackend for MetalContext {
             features: Features {
                 instancing: true,
                 resolve_attachments: false,
+                compute: false,
+                uniform_buffers: true,
+                texture_3d: true,
+                sized_texture_formats: true,
+                float_render_targets: true,
+                // 32-bit float filtering is optional on Apple GPUs
+                float32_filtering: cfg!(target_os = "macos"),
+                // BC is macOS only, ETC2 and ASTC are available on Apple GPUs,
+                // but not on Intel macs
+                texture_compression_s3tc: cfg!(target_os = "macos"),
+                texture_compression_rgtc: cfg!(target_os = "macos"),
+                texture_compression_bptc: cfg!(target_os = "macos"),
+                texture_compression_etc2: cfg!(target_os = "ios"),
+                texture_compression_astc: cfg!(target_os = "ios"),
+                base_vertex: true,
+                draw_indirect: true,
+                multi_draw_indirect: false,
+                buffer_read: true,
+                buffer_map: true,
+                query_time_elapsed: false,
+                query_timestamp: false,
+                query_any_samples_passed: true,
+                query_samples_passed: true,
+                query_primitives_generated: false,
+                async_readback: true,
+                independent_blend: true,
+                line_loop_triangle_fan: false,
+                primitive_restart: true,
+                sampler_objects: true,
+                // border colors and mirror clamp are macOS only
+                clamp_to_border: cfg!(target_os = "macos"),
+                mirror_clamp_to_edge: cfg!(target_os = "macos"),
+                sampler_compare: true,
             },
+            // 8192 on the oldest iOS GPUs, miniquad does not query the GPU family
+            max_texture_size: 16384,
+            max_anisotropy: 16.,
         }
     }
     fn buffer_size(&mut self, buffer: BufferId) -> usize {
@@ -395,18 +622,123 @@ impl RenderingBackend for MetalContext {
         buffer.size
     }
     fn delete_buffer(&mut self, buffer: BufferId) {
-        let buffer = &self.buffers[buffer.0];
+        self.try_delete_buffer(buffer)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        let internal = self
+            .buffers
+            .remove(buffer.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", buffer)))?;
         unsafe {
-            for buffer in &buffer.raw {
+            for buffer in &internal.raw {
                 msg_send_![*buffer, release];
             }
         }
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        assert!(
+            self.info().query_supported(kind),
+            "{:?} queries are not supported on Metal",
+            kind
+        );
+        let query = Query {
+            kind,
+            end_frame: None,
+        };
+        let id = self.queries.add(query);
+        assert!((id.index as usize) < MAX_QUERIES, "Too many queries");
+        QueryId(id)
     }
+
+    fn begin_query(&mut self, query: QueryId) {
+        assert!(
+            self.render_encoder.is_some(),
+            "begin_query before begin_pass"
+        );
+        let render_encoder = self.render_encoder.unwrap();
+        let slot = query.0.index as usize;
+        let query = &mut self.queries[query.0];
+        query.end_frame = None;
+        let mode = match query.kind {
+            QueryKind::AnySamplesPassed => MTLVisibilityResultMode::Boolean,
+            _ => MTLVisibilityResultMode::Counting,
+        };
+        unsafe {
+            // counting results accumulate, so the slot should start from zero
+            let contents: *mut u64 = msg_send![self.visibility_buffer, contents];
+            *contents.add(slot) = 0;
+            msg_send_![render_encoder, setVisibilityResultMode:mode
+                       offset:(slot * 8) as u64];
+        }
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        assert!(self.render_encoder.is_some(), "end_query before begin_pass");
+        let render_encoder = self.render_encoder.unwrap();
+        self.queries[query.0].end_frame = Some(self.frame_counter);
+        unsafe {
+            msg_send_![render_encoder, setVisibilityResultMode:MTLVisibilityResultMode::Disabled
+                       offset:0u64];
+        }
+    }
+
+    fn query_result(&mut self, query: QueryId) -> Option<u64> {
+        let slot = query.0.index as usize;
+        let query = self.queries[query.0];
+        // commit_frame waits for the command buffer to complete
+        if query.end_frame? >= self.frame_counter {
+            return None;
+        }
+        let result = unsafe {
+            let contents: *const u64 = msg_send![self.visibility_buffer, contents];
+            *contents.add(slot)
+        };
+        match query.kind {
+            QueryKind::AnySamplesPassed => Some(result.min(1)),
+            _ => Some(result),
+        }
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        self.queries
+            .remove(query.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", query))));
+    }
+
+    fn insert_fence(&mut self) -> FenceId {
+        FenceId(self.fences.add(self.frame_counter))
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        self.fences[fence.0] < self.frame_counter
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        self.fences
+            .remove(fence.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", fence))));
+    }
+
     fn delete_texture(&mut self, texture: TextureId) {
-        let texture = self.textures.get(texture);
+        self.try_delete_texture(texture)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        let internal = match texture.0 {
+            TextureIdInner::Managed(id) => self
+                .textures
+                .0
+                .remove(id)
+                .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", texture)))?,
+            TextureIdInner::Raw(_) => self.textures.get(texture),
+        };
         unsafe {
-            msg_send_![texture.texture, release];
+            msg_send_![internal.texture, release];
         }
+        Ok(())
     }
     fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
@@ -466,17 +798,8 @@ impl RenderingBackend for MetalContext {
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
         let texture = self.textures.get_mut(texture);
 
-        let wrap_s = match wrap_x {
-            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
-            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
-            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
-        };
-
-        let wrap_t = match wrap_y {
-            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
-            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
-            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
-        };
+        let wrap_s = MTLSamplerAddressMode::from(wrap_x);
+        let wrap_t = MTLSamplerAddressMode::from(wrap_y);
 
         texture.sampler = unsafe {
             //msg_send_![texture.sampler_descriptor, setRAddressMode: wrap];
@@ -485,6 +808,51 @@ impl RenderingBackend for MetalContext {
             msg_send_![self.device, newSamplerStateWithDescriptor: texture.sampler_descriptor]
         };
     }
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId {
+        self.info()
+            .check_sampler_params(&params)
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let filter = |filter| match filter {
+            FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
+            FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
+        };
+        let mipmap_filter = match params.mipmap_filter {
+            MipmapFilterMode::None => MTLSamplerMipFilter::NotMipmapped,
+            MipmapFilterMode::Nearest => MTLSamplerMipFilter::Nearest,
+            MipmapFilterMode::Linear => MTLSamplerMipFilter::Linear,
+        };
+        // the closest of the three colors Metal has
+        let border_color = match params.border_color {
+            [_, _, _, a] if a < 0.5 => MTLSamplerBorderColor::TransparentBlack,
+            [r, g, b, _] if r + g + b < 1.5 => MTLSamplerBorderColor::OpaqueBlack,
+            _ => MTLSamplerBorderColor::OpaqueWhite,
+        };
+
+        let sampler = unsafe {
+            let descriptor = msg_send_![class!(MTLSamplerDescriptor), new];
+            msg_send_![descriptor, setMinFilter: filter(params.min_filter)];
+            msg_send_![descriptor, setMagFilter: filter(params.mag_filter)];
+            msg_send_![descriptor, setMipFilter: mipmap_filter];
+            msg_send_![descriptor, setSAddressMode: MTLSamplerAddressMode::from(params.wrap_x)];
+            msg_send_![descriptor, setTAddressMode: MTLSamplerAddressMode::from(params.wrap_y)];
+            msg_send_![descriptor, setRAddressMode: MTLSamplerAddressMode::from(params.wrap_z)];
+            if cfg!(target_os = "macos") {
+                msg_send_![descriptor, setBorderColor: border_color];
+            }
+            let anisotropy = params.max_anisotropy.max(1.).min(16.) as u64;
+            msg_send_![descriptor, setMaxAnisotropy: anisotropy];
+            msg_send_![descriptor, setLodMinClamp: params.min_lod.max(0.)];
+            msg_send_![descriptor, setLodMaxClamp: params.max_lod];
+            if let Some(compare) = params.compare {
+                msg_send_![descriptor, setCompareFunction: MTLCompareFunction::from(compare)];
+            }
+            let sampler = msg_send_![self.device, newSamplerStateWithDescriptor: descriptor];
+            msg_send_![descriptor, release];
+            sampler
+        };
+        SamplerId(self.samplers.add(sampler))
+    }
     fn texture_resize(
         &mut self,
         _texture: TextureId,
@@ -494,9 +862,75 @@ impl RenderingBackend for MetalContext {
     ) {
         unimplemented!()
     }
-    fn texture_read_pixels(&mut self, _texture: TextureId, _bytes: &mut [u8]) {
+    fn texture_read_mip_pixels(
+        &mut self,
+        _texture: TextureId,
+        _mip_level: u32,
+        _layer: u32,
+        _bytes: &mut [u8],
+    ) {
         unimplemented!()
     }
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
+        assert!(
+            self.render_encoder.is_none(),
+            "texture_read_pixels_async inside of a render pass"
+        );
+        let (x, y, width, height) = rect;
+        let texture = self.textures.get(texture);
+        assert!(x + width <= texture.params.width && y + height <= texture.params.height);
+        let format = texture.params.format;
+        let size = format.size(width, height) as usize;
+
+        let buffer = unsafe {
+            if self.command_buffer.is_none() {
+                self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+            }
+            let buffer: ObjcId = msg_send![self.device,
+                                           newBufferWithLength:size as u64
+                                           options:MTLResourceOptions::StorageModeShared];
+            let encoder = msg_send_![self.command_buffer.unwrap(), blitCommandEncoder];
+            msg_send_![encoder, copyFromTexture:texture.texture
+                       sourceSlice:0u64
+                       sourceLevel:0u64
+                       sourceOrigin:MTLOrigin { x: x as _, y: y as _, z: 0 }
+                       sourceSize:MTLSize { width: width as _, height: height as _, depth: 1 }
+                       toBuffer:buffer
+                       destinationOffset:0u64
+                       destinationBytesPerRow:format.size(width, 1) as u64
+                       destinationBytesPerImage:size as u64];
+            msg_send_![encoder, endEncoding];
+            buffer
+        };
+        ReadbackId(self.readbacks.add(Readback {
+            buffer,
+            size,
+            frame: self.frame_counter,
+        }))
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        self.readbacks[readback.0].frame < self.frame_counter
+    }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        if !self.poll_readback(readback) {
+            return None;
+        }
+        let Readback { buffer, size, .. } = self.readbacks.remove(readback.0)?;
+        let mut data = vec![0; size];
+        unsafe {
+            let contents: *const u8 = msg_send![buffer, contents];
+            std::ptr::copy_nonoverlapping(contents, data.as_mut_ptr(), size);
+            msg_send_![buffer, release];
+        }
+        Some(data)
+    }
+
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         unsafe {
             if self.command_buffer.is_none() {
@@ -533,11 +967,11 @@ impl RenderingBackend for MetalContext {
         self.end_render_pass();
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_targets(
         &mut self,
-        color_img: &[TextureId],
+        color_img: &[RenderTarget],
         resolve_img: Option<&[TextureId]>,
-        depth_img: Option<TextureId>,
+        depth_img: Option<RenderTarget>,
     ) -> RenderPass {
         if resolve_img.is_some() {
             unimplemented!("resolve textures are not yet implemented on metal");
@@ -547,49 +981,67 @@ impl RenderingBackend for MetalContext {
                 msg_send_![class!(MTLRenderPassDescriptor), renderPassDescriptor];
             msg_send_![render_pass_desc, retain];
             assert!(!render_pass_desc.is_null());
-            for (i, color_img) in color_img.iter().enumerate() {
-                let color_texture = self.textures.get(*color_img).texture;
+            // cubemap faces and array layers are slices, 3D texture layers are depth planes
+            let set_target = |attachment: ObjcId, target: &RenderTarget| {
+                let texture = self.textures.get(target.texture);
+                msg_send_![attachment, setTexture: texture.texture];
+                msg_send_![attachment, setLevel: target.mip_level as u64];
+                match texture.params.kind {
+                    TextureKind::Texture3D => {
+                        msg_send_![attachment, setDepthPlane: target.layer as u64];
+                    }
+                    _ => {
+                        msg_send_![attachment, setSlice: target.layer as u64];
+                    }
+                }
+            };
+            for (i, target) in color_img.iter().enumerate() {
                 let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
-                msg_send_![color_attachment, setTexture: color_texture];
+                set_target(color_attachment, target);
                 msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
             }
-            if let Some(depth_img) = depth_img {
-                let depth_texture = self.textures.get(depth_img).texture;
-
+            if let Some(target) = &depth_img {
                 let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
-                msg_send_![depth_attachment, setTexture: depth_texture];
+                set_target(depth_attachment, target);
                 msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
                 msg_send_![depth_attachment, setClearDepth:1.];
 
                 let stencil_attachment = msg_send_![render_pass_desc, stencilAttachment];
-                msg_send_![stencil_attachment, setTexture: depth_texture];
+                set_target(stencil_attachment, target);
             }
             let pass = RenderPassInternal {
                 render_pass_desc,
-                texture: color_img.to_vec(),
-                _depth_texture: depth_img,
+                texture: color_img.iter().map(|target| target.texture).collect(),
+                depth_texture: depth_img.map(|target| target.texture),
             };
 
-            self.passes.push(pass);
-
-            RenderPass(self.passes.len() - 1)
+            RenderPass(self.passes.add(pass))
         }
     }
 
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
-        let render_pass = &self.passes[render_pass.0];
+        self.try_delete_render_pass(render_pass)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        let internal = self
+            .passes
+            .remove(render_pass.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", render_pass)))?;
         unsafe {
-            msg_send_![render_pass.render_pass_desc, release];
+            msg_send_![internal.render_pass_desc, release];
         }
+        Ok(())
     }
 
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         &self.passes[render_pass.0].texture
     }
 
-    fn new_buffer(&mut self, _: BufferType, _usage: BufferUsage, data: BufferSource) -> BufferId {
+    fn new_buffer(&mut self, _: BufferType, usage: BufferUsage, data: BufferSource) -> BufferId {
         let mut raw = [nil; BUFFERS_IN_ROTATION];
         let size = match &data {
             BufferSource::Slice(data) => data.size,
@@ -627,27 +1079,60 @@ impl RenderingBackend for MetalContext {
         let buffer = Buffer {
             raw,
             size,
+            usage,
             value: 0,
             next_value: 0,
         };
-        self.buffers.push(buffer);
-        BufferId(self.buffers.len() - 1)
+        BufferId(self.buffers.add(buffer))
     }
 
-    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
         let data = match data {
             BufferSource::Slice(data) => data,
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
         let buffer = &mut self.buffers[buffer.0];
-        assert!(data.size <= buffer.size);
+        assert!(byte_offset + data.size <= buffer.size);
 
+        let partial = byte_offset != 0 || data.size != buffer.size;
         unsafe {
-            let dest: *mut std::ffi::c_void = msg_send![buffer.raw[buffer.next_value], contents];
-            std::ptr::copy(data.ptr, dest, data.size);
+            let dest = buffer.next_contents(partial);
+            std::ptr::copy(data.ptr, dest.add(byte_offset) as _, data.size);
 
             #[cfg(target_os = "macos")]
-            msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, data.size as u64)];
+            msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, buffer.size as u64)];
+        }
+        buffer.value = buffer.next_value;
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        let buffer = &self.buffers[buffer.0];
+        assert!(byte_offset + data.len() <= buffer.size);
+
+        unsafe {
+            let src: *const u8 = msg_send![buffer.raw[buffer.value], contents];
+            std::ptr::copy_nonoverlapping(src.add(byte_offset), data.as_mut_ptr(), data.len());
+        }
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        let buffer = &mut self.buffers[buffer.0];
+        assert!(byte_offset + size <= buffer.size);
+
+        // Stream buffers are orphaned, the rest of the buffer is not preserved
+        let keep_contents = buffer.usage != BufferUsage::Stream;
+        unsafe {
+            let dest = buffer.next_contents(keep_contents);
+            f(std::slice::from_raw_parts_mut(dest.add(byte_offset), size));
+
+            #[cfg(target_os = "macos")]
+            msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, buffer.size as u64)];
         }
         buffer.value = buffer.next_value;
     }
@@ -660,32 +1145,91 @@ impl RenderingBackend for MetalContext {
         unsafe {
             let program = match shader {
                 ShaderSource::Msl { program } => program,
+                ShaderSource::Native(_) => panic!("Native shaders require SoftwareContext"),
                 _ => panic!("OpenGl source on Metal context"),
             };
-            let shader = apple_util::str_to_nsstring(program);
-            let mut error: ObjcId = nil;
-            let library: ObjcId = msg_send![
-                self.device,
-                newLibraryWithSource: shader
-                options:nil
-                error: &mut error
-            ];
-            if library.is_null() {
-                let description: ObjcId = msg_send![error, localizedDescription];
-                let string = apple_util::nsstring_to_string(description);
-                panic!("Shader {}", string);
-            }
+            let shader = self
+                .load_library(program)
+                .unwrap_or_else(|error| panic!("Shader {}", error));
+            Ok(ShaderId(self.shaders.add(shader)))
+        }
+    }
 
-            let vertex_function: ObjcId = msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("vertexShader")];
-            assert!(!vertex_function.is_null());
-            let fragment_function: ObjcId = msg_send![library, newFunctionWithName: apple_util::str_to_nsstring("fragmentShader")];
-            assert!(!fragment_function.is_null());
-            let shader = ShaderInternal {
-                vertex_function,
-                fragment_function,
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        // same as in new_shader, everything is bound by index and the layout is not needed
+        _meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        unsafe {
+            let program = match source {
+                ShaderSource::Msl { program } => program,
+                ShaderSource::Native(_) => panic!("Native shaders require SoftwareContext"),
+                _ => panic!("OpenGl source on Metal context"),
             };
-            self.shaders.push(shader);
-            Ok(ShaderId(self.shaders.len() - 1))
+            // vertex and fragment functions are compiled together, in one library
+            let new = self.load_library(program).map_err(|error_message| {
+                ShaderError::CompilationError {
+                    shader_type: ShaderType::Vertex,
+                    error_message,
+                }
+            })?;
+
+            // all the pipeline states are made first, to keep the old ones on error
+            let mut states = vec![];
+            let mut error_message = None;
+            for pipeline in self.pipelines.iter_mut() {
+                if pipeline.shader != shader {
+                    continue;
+                }
+                let descriptor = pipeline.descriptor;
+                msg_send_![descriptor, setVertexFunction: new.vertex_function];
+                msg_send_![descriptor, setFragmentFunction: new.fragment_function];
+                let mut error: ObjcId = nil;
+                let pipeline_state: ObjcId = msg_send![
+                    self.device,
+                    newRenderPipelineStateWithDescriptor: descriptor
+                    error: &mut error
+                ];
+                if pipeline_state.is_null() {
+                    let description: ObjcId = msg_send![error, localizedDescription];
+                    error_message = Some(apple_util::nsstring_to_string(description));
+                    break;
+                }
+                states.push(pipeline_state);
+            }
+
+            if let Some(error_message) = error_message {
+                let old = &self.shaders[shader.0];
+                for pipeline in self.pipelines.iter_mut() {
+                    if pipeline.shader == shader {
+                        msg_send_![pipeline.descriptor, setVertexFunction: old.vertex_function];
+                        msg_send_![pipeline.descriptor, setFragmentFunction: old.fragment_function];
+                    }
+                }
+                for state in states {
+                    msg_send_![state, release];
+                }
+                msg_send_![new.vertex_function, release];
+                msg_send_![new.fragment_function, release];
+                return Err(ShaderError::LinkError(error_message));
+            }
+
+            let pipelines = self
+                .pipelines
+                .iter_mut()
+                .filter(|pipeline| pipeline.shader == shader);
+            for (pipeline, pipeline_state) in pipelines.zip(states) {
+                msg_send_![pipeline.pipeline_state, release];
+                pipeline.pipeline_state = pipeline_state;
+            }
+            // the descriptors and pipeline states hold their own references
+            let old = std::mem::replace(&mut self.shaders[shader.0], new);
+            msg_send_![old.vertex_function, release];
+            msg_send_![old.fragment_function, release];
+            self.current_pipeline = None;
+            Ok(())
         }
     }
 
@@ -749,6 +1293,14 @@ impl RenderingBackend for MetalContext {
             TextureKind::CubeMap => unsafe {
                 msg_send_![descriptor, setTextureType: MTLTextureType::CubeArray];
             },
+            TextureKind::Texture2DArray => unsafe {
+                msg_send_![descriptor, setTextureType: MTLTextureType::D2Array];
+                msg_send_![descriptor, setArrayLength: params.depth as u64];
+            },
+            TextureKind::Texture3D => unsafe {
+                msg_send_![descriptor, setTextureType: MTLTextureType::D3];
+                msg_send_![descriptor, setDepth: params.depth as u64];
+            },
         }
 
         let texture = unsafe {
@@ -779,36 +1331,73 @@ impl RenderingBackend for MetalContext {
             ];
             let raw_texture = msg_send_![self.device, newTextureWithDescriptor: descriptor];
             msg_send_![raw_texture, retain];
-            self.textures.0.push(Texture {
+            TextureId(TextureIdInner::Managed(self.textures.0.add(Texture {
                 sampler: sampler_state,
                 texture: raw_texture,
                 sampler_descriptor,
                 params,
-            });
-            TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+            })))
         };
 
         match bytes {
             TextureSource::Empty => {}
             TextureSource::Bytes(bytes) => {
                 assert_eq!(
-                    params.format.size(params.width, params.height) as usize,
+                    params.format.size(params.width, params.height) as usize
+                        * params.depth as usize,
                     bytes.len()
                 );
 
-                self.texture_update_part(
-                    texture,
-                    0,
-                    0,
-                    params.width as _,
-                    params.height as _,
-                    bytes,
+                self.texture_update(texture, bytes);
+            }
+            TextureSource::Array(array)
+                if matches!(
+                    params.kind,
+                    TextureKind::Texture2DArray | TextureKind::Texture3D
+                ) =>
+            {
+                assert!(
+                    array.len() == 1,
+                    "Layered textures require TextureSource::Array of 1 element."
                 );
+                let raw_texture = self.textures.get(texture).texture;
+                for (mipmap_level, bytes) in array[0].iter().enumerate() {
+                    let width = (params.width >> mipmap_level).max(1);
+                    let height = (params.height >> mipmap_level).max(1);
+                    let layer_size = params.format.size(width, height) as usize;
+                    let (slices, depth) = match params.kind {
+                        TextureKind::Texture3D => (1, (params.depth >> mipmap_level).max(1)),
+                        _ => (params.depth, 1),
+                    };
+                    assert_eq!(layer_size * (slices * depth) as usize, bytes.len());
+                    for slice in 0..slices {
+                        let region = MTLRegion {
+                            origin: MTLOrigin { x: 0, y: 0, z: 0 },
+                            size: MTLSize {
+                                width: width as u64,
+                                height: height as u64,
+                                depth: depth as u64,
+                            },
+                        };
+                        let offset = slice as usize * layer_size;
+                        unsafe {
+                            msg_send_![raw_texture, replaceRegion:region
+                                  mipmapLevel:mipmap_level
+                                  slice:slice as u64
+                                  withBytes:bytes[offset..].as_ptr()
+                                  bytesPerRow:params.format.size(width, 1) as u64
+                                  bytesPerImage:layer_size as u64
+                            ];
+                        }
+                    }
+                }
             }
             TextureSource::Array(array) => {
                 for (n, face) in array.iter().enumerate() {
                     for (mipmap_level, bytes) in face.iter().enumerate() {
                         let raw_texture = self.textures.get(texture).texture;
+                        let width = (params.width >> mipmap_level).max(1);
+                        let height = (params.height >> mipmap_level).max(1);
                         let region = MTLRegion {
                             origin: MTLOrigin {
                                 x: 0_u64,
@@ -816,18 +1405,18 @@ impl RenderingBackend for MetalContext {
                                 z: 0,
                             },
                             size: MTLSize {
-                                width: params.width as u64,
-                                height: params.height as u64,
+                                width: width as u64,
+                                height: height as u64,
                                 depth: 1,
                             },
                         };
-                        assert!(bytes.len() as u32 == params.width * params.height * 4);
+                        assert!(bytes.len() as u32 == params.format.size(width, height));
                         unsafe {
                             msg_send_![raw_texture, replaceRegion:region
                                   mipmapLevel:mipmap_level
                                   slice: n
                                   withBytes:bytes.as_ptr()
-                                  bytesPerRow:(params.width * 4) as u64
+                                  bytesPerRow:params.format.size(width, 1) as u64
                                   bytesPerImage:0
                             ];
                         }
@@ -838,21 +1427,31 @@ impl RenderingBackend for MetalContext {
         texture
     }
 
-    fn texture_update_part(
+    fn texture_update_mip_part(
         &mut self,
         texture: TextureId,
+        mip_level: u32,
+        layer: u32,
         x_offset: i32,
         y_offset: i32,
         width: i32,
         height: i32,
         bytes: &[u8],
     ) {
-        let raw_texture = self.textures.get(texture).texture;
+        let texture = self.textures.get(texture);
+        assert!(mip_level < texture.params.mip_levels());
+        assert!(layer < texture.params.mip_layers(mip_level));
+        let raw_texture = texture.texture;
+        // cubemap faces and array layers are slices, 3D texture layers are a depth offset
+        let (slice, z) = match texture.params.kind {
+            TextureKind::Texture3D => (0, layer as u64),
+            _ => (layer as u64, 0),
+        };
         let region = MTLRegion {
             origin: MTLOrigin {
                 x: x_offset as u64,
                 y: y_offset as u64,
-                z: 0,
+                z,
             },
             size: MTLSize {
                 width: width as u64,
@@ -862,9 +1461,11 @@ impl RenderingBackend for MetalContext {
         };
         unsafe {
             msg_send_![raw_texture, replaceRegion:region
-                       mipmapLevel:0
+                       mipmapLevel:mip_level as u64
+                       slice:slice
                        withBytes:bytes.as_ptr()
-                       bytesPerRow:(width * 4) as u64];
+                       bytesPerRow:texture.params.format.size(width as _, 1) as u64
+                       bytesPerImage:0];
         }
     }
 
@@ -930,11 +1531,18 @@ impl RenderingBackend for MetalContext {
             msg_send_![descriptor, setFragmentFunction:shader_internal.fragment_function];
             msg_send_![descriptor, setVertexDescriptor: vertex_descriptor];
             let color_attachments = msg_send_![descriptor, colorAttachments];
-            for i in 0..2 {
+            let attachments = params.attachment_blend.unwrap_or(
+                [AttachmentBlend {
+                    color_blend: params.color_blend,
+                    alpha_blend: params.alpha_blend,
+                    color_write: params.color_write,
+                }; MAX_COLOR_ATTACHMENTS],
+            );
+            for (i, attachment) in attachments.iter().enumerate() {
                 let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
                 let view_pixel_format: MTLPixelFormat = msg_send![self.view, colorPixelFormat];
                 msg_send_![color_attachment, setPixelFormat: view_pixel_format];
-                if let Some(color_blend) = params.color_blend {
+                if let Some(color_blend) = attachment.color_blend {
                     msg_send_![color_attachment, setBlendingEnabled: true];
 
                     let BlendState {
@@ -946,7 +1554,7 @@ impl RenderingBackend for MetalContext {
                         equation: eq_alpha,
                         sfactor: src_alpha,
                         dfactor: dst_alpha,
-                    } = color_blend;
+                    } = attachment.alpha_blend.unwrap_or(color_blend);
                     msg_send_![
                         color_attachment,
                         setRgbBlendOperation: MTLBlendOperation::from(eq_rgb)
@@ -959,10 +1567,6 @@ impl RenderingBackend for MetalContext {
                         color_attachment,
                         setSourceRGBBlendFactor: MTLBlendFactor::from(src_rgb)
                     ];
-                    msg_send_![
-                        color_attachment,
-                        setSourceRGBBlendFactor: MTLBlendFactor::from(src_rgb)
-                    ];
                     msg_send_![
                         color_attachment,
                         setSourceAlphaBlendFactor: MTLBlendFactor::from(src_alpha)
@@ -976,6 +1580,9 @@ impl RenderingBackend for MetalContext {
                         setDestinationAlphaBlendFactor: MTLBlendFactor::from(dst_alpha)
                     ];
                 }
+                let (r, g, b, a) = attachment.color_write;
+                let write_mask = (r as u64) << 3 | (g as u64) << 2 | (b as u64) << 1 | a as u64;
+                msg_send_![color_attachment, setWriteMask: write_mask];
             }
             msg_send_![
                 descriptor,
@@ -1002,29 +1609,14 @@ impl RenderingBackend for MetalContext {
             msg_send_![depth_stencil_desc, setDepthWriteEnabled: BOOL::from(params.depth_write)];
             msg_send_![depth_stencil_desc, setDepthCompareFunction: MTLCompareFunction::from(params.depth_test)];
 
-            // if let Some(stencil_test) = params.stencil_test {
-            //     let back_face_stencil_desc = StencilDescriptor::new();
-            //     back_face_stencil_desc.set_stencil_compare_function(stencil_test.back.test_func.into());
-            //     back_face_stencil_desc.set_stencil_failure_operation(stencil_test.back.fail_op.into());
-            //     back_face_stencil_desc
-            //         .set_depth_failure_operation(stencil_test.back.depth_fail_op.into());
-            //     back_face_stencil_desc.set_read_mask(stencil_test.back.test_mask);
-            //     back_face_stencil_desc.set_write_mask(stencil_test.back.write_mask);
-
-            //     depth_stencil_desc.set_back_face_stencil(Some(back_face_stencil_desc.as_ref()));
-
-            //     let front_face_stencil_desc = StencilDescriptor::new();
-            //     front_face_stencil_desc
-            //         .set_stencil_compare_function(stencil_test.front.test_func.into());
-            //     front_face_stencil_desc
-            //         .set_stencil_failure_operation(stencil_test.front.fail_op.into());
-            //     front_face_stencil_desc
-            //         .set_depth_failure_operation(stencil_test.front.depth_fail_op.into());
-            //     front_face_stencil_desc.set_read_mask(stencil_test.front.test_mask);
-            //     front_face_stencil_desc.set_write_mask(stencil_test.front.write_mask);
-
-            //     depth_stencil_desc.set_front_face_stencil(Some(front_face_stencil_desc.as_ref()))
-            // }
+            if let Some(stencil_test) = params.stencil_test {
+                let back_face_stencil_desc = new_stencil_descriptor(&stencil_test.back);
+                msg_send_![depth_stencil_desc, setBackFaceStencil: back_face_stencil_desc];
+                let front_face_stencil_desc = new_stencil_descriptor(&stencil_test.front);
+                msg_send_![depth_stencil_desc, setFrontFaceStencil: front_face_stencil_desc];
+                msg_send_![back_face_stencil_desc, release];
+                msg_send_![front_face_stencil_desc, release];
+            }
 
             let depth_stencil_state = msg_send_![
                 self.device,
@@ -1036,13 +1628,16 @@ impl RenderingBackend for MetalContext {
                 depth_stencil_state,
                 //layout: buffer_layout.to_vec(),
                 //attributes: vertex_layout,
-                _shader: shader,
+                descriptor,
+                shader,
+                primitive_type: params.primitive_type.into(),
+                stencil_ref: params
+                    .stencil_test
+                    .map(|s| (s.front.test_ref as u32, s.back.test_ref as u32)),
                 //params,
             };
 
-            self.pipelines.push(pipeline);
-
-            Pipeline(self.pipelines.len() - 1)
+            Pipeline(self.pipelines.add(pipeline))
         }
     }
 
@@ -1059,16 +1654,21 @@ impl RenderingBackend for MetalContext {
 
             msg_send_![render_encoder, setRenderPipelineState: pipeline.pipeline_state];
             msg_send_![render_encoder, setDepthStencilState:pipeline.depth_stencil_state];
+            if let Some((front, back)) = pipeline.stencil_ref {
+                msg_send_![render_encoder, setStencilFrontReferenceValue:front
+                           backReferenceValue:back];
+            }
             // render_encoder.set_front_facing_winding(pipeline.params.front_face_order.into());
             // render_encoder.set_cull_mode(pipeline.params.cull_face.into());
         }
     }
 
-    fn apply_bindings_from_slice(
+    fn apply_bindings_with_samplers(
         &mut self,
         vertex_buffers: &[BufferId],
-        index_buffer: BufferId,
+        index_buffer: Option<BufferId>,
         textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
     ) {
         assert!(
             self.render_encoder.is_some(),
@@ -1085,9 +1685,11 @@ impl RenderingBackend for MetalContext {
                                    atIndex:(index + 1) as u64];
                 buffer.next_value = buffer.value + 1;
             }
-            let index_buffer = &mut self.buffers[index_buffer.0];
-            self.index_buffer = Some(index_buffer.raw[index_buffer.value]);
-            index_buffer.next_value = index_buffer.value + 1;
+            self.index_buffer = index_buffer.map(|index_buffer| {
+                let index_buffer = &mut self.buffers[index_buffer.0];
+                index_buffer.next_value = index_buffer.value + 1;
+                index_buffer.raw[index_buffer.value]
+            });
 
             let img_count = textures.len();
             if img_count > 0 {
@@ -1095,6 +1697,10 @@ impl RenderingBackend for MetalContext {
                     let Texture {
                         sampler, texture, ..
                     } = self.textures.get(*img);
+                    let sampler = match samplers.get(n).copied().flatten() {
+                        Some(sampler) => self.samplers[sampler.0],
+                        None => sampler,
+                    };
                     msg_send_![render_encoder, setFragmentSamplerState:sampler
                                atIndex:n
                     ];
@@ -1107,45 +1713,21 @@ impl RenderingBackend for MetalContext {
     }
 
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
-        assert!(
-            self.current_pipeline.is_some(),
-            "apply_uniforms before apply_pipeline"
-        );
-        assert!(
-            self.render_encoder.is_some(),
-            "apply_uniforms before begin_pass"
-        );
-
-        let render_encoder = self.render_encoder.unwrap();
-
-        self.current_frame_index = (self.current_frame_index + 1) % NUM_INFLIGHT_FRAMES;
-
-        assert!(size < MAX_UNIFORM_BUFFER_SIZE as usize);
-
-        assert!(self.current_ub_offset < MAX_UNIFORM_BUFFER_SIZE);
-
-        let buffer = self.uniform_buffers[self.current_frame_index];
-        unsafe {
-            let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
-            std::ptr::copy(
-                uniform_ptr as _,
-                dest.add(self.current_ub_offset as usize),
-                size,
-            );
-
-            #[cfg(target_os = "macos")]
-            msg_send_![buffer, didModifyRange:NSRange::new(0, size as u64)];
+        self.push_uniforms(uniform_ptr, size, 0);
+    }
 
-            msg_send_![render_encoder,
-                       setVertexBuffer:buffer
-                       offset:self.current_ub_offset
-                       atIndex:0];
-            msg_send_![render_encoder,
-                       setFragmentBuffer:buffer
-                       offset:self.current_ub_offset
-                       atIndex:0];
-        }
-        self.current_ub_offset = roundup_ub_buffer(self.current_ub_offset + size as u64);
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        assert!(block_index < MAX_UNIFORM_BLOCKS);
+        self.push_uniforms(
+            uniform_ptr,
+            size,
+            (UNIFORM_BLOCKS_BUFFER_INDEX + block_index) as u64,
+        );
     }
 
     fn begin_default_pass(&mut self, action: PassAction) {
@@ -1175,7 +1757,7 @@ impl RenderingBackend for MetalContext {
                         .texture
                         .first()
                         .copied()
-                        //.or(pass.depth_texture)
+                        .or(pass.depth_texture)
                         .unwrap();
 
                     (
@@ -1186,22 +1768,46 @@ impl RenderingBackend for MetalContext {
                 }
             };
             assert!(!descriptor.is_null());
+            msg_send_![descriptor, setVisibilityResultBuffer: self.visibility_buffer];
 
             let color_attachments = msg_send_![descriptor, colorAttachments];
             let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: 0];
 
             msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
 
+            let depth_attachment = msg_send_![descriptor, depthAttachment];
+            let stencil_attachment = msg_send_![descriptor, stencilAttachment];
+            msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
+            msg_send_![stencil_attachment, setStoreAction: MTLStoreAction::Store];
+
             match action {
-                PassAction::Clear { color, .. } => {
+                PassAction::Clear {
+                    color,
+                    depth,
+                    stencil,
+                } => {
                     msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
 
                     if let Some(color) = color {
                         msg_send_![color_attachment, setClearColor:MTLClearColor::new(color.0 as _, color.1 as _, color.2 as _, color.3 as _)];
                     }
+                    if let Some(depth) = depth {
+                        msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
+                        msg_send_![depth_attachment, setClearDepth: depth as f64];
+                    } else {
+                        msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Load];
+                    }
+                    if let Some(stencil) = stencil {
+                        msg_send_![stencil_attachment, setLoadAction: MTLLoadAction::Clear];
+                        msg_send_![stencil_attachment, setClearStencil: stencil as u32];
+                    } else {
+                        msg_send_![stencil_attachment, setLoadAction: MTLLoadAction::Load];
+                    }
                 }
                 PassAction::Nothing => {
                     msg_send_![color_attachment, setLoadAction: MTLLoadAction::Load];
+                    msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Load];
+                    msg_send_![stencil_attachment, setLoadAction: MTLLoadAction::Load];
                 }
             }
 
@@ -1245,12 +1851,18 @@ impl RenderingBackend for MetalContext {
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
         assert!(self.render_encoder.is_some(), "draw before begin_pass!");
         let render_encoder = self.render_encoder.unwrap();
-        assert!(self.index_buffer.is_some());
-        let index_buffer = self.index_buffer.unwrap();
+        let index_buffer = self
+            .index_buffer
+            .expect("draw without an index buffer, use draw_arrays instead");
+
+        let pipeline = self
+            .current_pipeline
+            .expect("Drawing without any binded pipeline");
+        let primitive_type = self.pipelines[pipeline.0].primitive_type;
 
         assert!(base_element == 0); // TODO: figure indexBufferOffset/baseVertex
         unsafe {
-            msg_send_![render_encoder, drawIndexedPrimitives:MTLPrimitiveType::Triangle
+            msg_send_![render_encoder, drawIndexedPrimitives:primitive_type
                        indexCount:num_elements as u64
                        indexType:MTLIndexType::UInt16
                        indexBuffer:index_buffer
@@ -1262,11 +1874,147 @@ impl RenderingBackend for MetalContext {
         }
     }
 
-    fn delete_shader(&mut self, _shader: ShaderId) {
-        // TODO: place holder
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+        let pipeline = self
+            .current_pipeline
+            .expect("Drawing without any binded pipeline");
+        let primitive_type = self.pipelines[pipeline.0].primitive_type;
+
+        unsafe {
+            msg_send_![render_encoder, drawPrimitives:primitive_type
+                       vertexStart:first_vertex as u64
+                       vertexCount:num_vertices as u64
+                       instanceCount:num_instances as u64
+            ];
+        }
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+        let index_buffer = self
+            .index_buffer
+            .expect("draw without an index buffer, use draw_arrays instead");
+        let pipeline = self
+            .current_pipeline
+            .expect("Drawing without any binded pipeline");
+        let primitive_type = self.pipelines[pipeline.0].primitive_type;
+
+        for draw in draws {
+            // index buffers are always UInt16 on metal
+            let index_offset = draw.base_element as u64 * 2;
+            unsafe {
+                msg_send_![render_encoder, drawIndexedPrimitives:primitive_type
+                           indexCount:draw.num_elements as u64
+                           indexType:MTLIndexType::UInt16
+                           indexBuffer:index_buffer
+                           indexBufferOffset:index_offset
+                           instanceCount:draw.num_instances as u64
+                           baseVertex:draw.base_vertex as i64
+                           baseInstance:0
+                ];
+            }
+        }
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+        let index_buffer = self
+            .index_buffer
+            .expect("draw without an index buffer, use draw_arrays instead");
+        let pipeline = self
+            .current_pipeline
+            .expect("Drawing without any binded pipeline");
+        let primitive_type = self.pipelines[pipeline.0].primitive_type;
+        let indirect_buffer = &self.buffers[indirect_buffer.0];
+        let stride = std::mem::size_of::<DrawIndirectArgs>();
+        assert!(
+            offset + draw_count * stride <= indirect_buffer.size,
+            "Indirect draw arguments out of the buffer bounds"
+        );
+
+        for n in 0..draw_count {
+            unsafe {
+                msg_send_![render_encoder, drawIndexedPrimitives:primitive_type
+                           indexType:MTLIndexType::UInt16
+                           indexBuffer:index_buffer
+                           indexBufferOffset:0
+                           indirectBuffer:indirect_buffer.raw[indirect_buffer.value]
+                           indirectBufferOffset:(offset + n * stride) as u64
+                ];
+            }
+        }
+    }
+
+    fn new_compute_pipeline(&mut self, _shader: ShaderId) -> Pipeline {
+        panic!("Compute shaders are not supported by the GPU")
+    }
+
+    fn apply_compute_bindings(&mut self, _bindings: &ComputeBindings) {
+        panic!("Compute shaders are not supported by the GPU")
     }
-    fn delete_pipeline(&mut self, _pipeline: Pipeline) {
-        // TODO: place holder
+
+    fn dispatch(&mut self, _groups_x: u32, _groups_y: u32, _groups_z: u32) {
+        panic!("Compute shaders are not supported by the GPU")
+    }
+
+    fn memory_barrier(&mut self, _barriers: &[MemoryBarrier]) {
+        // without compute there are no incoherent writes to wait for
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        self.try_delete_sampler(sampler)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        let sampler = self
+            .samplers
+            .remove(sampler.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", sampler)))?;
+        unsafe {
+            msg_send_![sampler, release];
+        }
+        Ok(())
+    }
+    fn delete_shader(&mut self, shader: ShaderId) {
+        self.try_delete_shader(shader)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn try_delete_shader(&mut self, shader: ShaderId) -> Result<(), GraphicsError> {
+        let internal = self
+            .shaders
+            .remove(shader.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", shader)))?;
+        unsafe {
+            msg_send_![internal.vertex_function, release];
+            msg_send_![internal.fragment_function, release];
+        }
+        Ok(())
+    }
+    fn shader_reflection(&self, _shader: ShaderId) -> Option<&ShaderReflection> {
+        None
+    }
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.try_delete_pipeline(pipeline)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        let internal = self
+            .pipelines
+            .remove(pipeline.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", pipeline)))?;
+        unsafe {
+            msg_send_![internal.pipeline_state, release];
+            msg_send_![internal.depth_stencil_state, release];
+            msg_send_![internal.descriptor, release];
+        }
+        if self.current_pipeline == Some(pipeline) {
+            self.current_pipeline = None;
+        }
+        Ok(())
     }
 
     fn commit_frame(&mut self) {
@@ -1278,9 +2026,10 @@ impl RenderingBackend for MetalContext {
             msg_send_![self.command_buffer.unwrap(), commit];
             msg_send_![self.command_buffer.unwrap(), waitUntilCompleted];
         }
-        for buffer in &mut self.buffers {
+        for buffer in self.buffers.iter_mut() {
             buffer.next_value = 0;
         }
+        self.frame_counter += 1;
         self.current_ub_offset = 0;
         self.current_pipeline = None;
         self.command_buffer = None;
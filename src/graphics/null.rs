@@ -0,0 +1,805 @@
+//! Rendering backend without a GPU, see `NullContext`.
+
+use super::*;
+
+use crate::{ResourceId, ResourceManager};
+
+use std::cell::Cell;
+
+struct RenderPassInternal {
+    color_textures: Vec<TextureId>,
+    resolve_textures: Vec<TextureId>,
+    depth_texture: Option<TextureId>,
+}
+
+struct Readback {
+    data: Vec<u8>,
+}
+
+/// Number of live objects of each kind, see `NullContext::resource_counts`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceCounts {
+    pub shaders: usize,
+    pub textures: usize,
+    pub samplers: usize,
+    pub render_passes: usize,
+    pub pipelines: usize,
+    pub buffers: usize,
+    pub queries: usize,
+    pub readbacks: usize,
+    pub fences: usize,
+}
+
+/// `RenderingBackend` that issues no GPU calls and needs no window,
+/// for testing code on top of miniquad with plain `cargo test`.
+///
+/// Resources are tracked the same way the GL backend tracks them: a stale id
+/// panics, `try_delete_*` reports double deletes, and deleting a render pass
+/// deletes its attachments. Buffers keep their data, so `buffer_read` returns
+/// what was uploaded. Textures keep only their params, pixels read back are zeros.
+/// Queries, readbacks and fences are finished right away.
+///
+/// ```
+/// # use miniquad::*;
+/// let mut ctx: Box<dyn RenderingBackend> = Box::new(NullContext::new());
+/// let texture = ctx.new_render_texture(TextureParams {
+///     width: 256,
+///     height: 256,
+///     ..Default::default()
+/// });
+/// assert_eq!(ctx.texture_size(texture), (256, 256));
+/// ```
+///
+/// Wrap it into `ValidatingBackend` to check the calls as well.
+pub struct NullContext {
+    info: ContextInfo,
+    shaders: ResourceManager<()>,
+    textures: ResourceManager<TextureParams>,
+    samplers: ResourceManager<()>,
+    passes: ResourceManager<RenderPassInternal>,
+    pipelines: ResourceManager<ShaderId>,
+    buffers: ResourceManager<Vec<u8>>,
+    queries: ResourceManager<QueryKind>,
+    readbacks: ResourceManager<Readback>,
+    fences: ResourceManager<()>,
+    // Some(None) is the default pass
+    cur_pass: Option<Option<RenderPass>>,
+    cur_pipeline: Option<Pipeline>,
+    cur_bindings: Option<Bindings>,
+    cur_uniforms: Vec<u8>,
+    draw_calls: Cell<usize>,
+    frames: usize,
+}
+
+impl Default for NullContext {
+    fn default() -> NullContext {
+        NullContext::new()
+    }
+}
+
+impl NullContext {
+    /// `Features::default()` and GL as the backend.
+    pub fn new() -> NullContext {
+        NullContext::with_info(ContextInfo {
+            backend: Backend::OpenGl,
+            gl_version_string: "Null".to_string(),
+            glsl_support: GlslSupport {
+                v130: true,
+                v150: true,
+                v330: true,
+                v300es: true,
+                v100_ext: true,
+                v100: true,
+            },
+            features: Features::default(),
+            max_texture_size: 16384,
+            max_anisotropy: 16.,
+        })
+    }
+
+    /// Pretend to be a specific backend. `try_new_*` check their arguments against `info`.
+    pub fn with_info(info: ContextInfo) -> NullContext {
+        NullContext {
+            info,
+            shaders: ResourceManager::default(),
+            textures: ResourceManager::default(),
+            samplers: ResourceManager::default(),
+            passes: ResourceManager::default(),
+            pipelines: ResourceManager::default(),
+            buffers: ResourceManager::default(),
+            queries: ResourceManager::default(),
+            readbacks: ResourceManager::default(),
+            fences: ResourceManager::default(),
+            cur_pass: None,
+            cur_pipeline: None,
+            cur_bindings: None,
+            cur_uniforms: vec![],
+            draw_calls: Cell::new(0),
+            frames: 0,
+        }
+    }
+
+    /// Live objects of each kind, handy to check for leaks.
+    pub fn resource_counts(&self) -> ResourceCounts {
+        ResourceCounts {
+            shaders: self.shaders.len(),
+            textures: self.textures.len(),
+            samplers: self.samplers.len(),
+            render_passes: self.passes.len(),
+            pipelines: self.pipelines.len(),
+            buffers: self.buffers.len(),
+            queries: self.queries.len(),
+            readbacks: self.readbacks.len(),
+            fences: self.fences.len(),
+        }
+    }
+
+    /// Pass begun with `begin_pass`, `Some(None)` for the default pass
+    /// and `None` outside of a pass.
+    pub fn current_pass(&self) -> Option<Option<RenderPass>> {
+        self.cur_pass
+    }
+
+    /// Last applied pipeline, `None` at the start of a frame.
+    pub fn current_pipeline(&self) -> Option<Pipeline> {
+        self.cur_pipeline
+    }
+
+    /// Last applied bindings, `None` at the start of a frame.
+    pub fn current_bindings(&self) -> Option<&Bindings> {
+        self.cur_bindings.as_ref()
+    }
+
+    /// Bytes of the last `apply_uniforms`.
+    pub fn current_uniforms(&self) -> &[u8] {
+        &self.cur_uniforms
+    }
+
+    /// Number of draw calls since the context was created, a `multi_draw`
+    /// or `multi_draw_indirect` counts as one call per draw.
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls.get()
+    }
+
+    /// Number of `commit_frame` calls.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn texture(&self, texture: TextureId) -> &TextureParams {
+        match texture.0 {
+            TextureIdInner::Managed(id) => &self.textures[id],
+            TextureIdInner::Raw(_) => panic!("NullContext has no raw textures"),
+        }
+    }
+
+    fn texture_mut(&mut self, texture: TextureId) -> &mut TextureParams {
+        match texture.0 {
+            TextureIdInner::Managed(id) => &mut self.textures[id],
+            TextureIdInner::Raw(_) => panic!("NullContext has no raw textures"),
+        }
+    }
+
+    fn buffer_range(&mut self, buffer: BufferId, byte_offset: usize, size: usize) -> &mut [u8] {
+        let data = &mut self.buffers[buffer.0];
+        assert!(
+            byte_offset + size <= data.len(),
+            "{}..{} is out of bounds of {:?} of {} bytes",
+            byte_offset,
+            byte_offset + size,
+            buffer,
+            data.len()
+        );
+        &mut data[byte_offset..byte_offset + size]
+    }
+}
+
+/// Panics on a stale or unknown id, same as the GL backend would.
+pub(super) fn check<T>(resources: &ResourceManager<T>, id: ResourceId) {
+    let _ = &resources[id];
+}
+
+impl RenderingBackend for NullContext {
+    fn info(&self) -> ContextInfo {
+        self.info.clone()
+    }
+
+    fn new_shader(
+        &mut self,
+        _shader: ShaderSource,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        Ok(ShaderId(self.shaders.add(())))
+    }
+
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        _source: ShaderSource,
+        _meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        check(&self.shaders, shader.0);
+        Ok(())
+    }
+
+    fn shader_reflection(&self, _shader: ShaderId) -> Option<&ShaderReflection> {
+        None
+    }
+
+    fn new_texture(
+        &mut self,
+        _access: TextureAccess,
+        _data: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        TextureId(TextureIdInner::Managed(self.textures.add(params)))
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        *self.texture(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.texture(texture);
+        RawId::OpenGl(0)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let params = self.texture_mut(texture);
+        params.min_filter = filter;
+        params.mipmap_filter = mipmap_filter;
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.texture_mut(texture).mag_filter = filter;
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, _wrap_y: TextureWrap) {
+        self.texture_mut(texture).wrap = wrap_x;
+    }
+
+    fn new_sampler(&mut self, _params: SamplerParams) -> SamplerId {
+        SamplerId(self.samplers.add(()))
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        self.texture(texture);
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        _bytes: Option<&[u8]>,
+    ) {
+        let params = self.texture_mut(texture);
+        params.width = width;
+        params.height = height;
+    }
+
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        _mip_level: u32,
+        _layer: u32,
+        bytes: &mut [u8],
+    ) {
+        self.texture(texture);
+        bytes.fill(0);
+    }
+
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
+        let (_, _, width, height) = rect;
+        let size = self.texture(texture).format.size(width, height) as usize;
+        ReadbackId(self.readbacks.add(Readback {
+            data: vec![0; size],
+        }))
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        self.readbacks.contains(readback.0)
+    }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        Some(self.readbacks.remove(readback.0)?.data)
+    }
+
+    fn texture_update_mip_part(
+        &mut self,
+        texture: TextureId,
+        _mip_level: u32,
+        _layer: u32,
+        _x_offset: i32,
+        _y_offset: i32,
+        _width: i32,
+        _height: i32,
+        _bytes: &[u8],
+    ) {
+        self.texture(texture);
+    }
+
+    fn new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> RenderPass {
+        let color_textures: Vec<TextureId> =
+            color_img.iter().map(|target| target.texture).collect();
+        let depth_texture = depth_img.map(|target| target.texture);
+        let attachments = color_textures
+            .iter()
+            .chain(resolve_img.into_iter().flatten())
+            .chain(depth_texture.iter());
+        for &texture in attachments {
+            self.texture(texture);
+        }
+        RenderPass(self.passes.add(RenderPassInternal {
+            color_textures,
+            resolve_textures: resolve_img.unwrap_or(&[]).to_vec(),
+            depth_texture,
+        }))
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        &self.passes[render_pass.0].color_textures
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.try_delete_render_pass(render_pass)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        let pass = self
+            .passes
+            .remove(render_pass.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", render_pass)))?;
+        let attachments = pass
+            .color_textures
+            .iter()
+            .chain(&pass.resolve_textures)
+            .chain(pass.depth_texture.iter());
+        for &texture in attachments {
+            // same as GL, could be shared with another pass
+            let _ = self.try_delete_texture(texture);
+        }
+        Ok(())
+    }
+
+    fn new_pipeline(
+        &mut self,
+        _buffer_layout: &[BufferLayout],
+        _attributes: &[VertexAttribute],
+        shader: ShaderId,
+        _params: PipelineParams,
+    ) -> Pipeline {
+        check(&self.shaders, shader.0);
+        Pipeline(self.pipelines.add(shader))
+    }
+
+    fn new_compute_pipeline(&mut self, shader: ShaderId) -> Pipeline {
+        check(&self.shaders, shader.0);
+        Pipeline(self.pipelines.add(shader))
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        check(&self.pipelines, pipeline.0);
+        self.cur_pipeline = Some(*pipeline);
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.try_delete_pipeline(pipeline)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.pipelines
+            .remove(pipeline.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", pipeline)))?;
+        if self.cur_pipeline == Some(pipeline) {
+            self.cur_pipeline = None;
+        }
+        Ok(())
+    }
+
+    fn new_buffer(
+        &mut self,
+        _type_: BufferType,
+        _usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let data = match data {
+            BufferSource::Slice(data) => {
+                unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) }.to_vec()
+            }
+            BufferSource::Empty { size, .. } => vec![0; size],
+        };
+        BufferId(self.buffers.add(data))
+    }
+
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
+        let BufferSource::Slice(data) = data else {
+            return;
+        };
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        self.buffer_range(buffer, byte_offset, bytes.len())
+            .copy_from_slice(bytes);
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        assert!(
+            self.info.features.buffer_read,
+            "buffer_read is not supported"
+        );
+        data.copy_from_slice(self.buffer_range(buffer, byte_offset, data.len()));
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        f(self.buffer_range(buffer, byte_offset, size));
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffers[buffer.0].len()
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.try_delete_buffer(buffer)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        self.buffers
+            .remove(buffer.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", buffer)))?;
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        assert!(
+            self.info.query_supported(kind),
+            "{:?} queries are not supported",
+            kind
+        );
+        QueryId(self.queries.add(kind))
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        check(&self.queries, query.0);
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        check(&self.queries, query.0);
+    }
+
+    fn query_result(&mut self, query: QueryId) -> Option<u64> {
+        check(&self.queries, query.0);
+        Some(0)
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        self.queries
+            .remove(query.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", query))));
+    }
+
+    fn insert_fence(&mut self) -> FenceId {
+        FenceId(self.fences.add(()))
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        check(&self.fences, fence.0);
+        true
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        self.fences
+            .remove(fence.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", fence))));
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.try_delete_texture(texture)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        let TextureIdInner::Managed(id) = texture.0 else {
+            return Err(GraphicsError::InvalidId(format!("{:?}", texture)));
+        };
+        self.textures
+            .remove(id)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", texture)))?;
+        Ok(())
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        self.try_delete_sampler(sampler)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        self.samplers
+            .remove(sampler.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", sampler)))
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.try_delete_shader(program)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        self.shaders
+            .remove(program.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", program)))
+    }
+
+    fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
+
+    fn apply_scissor_rect(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
+
+    fn apply_bindings_with_samplers(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
+        textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
+    ) {
+        for buffer in vertex_buffers.iter().chain(index_buffer.iter()) {
+            check(&self.buffers, buffer.0);
+        }
+        for &texture in textures {
+            if let TextureIdInner::Managed(id) = texture.0 {
+                check(&self.textures, id);
+            }
+        }
+        for sampler in samplers.iter().flatten() {
+            check(&self.samplers, sampler.0);
+        }
+        self.cur_bindings = Some(Bindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+            images: textures.to_vec(),
+            samplers: samplers.to_vec(),
+        });
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.cur_uniforms.clear();
+        self.cur_uniforms
+            .extend_from_slice(unsafe { std::slice::from_raw_parts(uniform_ptr, size) });
+    }
+
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        _block_index: usize,
+        _uniform_ptr: *const u8,
+        _size: usize,
+    ) {
+    }
+
+    fn clear(
+        &mut self,
+        _color: Option<(f32, f32, f32, f32)>,
+        _depth: Option<f32>,
+        _stencil: Option<i32>,
+    ) {
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, _action: PassAction) {
+        if let Some(pass) = pass {
+            check(&self.passes, pass.0);
+        }
+        self.cur_pass = Some(pass);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.cur_pass = None;
+    }
+
+    fn commit_frame(&mut self) {
+        self.cur_pipeline = None;
+        self.cur_bindings = None;
+        self.frames += 1;
+    }
+
+    fn draw(&self, _base_element: i32, _num_elements: i32, _num_instances: i32) {
+        self.draw_calls.set(self.draw_calls.get() + 1);
+    }
+
+    fn draw_arrays(&self, _first_vertex: i32, _num_vertices: i32, _num_instances: i32) {
+        self.draw_calls.set(self.draw_calls.get() + 1);
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        self.draw_calls.set(self.draw_calls.get() + draws.len());
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, _offset: usize, draw_count: usize) {
+        check(&self.buffers, indirect_buffer.0);
+        self.draw_calls.set(self.draw_calls.get() + draw_count);
+    }
+
+    fn apply_compute_bindings(&mut self, bindings: &ComputeBindings) {
+        for buffer in &bindings.storage_buffers {
+            check(&self.buffers, buffer.0);
+        }
+    }
+
+    fn dispatch(&mut self, _groups_x: u32, _groups_y: u32, _groups_z: u32) {
+        assert!(self.info.features.compute, "compute is not supported");
+    }
+
+    fn memory_barrier(&mut self, _barriers: &[MemoryBarrier]) {}
+}
+
+#[test]
+fn test_null_context_lifetimes() {
+    let mut ctx = NullContext::new();
+
+    let data = [1u16, 2, 3, 4];
+    let buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Dynamic,
+        BufferSource::slice(&data),
+    );
+    ctx.buffer_update_part(buffer, 2, BufferSource::slice(&[7u16]));
+    let mut read = [0u8; 4];
+    ctx.buffer_read(buffer, 0, &mut read);
+    assert_eq!(read, [1, 0, 7, 0]);
+
+    let color = ctx.new_render_texture(TextureParams {
+        width: 4,
+        height: 4,
+        ..Default::default()
+    });
+    let depth = ctx.new_render_texture(TextureParams {
+        width: 4,
+        height: 4,
+        format: TextureFormat::Depth,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(color, Some(depth));
+    ctx.begin_pass(Some(pass), PassAction::Nothing);
+    assert_eq!(ctx.current_pass(), Some(Some(pass)));
+    ctx.draw(0, 4, 1);
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert_eq!(ctx.draw_calls(), 1);
+
+    ctx.delete_render_pass(pass);
+    ctx.delete_buffer(buffer);
+    assert_eq!(ctx.resource_counts(), ResourceCounts::default());
+    assert!(ctx.try_delete_buffer(buffer).is_err());
+    assert!(ctx.try_delete_texture(color).is_err());
+}
+
+#[test]
+fn test_null_context_double_delete() {
+    let mut ctx = NullContext::new();
+
+    let source = ShaderSource::Glsl {
+        vertex: "",
+        fragment: "",
+    };
+    let shader = ctx.new_shader(source, ShaderMeta::default()).unwrap();
+    let pipeline = ctx.new_pipeline(&[], &[], shader, PipelineParams::default());
+    let buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0f32; 4]),
+    );
+    let texture = ctx.new_render_texture(TextureParams::default());
+    let attachment = ctx.new_render_texture(TextureParams::default());
+    let pass = ctx.new_render_pass(attachment, None);
+    let sampler = ctx.new_sampler(SamplerParams::default());
+
+    let is_invalid_id =
+        |result: Result<(), GraphicsError>| matches!(result, Err(GraphicsError::InvalidId(_)));
+    assert!(ctx.try_delete_pipeline(pipeline).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_pipeline(pipeline)));
+    assert!(ctx.try_delete_shader(shader).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_shader(shader)));
+    assert!(ctx.try_delete_buffer(buffer).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_buffer(buffer)));
+    assert!(ctx.try_delete_texture(texture).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_texture(texture)));
+    assert!(ctx.try_delete_render_pass(pass).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_render_pass(pass)));
+    assert!(is_invalid_id(ctx.try_delete_texture(attachment)));
+    assert!(ctx.try_delete_sampler(sampler).is_ok());
+    assert!(is_invalid_id(ctx.try_delete_sampler(sampler)));
+    assert_eq!(ctx.resource_counts(), ResourceCounts::default());
+
+    let panicked =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ctx.delete_texture(texture)));
+    assert!(panicked.is_err());
+}
+
+#[test]
+fn test_null_context_invalid_texture_params() {
+    let mut ctx = NullContext::new();
+    assert!(!ctx.info().features.compute);
+
+    let storage = ctx.try_new_texture(
+        TextureAccess::Storage,
+        TextureSource::Empty,
+        TextureParams::default(),
+    );
+    assert_eq!(storage, Err(GraphicsError::UnsupportedFeature("compute")));
+
+    let multisampled = TextureParams {
+        sample_count: 4,
+        ..Default::default()
+    };
+    let is_invalid_params = |result: Result<TextureId, GraphicsError>| {
+        matches!(result, Err(GraphicsError::InvalidParams(_)))
+    };
+    assert!(is_invalid_params(ctx.try_new_texture(
+        TextureAccess::Static,
+        TextureSource::Empty,
+        multisampled
+    )));
+    assert!(ctx
+        .try_new_texture(
+            TextureAccess::RenderTarget,
+            TextureSource::Empty,
+            multisampled
+        )
+        .is_ok());
+
+    let params = TextureParams {
+        width: 2,
+        height: 2,
+        ..Default::default()
+    };
+    assert!(is_invalid_params(ctx.try_new_texture(
+        TextureAccess::Static,
+        TextureSource::Bytes(&[0; 15]),
+        params
+    )));
+    let cubemap = TextureParams {
+        kind: TextureKind::CubeMap,
+        ..params
+    };
+    assert!(is_invalid_params(ctx.try_new_texture(
+        TextureAccess::Static,
+        TextureSource::Bytes(&[0; 16]),
+        cubemap
+    )));
+    let flat = TextureParams { depth: 2, ..params };
+    assert!(is_invalid_params(ctx.try_new_texture(
+        TextureAccess::Static,
+        TextureSource::Empty,
+        flat
+    )));
+    assert!(ctx
+        .try_new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(&[0; 16]),
+            params
+        )
+        .is_ok());
+}
@@ -0,0 +1,2190 @@
+//! CPU rasterizer, see `SoftwareContext`.
+
+use super::*;
+
+use super::null::check;
+use crate::ResourceManager;
+
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    convert::TryInto,
+    rc::Rc,
+};
+
+/// Clip space w below this is behind the camera.
+const W_EPSILON: f32 = 1e-5;
+
+/// Minimum resolvable depth difference for `PipelineParams::depth_write_offset` units.
+const DEPTH_UNIT: f32 = 1. / (1 << 24) as f32;
+
+type VertexFn = dyn Fn(&VertexInput, &mut [f32]) -> [f32; 4];
+type FragmentFn = dyn Fn(&FragmentInput, &mut [[f32; 4]]) -> bool;
+
+/// Shader of `SoftwareContext`, a pair of Rust closures.
+///
+/// `vertex` writes `varyings` floats into its output slice and returns the clip space
+/// position, same as `gl_Position`. Varyings are interpolated perspective-correct
+/// across the primitive.
+///
+/// `fragment` writes a color for each color attachment of the pass and returns
+/// `false` to discard the fragment. Outputs start as (0, 0, 0, 1).
+#[derive(Clone)]
+pub struct NativeShader {
+    pub varyings: usize,
+    pub vertex: Rc<VertexFn>,
+    pub fragment: Rc<FragmentFn>,
+}
+
+impl NativeShader {
+    pub fn new(
+        varyings: usize,
+        vertex: impl Fn(&VertexInput, &mut [f32]) -> [f32; 4] + 'static,
+        fragment: impl Fn(&FragmentInput, &mut [[f32; 4]]) -> bool + 'static,
+    ) -> NativeShader {
+        NativeShader {
+            varyings,
+            vertex: Rc::new(vertex),
+            fragment: Rc::new(fragment),
+        }
+    }
+}
+
+impl std::fmt::Debug for NativeShader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeShader")
+            .field("varyings", &self.varyings)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Vertex shader arguments of a `NativeShader`.
+pub struct VertexInput<'a> {
+    /// Attributes in `new_pipeline` order, a `Mat4` takes 4 consecutive slots.
+    /// Missing components are (0, 0, 0, 1), integer formats are converted to floats.
+    pub attributes: &'a [[f32; 4]],
+    /// Bytes of the last `apply_uniforms`.
+    pub uniforms: &'a [u8],
+    /// Bytes of each `apply_uniform_block`.
+    pub uniform_blocks: &'a [Vec<u8>],
+    pub vertex_id: u32,
+    pub instance_id: u32,
+}
+
+impl<'a> VertexInput<'a> {
+    /// `uniforms` as `T`.
+    ///
+    /// # Safety
+    ///
+    /// `T` should be the type given to `apply_uniforms`.
+    pub unsafe fn read_uniforms<T: Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+}
+
+/// Fragment shader arguments of a `NativeShader`.
+pub struct FragmentInput<'a> {
+    /// Interpolated outputs of the vertex shader.
+    pub varyings: &'a [f32],
+    /// Bytes of the last `apply_uniforms`.
+    pub uniforms: &'a [u8],
+    /// Bytes of each `apply_uniform_block`.
+    pub uniform_blocks: &'a [Vec<u8>],
+    /// Window coordinates of the pixel center and the depth, same as `gl_FragCoord.xyz`.
+    pub position: [f32; 3],
+    pub front_facing: bool,
+    images: &'a [Image<'a>],
+}
+
+impl<'a> FragmentInput<'a> {
+    /// `uniforms` as `T`.
+    ///
+    /// # Safety
+    ///
+    /// `T` should be the type given to `apply_uniforms`.
+    pub unsafe fn read_uniforms<T: Copy>(&self) -> T {
+        read_uniforms(self.uniforms)
+    }
+
+    /// Sample `Bindings::images[image]` at `uv`, same as `texture2D`.
+    pub fn sample(&self, image: usize, uv: [f32; 2]) -> [f32; 4] {
+        self.sample_layer(image, uv, 0)
+    }
+
+    /// Sample a layer of a `Texture2DArray`, a slice of a `Texture3D`
+    /// or a face of a `CubeMap`.
+    pub fn sample_layer(&self, image: usize, uv: [f32; 2], layer: u32) -> [f32; 4] {
+        self.images[image].sample(uv, layer, |texel| texel)
+    }
+
+    /// Compare `reference` with the depth at `uv` using `SamplerParams::compare`,
+    /// same as `texture` on a `sampler2DShadow`: 1.0 passes and 0.0 fails,
+    /// in between with linear filtering. Without a compare function the depth is returned.
+    pub fn sample_compare(&self, image: usize, uv: [f32; 2], reference: f32) -> f32 {
+        let image = &self.images[image];
+        let passed = |depth: f32| match image.sampler.compare {
+            None => depth,
+            Some(Comparison::Never) => 0.,
+            Some(Comparison::Less) => (reference < depth) as u8 as f32,
+            Some(Comparison::LessOrEqual) => (reference <= depth) as u8 as f32,
+            Some(Comparison::Greater) => (reference > depth) as u8 as f32,
+            Some(Comparison::GreaterOrEqual) => (reference >= depth) as u8 as f32,
+            Some(Comparison::Equal) => (reference == depth) as u8 as f32,
+            Some(Comparison::NotEqual) => (reference != depth) as u8 as f32,
+            Some(Comparison::Always) => 1.,
+        };
+        image.sample(uv, 0, |texel| [passed(texel[0]); 4])[0]
+    }
+}
+
+unsafe fn read_uniforms<T: Copy>(uniforms: &[u8]) -> T {
+    assert!(
+        uniforms.len() >= std::mem::size_of::<T>(),
+        "{} bytes of uniforms applied, {} expected",
+        uniforms.len(),
+        std::mem::size_of::<T>()
+    );
+    std::ptr::read_unaligned(uniforms.as_ptr() as *const T)
+}
+
+struct Texture {
+    params: TextureParams,
+    // own sampling state, from `params` and texture_set_*
+    sampler: SamplerParams,
+    /// All the mip levels one after another, each with all its layers,
+    /// bottom row first, same as GL.
+    data: RefCell<Vec<u8>>,
+}
+
+impl Texture {
+    fn layer_size(&self) -> usize {
+        self.params
+            .format
+            .size(self.params.width, self.params.height) as usize
+    }
+}
+
+/// Offset of a layer of a mip level in `Texture::data`.
+fn mip_offset(params: &TextureParams, mip_level: u32, layer: u32) -> usize {
+    let layer_size = |level| {
+        let (width, height) = params.mip_size(level);
+        params.format.size(width, height) as usize
+    };
+    let levels: usize = (0..mip_level)
+        .map(|level| layer_size(level) * params.mip_layers(level) as usize)
+        .sum();
+    levels + layer as usize * layer_size(mip_level)
+}
+
+/// Size of `Texture::data`, all the levels and layers.
+fn data_size(params: &TextureParams) -> usize {
+    mip_offset(params, params.mip_levels(), 0)
+}
+
+/// Texture borrowed for sampling during a draw.
+struct Image<'a> {
+    texture: &'a Texture,
+    // the bound sampler or the texture's own state
+    sampler: SamplerParams,
+    data: Ref<'a, Vec<u8>>,
+}
+
+impl<'a> Image<'a> {
+    /// `map` is applied to each texel before filtering.
+    fn sample(&self, [u, v]: [f32; 2], layer: u32, map: impl Fn([f32; 4]) -> [f32; 4]) -> [f32; 4] {
+        let params = &self.texture.params;
+        let sampler = &self.sampler;
+        // mipmaps are not sampled, only the top level
+        let (width, height) = (params.width as i32, params.height as i32);
+        let size = texel_size(params.format);
+        let layer = &self.data[layer as usize * self.texture.layer_size()..];
+        let texel = |x: i32, y: i32| match (
+            wrap(x, width, sampler.wrap_x),
+            wrap(y, height, sampler.wrap_y),
+        ) {
+            (Some(x), Some(y)) => {
+                let i = (y * width + x) as usize * size;
+                map(load(params.format, &layer[i..i + size]))
+            }
+            _ => map(sampler.border_color),
+        };
+        match sampler.mag_filter {
+            FilterMode::Nearest => texel(
+                (u * width as f32).floor() as i32,
+                (v * height as f32).floor() as i32,
+            ),
+            FilterMode::Linear => {
+                let x = u * width as f32 - 0.5;
+                let y = v * height as f32 - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                let bottom = lerp4(texel(x0, y0), texel(x0 + 1, y0), fx);
+                let top = lerp4(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), fx);
+                lerp4(bottom, top, fy)
+            }
+        }
+    }
+}
+
+/// None outside of the texture with `ClampToBorder`.
+fn wrap(coord: i32, size: i32, wrap: TextureWrap) -> Option<i32> {
+    match wrap {
+        TextureWrap::Repeat => Some(coord.rem_euclid(size)),
+        TextureWrap::Mirror => {
+            let coord = coord.rem_euclid(2 * size);
+            if coord >= size {
+                Some(2 * size - 1 - coord)
+            } else {
+                Some(coord)
+            }
+        }
+        TextureWrap::Clamp => Some(coord.clamp(0, size - 1)),
+        TextureWrap::ClampToBorder => (0..size).contains(&coord).then_some(coord),
+        TextureWrap::MirrorClampToEdge => {
+            let coord = if coord < 0 { -coord - 1 } else { coord };
+            Some(coord.min(size - 1))
+        }
+    }
+}
+
+fn lerp4(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut res = a;
+    for i in 0..4 {
+        res[i] += (b[i] - a[i]) * t;
+    }
+    res
+}
+
+fn texel_size(format: TextureFormat) -> usize {
+    format.size(1, 1) as usize
+}
+
+/// Formats clamped to [0, 1] before blending and on store.
+fn is_normalized(format: TextureFormat) -> bool {
+    use TextureFormat::*;
+
+    matches!(format, RGB8 | RGBA8 | SRGB8A8 | Alpha | R8 | RG8 | RGB10A2)
+}
+
+/// Decode a texel into RGBA. Depth formats are (depth, stencil, 0, 1).
+fn load(format: TextureFormat, texel: &[u8]) -> [f32; 4] {
+    use TextureFormat::*;
+
+    let unorm8 = |i: usize| texel[i] as f32 / 255.;
+    let u16_at = |i: usize| u16::from_le_bytes(texel[i * 2..i * 2 + 2].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(texel[i * 4..i * 4 + 4].try_into().unwrap());
+    let half = |i: usize| f16_to_f32(u16_at(i));
+    let float = |i: usize| f32::from_bits(u32_at(i));
+    match format {
+        RGB8 => [unorm8(0), unorm8(1), unorm8(2), 1.],
+        RGBA8 => [unorm8(0), unorm8(1), unorm8(2), unorm8(3)],
+        SRGB8A8 => [
+            srgb_to_linear(unorm8(0)),
+            srgb_to_linear(unorm8(1)),
+            srgb_to_linear(unorm8(2)),
+            unorm8(3),
+        ],
+        Alpha => [0., 0., 0., unorm8(0)],
+        R8 => [unorm8(0), 0., 0., 1.],
+        RG8 => [unorm8(0), unorm8(1), 0., 1.],
+        R16F => [half(0), 0., 0., 1.],
+        RG16F => [half(0), half(1), 0., 1.],
+        RGBA16F => [half(0), half(1), half(2), half(3)],
+        R32F => [float(0), 0., 0., 1.],
+        RGBA32F => [float(0), float(1), float(2), float(3)],
+        R32UI => [u32_at(0) as f32, 0., 0., 1.],
+        RGB10A2 => {
+            let bits = u32_at(0);
+            [
+                (bits & 0x3ff) as f32 / 1023.,
+                (bits >> 10 & 0x3ff) as f32 / 1023.,
+                (bits >> 20 & 0x3ff) as f32 / 1023.,
+                (bits >> 30) as f32 / 3.,
+            ]
+        }
+        Depth => [u16_at(0) as f32 / 65535., 0., 0., 1.],
+        Depth32 => [float(0), 0., 0., 1.],
+        Depth24Stencil8 => {
+            let bits = u32_at(0);
+            [(bits >> 8) as f32 / 16777215., (bits & 0xff) as f32, 0., 1.]
+        }
+        _ => panic!("{:?} is not supported by SoftwareContext", format),
+    }
+}
+
+/// Encode RGBA into a texel, the opposite of `load`.
+fn store(format: TextureFormat, color: [f32; 4], texel: &mut [u8]) {
+    use TextureFormat::*;
+
+    let unorm = |v: f32, max: u32| (v.clamp(0., 1.) * max as f32).round() as u32;
+    let mut write = |bytes: &[u8]| texel.copy_from_slice(bytes);
+    let [r, g, b, a] = color;
+    match format {
+        RGB8 => write(&[
+            unorm(r, 255) as u8,
+            unorm(g, 255) as u8,
+            unorm(b, 255) as u8,
+        ]),
+        RGBA8 => write(&[
+            unorm(r, 255) as u8,
+            unorm(g, 255) as u8,
+            unorm(b, 255) as u8,
+            unorm(a, 255) as u8,
+        ]),
+        SRGB8A8 => write(&[
+            unorm(linear_to_srgb(r), 255) as u8,
+            unorm(linear_to_srgb(g), 255) as u8,
+            unorm(linear_to_srgb(b), 255) as u8,
+            unorm(a, 255) as u8,
+        ]),
+        Alpha => write(&[unorm(a, 255) as u8]),
+        R8 => write(&[unorm(r, 255) as u8]),
+        RG8 => write(&[unorm(r, 255) as u8, unorm(g, 255) as u8]),
+        R16F => write(&f32_to_f16(r).to_le_bytes()),
+        RG16F => {
+            let [r, g] = [f32_to_f16(r), f32_to_f16(g)];
+            write(&(r as u32 | (g as u32) << 16).to_le_bytes())
+        }
+        RGBA16F => {
+            let [r, g, b, a] = [r, g, b, a].map(f32_to_f16);
+            let bits = r as u64 | (g as u64) << 16 | (b as u64) << 32 | (a as u64) << 48;
+            write(&bits.to_le_bytes())
+        }
+        R32F => write(&r.to_le_bytes()),
+        RGBA32F => {
+            for (i, v) in color.iter().enumerate() {
+                texel[i * 4..i * 4 + 4].copy_from_slice(&v.to_le_bytes());
+            }
+        }
+        R32UI => write(&(r.max(0.) as u32).to_le_bytes()),
+        RGB10A2 => {
+            let bits =
+                unorm(r, 1023) | unorm(g, 1023) << 10 | unorm(b, 1023) << 20 | unorm(a, 3) << 30;
+            write(&bits.to_le_bytes())
+        }
+        Depth => write(&(unorm(r, 65535) as u16).to_le_bytes()),
+        Depth32 => write(&r.to_le_bytes()),
+        Depth24Stencil8 => {
+            let bits = unorm(r, 16777215) << 8 | g as u32 & 0xff;
+            write(&bits.to_le_bytes())
+        }
+        _ => panic!("{:?} is not supported by SoftwareContext", format),
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = (half as u32 & 0x8000) << 16;
+    let exponent = (half >> 10 & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    match exponent {
+        0 => {
+            let magnitude = mantissa as f32 / (1 << 24) as f32;
+            f32::from_bits(magnitude.to_bits() | sign)
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 112) << 23 | mantissa << 13),
+    }
+}
+
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16 & 0x8000) as u16;
+    let exponent = (bits >> 23 & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - exponent) as u32;
+        let mantissa = mantissa | 0x80_0000;
+        return sign | ((mantissa + (1 << (shift - 1))) >> shift) as u16;
+    }
+    // a carry out of the mantissa correctly bumps the exponent
+    sign | (((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13)) as u16
+}
+
+struct Buffer {
+    data: Vec<u8>,
+    element_size: usize,
+}
+
+struct RenderPassInternal {
+    color_textures: Vec<TextureId>,
+    color_targets: Vec<RenderTarget>,
+    resolve_textures: Vec<TextureId>,
+    depth_target: Option<RenderTarget>,
+}
+
+struct Attribute {
+    buffer_index: usize,
+    offset: usize,
+    format: VertexFormat,
+    step_func: VertexStep,
+    step_rate: i32,
+}
+
+struct PipelineInternal {
+    shader: ShaderId,
+    attributes: Vec<Attribute>,
+    strides: Vec<usize>,
+    params: PipelineParams,
+}
+
+/// Vertex shader output.
+#[derive(Clone)]
+struct Vertex {
+    position: [f32; 4],
+    varyings: Vec<f32>,
+}
+
+impl Vertex {
+    fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+        Vertex {
+            position: lerp4(self.position, other.position, t),
+            varyings: self
+                .varyings
+                .iter()
+                .zip(&other.varyings)
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+        }
+    }
+}
+
+/// Vertex after the perspective division and the viewport transform.
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    /// Varyings divided by w, for perspective-correct interpolation.
+    varyings: Vec<f32>,
+}
+
+/// Clip a polygon against the near plane, and against w = 0 for the perspective division.
+/// Anything outside of the viewport is left to the rasterizer.
+fn clip_polygon(mut polygon: Vec<Vertex>) -> Vec<Vertex> {
+    let planes: [fn(&[f32; 4]) -> f32; 2] = [|p| p[2] + p[3], |p| p[3] - W_EPSILON];
+    for plane in planes {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (i, a) in polygon.iter().enumerate() {
+            let b = &polygon[(i + 1) % polygon.len()];
+            let (da, db) = (plane(&a.position), plane(&b.position));
+            if da >= 0. {
+                clipped.push(a.clone());
+            }
+            if (da >= 0.) != (db >= 0.) {
+                clipped.push(a.lerp(b, da / (da - db)));
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+fn clip_line(mut a: Vertex, mut b: Vertex) -> Option<(Vertex, Vertex)> {
+    let planes: [fn(&[f32; 4]) -> f32; 2] = [|p| p[2] + p[3], |p| p[3] - W_EPSILON];
+    for plane in planes {
+        let (da, db) = (plane(&a.position), plane(&b.position));
+        match (da >= 0., db >= 0.) {
+            (true, true) => {}
+            (false, false) => return None,
+            (true, false) => b = a.lerp(&b, da / (da - db)),
+            (false, true) => a = a.lerp(&b, da / (da - db)),
+        }
+    }
+    Some((a, b))
+}
+
+fn edge(a: &WindowVertex, b: &WindowVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// Top-left fill rule for counter-clockwise triangles with y pointing up,
+/// so pixels on an edge shared by two triangles are drawn once.
+fn is_top_left(a: &WindowVertex, b: &WindowVertex) -> bool {
+    (a.y == b.y && b.x < a.x) || b.y < a.y
+}
+
+fn compare<T: PartialOrd>(func: Comparison, a: T, b: T) -> bool {
+    match func {
+        Comparison::Never => false,
+        Comparison::Less => a < b,
+        Comparison::LessOrEqual => a <= b,
+        Comparison::Greater => a > b,
+        Comparison::GreaterOrEqual => a >= b,
+        Comparison::Equal => a == b,
+        Comparison::NotEqual => a != b,
+        Comparison::Always => true,
+    }
+}
+
+fn stencil_compare(func: CompareFunc, a: u32, b: u32) -> bool {
+    match func {
+        CompareFunc::Always => true,
+        CompareFunc::Never => false,
+        CompareFunc::Less => a < b,
+        CompareFunc::Equal => a == b,
+        CompareFunc::LessOrEqual => a <= b,
+        CompareFunc::Greater => a > b,
+        CompareFunc::NotEqual => a != b,
+        CompareFunc::GreaterOrEqual => a >= b,
+    }
+}
+
+fn stencil_op(op: StencilOp, face: &StencilFaceState, value: u8) -> u8 {
+    let res = match op {
+        StencilOp::Keep => value,
+        StencilOp::Zero => 0,
+        StencilOp::Replace => face.test_ref as u8,
+        StencilOp::IncrementClamp => value.saturating_add(1),
+        StencilOp::DecrementClamp => value.saturating_sub(1),
+        StencilOp::Invert => !value,
+        StencilOp::IncrementWrap => value.wrapping_add(1),
+        StencilOp::DecrementWrap => value.wrapping_sub(1),
+    };
+    let write_mask = face.write_mask as u8;
+    value & !write_mask | res & write_mask
+}
+
+fn blend_factor(factor: BlendFactor, channel: usize, src: [f32; 4], dst: [f32; 4]) -> f32 {
+    let value = |value| match value {
+        BlendValue::SourceColor => src[channel],
+        BlendValue::SourceAlpha => src[3],
+        BlendValue::DestinationColor => dst[channel],
+        BlendValue::DestinationAlpha => dst[3],
+    };
+    match factor {
+        BlendFactor::Zero => 0.,
+        BlendFactor::One => 1.,
+        BlendFactor::Value(v) => value(v),
+        BlendFactor::OneMinusValue(v) => 1. - value(v),
+        BlendFactor::SourceAlphaSaturate if channel == 3 => 1.,
+        BlendFactor::SourceAlphaSaturate => src[3].min(1. - dst[3]),
+    }
+}
+
+fn blend(state: BlendState, channel: usize, src: [f32; 4], dst: [f32; 4]) -> f32 {
+    let s = src[channel] * blend_factor(state.sfactor, channel, src, dst);
+    let d = dst[channel] * blend_factor(state.dfactor, channel, src, dst);
+    match state.equation {
+        Equation::Add => s + d,
+        Equation::Subtract => s - d,
+        Equation::ReverseSubtract => d - s,
+    }
+}
+
+fn read_attribute(format: VertexFormat, bytes: &[u8]) -> [f32; 4] {
+    use VertexFormat::*;
+
+    let mut value = [0., 0., 0., 1.];
+    for (i, v) in value
+        .iter_mut()
+        .enumerate()
+        .take(format.components() as usize)
+    {
+        *v = match format {
+            Float1 | Float2 | Float3 | Float4 | Mat4 => {
+                f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap())
+            }
+            Byte1 | Byte2 | Byte3 | Byte4 => bytes[i] as f32,
+            Short1 | Short2 | Short3 | Short4 => {
+                u16::from_le_bytes(bytes[i * 2..i * 2 + 2].try_into().unwrap()) as f32
+            }
+            Int1 | Int2 | Int3 | Int4 => {
+                u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as f32
+            }
+        };
+    }
+    value
+}
+
+/// Render pass attachment borrowed for a draw.
+struct Attachment<'a> {
+    format: TextureFormat,
+    data: RefMut<'a, Vec<u8>>,
+    // of the rendered mip level and layer in `data`
+    offset: usize,
+}
+
+impl<'a> Attachment<'a> {
+    fn load(&self, pixel: usize) -> [f32; 4] {
+        let size = texel_size(self.format);
+        let start = self.offset + pixel * size;
+        load(self.format, &self.data[start..start + size])
+    }
+
+    fn store(&mut self, pixel: usize, color: [f32; 4]) {
+        let size = texel_size(self.format);
+        let start = self.offset + pixel * size;
+        store(self.format, color, &mut self.data[start..start + size])
+    }
+
+    /// `depth` converted to the attachment precision, as the depth test compares it.
+    fn quantize_depth(&self, depth: f32) -> f32 {
+        let mut texel = [0; 4];
+        let texel = &mut texel[..texel_size(self.format)];
+        store(self.format, [depth, 0., 0., 1.], texel);
+        load(self.format, texel)[0]
+    }
+}
+
+struct Target<'a> {
+    width: i32,
+    height: i32,
+    colors: Vec<Attachment<'a>>,
+    depth: Option<Attachment<'a>>,
+}
+
+/// Per-draw state of the rasterizer.
+struct Raster<'a> {
+    params: &'a PipelineParams,
+    shader: &'a NativeShader,
+    uniforms: &'a [u8],
+    uniform_blocks: &'a [Vec<u8>],
+    images: &'a [Image<'a>],
+    blend: [AttachmentBlend; MAX_COLOR_ATTACHMENTS],
+    viewport: (f32, f32, f32, f32),
+    /// Viewport, scissor and target bounds, (x0, y0, x1, y1) with x1 and y1 excluded.
+    bounds: (i32, i32, i32, i32),
+}
+
+impl<'a> Raster<'a> {
+    fn primitives(&self, target: &mut Target, v: &[&Vertex]) {
+        let n = v.len();
+        match self.params.primitive_type {
+            PrimitiveType::Triangles => {
+                for t in v.chunks_exact(3) {
+                    self.triangle(target, [t[0], t[1], t[2]]);
+                }
+            }
+            PrimitiveType::TriangleStrip => {
+                for i in 0..n.saturating_sub(2) {
+                    if i % 2 == 0 {
+                        self.triangle(target, [v[i], v[i + 1], v[i + 2]]);
+                    } else {
+                        self.triangle(target, [v[i + 1], v[i], v[i + 2]]);
+                    }
+                }
+            }
+            PrimitiveType::TriangleFan => {
+                for i in 1..n.saturating_sub(1) {
+                    self.triangle(target, [v[0], v[i], v[i + 1]]);
+                }
+            }
+            PrimitiveType::Lines => {
+                for l in v.chunks_exact(2) {
+                    self.line(target, l[0], l[1]);
+                }
+            }
+            PrimitiveType::LineStrip | PrimitiveType::LineLoop => {
+                for l in v.windows(2) {
+                    self.line(target, l[0], l[1]);
+                }
+                if self.params.primitive_type == PrimitiveType::LineLoop && n > 1 {
+                    self.line(target, v[n - 1], v[0]);
+                }
+            }
+            PrimitiveType::Points => {
+                for &p in v {
+                    self.point(target, p);
+                }
+            }
+        }
+    }
+
+    fn to_window(&self, vertex: &Vertex) -> WindowVertex {
+        let [x, y, z, w] = vertex.position;
+        let (vx, vy, vw, vh) = self.viewport;
+        let inv_w = 1. / w;
+        WindowVertex {
+            x: vx + (x * inv_w + 1.) * 0.5 * vw,
+            y: vy + (y * inv_w + 1.) * 0.5 * vh,
+            z: (z * inv_w + 1.) * 0.5,
+            inv_w,
+            varyings: vertex.varyings.iter().map(|v| v * inv_w).collect(),
+        }
+    }
+
+    fn triangle(&self, target: &mut Target, vertices: [&Vertex; 3]) {
+        let polygon = clip_polygon(vertices.iter().map(|&v| v.clone()).collect());
+        if polygon.len() < 3 {
+            return;
+        }
+        let polygon: Vec<WindowVertex> = polygon.iter().map(|v| self.to_window(v)).collect();
+
+        let area: f32 = (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        if area == 0. {
+            return;
+        }
+        let front_facing =
+            (area > 0.) == (self.params.front_face_order == FrontFaceOrder::CounterClockwise);
+        match self.params.cull_face {
+            CullFace::Front if front_facing => return,
+            CullFace::Back if !front_facing => return,
+            _ => {}
+        }
+
+        for i in 1..polygon.len() - 1 {
+            self.fill_triangle(
+                target,
+                [&polygon[0], &polygon[i], &polygon[i + 1]],
+                front_facing,
+            );
+        }
+    }
+
+    fn fill_triangle(&self, target: &mut Target, [a, b, c]: [&WindowVertex; 3], front: bool) {
+        let (a, b, c) = if edge(a, b, c.x, c.y) < 0. {
+            (a, c, b)
+        } else {
+            (a, b, c)
+        };
+        let area = edge(a, b, c.x, c.y);
+        if area <= 0. {
+            return;
+        }
+
+        let offset = match self.params.depth_write_offset {
+            Some((factor, units)) => {
+                let dzdx = ((b.z - a.z) * (c.y - a.y) - (b.y - a.y) * (c.z - a.z)) / area;
+                let dzdy = ((b.x - a.x) * (c.z - a.z) - (b.z - a.z) * (c.x - a.x)) / area;
+                factor * dzdx.abs().max(dzdy.abs()) + units * DEPTH_UNIT
+            }
+            None => 0.,
+        };
+
+        let (x0, y0, x1, y1) = self.bounds;
+        let min_x = (a.x.min(b.x).min(c.x).floor() as i32).max(x0);
+        let min_y = (a.y.min(b.y).min(c.y).floor() as i32).max(y0);
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as i32).min(x1);
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as i32).min(y1);
+
+        let mut varyings = vec![0.; self.shader.varyings];
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(b, c, px, py);
+                let w1 = edge(c, a, px, py);
+                let w2 = edge(a, b, px, py);
+                let inside = |w: f32, from, to| w > 0. || w == 0. && is_top_left(from, to);
+                if !inside(w0, b, c) || !inside(w1, c, a) || !inside(w2, a, b) {
+                    continue;
+                }
+                let (l0, l1, l2) = (w0 / area, w1 / area, w2 / area);
+                let z = l0 * a.z + l1 * b.z + l2 * c.z + offset;
+                let inv_w = l0 * a.inv_w + l1 * b.inv_w + l2 * c.inv_w;
+                for (i, v) in varyings.iter_mut().enumerate() {
+                    *v = (l0 * a.varyings[i] + l1 * b.varyings[i] + l2 * c.varyings[i]) / inv_w;
+                }
+                self.fragment(target, x, y, z, &varyings, front);
+            }
+        }
+    }
+
+    fn line(&self, target: &mut Target, a: &Vertex, b: &Vertex) {
+        let Some((a, b)) = clip_line(a.clone(), b.clone()) else {
+            return;
+        };
+        let (a, b) = (self.to_window(&a), self.to_window(&b));
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let x_major = dx.abs() >= dy.abs();
+        let (start, end, delta) = if x_major {
+            (a.x, b.x, dx)
+        } else {
+            (a.y, b.y, dy)
+        };
+        if delta == 0. {
+            return;
+        }
+
+        // a fragment per pixel center crossed along the major axis
+        let first = (start.min(end) - 0.5).ceil() as i32;
+        let last = (start.max(end) - 0.5).ceil() as i32;
+        let mut varyings = vec![0.; self.shader.varyings];
+        for major in first..last {
+            let t = (major as f32 + 0.5 - start) / delta;
+            let (x, y) = if x_major {
+                (major, (a.y + dy * t).floor() as i32)
+            } else {
+                ((a.x + dx * t).floor() as i32, major)
+            };
+            if !self.inside(x, y) {
+                continue;
+            }
+            let z = a.z + (b.z - a.z) * t;
+            let inv_w = a.inv_w + (b.inv_w - a.inv_w) * t;
+            for (i, v) in varyings.iter_mut().enumerate() {
+                *v = (a.varyings[i] + (b.varyings[i] - a.varyings[i]) * t) / inv_w;
+            }
+            self.fragment(target, x, y, z, &varyings, true);
+        }
+    }
+
+    fn point(&self, target: &mut Target, p: &Vertex) {
+        let [_, _, z, w] = p.position;
+        if w < W_EPSILON || z < -w {
+            return;
+        }
+        let p = self.to_window(p);
+        let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+        if self.inside(x, y) {
+            let varyings: Vec<f32> = p.varyings.iter().map(|v| v / p.inv_w).collect();
+            self.fragment(target, x, y, p.z, &varyings, true);
+        }
+    }
+
+    fn inside(&self, x: i32, y: i32) -> bool {
+        let (x0, y0, x1, y1) = self.bounds;
+        x >= x0 && x < x1 && y >= y0 && y < y1
+    }
+
+    fn fragment(
+        &self,
+        target: &mut Target,
+        x: i32,
+        y: i32,
+        z: f32,
+        varyings: &[f32],
+        front_facing: bool,
+    ) {
+        // far plane, the near one is clipped
+        if z > 1. {
+            return;
+        }
+        let input = FragmentInput {
+            varyings,
+            uniforms: self.uniforms,
+            uniform_blocks: self.uniform_blocks,
+            position: [x as f32 + 0.5, y as f32 + 0.5, z],
+            front_facing,
+            images: self.images,
+        };
+        let mut outputs = [[0., 0., 0., 1.]; MAX_COLOR_ATTACHMENTS];
+        if !(self.shader.fragment)(&input, &mut outputs[..target.colors.len()]) {
+            return;
+        }
+
+        let pixel = (y * target.width + x) as usize;
+        if let Some(depth) = &mut target.depth {
+            let [stored_z, stored_stencil, ..] = depth.load(pixel);
+            let z = depth.quantize_depth(z);
+            let mut stencil = stored_stencil as u8;
+            let face = match self.params.stencil_test {
+                Some(state) if depth.format.has_stencil() => Some(if front_facing {
+                    state.front
+                } else {
+                    state.back
+                }),
+                _ => None,
+            };
+            if let Some(face) = &face {
+                let reference = face.test_ref as u32 & face.test_mask & 0xff;
+                let value = stencil as u32 & face.test_mask;
+                if !stencil_compare(face.test_func, reference, value) {
+                    stencil = stencil_op(face.fail_op, face, stencil);
+                    depth.store(pixel, [stored_z, stencil as f32, 0., 1.]);
+                    return;
+                }
+            }
+            // same as GL backend, the depth test is only enabled along with depth_write
+            if self.params.depth_write && !compare(self.params.depth_test, z, stored_z) {
+                if let Some(face) = &face {
+                    stencil = stencil_op(face.depth_fail_op, face, stencil);
+                    depth.store(pixel, [stored_z, stencil as f32, 0., 1.]);
+                }
+                return;
+            }
+            if let Some(face) = &face {
+                stencil = stencil_op(face.pass_op, face, stencil);
+            }
+            let z = if self.params.depth_write { z } else { stored_z };
+            depth.store(pixel, [z, stencil as f32, 0., 1.]);
+        }
+
+        for (i, color) in target.colors.iter_mut().enumerate() {
+            let state = &self.blend[i];
+            let dst = color.load(pixel);
+            let mut src = outputs[i];
+            if is_normalized(color.format) {
+                src = src.map(|v| v.clamp(0., 1.));
+            }
+            let mut res = src;
+            if let Some(color_blend) = state.color_blend {
+                for (channel, res) in res.iter_mut().enumerate().take(3) {
+                    *res = blend(color_blend, channel, src, dst);
+                }
+                res[3] = blend(state.alpha_blend.unwrap_or(color_blend), 3, src, dst);
+            }
+            let (r, g, b, a) = state.color_write;
+            for (channel, &write) in [r, g, b, a].iter().enumerate() {
+                if !write {
+                    res[channel] = dst[channel];
+                }
+            }
+            color.store(pixel, res);
+        }
+    }
+}
+
+/// `RenderingBackend` rasterizing on the CPU, for golden-image tests that
+/// should give the same pixels on every machine and for running with no GPU at all.
+///
+/// Shaders are Rust closures, see `NativeShader` and `ShaderSource::Native`.
+/// Triangles, lines and points, depth and stencil tests, blending, instancing,
+/// render passes and texture sampling follow the GL backend. Lines and points
+/// are a single pixel wide, textures are sampled from the top mip level only,
+/// with `mag_filter`, and multisampled textures hold a single sample.
+///
+/// The default pass renders into `default_framebuffer`. There is no way to present
+/// it on screen directly yet: windows always get a GL context, there is no X11 `XPutImage`
+/// or Wayland `wl_shm` path. Upload the pixels read with `texture_read_pixels` to a texture
+/// of the window's backend and draw it there instead.
+///
+/// ```
+/// # use miniquad::*;
+/// let mut ctx = SoftwareContext::new(2, 2);
+/// let shader = NativeShader::new(
+///     0,
+///     |input, _| {
+///         let [x, y, ..] = input.attributes[0];
+///         [x, y, 0., 1.]
+///     },
+///     |_, outputs| {
+///         outputs[0] = [1., 0., 0., 1.];
+///         true
+///     },
+/// );
+/// let meta = ShaderMeta {
+///     uniforms: UniformBlockLayout { uniforms: vec![] },
+///     uniform_blocks: vec![],
+///     images: vec![],
+/// };
+/// let shader = ctx.new_shader(ShaderSource::Native(&shader), meta).unwrap();
+/// let pipeline = ctx.new_pipeline(
+///     &[BufferLayout::default()],
+///     &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+///     shader,
+///     PipelineParams::default(),
+/// );
+/// let vertices: [f32; 6] = [-1., -1., 3., -1., -1., 3.];
+/// let vertex_buffer = ctx.new_buffer(
+///     BufferType::VertexBuffer,
+///     BufferUsage::Immutable,
+///     BufferSource::slice(&vertices),
+/// );
+///
+/// ctx.begin_default_pass(PassAction::Nothing);
+/// ctx.apply_pipeline(&pipeline);
+/// ctx.apply_bindings_from_slice(&[vertex_buffer], None, &[]);
+/// ctx.draw_arrays(0, 3, 1);
+/// ctx.end_render_pass();
+///
+/// let mut pixels = [0; 16];
+/// ctx.texture_read_pixels(ctx.default_framebuffer(), &mut pixels);
+/// assert_eq!(pixels[..4], [255, 0, 0, 255]);
+/// ```
+pub struct SoftwareContext {
+    info: ContextInfo,
+    shaders: ResourceManager<NativeShader>,
+    textures: ResourceManager<Texture>,
+    samplers: ResourceManager<SamplerParams>,
+    passes: ResourceManager<RenderPassInternal>,
+    pipelines: ResourceManager<PipelineInternal>,
+    buffers: ResourceManager<Buffer>,
+    readbacks: ResourceManager<Vec<u8>>,
+    fences: ResourceManager<()>,
+    default_color: TextureId,
+    default_depth: TextureId,
+    cur_pass: Option<RenderPass>,
+    cur_pipeline: Option<Pipeline>,
+    vertex_buffers: Vec<BufferId>,
+    index_buffer: Option<BufferId>,
+    images: Vec<TextureId>,
+    image_samplers: Vec<Option<SamplerId>>,
+    uniforms: Vec<u8>,
+    uniform_blocks: Vec<Vec<u8>>,
+    viewport: (i32, i32, i32, i32),
+    scissor: (i32, i32, i32, i32),
+}
+
+impl SoftwareContext {
+    /// Context with a `width` x `height` default framebuffer,
+    /// RGBA8 color and `Depth24Stencil8` depth.
+    pub fn new(width: u32, height: u32) -> SoftwareContext {
+        let mut textures = ResourceManager::default();
+        let mut new_texture = |format| {
+            let params = TextureParams {
+                format,
+                width,
+                height,
+                ..Default::default()
+            };
+            TextureId(TextureIdInner::Managed(textures.add(Texture {
+                params,
+                sampler: SamplerParams::from_texture_params(&params),
+                data: RefCell::new(vec![0; format.size(width, height) as usize]),
+            })))
+        };
+        let default_color = new_texture(TextureFormat::RGBA8);
+        let default_depth = new_texture(TextureFormat::Depth24Stencil8);
+        SoftwareContext {
+            info: ContextInfo {
+                backend: Backend::OpenGl,
+                gl_version_string: "Software".to_string(),
+                glsl_support: GlslSupport::default(),
+                features: Features {
+                    async_readback: false,
+                    independent_blend: true,
+                    primitive_restart: true,
+                    mirror_clamp_to_edge: true,
+                    ..Features::default()
+                },
+                max_texture_size: 16384,
+                max_anisotropy: 1.,
+            },
+            shaders: ResourceManager::default(),
+            textures,
+            samplers: ResourceManager::default(),
+            passes: ResourceManager::default(),
+            pipelines: ResourceManager::default(),
+            buffers: ResourceManager::default(),
+            readbacks: ResourceManager::default(),
+            fences: ResourceManager::default(),
+            default_color,
+            default_depth,
+            cur_pass: None,
+            cur_pipeline: None,
+            vertex_buffers: vec![],
+            index_buffer: None,
+            images: vec![],
+            image_samplers: vec![],
+            uniforms: vec![],
+            uniform_blocks: vec![vec![]; MAX_UNIFORM_BLOCKS],
+            viewport: (0, 0, width as i32, height as i32),
+            scissor: (0, 0, width as i32, height as i32),
+        }
+    }
+
+    /// Color buffer of the default pass, RGBA8, bottom row first.
+    /// Read it with `texture_read_pixels`.
+    pub fn default_framebuffer(&self) -> TextureId {
+        self.default_color
+    }
+
+    /// Resize the default framebuffer, its contents are cleared.
+    pub fn resize_default_framebuffer(&mut self, width: u32, height: u32) {
+        self.texture_resize(self.default_color, width, height, None);
+        self.texture_resize(self.default_depth, width, height, None);
+    }
+
+    fn texture(&self, texture: TextureId) -> &Texture {
+        match texture.0 {
+            TextureIdInner::Managed(id) => &self.textures[id],
+            TextureIdInner::Raw(_) => panic!("SoftwareContext has no raw textures"),
+        }
+    }
+
+    fn texture_mut(&mut self, texture: TextureId) -> &mut Texture {
+        match texture.0 {
+            TextureIdInner::Managed(id) => &mut self.textures[id],
+            TextureIdInner::Raw(_) => panic!("SoftwareContext has no raw textures"),
+        }
+    }
+
+    fn buffer_range(&mut self, buffer: BufferId, byte_offset: usize, size: usize) -> &mut [u8] {
+        let data = &mut self.buffers[buffer.0].data;
+        assert!(
+            byte_offset + size <= data.len(),
+            "{}..{} is out of bounds of {:?} of {} bytes",
+            byte_offset,
+            byte_offset + size,
+            buffer,
+            data.len()
+        );
+        &mut data[byte_offset..byte_offset + size]
+    }
+
+    fn pipeline(&self) -> &PipelineInternal {
+        &self.pipelines[self
+            .cur_pipeline
+            .expect("Drawing without any binded pipeline")
+            .0]
+    }
+
+    /// Attachments of the current pass, (color, depth).
+    fn attachments(&self) -> (Vec<RenderTarget>, Option<RenderTarget>) {
+        match self.cur_pass {
+            None => (
+                vec![self.default_color.into()],
+                Some(self.default_depth.into()),
+            ),
+            Some(pass) => {
+                let pass = &self.passes[pass.0];
+                (pass.color_targets.clone(), pass.depth_target)
+            }
+        }
+    }
+
+    fn target(&self) -> Target<'_> {
+        let (colors, depth) = self.attachments();
+        let first = colors.first().copied().or(depth).unwrap();
+        let (width, height) = self.texture(first.texture).params.mip_size(first.mip_level);
+        let attachment = |target: RenderTarget| {
+            let texture = self.texture(target.texture);
+            Attachment {
+                format: texture.params.format,
+                data: texture
+                    .data
+                    .try_borrow_mut()
+                    .expect("Rendering into a texture bound for sampling"),
+                offset: mip_offset(&texture.params, target.mip_level, target.layer),
+            }
+        };
+        Target {
+            width: width as i32,
+            height: height as i32,
+            colors: colors.into_iter().map(attachment).collect(),
+            depth: depth.map(attachment),
+        }
+    }
+
+    fn fetch(&self, pipeline: &PipelineInternal, vertex: u32, instance: u32, out: &mut [[f32; 4]]) {
+        for (slot, attribute) in pipeline.attributes.iter().enumerate() {
+            let index = match attribute.step_func {
+                VertexStep::PerVertex => vertex,
+                VertexStep::PerInstance => instance / attribute.step_rate.max(1) as u32,
+            };
+            let buffer = self
+                .vertex_buffers
+                .get(attribute.buffer_index)
+                .unwrap_or_else(|| panic!("No vertex buffer {}", attribute.buffer_index));
+            let data = &self.buffers[buffer.0].data;
+            let offset =
+                attribute.offset + index as usize * pipeline.strides[attribute.buffer_index];
+            out[slot] = read_attribute(attribute.format, &data[offset..]);
+        }
+    }
+
+    /// Draw `vertices` with the current pipeline, `None` restarts the primitive.
+    fn draw_vertices(&self, vertices: &[Option<u32>], num_instances: i32) {
+        let pipeline = self.pipeline();
+        let shader = &self.shaders[pipeline.shader.0];
+        let images: Vec<Image> = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(n, &texture)| {
+                let texture = self.texture(texture);
+                let sampler = match self.image_samplers.get(n).copied().flatten() {
+                    Some(sampler) => self.samplers[sampler.0],
+                    None => texture.sampler,
+                };
+                Image {
+                    texture,
+                    sampler,
+                    data: texture.data.borrow(),
+                }
+            })
+            .collect();
+        let mut target = self.target();
+
+        let (vx, vy, vw, vh) = self.viewport;
+        let (sx, sy, sw, sh) = self.scissor;
+        let bounds = (
+            vx.max(sx).max(0),
+            vy.max(sy).max(0),
+            (vx + vw).min(sx + sw).min(target.width),
+            (vy + vh).min(sy + sh).min(target.height),
+        );
+        let params = &pipeline.params;
+        let raster = Raster {
+            params,
+            shader,
+            uniforms: &self.uniforms,
+            uniform_blocks: &self.uniform_blocks,
+            images: &images,
+            blend: params.attachment_blend.unwrap_or(
+                [AttachmentBlend {
+                    color_blend: params.color_blend,
+                    alpha_blend: params.alpha_blend,
+                    color_write: params.color_write,
+                }; MAX_COLOR_ATTACHMENTS],
+            ),
+            viewport: (vx as f32, vy as f32, vw as f32, vh as f32),
+            bounds,
+        };
+
+        let mut attributes = vec![[0.; 4]; pipeline.attributes.len()];
+        for instance_id in 0..num_instances as u32 {
+            let shaded: Vec<Option<Vertex>> = vertices
+                .iter()
+                .map(|vertex| {
+                    let vertex_id = (*vertex)?;
+                    self.fetch(pipeline, vertex_id, instance_id, &mut attributes);
+                    let input = VertexInput {
+                        attributes: &attributes,
+                        uniforms: &self.uniforms,
+                        uniform_blocks: &self.uniform_blocks,
+                        vertex_id,
+                        instance_id,
+                    };
+                    let mut varyings = vec![0.; shader.varyings];
+                    let position = (shader.vertex)(&input, &mut varyings);
+                    Some(Vertex { position, varyings })
+                })
+                .collect();
+            for primitive in shaded.split(|vertex| vertex.is_none()) {
+                let primitive: Vec<&Vertex> = primitive.iter().flatten().collect();
+                raster.primitives(&mut target, &primitive);
+            }
+        }
+    }
+}
+
+impl RenderingBackend for SoftwareContext {
+    fn info(&self) -> ContextInfo {
+        self.info.clone()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        _meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        match shader {
+            ShaderSource::Native(shader) => Ok(ShaderId(self.shaders.add(shader.clone()))),
+            _ => panic!("SoftwareContext only runs ShaderSource::Native shaders"),
+        }
+    }
+
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        _meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        check(&self.shaders, shader.0);
+        match source {
+            ShaderSource::Native(source) => self.shaders[shader.0] = source.clone(),
+            // while hot-reloading, this is what a compilation error would be
+            _ => {
+                return Err(ShaderError::CompilationError {
+                    shader_type: ShaderType::Vertex,
+                    error_message: "SoftwareContext only runs ShaderSource::Native shaders"
+                        .to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn shader_reflection(&self, _shader: ShaderId) -> Option<&ShaderReflection> {
+        None
+    }
+
+    fn new_texture(
+        &mut self,
+        _access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        assert!(
+            !params.format.is_compressed(),
+            "{:?} is not supported by SoftwareContext",
+            params.format
+        );
+        let mut data = vec![0; data_size(&params)];
+        match source {
+            TextureSource::Empty => {}
+            TextureSource::Bytes(bytes) => {
+                let size = mip_offset(&params, 1, 0);
+                assert_eq!(bytes.len(), size, "Wrong texture data size");
+                data[..size].copy_from_slice(bytes);
+            }
+            TextureSource::Array(faces) => {
+                // levels past `mip_levels` are not allocated
+                for (face, levels) in faces.iter().enumerate() {
+                    let levels = levels.iter().take(params.mip_levels() as usize);
+                    for (level, bytes) in levels.enumerate() {
+                        let offset = mip_offset(&params, level as u32, face as u32);
+                        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+                    }
+                }
+            }
+        }
+        TextureId(TextureIdInner::Managed(self.textures.add(Texture {
+            params,
+            sampler: SamplerParams::from_texture_params(&params),
+            data: RefCell::new(data),
+        })))
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.texture(texture).params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.texture(texture);
+        RawId::OpenGl(0)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let texture = self.texture_mut(texture);
+        texture.params.min_filter = filter;
+        texture.params.mipmap_filter = mipmap_filter;
+        texture.sampler.min_filter = filter;
+        texture.sampler.mipmap_filter = mipmap_filter;
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        let texture = self.texture_mut(texture);
+        texture.params.mag_filter = filter;
+        texture.sampler.mag_filter = filter;
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        let texture = self.texture_mut(texture);
+        texture.params.wrap = wrap_x;
+        texture.sampler.wrap_x = wrap_x;
+        texture.sampler.wrap_y = wrap_y;
+    }
+
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId {
+        SamplerId(self.samplers.add(params))
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        self.texture(texture);
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let texture = self.texture_mut(texture);
+        texture.params.width = width;
+        texture.params.height = height;
+        let size = mip_offset(&texture.params, 1, 0);
+        let data = texture.data.get_mut();
+        *data = vec![0; data_size(&texture.params)];
+        if let Some(bytes) = bytes {
+            assert_eq!(bytes.len(), size, "Wrong texture data size");
+            data[..size].copy_from_slice(bytes);
+        }
+    }
+
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        bytes: &mut [u8],
+    ) {
+        let texture = self.texture(texture);
+        let offset = mip_offset(&texture.params, mip_level, layer);
+        let (width, height) = texture.params.mip_size(mip_level);
+        let layer_size = texture.params.format.size(width, height) as usize;
+        bytes.copy_from_slice(&texture.data.borrow()[offset..offset + layer_size]);
+    }
+
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
+        let (x, y, width, height) = rect;
+        let texture = self.texture(texture);
+        let size = texel_size(texture.params.format);
+        let stride = texture.params.width as usize * size;
+        let data = texture.data.borrow();
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * size);
+        for row in y as usize..(y + height) as usize {
+            let start = row * stride + x as usize * size;
+            pixels.extend_from_slice(&data[start..start + width as usize * size]);
+        }
+        drop(data);
+        ReadbackId(self.readbacks.add(pixels))
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        self.readbacks.contains(readback.0)
+    }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        self.readbacks.remove(readback.0)
+    }
+
+    fn texture_update_mip_part(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        let texture = self.texture_mut(texture);
+        let size = texel_size(texture.params.format);
+        let stride = texture.params.mip_size(mip_level).0 as usize * size;
+        let layer_offset = mip_offset(&texture.params, mip_level, layer);
+        let row_size = width as usize * size;
+        let data = texture.data.get_mut();
+        for (row, src) in bytes.chunks(row_size).take(height as usize).enumerate() {
+            let start =
+                layer_offset + (y_offset as usize + row) * stride + x_offset as usize * size;
+            data[start..start + row_size].copy_from_slice(src);
+        }
+    }
+
+    fn new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> RenderPass {
+        assert!(
+            !color_img.is_empty() || depth_img.is_some(),
+            "Render pass should have at least one non-none target"
+        );
+        for target in color_img.iter().chain(depth_img.iter()) {
+            let params = &self.texture(target.texture).params;
+            assert!(
+                target.mip_level < params.mip_levels()
+                    && target.layer < params.mip_layers(target.mip_level),
+                "{:?} is out of range of the texture",
+                target
+            );
+        }
+        for &texture in resolve_img.into_iter().flatten() {
+            self.texture(texture);
+        }
+        RenderPass(self.passes.add(RenderPassInternal {
+            color_textures: color_img.iter().map(|target| target.texture).collect(),
+            color_targets: color_img.to_vec(),
+            resolve_textures: resolve_img.unwrap_or(&[]).to_vec(),
+            depth_target: depth_img,
+        }))
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        &self.passes[render_pass.0].color_textures
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.try_delete_render_pass(render_pass)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        let pass = self
+            .passes
+            .remove(render_pass.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", render_pass)))?;
+        let attachments = pass
+            .color_textures
+            .iter()
+            .chain(&pass.resolve_textures)
+            .chain(pass.depth_target.iter().map(|target| &target.texture));
+        for &texture in attachments {
+            // same as GL, could be shared with another pass
+            let _ = self.try_delete_texture(texture);
+        }
+        Ok(())
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        check(&self.shaders, shader.0);
+
+        // same layout rules as the GL backend
+        let mut strides = vec![0; buffer_layout.len()];
+        for attribute in attributes {
+            let layout = &buffer_layout[attribute.buffer_index];
+            strides[attribute.buffer_index] = if layout.stride == 0 {
+                strides[attribute.buffer_index] + attribute.format.size_bytes() as usize
+            } else {
+                layout.stride as usize
+            };
+        }
+        let mut offsets = vec![0; buffer_layout.len()];
+        let mut slots = vec![];
+        for attribute in attributes {
+            let layout = &buffer_layout[attribute.buffer_index];
+            let (format, count) = match attribute.format {
+                VertexFormat::Mat4 => (VertexFormat::Float4, 4),
+                format => (format, 1),
+            };
+            for _ in 0..count {
+                slots.push(Attribute {
+                    buffer_index: attribute.buffer_index,
+                    offset: offsets[attribute.buffer_index],
+                    format,
+                    step_func: layout.step_func,
+                    step_rate: layout.step_rate,
+                });
+                offsets[attribute.buffer_index] += format.size_bytes() as usize;
+            }
+        }
+
+        Pipeline(self.pipelines.add(PipelineInternal {
+            shader,
+            attributes: slots,
+            strides,
+            params,
+        }))
+    }
+
+    fn new_compute_pipeline(&mut self, _shader: ShaderId) -> Pipeline {
+        panic!("compute is not supported by SoftwareContext")
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        check(&self.pipelines, pipeline.0);
+        self.cur_pipeline = Some(*pipeline);
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.try_delete_pipeline(pipeline)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.pipelines
+            .remove(pipeline.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", pipeline)))?;
+        if self.cur_pipeline == Some(pipeline) {
+            self.cur_pipeline = None;
+        }
+        Ok(())
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        _usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let (data, element_size) = match data {
+            BufferSource::Slice(data) => (
+                unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) }.to_vec(),
+                data.element_size,
+            ),
+            BufferSource::Empty { size, element_size } => (vec![0; size], element_size),
+        };
+        match type_ {
+            BufferType::IndexBuffer => assert!(
+                matches!(element_size, 1 | 2 | 4),
+                "unsupported index buffer dimension"
+            ),
+            BufferType::StorageBuffer => {
+                panic!("Storage buffers are not supported by SoftwareContext")
+            }
+            BufferType::VertexBuffer | BufferType::IndirectBuffer => {}
+        }
+        BufferId(self.buffers.add(Buffer { data, element_size }))
+    }
+
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
+        let BufferSource::Slice(data) = data else {
+            return;
+        };
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        self.buffer_range(buffer, byte_offset, bytes.len())
+            .copy_from_slice(bytes);
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        data.copy_from_slice(self.buffer_range(buffer, byte_offset, data.len()));
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        f(self.buffer_range(buffer, byte_offset, size));
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffers[buffer.0].data.len()
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.try_delete_buffer(buffer)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        self.buffers
+            .remove(buffer.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", buffer)))?;
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        panic!("{:?} queries are not supported", kind)
+    }
+
+    fn begin_query(&mut self, _query: QueryId) {}
+
+    fn end_query(&mut self, _query: QueryId) {}
+
+    fn query_result(&mut self, _query: QueryId) -> Option<u64> {
+        None
+    }
+
+    fn delete_query(&mut self, _query: QueryId) {}
+
+    fn insert_fence(&mut self) -> FenceId {
+        FenceId(self.fences.add(()))
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        check(&self.fences, fence.0);
+        true
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        self.fences
+            .remove(fence.0)
+            .unwrap_or_else(|| panic!("{}", GraphicsError::InvalidId(format!("{:?}", fence))));
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.try_delete_texture(texture)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        let TextureIdInner::Managed(id) = texture.0 else {
+            return Err(GraphicsError::InvalidId(format!("{:?}", texture)));
+        };
+        self.textures
+            .remove(id)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", texture)))?;
+        Ok(())
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        self.try_delete_sampler(sampler)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        self.samplers
+            .remove(sampler.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", sampler)))?;
+        Ok(())
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.try_delete_shader(program)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        self.shaders
+            .remove(program.0)
+            .ok_or_else(|| GraphicsError::InvalidId(format!("{:?}", program)))?;
+        Ok(())
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.viewport = (x, y, w, h);
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.scissor = (x, y, w, h);
+    }
+
+    fn apply_bindings_with_samplers(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
+        textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
+    ) {
+        for buffer in vertex_buffers.iter().chain(index_buffer.iter()) {
+            check(&self.buffers, buffer.0);
+        }
+        for &texture in textures {
+            self.texture(texture);
+        }
+        for sampler in samplers.iter().flatten() {
+            check(&self.samplers, sampler.0);
+        }
+        self.vertex_buffers = vertex_buffers.to_vec();
+        self.index_buffer = index_buffer;
+        self.images = textures.to_vec();
+        self.image_samplers = samplers.to_vec();
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.uniforms.clear();
+        self.uniforms
+            .extend_from_slice(unsafe { std::slice::from_raw_parts(uniform_ptr, size) });
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        let block = &mut self.uniform_blocks[block_index];
+        block.clear();
+        block.extend_from_slice(unsafe { std::slice::from_raw_parts(uniform_ptr, size) });
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        let mut target = self.target();
+        let (x, y, w, h) = self.scissor;
+        let (x0, y0) = (x.max(0), y.max(0));
+        let (x1, y1) = ((x + w).min(target.width), (y + h).min(target.height));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pixel = (y * target.width + x) as usize;
+                if let Some((r, g, b, a)) = color {
+                    for attachment in &mut target.colors {
+                        attachment.store(pixel, [r, g, b, a]);
+                    }
+                }
+                if let Some(attachment) = &mut target.depth {
+                    let [mut z, mut s, ..] = attachment.load(pixel);
+                    if let Some(depth) = depth {
+                        z = depth;
+                    }
+                    if let Some(stencil) = stencil {
+                        s = (stencil & 0xff) as f32;
+                    }
+                    attachment.store(pixel, [z, s, 0., 1.]);
+                }
+            }
+        }
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        if let Some(pass) = pass {
+            check(&self.passes, pass.0);
+        }
+        self.cur_pass = pass;
+        let (colors, depth) = self.attachments();
+        let first = colors.first().copied().or(depth).unwrap();
+        let (w, h) = self.texture(first.texture).params.mip_size(first.mip_level);
+        let (w, h) = (w as i32, h as i32);
+        self.viewport = (0, 0, w, h);
+        self.scissor = (0, 0, w, h);
+        if let PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        } = action
+        {
+            self.clear(color, depth, stencil);
+        }
+    }
+
+    fn end_render_pass(&mut self) {
+        if let Some(pass) = self.cur_pass.take() {
+            let pass = &self.passes[pass.0];
+            for (&color, &resolve) in pass.color_textures.iter().zip(&pass.resolve_textures) {
+                let data = self.texture(color).data.borrow().clone();
+                *self.texture(resolve).data.borrow_mut() = data;
+            }
+        }
+    }
+
+    fn commit_frame(&mut self) {
+        self.cur_pipeline = None;
+        self.vertex_buffers.clear();
+        self.index_buffer = None;
+        self.images.clear();
+        self.image_samplers.clear();
+        for block in &mut self.uniform_blocks {
+            block.clear();
+        }
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.multi_draw(&[DrawRange {
+            base_element,
+            num_elements,
+            base_vertex: 0,
+            num_instances,
+        }]);
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        let vertices: Vec<Option<u32>> = (first_vertex..first_vertex + num_vertices)
+            .map(|vertex| Some(vertex as u32))
+            .collect();
+        self.draw_vertices(&vertices, num_instances);
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        let buffer = &self.buffers[self
+            .index_buffer
+            .expect("Unset index buffer type, use draw_arrays to draw without an index buffer")
+            .0];
+        let size = buffer.element_size;
+        let restart_index = u32::MAX >> (32 - size * 8);
+        let restart = self.pipeline().params.primitive_restart;
+        for draw in draws {
+            let start = draw.base_element as usize * size;
+            let end = start + draw.num_elements as usize * size;
+            let vertices: Vec<Option<u32>> = buffer.data[start..end]
+                .chunks(size)
+                .map(|bytes| {
+                    let index = match *bytes {
+                        [i] => i as u32,
+                        [a, b] => u16::from_le_bytes([a, b]) as u32,
+                        _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+                    };
+                    if restart && index == restart_index {
+                        None
+                    } else {
+                        Some((index as i64 + draw.base_vertex as i64) as u32)
+                    }
+                })
+                .collect();
+            self.draw_vertices(&vertices, draw.num_instances);
+        }
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize) {
+        let data = &self.buffers[indirect_buffer.0].data;
+        let size = std::mem::size_of::<DrawIndirectArgs>();
+        let draws: Vec<DrawRange> = data[offset..offset + draw_count * size]
+            .chunks(size)
+            .map(|args| {
+                let field =
+                    |i: usize| u32::from_le_bytes(args[i * 4..i * 4 + 4].try_into().unwrap());
+                DrawRange {
+                    num_elements: field(0) as i32,
+                    num_instances: field(1) as i32,
+                    base_element: field(2) as i32,
+                    base_vertex: field(3) as i32,
+                }
+            })
+            .collect();
+        self.multi_draw(&draws);
+    }
+
+    fn apply_compute_bindings(&mut self, _bindings: &ComputeBindings) {
+        panic!("compute is not supported by SoftwareContext")
+    }
+
+    fn dispatch(&mut self, _groups_x: u32, _groups_y: u32, _groups_z: u32) {
+        panic!("compute is not supported by SoftwareContext")
+    }
+
+    fn memory_barrier(&mut self, _barriers: &[MemoryBarrier]) {}
+}
+
+#[test]
+fn test_software_context_rasterization() {
+    let mut ctx = SoftwareContext::new(1, 1);
+
+    let shader = NativeShader::new(
+        1,
+        |input, varyings| {
+            let [x, y, z, _] = input.attributes[0];
+            varyings[0] = input.instance_id as f32;
+            [x, y, z, 1.]
+        },
+        |input, outputs| {
+            let alpha = unsafe { input.read_uniforms::<f32>() };
+            outputs[0] = [1. - input.varyings[0], input.varyings[0], 0., alpha];
+            true
+        },
+    );
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout {
+            uniforms: vec![UniformDesc::new("alpha", UniformType::Float1)],
+        },
+        uniform_blocks: vec![],
+        images: vec![],
+    };
+    let shader = ctx.new_shader(ShaderSource::Native(&shader), meta).unwrap();
+    let attributes = [VertexAttribute::new("in_pos", VertexFormat::Float3)];
+    let depth_pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &attributes,
+        shader,
+        PipelineParams {
+            depth_test: Comparison::Less,
+            depth_write: true,
+            ..Default::default()
+        },
+    );
+    let blend_pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &attributes,
+        shader,
+        PipelineParams {
+            color_blend: Some(BlendState::new(
+                Equation::Add,
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+            )),
+            ..Default::default()
+        },
+    );
+    // left and right halves of the target
+    #[rustfmt::skip]
+    let vertices: [f32; 24] = [
+        -1., -1., 0.,   0., -1., 0.,   0., 1., 0.,   -1., 1., 0.,
+         0., -1., 0.,   1., -1., 0.,   1., 1., 0.,    0., 1., 0.,
+    ];
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1, 2, 0, 2, 3]),
+    );
+
+    let color = ctx.new_render_texture(TextureParams {
+        width: 4,
+        height: 4,
+        ..Default::default()
+    });
+    let depth = ctx.new_render_texture(TextureParams {
+        width: 4,
+        height: 4,
+        format: TextureFormat::Depth,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(color, Some(depth));
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0., 0., 1., 1.));
+    ctx.apply_bindings_from_slice(&[vertex_buffer], Some(index_buffer), &[]);
+    ctx.apply_pipeline(&depth_pipeline);
+    ctx.apply_uniforms(UniformsSource::table(&1.0f32));
+    // the second, green, instance fails the depth test
+    ctx.draw(0, 6, 2);
+    ctx.apply_pipeline(&blend_pipeline);
+    ctx.apply_uniforms(UniformsSource::table(&0.5f32));
+    ctx.multi_draw(&[DrawRange {
+        base_element: 0,
+        num_elements: 6,
+        base_vertex: 4,
+        num_instances: 1,
+    }]);
+    ctx.end_render_pass();
+
+    let mut pixels = [0u8; 4 * 4 * 4];
+    ctx.texture_read_pixels(color, &mut pixels);
+    for row in pixels.chunks(4 * 4) {
+        assert_eq!(row[..8], [255, 0, 0, 255, 255, 0, 0, 255]);
+        assert_eq!(row[8..], [128, 0, 128, 191, 128, 0, 128, 191]);
+    }
+}
+
+#[test]
+fn test_software_context_samplers() {
+    let mut ctx = SoftwareContext::new(1, 1);
+
+    let shader = NativeShader::new(
+        0,
+        |input, _| input.attributes[0],
+        |input, outputs| {
+            outputs[0] = input.sample(0, [1.25, 0.5]);
+            true
+        },
+    );
+    let meta = ShaderMeta {
+        uniforms: UniformBlockLayout { uniforms: vec![] },
+        uniform_blocks: vec![],
+        images: vec!["tex".to_string()],
+    };
+    let (_, pipeline, vertex_buffer) =
+        new_fullscreen_triangle(&mut ctx, ShaderSource::Native(&shader), meta);
+    // red and green, clamped by the texture's own state
+    let texture = ctx.new_texture_from_rgba8(2, 1, &[255, 0, 0, 255, 0, 255, 0, 255]);
+    ctx.texture_set_filter(texture, FilterMode::Nearest, MipmapFilterMode::None);
+    let nearest = SamplerParams {
+        min_filter: FilterMode::Nearest,
+        mag_filter: FilterMode::Nearest,
+        ..Default::default()
+    };
+    let repeat = ctx.new_sampler(SamplerParams {
+        wrap_x: TextureWrap::Repeat,
+        ..nearest
+    });
+    let border = ctx.new_sampler(SamplerParams {
+        wrap_x: TextureWrap::ClampToBorder,
+        border_color: [0., 0., 1., 1.],
+        ..nearest
+    });
+
+    let mut draw = |sampler: Option<SamplerId>| {
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_with_samplers(&[vertex_buffer], None, &[texture], &[sampler]);
+        ctx.draw_arrays(0, 3, 1);
+        ctx.end_render_pass();
+        let mut pixels = [0; 4];
+        ctx.texture_read_pixels(ctx.default_framebuffer(), &mut pixels);
+        pixels
+    };
+    assert_eq!(draw(None), [0, 255, 0, 255]);
+    assert_eq!(draw(Some(repeat)), [255, 0, 0, 255]);
+    assert_eq!(draw(Some(border)), [0, 0, 255, 255]);
+}
+
+#[test]
+fn test_software_context_mip_levels() {
+    let mut ctx = SoftwareContext::new(1, 1);
+
+    let params = TextureParams {
+        width: 4,
+        height: 2,
+        ..Default::default()
+    };
+    let texture = ctx.new_texture_from_mips(&[&[1; 32], &[2; 8], &[3; 4]], params);
+    assert_eq!(ctx.texture_params(texture).mip_levels(), 3);
+    ctx.texture_update_mip_part(texture, 1, 0, 1, 0, 1, 1, &[4; 4]);
+    let mut pixels = [0; 8];
+    ctx.texture_read_mip_pixels(texture, 1, 0, &mut pixels);
+    assert_eq!(pixels, [2, 2, 2, 2, 4, 4, 4, 4]);
+    let mut pixels = [0; 4];
+    ctx.texture_read_mip_pixels(texture, 2, 0, &mut pixels);
+    assert_eq!(pixels, [3; 4]);
+
+    // clear a single face of the second level of a cubemap
+    let cubemap = ctx.new_render_texture(TextureParams {
+        kind: TextureKind::CubeMap,
+        width: 2,
+        height: 2,
+        allocate_mipmaps: true,
+        ..Default::default()
+    });
+    let target = RenderTarget {
+        texture: cubemap,
+        mip_level: 1,
+        layer: 3,
+    };
+    let pass = ctx.new_render_pass_targets(&[target], None, None);
+    ctx.begin_pass(Some(pass), PassAction::clear_color(1., 0., 0., 1.));
+    ctx.end_render_pass();
+    let mut pixels = [0; 4];
+    for face in 0..6 {
+        ctx.texture_read_mip_pixels(cubemap, 1, face, &mut pixels);
+        let expected = if face == 3 { [255, 0, 0, 255] } else { [0; 4] };
+        assert_eq!(pixels, expected);
+    }
+    let mut pixels = [0; 16];
+    ctx.texture_read_mip_pixels(cubemap, 0, 3, &mut pixels);
+    assert_eq!(pixels, [0; 16]);
+
+    let out_of_range = RenderTarget {
+        mip_level: 2,
+        ..target
+    };
+    assert!(ctx
+        .try_new_render_pass_targets(&[out_of_range], None, None)
+        .is_err());
+}
+
+#[test]
+fn test_software_context_shader_replace() {
+    let mut ctx = SoftwareContext::new(1, 1);
+
+    let solid = |color: [f32; 4]| {
+        NativeShader::new(
+            0,
+            |input, _| input.attributes[0],
+            move |_, outputs| {
+                outputs[0] = color;
+                true
+            },
+        )
+    };
+    let red = solid([1., 0., 0., 1.]);
+    let (shader, pipeline, vertex_buffer) =
+        new_fullscreen_triangle(&mut ctx, ShaderSource::Native(&red), ShaderMeta::default());
+    let draw = |ctx: &mut SoftwareContext| {
+        ctx.begin_default_pass(PassAction::clear_color(0., 0., 0., 0.));
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings_from_slice(&[vertex_buffer], None, &[]);
+        ctx.draw_arrays(0, 3, 1);
+        ctx.end_render_pass();
+        let mut pixel = [0; 4];
+        ctx.texture_read_pixels(ctx.default_framebuffer(), &mut pixel);
+        pixel
+    };
+    assert_eq!(draw(&mut ctx), [255, 0, 0, 255]);
+
+    let glsl = ShaderSource::Glsl {
+        vertex: "",
+        fragment: "",
+    };
+    let result = ctx.shader_replace(shader, glsl, ShaderMeta::default());
+    assert!(matches!(result, Err(ShaderError::CompilationError { .. })));
+    assert_eq!(draw(&mut ctx), [255, 0, 0, 255]);
+
+    let green = solid([0., 1., 0., 1.]);
+    ctx.shader_replace(shader, ShaderSource::Native(&green), ShaderMeta::default())
+        .unwrap();
+    assert_eq!(draw(&mut ctx), [0, 255, 0, 255]);
+}
@@ -0,0 +1,510 @@
+//! KTX2 and DDS texture containers.
+//!
+//! Only the headers are parsed, texture data is passed to the GPU as is.
+//! Supercompressed (BasisU, zstd) KTX2 files are not supported.
+
+use super::*;
+
+use std::convert::TryFrom;
+
+#[derive(Debug)]
+pub enum TextureContainerError {
+    /// Neither KTX2 nor DDS magic number.
+    UnknownContainer,
+    /// The file is shorter than its header says.
+    UnexpectedEof,
+    /// Pixel format without a matching `TextureFormat`.
+    UnsupportedFormat(String),
+    /// KTX2 supercompression scheme.
+    Supercompressed(u32),
+    /// 1D textures, cubemap arrays, cubemaps with missing faces.
+    UnsupportedLayout,
+    /// The texture data size does not fit in `usize`.
+    TooLarge,
+}
+
+impl Display for TextureContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownContainer => write!(f, "Not a KTX2 or DDS file"),
+            Self::UnexpectedEof => write!(f, "Unexpected end of file"),
+            Self::UnsupportedFormat(format) => write!(f, "Unsupported pixel format: {format}"),
+            Self::Supercompressed(scheme) => {
+                write!(f, "Unsupported KTX2 supercompression scheme: {scheme}")
+            }
+            Self::UnsupportedLayout => write!(f, "Unsupported texture layout"),
+            Self::TooLarge => write!(f, "Texture is too large"),
+        }
+    }
+}
+
+impl Error for TextureContainerError {}
+
+/// Texture parameters and data read from a container file.
+#[derive(Debug)]
+pub struct TextureData {
+    pub params: TextureParams,
+    /// `[cubemap_face][mipmap_level][bytes]`, the same layout as `TextureSource::Array`.
+    pub data: Vec<Vec<Vec<u8>>>,
+}
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+impl TextureData {
+    /// Parse either a KTX2 or a DDS file, detected by its magic number.
+    pub fn parse(bytes: &[u8]) -> Result<TextureData, TextureContainerError> {
+        if bytes.starts_with(&KTX2_MAGIC) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(&DDS_MAGIC) {
+            Self::from_dds(bytes)
+        } else {
+            Err(TextureContainerError::UnknownContainer)
+        }
+    }
+
+    /// Create a `TextureAccess::Static` texture out of the parsed data.
+    pub fn new_texture(&self, ctx: &mut dyn RenderingBackend) -> TextureId {
+        let levels: Vec<Vec<&[u8]>> = self
+            .data
+            .iter()
+            .map(|face| face.iter().map(|level| &level[..]).collect())
+            .collect();
+        let faces: Vec<&[&[u8]]> = levels.iter().map(|face| &face[..]).collect();
+        ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Array(&faces),
+            self.params,
+        )
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<TextureData, TextureContainerError> {
+        if !bytes.starts_with(&KTX2_MAGIC) {
+            return Err(TextureContainerError::UnknownContainer);
+        }
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let layers = read_u32(bytes, 32)?;
+        let faces = read_u32(bytes, 36)?;
+        // 0 means "generate mipmaps at runtime", only the base level is in the file
+        let levels = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+        if supercompression != 0 {
+            return Err(TextureContainerError::Supercompressed(supercompression));
+        }
+        let format = ktx2_format(vk_format).ok_or_else(|| {
+            TextureContainerError::UnsupportedFormat(format!("vkFormat {vk_format}"))
+        })?;
+
+        let kind = match (faces, layers, depth) {
+            (6, 0, 0) => TextureKind::CubeMap,
+            (1, 0, 0) => TextureKind::Texture2D,
+            (1, _, 0) => TextureKind::Texture2DArray,
+            (1, 0, _) => TextureKind::Texture3D,
+            _ => return Err(TextureContainerError::UnsupportedLayout),
+        };
+        if height == 0 {
+            return Err(TextureContainerError::UnsupportedLayout);
+        }
+        let params = container_params(kind, format, width, height, depth.max(layers), levels)?;
+
+        // level index follows the 80 bytes of header and section index
+        let mut data = vec![vec![]; faces as usize];
+        for level in 0..levels as usize {
+            let offset = read_usize(bytes, 80 + level * 24)?;
+            let length = read_usize(bytes, 80 + level * 24 + 8)?;
+            let level_data = read_bytes(bytes, offset, length)?;
+            // within a level, all the faces are stored one after another
+            let face_size = level_size(&params, level)?;
+            for (face, data) in data.iter_mut().enumerate() {
+                let face_offset = face
+                    .checked_mul(face_size)
+                    .ok_or(TextureContainerError::TooLarge)?;
+                data.push(read_bytes(level_data, face_offset, face_size)?.to_vec());
+            }
+        }
+
+        Ok(TextureData { params, data })
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<TextureData, TextureContainerError> {
+        const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+        const DDPF_FOURCC: u32 = 0x4;
+        const DDPF_RGB: u32 = 0x40;
+        const DDSCAPS2_CUBEMAP: u32 = 0x200;
+        const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+        const DDSCAPS2_VOLUME: u32 = 0x200000;
+        const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+        const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+        if !bytes.starts_with(&DDS_MAGIC) {
+            return Err(TextureContainerError::UnknownContainer);
+        }
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let mut depth = read_u32(bytes, 24)?.max(1);
+        let levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+            read_u32(bytes, 28)?.max(1)
+        } else {
+            1
+        };
+        let pixel_flags = read_u32(bytes, 80)?;
+        let four_cc = read_bytes(bytes, 84, 4)?;
+        let caps2 = read_u32(bytes, 112)?;
+
+        let dx10 = pixel_flags & DDPF_FOURCC != 0 && four_cc == b"DX10";
+        let mut cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+        let mut volume = caps2 & DDSCAPS2_VOLUME != 0;
+        let mut array_size = 1;
+        let mut data_offset = 128;
+
+        if cubemap && !dx10 && caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+            return Err(TextureContainerError::UnsupportedLayout);
+        }
+
+        let format = if dx10 {
+            let dxgi_format = read_u32(bytes, 128)?;
+            let dimension = read_u32(bytes, 132)?;
+            let misc_flags = read_u32(bytes, 136)?;
+            array_size = read_u32(bytes, 140)?.max(1);
+            cubemap = misc_flags & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+            volume = dimension == DDS_DIMENSION_TEXTURE3D;
+            data_offset += 20;
+            dxgi_format_to_texture_format(dxgi_format).ok_or_else(|| {
+                TextureContainerError::UnsupportedFormat(format!("DXGI format {dxgi_format}"))
+            })?
+        } else if pixel_flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => TextureFormat::BC1,
+                b"DXT3" => TextureFormat::BC2,
+                b"DXT5" => TextureFormat::BC3,
+                b"ATI1" | b"BC4U" => TextureFormat::BC4,
+                b"ATI2" | b"BC5U" => TextureFormat::BC5,
+                _ => {
+                    return Err(TextureContainerError::UnsupportedFormat(format!(
+                        "FourCC {}",
+                        String::from_utf8_lossy(four_cc)
+                    )))
+                }
+            }
+        } else if pixel_flags & DDPF_RGB != 0 {
+            let bit_count = read_u32(bytes, 88)?;
+            let masks = [
+                read_u32(bytes, 92)?,
+                read_u32(bytes, 96)?,
+                read_u32(bytes, 100)?,
+                read_u32(bytes, 104)?,
+            ];
+            match (bit_count, masks) {
+                (32, [0xff, 0xff00, 0xff0000, 0xff000000]) => TextureFormat::RGBA8,
+                (24, [0xff, 0xff00, 0xff0000, 0]) => TextureFormat::RGB8,
+                _ => {
+                    return Err(TextureContainerError::UnsupportedFormat(format!(
+                        "{bit_count} bit RGB with {masks:x?} masks"
+                    )))
+                }
+            }
+        } else {
+            return Err(TextureContainerError::UnsupportedFormat(format!(
+                "pixel format flags {pixel_flags:#x}"
+            )));
+        };
+
+        if cubemap && array_size > 1 {
+            return Err(TextureContainerError::UnsupportedLayout);
+        }
+        let kind = if cubemap {
+            TextureKind::CubeMap
+        } else if volume {
+            TextureKind::Texture3D
+        } else if array_size > 1 {
+            depth = array_size;
+            TextureKind::Texture2DArray
+        } else {
+            depth = 1;
+            TextureKind::Texture2D
+        };
+        let params = container_params(kind, format, width, height, depth, levels)?;
+
+        // DDS stores the whole mip chain of every array element (or cubemap face)
+        // one after another, each level of a volume texture has all its slices
+        let images = match kind {
+            TextureKind::CubeMap => 6,
+            TextureKind::Texture2DArray => array_size as usize,
+            _ => 1,
+        };
+        let layer_params = TextureParams { depth: 1, ..params };
+        let mut offset = data_offset;
+        let mut chains = vec![];
+        for _ in 0..images {
+            let mut chain = vec![];
+            for level in 0..levels as usize {
+                let size = match kind {
+                    TextureKind::Texture2DArray => level_size(&layer_params, level)?,
+                    _ => level_size(&params, level)?,
+                };
+                chain.push(read_bytes(bytes, offset, size)?.to_vec());
+                // read_bytes made sure it is within `bytes`
+                offset += size;
+            }
+            chains.push(chain);
+        }
+
+        let data = match kind {
+            TextureKind::Texture2DArray => {
+                // all the layers of a level go together in TextureSource::Array
+                let levels = (0..levels as usize)
+                    .map(|level| {
+                        chains
+                            .iter()
+                            .flat_map(|chain| chain[level].iter().copied())
+                            .collect()
+                    })
+                    .collect();
+                vec![levels]
+            }
+            _ => chains,
+        };
+
+        Ok(TextureData { params, data })
+    }
+}
+
+fn container_params(
+    kind: TextureKind,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+    levels: u32,
+) -> Result<TextureParams, TextureContainerError> {
+    // more levels than the chain down to 1x1 has
+    let size = match kind {
+        TextureKind::Texture3D => width.max(height).max(depth),
+        _ => width.max(height),
+    };
+    if levels > 32 - size.leading_zeros() {
+        return Err(TextureContainerError::UnsupportedLayout);
+    }
+    Ok(TextureParams {
+        kind,
+        format,
+        width,
+        height,
+        depth: match kind {
+            TextureKind::Texture2DArray | TextureKind::Texture3D => depth.max(1),
+            _ => 1,
+        },
+        allocate_mipmaps: levels > 1,
+        mipmap_filter: if levels > 1 {
+            MipmapFilterMode::Linear
+        } else {
+            MipmapFilterMode::None
+        },
+        ..Default::default()
+    })
+}
+
+/// Size of a single cubemap face, or of all the layers of a layered texture.
+fn level_size(params: &TextureParams, level: usize) -> Result<usize, TextureContainerError> {
+    let width = (params.width >> level).max(1);
+    let height = (params.height >> level).max(1);
+    let depth = match params.kind {
+        TextureKind::Texture3D => (params.depth >> level).max(1),
+        _ => params.depth,
+    };
+    params
+        .format
+        .checked_size(width, height)
+        .and_then(|size| size.checked_mul(depth as u64))
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or(TextureContainerError::TooLarge)
+}
+
+fn read_bytes(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], TextureContainerError> {
+    let end = offset
+        .checked_add(len)
+        .ok_or(TextureContainerError::UnexpectedEof)?;
+    bytes
+        .get(offset..end)
+        .ok_or(TextureContainerError::UnexpectedEof)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, TextureContainerError> {
+    let bytes = read_bytes(bytes, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, TextureContainerError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+/// An offset or a length, these could not be larger than the file anyway.
+fn read_usize(bytes: &[u8], offset: usize) -> Result<usize, TextureContainerError> {
+    usize::try_from(read_u64(bytes, offset)?).map_err(|_| TextureContainerError::UnexpectedEof)
+}
+
+fn ktx2_format(vk_format: u32) -> Option<TextureFormat> {
+    use TextureFormat::*;
+
+    Some(match vk_format {
+        9 => R8,
+        16 => RG8,
+        23 => RGB8,
+        37 => RGBA8,
+        43 => SRGB8A8,
+        64 => RGB10A2,
+        76 => R16F,
+        83 => RG16F,
+        98 => R32UI,
+        100 => R32F,
+        109 => RGBA32F,
+        124 => Depth,
+        126 => Depth32,
+        129 => Depth24Stencil8,
+        131 | 133 => BC1,
+        135 => BC2,
+        137 => BC3,
+        139 => BC4,
+        141 => BC5,
+        143 => BC6H,
+        145 => BC7,
+        147 => ETC2RGB8,
+        151 => ETC2RGBA8,
+        153 => EACR11,
+        155 => EACRG11,
+        157 => ASTC4x4,
+        161 => ASTC5x5,
+        165 => ASTC6x6,
+        171 => ASTC8x8,
+        179 => ASTC10x10,
+        183 => ASTC12x12,
+        _ => return None,
+    })
+}
+
+fn dxgi_format_to_texture_format(dxgi_format: u32) -> Option<TextureFormat> {
+    use TextureFormat::*;
+
+    Some(match dxgi_format {
+        2 => RGBA32F,
+        24 => RGB10A2,
+        28 => RGBA8,
+        29 => SRGB8A8,
+        34 => RG16F,
+        41 => R32F,
+        42 => R32UI,
+        45 => Depth24Stencil8,
+        49 => RG8,
+        54 => R16F,
+        61 => R8,
+        71 => BC1,
+        74 => BC2,
+        77 => BC3,
+        80 => BC4,
+        83 => BC5,
+        95 => BC6H,
+        98 => BC7,
+        _ => return None,
+    })
+}
+
+#[test]
+fn test_texture_containers() {
+    // 16x16 DXT1 with 3 mipmap levels: 128, 32 and 8 bytes
+    let mut dds = vec![0u8; 128];
+    dds[0..4].copy_from_slice(b"DDS ");
+    dds[4..8].copy_from_slice(&124u32.to_le_bytes());
+    dds[8..12].copy_from_slice(&0x20000u32.to_le_bytes());
+    dds[12..16].copy_from_slice(&16u32.to_le_bytes());
+    dds[16..20].copy_from_slice(&16u32.to_le_bytes());
+    dds[28..32].copy_from_slice(&3u32.to_le_bytes());
+    dds[80..84].copy_from_slice(&0x4u32.to_le_bytes());
+    dds[84..88].copy_from_slice(b"DXT1");
+    dds.extend((0..128 + 32 + 8).map(|i| i as u8));
+
+    let texture = TextureData::parse(&dds).unwrap();
+    assert_eq!(texture.params.format, TextureFormat::BC1);
+    assert_eq!(texture.params.kind, TextureKind::Texture2D);
+    assert!(texture.params.allocate_mipmaps);
+    let lengths: Vec<usize> = texture.data[0].iter().map(|level| level.len()).collect();
+    assert_eq!(lengths, [128, 32, 8]);
+    assert_eq!(texture.data[0][1][0], 128);
+
+    // truncated data
+    assert!(matches!(
+        TextureData::parse(&dds[..200]),
+        Err(TextureContainerError::UnexpectedEof)
+    ));
+
+    // 33 levels of a 16x16 texture
+    let mut malformed = dds.clone();
+    malformed[28..32].copy_from_slice(&33u32.to_le_bytes());
+    assert!(matches!(
+        TextureData::parse(&malformed),
+        Err(TextureContainerError::UnsupportedLayout)
+    ));
+
+    // 16 GiB of RGBA8 in a header only file, then more than u64 could hold
+    let mut huge = dds[..128].to_vec();
+    huge[8..12].copy_from_slice(&0u32.to_le_bytes());
+    huge[80..84].copy_from_slice(&0x40u32.to_le_bytes());
+    for (offset, value) in [
+        (88, 32),
+        (92, 0xff),
+        (96, 0xff00),
+        (100, 0xff0000),
+        (104, 0xff000000u32),
+    ] {
+        huge[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    huge[12..20].copy_from_slice(&[0, 0, 1, 0, 0, 0, 1, 0]);
+    assert!(matches!(
+        TextureData::parse(&huge),
+        Err(TextureContainerError::UnexpectedEof)
+    ));
+    huge[12..20].copy_from_slice(&[0xff; 8]);
+    assert!(matches!(
+        TextureData::parse(&huge),
+        Err(TextureContainerError::TooLarge)
+    ));
+
+    // 2x2 RGBA8 cubemap, 2 levels
+    let mut ktx2 = vec![0u8; 80 + 2 * 24];
+    ktx2[0..12].copy_from_slice(&KTX2_MAGIC);
+    for (offset, value) in [(12, 37), (16, 1), (20, 2), (24, 2), (36, 6), (40, 2)] {
+        ktx2[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+    }
+    let levels = [(2 * 2 * 4 * 6) as u64, (4 * 6) as u64];
+    let mut offset = ktx2.len() as u64;
+    for (level, length) in levels.iter().enumerate() {
+        let index = 80 + level * 24;
+        ktx2[index..index + 8].copy_from_slice(&offset.to_le_bytes());
+        ktx2[index + 8..index + 16].copy_from_slice(&length.to_le_bytes());
+        offset += length;
+    }
+    for length in levels {
+        ktx2.extend((0..length).map(|i| i as u8));
+    }
+
+    let texture = TextureData::parse(&ktx2).unwrap();
+    assert_eq!(texture.params.kind, TextureKind::CubeMap);
+    assert_eq!(texture.params.format, TextureFormat::RGBA8);
+    assert_eq!(texture.data.len(), 6);
+    assert_eq!(texture.data[1][0].len(), 16);
+    assert_eq!(texture.data[1][0][0], 16);
+    assert_eq!(texture.data[5][1], [20, 21, 22, 23]);
+
+    // level index pointing past the end of the address space
+    let mut malformed = ktx2.clone();
+    malformed[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        TextureData::parse(&malformed),
+        Err(TextureContainerError::UnexpectedEof)
+    ));
+}
@@ -0,0 +1,1908 @@
+//! Debug validation layer, see `ValidatingBackend`.
+
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+
+struct ShaderState {
+    compute: bool,
+    images: usize,
+    uniforms_size: usize,
+    // name and the smallest accepted byte size of each block
+    uniform_blocks: Vec<(String, usize)>,
+}
+
+struct PipelineState {
+    shader: ShaderId,
+    compute: bool,
+    // each buffer referenced by the attributes
+    buffers: Vec<VertexBufferState>,
+}
+
+struct VertexBufferState {
+    stride: usize,
+    // end of the last attribute within a vertex, could be less than the stride
+    attributes_end: usize,
+    step_func: VertexStep,
+    step_rate: i32,
+}
+
+struct BufferState {
+    type_: BufferType,
+    usage: BufferUsage,
+    size: usize,
+    element_size: usize,
+}
+
+struct PassState {
+    color: Vec<TextureId>,
+    resolve: Vec<TextureId>,
+    depth: Option<TextureId>,
+}
+
+impl PassState {
+    fn new(
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> PassState {
+        PassState {
+            color: color_img.iter().map(|target| target.texture).collect(),
+            resolve: resolve_img.unwrap_or(&[]).to_vec(),
+            depth: depth_img.map(|target| target.texture),
+        }
+    }
+
+    fn attachments(&self) -> impl Iterator<Item = TextureId> + '_ {
+        self.color
+            .iter()
+            .chain(&self.resolve)
+            .chain(self.depth.iter())
+            .copied()
+    }
+}
+
+struct QueryState {
+    kind: QueryKind,
+    active: bool,
+}
+
+/// Wraps any `RenderingBackend` and checks every call against the tracked
+/// shader, pipeline, buffer and pass state before forwarding it.
+///
+/// Meant for debug builds: the backends themselves trust the caller and
+/// a wrong call usually ends in a GL error, a driver crash or an index
+/// out of bounds panic deep inside the backend. `ValidatingBackend` reports
+/// the offending call with the ids involved instead, and costs nothing
+/// when the backend is not wrapped.
+///
+/// ```ignore
+/// let ctx = window::new_rendering_backend();
+/// #[cfg(debug_assertions)]
+/// let ctx = Box::new(ValidatingBackend::new(ctx));
+/// ```
+///
+/// Only the objects created through the wrapper are known to it,
+/// textures made with `TextureId::from_raw_id` are never checked.
+pub struct ValidatingBackend<B: RenderingBackend + ?Sized = dyn RenderingBackend> {
+    backend: Box<B>,
+    report: Box<dyn Fn(&str)>,
+    info: ContextInfo,
+    shaders: HashMap<ShaderId, ShaderState>,
+    pipelines: HashMap<Pipeline, PipelineState>,
+    buffers: HashMap<BufferId, BufferState>,
+    textures: HashSet<TextureId>,
+    samplers: HashSet<SamplerId>,
+    passes: HashMap<RenderPass, PassState>,
+    queries: HashMap<QueryId, QueryState>,
+    readbacks: HashSet<ReadbackId>,
+    fences: HashSet<FenceId>,
+    // Some(None) is the default pass
+    cur_pass: Option<Option<RenderPass>>,
+    cur_pipeline: Option<Pipeline>,
+    cur_bindings: Option<(Vec<BufferId>, Option<BufferId>)>,
+    cur_compute_bindings: bool,
+}
+
+impl<B: RenderingBackend + ?Sized> ValidatingBackend<B> {
+    /// Panics with a description of the first invalid call.
+    pub fn new(backend: Box<B>) -> ValidatingBackend<B> {
+        Self::with_report(backend, |error| panic!("{}", error))
+    }
+
+    /// Calls `report` for each invalid call and forwards the call anyway,
+    /// the wrapped backend may still panic on it.
+    pub fn with_report(backend: Box<B>, report: impl Fn(&str) + 'static) -> ValidatingBackend<B> {
+        let info = backend.info();
+        ValidatingBackend {
+            backend,
+            report: Box::new(report),
+            info,
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            buffers: HashMap::new(),
+            textures: HashSet::new(),
+            samplers: HashSet::new(),
+            passes: HashMap::new(),
+            queries: HashMap::new(),
+            readbacks: HashSet::new(),
+            fences: HashSet::new(),
+            cur_pass: None,
+            cur_pipeline: None,
+            cur_bindings: None,
+            cur_compute_bindings: false,
+        }
+    }
+
+    pub fn into_inner(self) -> Box<B> {
+        self.backend
+    }
+
+    fn error(&self, method: &str, message: String) {
+        (self.report)(&format!("{}: {}", method, message));
+    }
+
+    fn check_shader(&self, method: &str, compute: bool, meta: &ShaderMeta) {
+        if compute && !self.info.features.compute {
+            self.error(
+                method,
+                "compute shader without features.compute".to_string(),
+            );
+        }
+        if meta.images.len() > MAX_SHADERSTAGE_IMAGES {
+            self.error(
+                method,
+                format!(
+                    "{} images, MAX_SHADERSTAGE_IMAGES is {}",
+                    meta.images.len(),
+                    MAX_SHADERSTAGE_IMAGES
+                ),
+            );
+        }
+        if meta.uniform_blocks.len() > MAX_UNIFORM_BLOCKS {
+            self.error(
+                method,
+                format!(
+                    "{} uniform blocks, MAX_UNIFORM_BLOCKS is {}",
+                    meta.uniform_blocks.len(),
+                    MAX_UNIFORM_BLOCKS
+                ),
+            );
+        }
+    }
+
+    fn check_texture(&self, method: &str, texture: TextureId) -> bool {
+        if let TextureIdInner::Raw(_) = texture.0 {
+            return true;
+        }
+        if !self.textures.contains(&texture) {
+            self.error(method, format!("{:?} was deleted or is unknown", texture));
+            return false;
+        }
+        true
+    }
+
+    fn untrack_attachments(&mut self, pass: &PassState) {
+        for texture in pass.attachments() {
+            self.textures.remove(&texture);
+        }
+    }
+
+    fn check_wrap(&self, method: &str, wrap: TextureWrap) {
+        if let Err(e) = self.info.check_wrap(wrap) {
+            self.error(method, e.to_string());
+        }
+    }
+
+    fn check_buffer(&self, method: &str, buffer: BufferId) -> Option<&BufferState> {
+        let state = self.buffers.get(&buffer);
+        if state.is_none() {
+            self.error(method, format!("{:?} was deleted or is unknown", buffer));
+        }
+        state
+    }
+
+    fn check_buffer_range(&self, method: &str, buffer: BufferId, offset: usize, size: usize) {
+        if let Some(state) = self.check_buffer(method, buffer) {
+            let end = offset.checked_add(size);
+            if end.map_or(true, |end| end > state.size) {
+                self.error(
+                    method,
+                    format!(
+                        "{} bytes at {} are outside of {:?} of {} bytes",
+                        size, offset, buffer, state.size
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_texture_rect(
+        &self,
+        method: &str,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        (x, y, w, h): (i32, i32, i32, i32),
+    ) -> Option<TextureParams> {
+        if !self.check_texture(method, texture) {
+            return None;
+        }
+        let params = self.backend.texture_params(texture);
+        if mip_level >= params.mip_levels() {
+            self.error(
+                method,
+                format!(
+                    "mip level {} of {:?} with {} levels",
+                    mip_level,
+                    texture,
+                    params.mip_levels()
+                ),
+            );
+            return None;
+        }
+        let layers = params.mip_layers(mip_level);
+        if layer >= layers {
+            self.error(
+                method,
+                format!("layer {} of {:?} with {} layers", layer, texture, layers),
+            );
+        }
+        let (width, height) = params.mip_size(mip_level);
+        if x < 0 || y < 0 || x + w > width as i32 || y + h > height as i32 {
+            self.error(
+                method,
+                format!(
+                    "rect ({}, {}, {}, {}) is outside of mip level {} of {:?} of {}x{}",
+                    x, y, w, h, mip_level, texture, width, height
+                ),
+            );
+        }
+        Some(params)
+    }
+
+    fn check_begin_pass(&mut self, method: &str, pass: Option<RenderPass>) {
+        if let Some(cur_pass) = self.cur_pass {
+            self.error(method, format!("{} was not ended", pass_name(cur_pass)));
+        }
+        if let Some(pass) = pass {
+            match self.passes.get(&pass) {
+                None => self.error(method, format!("{:?} was deleted or is unknown", pass)),
+                Some(state) => {
+                    for texture in state.attachments() {
+                        if let TextureIdInner::Managed(_) = texture.0 {
+                            if !self.textures.contains(&texture) {
+                                self.error(
+                                    method,
+                                    format!("{:?} attachment {:?} was deleted", pass, texture),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.cur_pass = Some(pass);
+        self.cur_pipeline = None;
+        self.cur_bindings = None;
+    }
+
+    // The pipeline and its bindings, if everything required for a draw call is in place.
+    fn check_draw(&self, method: &str) -> Option<(&PipelineState, &[BufferId], Option<BufferId>)> {
+        if self.cur_pass.is_none() {
+            self.error(method, "called outside of a render pass".to_string());
+        }
+        let Some(pipeline) = self.cur_pipeline else {
+            self.error(method, "no pipeline applied".to_string());
+            return None;
+        };
+        let Some(state) = self.pipelines.get(&pipeline) else {
+            self.error(method, format!("{:?} was deleted or is unknown", pipeline));
+            return None;
+        };
+        if state.compute {
+            self.error(method, format!("{:?} is a compute pipeline", pipeline));
+            return None;
+        }
+        let Some((vertex_buffers, index_buffer)) = &self.cur_bindings else {
+            self.error(
+                method,
+                format!("no bindings applied after apply_pipeline({:?})", pipeline),
+            );
+            return None;
+        };
+        Some((state, vertex_buffers, *index_buffer))
+    }
+
+    fn check_indices(&self, method: &str, index_buffer: Option<BufferId>, first: i32, count: i32) {
+        let Some(index_buffer) = index_buffer else {
+            self.error(method, "no index buffer bound".to_string());
+            return;
+        };
+        if first < 0 || count < 0 {
+            self.error(
+                method,
+                format!("negative index range: {} indices from {}", count, first),
+            );
+            return;
+        }
+        if let Some(state) = self.buffers.get(&index_buffer) {
+            let indices = state.size / state.element_size.max(1);
+            let end = (first as usize).checked_add(count as usize);
+            if end.map_or(true, |end| end > indices) {
+                self.error(
+                    method,
+                    format!(
+                        "{} indices from {} are outside of {:?} with {} indices",
+                        count, first, index_buffer, indices
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_vertices(
+        &self,
+        method: &str,
+        pipeline: &PipelineState,
+        vertex_buffers: &[BufferId],
+        vertices: Option<(i32, i32)>,
+        instances: i32,
+    ) {
+        if instances > 1 && !self.info.features.instancing {
+            self.error(
+                method,
+                format!("{} instances without features.instancing", instances),
+            );
+        }
+        for (n, layout) in pipeline.buffers.iter().enumerate() {
+            let Some(state) = vertex_buffers.get(n).and_then(|b| self.buffers.get(b)) else {
+                continue;
+            };
+            // index of the last element read, both ranges are not negative here
+            let (kind, last) = match layout.step_func {
+                VertexStep::PerVertex => match vertices {
+                    Some((_, 0)) | None => continue,
+                    Some((first, count)) => ("vertex", first as usize + (count as usize - 1)),
+                },
+                VertexStep::PerInstance if instances <= 0 => continue,
+                VertexStep::PerInstance => {
+                    let step_rate = layout.step_rate.max(1);
+                    ("instance", ((instances - 1) / step_rate) as usize)
+                }
+            };
+            let end = last
+                .checked_mul(layout.stride)
+                .and_then(|offset| offset.checked_add(layout.attributes_end));
+            if end.map_or(true, |end| end > state.size) {
+                self.error(
+                    method,
+                    format!(
+                        "{} {} is outside of {:?} of {} bytes with stride {}",
+                        kind, last, vertex_buffers[n], state.size, layout.stride
+                    ),
+                );
+            }
+        }
+    }
+}
+
+impl<B: RenderingBackend + ?Sized> RenderingBackend for ValidatingBackend<B> {
+    fn info(&self) -> ContextInfo {
+        self.backend.info()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let compute = matches!(shader, ShaderSource::GlslCompute { .. });
+        self.check_shader("new_shader", compute, &meta);
+        let state = shader_state(compute, &meta);
+        let id = self.backend.new_shader(shader, meta)?;
+        self.shaders.insert(id, state);
+        Ok(id)
+    }
+
+    fn new_shader_reflected(
+        &mut self,
+        shader: ShaderSource,
+    ) -> Result<(ShaderId, ShaderMeta), ShaderError> {
+        let (id, meta) = self.backend.new_shader_reflected(shader)?;
+        let compute = matches!(shader, ShaderSource::GlslCompute { .. });
+        self.shaders.insert(id, shader_state(compute, &meta));
+        Ok((id, meta))
+    }
+
+    fn shader_replace(
+        &mut self,
+        shader: ShaderId,
+        source: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<(), ShaderError> {
+        const METHOD: &str = "shader_replace";
+        let compute = matches!(source, ShaderSource::GlslCompute { .. });
+        match self.shaders.get(&shader) {
+            None => self.error(METHOD, format!("{:?} was deleted or is unknown", shader)),
+            // pipelines made with the shader would not fit the new one
+            Some(state) if state.compute != compute => self.error(
+                METHOD,
+                format!(
+                    "{:?} is a compute shader, the new one is not or vice versa",
+                    shader
+                ),
+            ),
+            Some(_) => {}
+        }
+        self.check_shader(METHOD, compute, &meta);
+        let state = shader_state(compute, &meta);
+        self.backend.shader_replace(shader, source, meta)?;
+        self.shaders.insert(shader, state);
+        self.cur_pipeline = None;
+        Ok(())
+    }
+
+    fn shader_reflection(&self, shader: ShaderId) -> Option<&ShaderReflection> {
+        self.backend.shader_reflection(shader)
+    }
+
+    fn new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> TextureId {
+        if matches!(
+            params.kind,
+            TextureKind::Texture2DArray | TextureKind::Texture3D
+        ) && !self.info.features.texture_3d
+        {
+            self.error(
+                "new_texture",
+                format!("{:?} without features.texture_3d", params.kind),
+            );
+        }
+        if params.width == 0 || params.height == 0 {
+            self.error(
+                "new_texture",
+                format!("empty {}x{} texture", params.width, params.height),
+            );
+        }
+        self.check_wrap("new_texture", params.wrap);
+        let texture = self.backend.new_texture(access, data, params);
+        self.textures.insert(texture);
+        texture
+    }
+
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, GraphicsError> {
+        let texture = self.backend.try_new_texture(access, data, params)?;
+        self.textures.insert(texture);
+        Ok(texture)
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.check_texture("texture_params", texture);
+        self.backend.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.check_texture("texture_raw_id", texture);
+        self.backend.texture_raw_id(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        self.check_texture("texture_set_min_filter", texture);
+        self.backend
+            .texture_set_min_filter(texture, filter, mipmap_filter)
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.check_texture("texture_set_mag_filter", texture);
+        self.backend.texture_set_mag_filter(texture, filter)
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        self.check_texture("texture_set_wrap", texture);
+        self.check_wrap("texture_set_wrap", wrap_x);
+        self.check_wrap("texture_set_wrap", wrap_y);
+        self.backend.texture_set_wrap(texture, wrap_x, wrap_y)
+    }
+
+    fn new_sampler(&mut self, params: SamplerParams) -> SamplerId {
+        if let Err(e) = self.info.check_sampler_params(&params) {
+            self.error("new_sampler", e.to_string());
+        }
+        if params.min_lod > params.max_lod {
+            self.error(
+                "new_sampler",
+                format!(
+                    "min_lod {} above max_lod {}",
+                    params.min_lod, params.max_lod
+                ),
+            );
+        }
+        let sampler = self.backend.new_sampler(params);
+        self.samplers.insert(sampler);
+        sampler
+    }
+
+    fn try_new_sampler(&mut self, params: SamplerParams) -> Result<SamplerId, GraphicsError> {
+        let sampler = self.backend.try_new_sampler(params)?;
+        self.samplers.insert(sampler);
+        Ok(sampler)
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        if self.check_texture("texture_generate_mipmaps", texture)
+            && !self.backend.texture_params(texture).allocate_mipmaps
+        {
+            self.error(
+                "texture_generate_mipmaps",
+                format!("{:?} was created without allocate_mipmaps", texture),
+            );
+        }
+        self.backend.texture_generate_mipmaps(texture)
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        if self.check_texture("texture_resize", texture) {
+            let format = self.backend.texture_params(texture).format;
+            match bytes {
+                Some(bytes) if bytes.len() != format.size(width, height) as usize => self.error(
+                    "texture_resize",
+                    format!(
+                        "{} bytes for {}x{} {:?} texture {:?}, expected {}",
+                        bytes.len(),
+                        width,
+                        height,
+                        format,
+                        texture,
+                        format.size(width, height)
+                    ),
+                ),
+                _ => {}
+            }
+        }
+        self.backend.texture_resize(texture, width, height, bytes)
+    }
+
+    fn texture_read_mip_pixels(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        bytes: &mut [u8],
+    ) {
+        if let Some(params) = self.check_texture_rect(
+            "texture_read_mip_pixels",
+            texture,
+            mip_level,
+            layer,
+            (0, 0, 0, 0),
+        ) {
+            let (width, height) = params.mip_size(mip_level);
+            let size = params.format.size(width, height) as usize;
+            if bytes.len() < size {
+                self.error(
+                    "texture_read_mip_pixels",
+                    format!(
+                        "{} bytes for {}x{} {:?} mip level {} of {:?}, expected {}",
+                        bytes.len(),
+                        width,
+                        height,
+                        params.format,
+                        mip_level,
+                        texture,
+                        size
+                    ),
+                );
+            }
+        }
+        self.backend
+            .texture_read_mip_pixels(texture, mip_level, layer, bytes)
+    }
+
+    fn texture_read_pixels_async(
+        &mut self,
+        texture: TextureId,
+        rect: (u32, u32, u32, u32),
+    ) -> ReadbackId {
+        let (x, y, w, h) = rect;
+        self.check_texture_rect(
+            "texture_read_pixels_async",
+            texture,
+            0,
+            0,
+            (x as _, y as _, w as _, h as _),
+        );
+        let readback = self.backend.texture_read_pixels_async(texture, rect);
+        self.readbacks.insert(readback);
+        readback
+    }
+
+    fn poll_readback(&mut self, readback: ReadbackId) -> bool {
+        if !self.readbacks.contains(&readback) {
+            self.error(
+                "poll_readback",
+                format!("{:?} was already taken or is unknown", readback),
+            );
+        }
+        self.backend.poll_readback(readback)
+    }
+
+    fn take_readback(&mut self, readback: ReadbackId) -> Option<Vec<u8>> {
+        if !self.readbacks.contains(&readback) {
+            self.error(
+                "take_readback",
+                format!("{:?} was already taken or is unknown", readback),
+            );
+        }
+        let data = self.backend.take_readback(readback);
+        if data.is_some() {
+            self.readbacks.remove(&readback);
+        }
+        data
+    }
+
+    fn texture_update_mip_part(
+        &mut self,
+        texture: TextureId,
+        mip_level: u32,
+        layer: u32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        if let Some(params) = self.check_texture_rect(
+            "texture_update_mip_part",
+            texture,
+            mip_level,
+            layer,
+            (x_offset, y_offset, width, height),
+        ) {
+            let size = params.format.size(width as _, height as _) as usize;
+            if bytes.len() != size {
+                self.error(
+                    "texture_update_mip_part",
+                    format!(
+                        "{} bytes for {}x{} {:?} rect of {:?}, expected {}",
+                        bytes.len(),
+                        width,
+                        height,
+                        params.format,
+                        texture,
+                        size
+                    ),
+                );
+            }
+        }
+        self.backend.texture_update_mip_part(
+            texture, mip_level, layer, x_offset, y_offset, width, height, bytes,
+        )
+    }
+
+    fn new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> RenderPass {
+        if color_img.len() > MAX_COLOR_ATTACHMENTS {
+            self.error(
+                "new_render_pass_targets",
+                format!(
+                    "{} color attachments, MAX_COLOR_ATTACHMENTS is {}",
+                    color_img.len(),
+                    MAX_COLOR_ATTACHMENTS
+                ),
+            );
+        }
+        if color_img.is_empty() && depth_img.is_none() {
+            self.error("new_render_pass_targets", "no attachments".to_string());
+        }
+        if resolve_img.is_some() && !self.info.features.resolve_attachments {
+            self.error(
+                "new_render_pass_targets",
+                "resolve_img without features.resolve_attachments".to_string(),
+            );
+        }
+        let resolves = resolve_img.into_iter().flatten().map(|&img| img.into());
+        let attachments = color_img.iter().copied().chain(resolves).chain(depth_img);
+        let mut size = None;
+        for target in attachments {
+            let texture = target.texture;
+            if !self.check_texture("new_render_pass_targets", texture) {
+                continue;
+            }
+            let params = self.backend.texture_params(texture);
+            if target.mip_level >= params.mip_levels()
+                || target.layer >= params.mip_layers(target.mip_level)
+            {
+                self.error(
+                    "new_render_pass_targets",
+                    format!("{:?} is out of range of the texture", target),
+                );
+                continue;
+            }
+            let (w, h) = params.mip_size(target.mip_level);
+            match size {
+                None => size = Some((w, h)),
+                Some((width, height)) if (width, height) != (w, h) => self.error(
+                    "new_render_pass_targets",
+                    format!(
+                        "{:?} is {}x{}, other attachments are {}x{}",
+                        target, w, h, width, height
+                    ),
+                ),
+                _ => {}
+            }
+        }
+        if let Some(RenderTarget { texture: depth, .. }) = depth_img {
+            if self.check_texture("new_render_pass_targets", depth)
+                && !matches!(
+                    self.backend.texture_params(depth).format,
+                    TextureFormat::Depth | TextureFormat::Depth32 | TextureFormat::Depth24Stencil8
+                )
+            {
+                self.error(
+                    "new_render_pass_targets",
+                    format!("depth_img {:?} is not a depth texture", depth),
+                );
+            }
+        }
+        let pass = self
+            .backend
+            .new_render_pass_targets(color_img, resolve_img, depth_img);
+        self.passes
+            .insert(pass, PassState::new(color_img, resolve_img, depth_img));
+        pass
+    }
+
+    fn try_new_render_pass_targets(
+        &mut self,
+        color_img: &[RenderTarget],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<RenderTarget>,
+    ) -> Result<RenderPass, GraphicsError> {
+        let pass = self
+            .backend
+            .try_new_render_pass_targets(color_img, resolve_img, depth_img)?;
+        self.passes
+            .insert(pass, PassState::new(color_img, resolve_img, depth_img));
+        Ok(pass)
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        if !self.passes.contains_key(&render_pass) {
+            self.error(
+                "render_pass_color_attachments",
+                format!("{:?} was deleted or is unknown", render_pass),
+            );
+        }
+        self.backend.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        match self.passes.remove(&render_pass) {
+            // the backend deletes the attachments along with the pass
+            Some(state) => self.untrack_attachments(&state),
+            None => self.error(
+                "delete_render_pass",
+                format!("{:?} was deleted or is unknown", render_pass),
+            ),
+        }
+        if self.cur_pass == Some(Some(render_pass)) {
+            self.error(
+                "delete_render_pass",
+                format!("{:?} is the current render pass", render_pass),
+            );
+        }
+        self.backend.delete_render_pass(render_pass)
+    }
+
+    fn try_delete_render_pass(&mut self, render_pass: RenderPass) -> Result<(), GraphicsError> {
+        self.backend.try_delete_render_pass(render_pass)?;
+        if let Some(state) = self.passes.remove(&render_pass) {
+            self.untrack_attachments(&state);
+        }
+        Ok(())
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        const METHOD: &str = "new_pipeline";
+        match self.shaders.get(&shader) {
+            None => self.error(METHOD, format!("{:?} was deleted or is unknown", shader)),
+            Some(state) if state.compute => {
+                self.error(METHOD, format!("{:?} is a compute shader", shader))
+            }
+            _ => {}
+        }
+        if attributes.len() > MAX_VERTEX_ATTRIBUTES {
+            self.error(
+                METHOD,
+                format!(
+                    "{} attributes, MAX_VERTEX_ATTRIBUTES is {}",
+                    attributes.len(),
+                    MAX_VERTEX_ATTRIBUTES
+                ),
+            );
+        }
+        for attribute in attributes {
+            if attribute.buffer_index >= buffer_layout.len() {
+                self.error(
+                    METHOD,
+                    format!(
+                        "attribute {} uses buffer {}, but only {} buffer layouts are given",
+                        attribute.name,
+                        attribute.buffer_index,
+                        buffer_layout.len()
+                    ),
+                );
+            }
+        }
+        for layout in buffer_layout {
+            if layout.step_func == VertexStep::PerInstance && !self.info.features.instancing {
+                self.error(
+                    METHOD,
+                    "VertexStep::PerInstance without features.instancing".to_string(),
+                );
+            }
+        }
+        if matches!(
+            params.primitive_type,
+            PrimitiveType::LineLoop | PrimitiveType::TriangleFan
+        ) && !self.info.features.line_loop_triangle_fan
+        {
+            self.error(
+                METHOD,
+                format!(
+                    "{:?} without features.line_loop_triangle_fan",
+                    params.primitive_type
+                ),
+            );
+        }
+        if params.primitive_restart && !self.info.features.primitive_restart {
+            self.error(
+                METHOD,
+                "primitive_restart without features.primitive_restart".to_string(),
+            );
+        }
+        if params.color_blend.is_none() && params.alpha_blend.is_some() {
+            self.error(METHOD, "alpha_blend without color_blend".to_string());
+        }
+        if let Some(attachments) = params.attachment_blend {
+            if attachments.iter().any(|a| *a != attachments[0])
+                && !self.info.features.independent_blend
+            {
+                self.error(
+                    METHOD,
+                    "different attachment_blend states without features.independent_blend"
+                        .to_string(),
+                );
+            }
+        }
+
+        let pipeline = self
+            .backend
+            .new_pipeline(buffer_layout, attributes, shader, params);
+        self.pipelines.insert(
+            pipeline,
+            PipelineState {
+                shader,
+                compute: false,
+                buffers: pipeline_buffers(buffer_layout, attributes),
+            },
+        );
+        pipeline
+    }
+
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, GraphicsError> {
+        let pipeline = self
+            .backend
+            .try_new_pipeline(buffer_layout, attributes, shader, params)?;
+        self.pipelines.insert(
+            pipeline,
+            PipelineState {
+                shader,
+                compute: false,
+                buffers: pipeline_buffers(buffer_layout, attributes),
+            },
+        );
+        Ok(pipeline)
+    }
+
+    fn new_compute_pipeline(&mut self, shader: ShaderId) -> Pipeline {
+        match self.shaders.get(&shader) {
+            None => self.error(
+                "new_compute_pipeline",
+                format!("{:?} was deleted or is unknown", shader),
+            ),
+            Some(state) if !state.compute => self.error(
+                "new_compute_pipeline",
+                format!("{:?} is not a compute shader", shader),
+            ),
+            _ => {}
+        }
+        let pipeline = self.backend.new_compute_pipeline(shader);
+        self.pipelines.insert(
+            pipeline,
+            PipelineState {
+                shader,
+                compute: true,
+                buffers: vec![],
+            },
+        );
+        pipeline
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        match self.pipelines.get(pipeline) {
+            None => self.error(
+                "apply_pipeline",
+                format!("{:?} was deleted or is unknown", pipeline),
+            ),
+            Some(state) => {
+                if !self.shaders.contains_key(&state.shader) {
+                    self.error(
+                        "apply_pipeline",
+                        format!("{:?} uses deleted {:?}", pipeline, state.shader),
+                    );
+                }
+                if !state.compute && self.cur_pass.is_none() {
+                    self.error(
+                        "apply_pipeline",
+                        format!("{:?} applied outside of a render pass", pipeline),
+                    );
+                }
+                if state.compute && self.cur_pass.is_some() {
+                    self.error(
+                        "apply_pipeline",
+                        format!("compute {:?} applied inside a render pass", pipeline),
+                    );
+                }
+            }
+        }
+        self.cur_pipeline = Some(*pipeline);
+        self.cur_bindings = None;
+        self.cur_compute_bindings = false;
+        self.backend.apply_pipeline(pipeline)
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        if self.pipelines.remove(&pipeline).is_none() {
+            self.error(
+                "delete_pipeline",
+                format!("{:?} was deleted or is unknown", pipeline),
+            );
+        }
+        if self.cur_pipeline == Some(pipeline) {
+            self.cur_pipeline = None;
+        }
+        self.backend.delete_pipeline(pipeline)
+    }
+
+    fn try_delete_pipeline(&mut self, pipeline: Pipeline) -> Result<(), GraphicsError> {
+        self.backend.try_delete_pipeline(pipeline)?;
+        self.pipelines.remove(&pipeline);
+        if self.cur_pipeline == Some(pipeline) {
+            self.cur_pipeline = None;
+        }
+        Ok(())
+    }
+
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        let (size, element_size, empty) = match &data {
+            BufferSource::Slice(data) => (data.size, data.element_size, false),
+            BufferSource::Empty { size, element_size } => (*size, *element_size, true),
+        };
+        if type_ == BufferType::IndexBuffer && ![1, 2, 4].contains(&element_size) {
+            self.error(
+                "new_buffer",
+                format!(
+                    "{} bytes index type, only u8, u16 and u32 are supported",
+                    element_size
+                ),
+            );
+        }
+        if type_ == BufferType::StorageBuffer && !self.info.features.compute {
+            self.error(
+                "new_buffer",
+                "BufferType::StorageBuffer without features.compute".to_string(),
+            );
+        }
+        if usage == BufferUsage::Immutable && empty {
+            self.error(
+                "new_buffer",
+                "BufferUsage::Immutable buffer without data".to_string(),
+            );
+        }
+        let buffer = self.backend.new_buffer(type_, usage, data);
+        self.buffers.insert(
+            buffer,
+            BufferState {
+                type_,
+                usage,
+                size,
+                element_size,
+            },
+        );
+        buffer
+    }
+
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, GraphicsError> {
+        let (size, element_size) = match &data {
+            BufferSource::Slice(data) => (data.size, data.element_size),
+            BufferSource::Empty { size, element_size } => (*size, *element_size),
+        };
+        let buffer = self.backend.try_new_buffer(type_, usage, data)?;
+        self.buffers.insert(
+            buffer,
+            BufferState {
+                type_,
+                usage,
+                size,
+                element_size,
+            },
+        );
+        Ok(buffer)
+    }
+
+    fn buffer_update_part(&mut self, buffer: BufferId, byte_offset: usize, data: BufferSource) {
+        let size = match &data {
+            BufferSource::Slice(data) => data.size,
+            BufferSource::Empty { size, .. } => *size,
+        };
+        self.check_buffer_range("buffer_update_part", buffer, byte_offset, size);
+        if let Some(state) = self.buffers.get(&buffer) {
+            if state.usage == BufferUsage::Immutable {
+                self.error(
+                    "buffer_update_part",
+                    format!("{:?} is BufferUsage::Immutable", buffer),
+                );
+            }
+        }
+        self.backend.buffer_update_part(buffer, byte_offset, data)
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, byte_offset: usize, data: &mut [u8]) {
+        if !self.info.features.buffer_read {
+            self.error(
+                "buffer_read",
+                "called without features.buffer_read".to_string(),
+            );
+        }
+        self.check_buffer_range("buffer_read", buffer, byte_offset, data.len());
+        self.backend.buffer_read(buffer, byte_offset, data)
+    }
+
+    fn buffer_map_write(
+        &mut self,
+        buffer: BufferId,
+        byte_offset: usize,
+        size: usize,
+        f: &mut dyn FnMut(&mut [u8]),
+    ) {
+        self.check_buffer_range("buffer_map_write", buffer, byte_offset, size);
+        if let Some(state) = self.buffers.get(&buffer) {
+            if state.usage == BufferUsage::Immutable {
+                self.error(
+                    "buffer_map_write",
+                    format!("{:?} is BufferUsage::Immutable", buffer),
+                );
+            }
+        }
+        self.backend.buffer_map_write(buffer, byte_offset, size, f)
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.check_buffer("buffer_size", buffer);
+        self.backend.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        if self.buffers.remove(&buffer).is_none() {
+            self.error(
+                "delete_buffer",
+                format!("{:?} was deleted or is unknown", buffer),
+            );
+        }
+        self.backend.delete_buffer(buffer)
+    }
+
+    fn try_delete_buffer(&mut self, buffer: BufferId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_buffer(buffer)?;
+        self.buffers.remove(&buffer);
+        Ok(())
+    }
+
+    fn new_query(&mut self, kind: QueryKind) -> QueryId {
+        if !self.info.query_supported(kind) {
+            self.error(
+                "new_query",
+                format!("{:?} is not supported by the backend", kind),
+            );
+        }
+        let query = self.backend.new_query(kind);
+        self.queries.insert(
+            query,
+            QueryState {
+                kind,
+                active: false,
+            },
+        );
+        query
+    }
+
+    fn begin_query(&mut self, query: QueryId) {
+        match self.queries.get(&query) {
+            None => self.error(
+                "begin_query",
+                format!("{:?} was deleted or is unknown", query),
+            ),
+            Some(state) if state.kind == QueryKind::Timestamp => self.error(
+                "begin_query",
+                format!(
+                    "{:?} is a timestamp, those are recorded with end_query alone",
+                    query
+                ),
+            ),
+            Some(state) if state.active => {
+                self.error("begin_query", format!("{:?} is already active", query))
+            }
+            Some(state) => {
+                let kind = state.kind;
+                if let Some((other, _)) = self
+                    .queries
+                    .iter()
+                    .find(|(_, other)| other.active && other.kind == kind)
+                {
+                    self.error(
+                        "begin_query",
+                        format!("{:?} of the same kind {:?} is still active", other, kind),
+                    );
+                }
+            }
+        }
+        if let Some(state) = self.queries.get_mut(&query) {
+            state.active = true;
+        }
+        self.backend.begin_query(query)
+    }
+
+    fn end_query(&mut self, query: QueryId) {
+        match self.queries.get_mut(&query) {
+            None => self.error(
+                "end_query",
+                format!("{:?} was deleted or is unknown", query),
+            ),
+            Some(state) if state.active || state.kind == QueryKind::Timestamp => {
+                state.active = false;
+            }
+            Some(_) => self.error("end_query", format!("{:?} is not active", query)),
+        }
+        self.backend.end_query(query)
+    }
+
+    fn query_result(&mut self, query: QueryId) -> Option<u64> {
+        match self.queries.get(&query) {
+            None => self.error(
+                "query_result",
+                format!("{:?} was deleted or is unknown", query),
+            ),
+            Some(state) if state.active => {
+                self.error("query_result", format!("{:?} is still active", query))
+            }
+            _ => {}
+        }
+        self.backend.query_result(query)
+    }
+
+    fn delete_query(&mut self, query: QueryId) {
+        if self.queries.remove(&query).is_none() {
+            self.error(
+                "delete_query",
+                format!("{:?} was deleted or is unknown", query),
+            );
+        }
+        self.backend.delete_query(query)
+    }
+
+    fn insert_fence(&mut self) -> FenceId {
+        let fence = self.backend.insert_fence();
+        self.fences.insert(fence);
+        fence
+    }
+
+    fn fence_signaled(&mut self, fence: FenceId) -> bool {
+        if !self.fences.contains(&fence) {
+            self.error(
+                "fence_signaled",
+                format!("{:?} was deleted or is unknown", fence),
+            );
+        }
+        self.backend.fence_signaled(fence)
+    }
+
+    fn delete_fence(&mut self, fence: FenceId) {
+        if !self.fences.remove(&fence) {
+            self.error(
+                "delete_fence",
+                format!("{:?} was deleted or is unknown", fence),
+            );
+        }
+        self.backend.delete_fence(fence)
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        if let TextureIdInner::Managed(_) = texture.0 {
+            if !self.textures.remove(&texture) {
+                self.error(
+                    "delete_texture",
+                    format!("{:?} was deleted or is unknown", texture),
+                );
+            }
+        }
+        self.backend.delete_texture(texture)
+    }
+
+    fn try_delete_texture(&mut self, texture: TextureId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_texture(texture)?;
+        self.textures.remove(&texture);
+        Ok(())
+    }
+
+    fn delete_sampler(&mut self, sampler: SamplerId) {
+        if !self.samplers.remove(&sampler) {
+            self.error(
+                "delete_sampler",
+                format!("{:?} was deleted or is unknown", sampler),
+            );
+        }
+        self.backend.delete_sampler(sampler)
+    }
+
+    fn try_delete_sampler(&mut self, sampler: SamplerId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_sampler(sampler)?;
+        self.samplers.remove(&sampler);
+        Ok(())
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        if self.shaders.remove(&program).is_none() {
+            self.error(
+                "delete_shader",
+                format!("{:?} was deleted or is unknown", program),
+            );
+        }
+        self.backend.delete_shader(program)
+    }
+
+    fn try_delete_shader(&mut self, program: ShaderId) -> Result<(), GraphicsError> {
+        self.backend.try_delete_shader(program)?;
+        self.shaders.remove(&program);
+        Ok(())
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        if self.cur_pass.is_none() {
+            self.error(
+                "apply_viewport",
+                "called outside of a render pass".to_string(),
+            );
+        }
+        self.backend.apply_viewport(x, y, w, h)
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        if self.cur_pass.is_none() {
+            self.error(
+                "apply_scissor_rect",
+                "called outside of a render pass".to_string(),
+            );
+        }
+        self.backend.apply_scissor_rect(x, y, w, h)
+    }
+
+    fn apply_bindings_with_samplers(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
+        textures: &[TextureId],
+        samplers: &[Option<SamplerId>],
+    ) {
+        const METHOD: &str = "apply_bindings";
+        let pipeline = self.cur_pipeline.and_then(|p| self.pipelines.get(&p));
+        match pipeline {
+            None => self.error(METHOD, "no pipeline applied".to_string()),
+            Some(state) if state.compute => self.error(
+                METHOD,
+                format!(
+                    "{:?} is a compute pipeline, use apply_compute_bindings",
+                    self.cur_pipeline.unwrap()
+                ),
+            ),
+            Some(state) => {
+                if vertex_buffers.len() < state.buffers.len() {
+                    self.error(
+                        METHOD,
+                        format!(
+                            "{} vertex buffers, {:?} expects {}",
+                            vertex_buffers.len(),
+                            self.cur_pipeline.unwrap(),
+                            state.buffers.len()
+                        ),
+                    );
+                }
+                if let Some(shader) = self.shaders.get(&state.shader) {
+                    if textures.len() < shader.images {
+                        self.error(
+                            METHOD,
+                            format!(
+                                "{} images, {:?} expects {}",
+                                textures.len(),
+                                state.shader,
+                                shader.images
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        for &buffer in vertex_buffers {
+            if let Some(state) = self.check_buffer(METHOD, buffer) {
+                if !matches!(
+                    state.type_,
+                    BufferType::VertexBuffer | BufferType::StorageBuffer
+                ) {
+                    self.error(
+                        METHOD,
+                        format!("{:?} is {:?}, not a vertex buffer", buffer, state.type_),
+                    );
+                }
+            }
+        }
+        if let Some(buffer) = index_buffer {
+            if let Some(state) = self.check_buffer(METHOD, buffer) {
+                if state.type_ != BufferType::IndexBuffer {
+                    self.error(
+                        METHOD,
+                        format!("{:?} is {:?}, not an index buffer", buffer, state.type_),
+                    );
+                }
+            }
+        }
+        let pass = self.cur_pass.flatten().and_then(|p| self.passes.get(&p));
+        for &texture in textures {
+            self.check_texture(METHOD, texture);
+            if let Some(pass) = pass {
+                if pass.attachments().any(|attachment| attachment == texture) {
+                    self.error(
+                        METHOD,
+                        format!(
+                            "{:?} is an attachment of the current {:?}",
+                            texture,
+                            self.cur_pass.flatten().unwrap()
+                        ),
+                    );
+                }
+            }
+        }
+        for &sampler in samplers.iter().flatten() {
+            if !self.samplers.contains(&sampler) {
+                self.error(METHOD, format!("{:?} was deleted or is unknown", sampler));
+            }
+        }
+        if samplers.len() > textures.len() {
+            self.error(
+                METHOD,
+                format!("{} samplers for {} images", samplers.len(), textures.len()),
+            );
+        }
+        self.cur_bindings = Some((vertex_buffers.to_vec(), index_buffer));
+        self.backend
+            .apply_bindings_with_samplers(vertex_buffers, index_buffer, textures, samplers)
+    }
+
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let shader = self
+            .cur_pipeline
+            .and_then(|p| self.pipelines.get(&p))
+            .map(|p| p.shader);
+        match shader {
+            None => self.error("apply_uniforms", "no pipeline applied".to_string()),
+            Some(shader) => {
+                if let Some(state) = self.shaders.get(&shader) {
+                    if size < state.uniforms_size {
+                        self.error(
+                            "apply_uniforms",
+                            format!(
+                                "{} bytes, {:?} uniforms take {} bytes",
+                                size, shader, state.uniforms_size
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        self.backend.apply_uniforms_from_bytes(uniform_ptr, size)
+    }
+
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block_index: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        let shader = self
+            .cur_pipeline
+            .and_then(|p| self.pipelines.get(&p))
+            .map(|p| p.shader);
+        match shader {
+            None => self.error("apply_uniform_block", "no pipeline applied".to_string()),
+            Some(shader) => {
+                if let Some(state) = self.shaders.get(&shader) {
+                    match state.uniform_blocks.get(block_index) {
+                        None => self.error(
+                            "apply_uniform_block",
+                            format!(
+                                "block {}, {:?} has {} uniform blocks",
+                                block_index,
+                                shader,
+                                state.uniform_blocks.len()
+                            ),
+                        ),
+                        Some((name, min_size)) if size < *min_size => self.error(
+                            "apply_uniform_block",
+                            format!(
+                                "{} bytes, block {} \"{}\" of {:?} takes {} bytes",
+                                size, block_index, name, shader, min_size
+                            ),
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        self.backend
+            .apply_uniform_block_from_bytes(block_index, uniform_ptr, size)
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        if self.cur_pass.is_none() {
+            self.error("clear", "called outside of a render pass".to_string());
+        }
+        self.backend.clear(color, depth, stencil)
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.check_begin_pass("begin_default_pass", None);
+        self.backend.begin_default_pass(action)
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.check_begin_pass("begin_pass", pass);
+        self.backend.begin_pass(pass, action)
+    }
+
+    fn end_render_pass(&mut self) {
+        if self.cur_pass.is_none() {
+            self.error("end_render_pass", "no render pass to end".to_string());
+        }
+        self.cur_pass = None;
+        self.cur_pipeline = None;
+        self.cur_bindings = None;
+        self.backend.end_render_pass()
+    }
+
+    fn commit_frame(&mut self) {
+        if let Some(pass) = self.cur_pass {
+            self.error("commit_frame", format!("{} was not ended", pass_name(pass)));
+        }
+        if let Some((query, _)) = self.queries.iter().find(|(_, state)| state.active) {
+            self.error("commit_frame", format!("{:?} is still active", query));
+        }
+        self.backend.commit_frame()
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        if let Some((pipeline, vertex_buffers, index_buffer)) = self.check_draw("draw") {
+            self.check_indices("draw", index_buffer, base_element, num_elements);
+            self.check_vertices("draw", pipeline, vertex_buffers, None, num_instances);
+        }
+        self.backend.draw(base_element, num_elements, num_instances)
+    }
+
+    fn draw_arrays(&self, first_vertex: i32, num_vertices: i32, num_instances: i32) {
+        if let Some((pipeline, vertex_buffers, _)) = self.check_draw("draw_arrays") {
+            if first_vertex < 0 || num_vertices < 0 {
+                self.error(
+                    "draw_arrays",
+                    format!(
+                        "negative vertex range: {} vertices from {}",
+                        num_vertices, first_vertex
+                    ),
+                );
+            } else {
+                self.check_vertices(
+                    "draw_arrays",
+                    pipeline,
+                    vertex_buffers,
+                    Some((first_vertex, num_vertices)),
+                    num_instances,
+                );
+            }
+        }
+        self.backend
+            .draw_arrays(first_vertex, num_vertices, num_instances)
+    }
+
+    fn multi_draw(&self, draws: &[DrawRange]) {
+        if let Some((pipeline, vertex_buffers, index_buffer)) = self.check_draw("multi_draw") {
+            for draw in draws {
+                self.check_indices(
+                    "multi_draw",
+                    index_buffer,
+                    draw.base_element,
+                    draw.num_elements,
+                );
+                self.check_vertices(
+                    "multi_draw",
+                    pipeline,
+                    vertex_buffers,
+                    None,
+                    draw.num_instances,
+                );
+            }
+        }
+        self.backend.multi_draw(draws)
+    }
+
+    fn multi_draw_indirect(&self, indirect_buffer: BufferId, offset: usize, draw_count: usize) {
+        const METHOD: &str = "multi_draw_indirect";
+        if let Some((_, _, index_buffer)) = self.check_draw(METHOD) {
+            if index_buffer.is_none() {
+                self.error(METHOD, "no index buffer bound".to_string());
+            }
+        }
+        if let Some(state) = self.check_buffer(METHOD, indirect_buffer) {
+            if state.type_ != BufferType::IndirectBuffer {
+                self.error(
+                    METHOD,
+                    format!(
+                        "{:?} is {:?}, not an indirect buffer",
+                        indirect_buffer, state.type_
+                    ),
+                );
+            }
+        }
+        if offset % 4 != 0 {
+            self.error(METHOD, format!("offset {} is not 4 bytes aligned", offset));
+        }
+        self.check_buffer_range(
+            METHOD,
+            indirect_buffer,
+            offset,
+            draw_count.saturating_mul(std::mem::size_of::<DrawIndirectArgs>()),
+        );
+        self.backend
+            .multi_draw_indirect(indirect_buffer, offset, draw_count)
+    }
+
+    fn apply_compute_bindings(&mut self, bindings: &ComputeBindings) {
+        const METHOD: &str = "apply_compute_bindings";
+        let pipeline = self.cur_pipeline.and_then(|p| self.pipelines.get(&p));
+        match pipeline {
+            None => self.error(METHOD, "no compute pipeline applied".to_string()),
+            Some(state) if !state.compute => self.error(
+                METHOD,
+                format!("{:?} is a render pipeline", self.cur_pipeline.unwrap()),
+            ),
+            Some(state) => {
+                if let Some(shader) = self.shaders.get(&state.shader) {
+                    if bindings.images.len() < shader.images {
+                        self.error(
+                            METHOD,
+                            format!(
+                                "{} images, {:?} expects {}",
+                                bindings.images.len(),
+                                state.shader,
+                                shader.images
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        for &buffer in &bindings.storage_buffers {
+            if let Some(state) = self.check_buffer(METHOD, buffer) {
+                if state.type_ != BufferType::StorageBuffer {
+                    self.error(
+                        METHOD,
+                        format!("{:?} is {:?}, not a storage buffer", buffer, state.type_),
+                    );
+                }
+            }
+        }
+        for image in &bindings.storage_images {
+            self.check_texture(METHOD, image.texture);
+        }
+        for &texture in &bindings.images {
+            self.check_texture(METHOD, texture);
+        }
+        self.cur_compute_bindings = true;
+        self.backend.apply_compute_bindings(bindings)
+    }
+
+    fn dispatch(&mut self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        if let Some(pass) = self.cur_pass {
+            self.error("dispatch", format!("called inside {}", pass_name(pass)));
+        }
+        match self.cur_pipeline.and_then(|p| self.pipelines.get(&p)) {
+            Some(state) if state.compute => {
+                if !self.cur_compute_bindings {
+                    self.error(
+                        "dispatch",
+                        format!(
+                            "no compute bindings applied after apply_pipeline({:?})",
+                            self.cur_pipeline.unwrap()
+                        ),
+                    );
+                }
+            }
+            _ => self.error("dispatch", "no compute pipeline applied".to_string()),
+        }
+        self.backend.dispatch(groups_x, groups_y, groups_z)
+    }
+
+    fn memory_barrier(&mut self, barriers: &[MemoryBarrier]) {
+        self.backend.memory_barrier(barriers)
+    }
+}
+
+fn pipeline_buffers(
+    buffer_layout: &[BufferLayout],
+    attributes: &[VertexAttribute],
+) -> Vec<VertexBufferState> {
+    let buffers_used = attributes
+        .iter()
+        .map(|a| a.buffer_index + 1)
+        .max()
+        .unwrap_or(0)
+        .min(buffer_layout.len());
+    buffer_layout[..buffers_used]
+        .iter()
+        .enumerate()
+        .map(|(n, layout)| {
+            // attributes are packed one after another, same as in the backends
+            let attributes_end = attributes
+                .iter()
+                .filter(|a| a.buffer_index == n)
+                .map(|a| a.format.size_bytes() as usize)
+                .sum();
+            let stride = if layout.stride == 0 {
+                attributes_end
+            } else {
+                layout.stride as usize
+            };
+            VertexBufferState {
+                stride,
+                attributes_end,
+                step_func: layout.step_func,
+                step_rate: layout.step_rate,
+            }
+        })
+        .collect()
+}
+
+fn pass_name(pass: Option<RenderPass>) -> String {
+    match pass {
+        Some(pass) => format!("{:?}", pass),
+        None => "the default pass".to_string(),
+    }
+}
+
+fn shader_state(compute: bool, meta: &ShaderMeta) -> ShaderState {
+    let uniforms_size = meta
+        .uniforms
+        .uniforms
+        .iter()
+        .map(|u| u.uniform_type.size() * u.array_count)
+        .sum();
+    // the data may end with the last field, without the std140 tail padding
+    let uniform_blocks = meta
+        .uniform_blocks
+        .iter()
+        .map(|block| {
+            let offsets = block.layout.std140_offsets();
+            let size = block
+                .layout
+                .uniforms
+                .iter()
+                .zip(offsets)
+                .map(|(u, offset)| {
+                    let stride = (u.uniform_type.size() + 15) / 16 * 16;
+                    offset + stride * (u.array_count.max(1) - 1) + u.uniform_type.size()
+                })
+                .max()
+                .unwrap_or(0);
+            (block.name.clone(), size)
+        })
+        .collect();
+    ShaderState {
+        compute,
+        images: meta.images.len(),
+        uniforms_size,
+        uniform_blocks,
+    }
+}
+
+#[test]
+fn test_validating_backend() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let errors = Rc::new(RefCell::new(Vec::<String>::new()));
+    let report = errors.clone();
+    let mut ctx = ValidatingBackend::with_report(Box::new(NullContext::new()), move |error| {
+        report.borrow_mut().push(error.to_string())
+    });
+    let take_errors = || std::mem::take(&mut *errors.borrow_mut());
+
+    let source = ShaderSource::Glsl {
+        vertex: "",
+        fragment: "",
+    };
+    let shader = ctx.new_shader(source, ShaderMeta::default()).unwrap();
+    let pipeline = ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float3)],
+        shader,
+        PipelineParams::default(),
+    );
+    let vertices = [0f32; 9];
+    let vertex_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&vertices),
+    );
+    let texture = ctx.new_render_texture(TextureParams {
+        width: 4,
+        height: 4,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+
+    // a valid frame
+    ctx.begin_pass(Some(pass), PassAction::clear_color(0., 0., 0., 1.));
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], None, &[]);
+    ctx.draw_arrays(0, 3, 1);
+    ctx.end_render_pass();
+    ctx.commit_frame();
+    assert_eq!(take_errors(), Vec::<String>::new());
+
+    // 6 vertices of 12 bytes from a buffer of 3
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[vertex_buffer], None, &[]);
+    ctx.draw_arrays(0, 6, 1);
+    ctx.end_render_pass();
+    let errors = take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].starts_with("draw_arrays: vertex 5"),
+        "{}",
+        errors[0]
+    );
+
+    // the second vertex starts within the 20 bytes, but does not fit
+    let short_buffer = ctx.new_buffer(
+        BufferType::VertexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0f32; 5]),
+    );
+    let index_buffer = ctx.new_buffer(
+        BufferType::IndexBuffer,
+        BufferUsage::Immutable,
+        BufferSource::slice(&[0u16, 1]),
+    );
+    ctx.begin_default_pass(PassAction::Nothing);
+    ctx.apply_pipeline(&pipeline);
+    ctx.apply_bindings_from_slice(&[short_buffer], Some(index_buffer), &[]);
+    ctx.draw_arrays(0, 1, 1);
+    assert_eq!(take_errors(), Vec::<String>::new());
+    ctx.draw_arrays(0, 2, 1);
+    ctx.draw_arrays(i32::MAX, i32::MAX, 1);
+    ctx.draw(i32::MAX, i32::MAX, 1);
+    ctx.end_render_pass();
+    let errors = take_errors();
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(
+        errors[0].starts_with("draw_arrays: vertex 1"),
+        "{:?}",
+        errors
+    );
+
+    // NullContext panics on unknown ids too, but only after the report
+    let rejected = |call: &mut dyn FnMut()| {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(call));
+        assert!(result.is_err());
+        take_errors()
+    };
+
+    // the pass took its attachments with it
+    ctx.delete_render_pass(pass);
+    let errors = rejected(&mut || ctx.delete_texture(texture));
+    assert_eq!(
+        errors,
+        [format!(
+            "delete_texture: {:?} was deleted or is unknown",
+            texture
+        )]
+    );
+
+    ctx.delete_pipeline(pipeline);
+    ctx.begin_default_pass(PassAction::Nothing);
+    let errors = rejected(&mut || ctx.apply_pipeline(&pipeline));
+    assert_eq!(
+        errors,
+        [format!(
+            "apply_pipeline: {:?} was deleted or is unknown",
+            pipeline
+        )]
+    );
+    ctx.draw_arrays(0, 3, 1);
+    ctx.end_render_pass();
+    assert_eq!(
+        take_errors(),
+        [format!(
+            "draw_arrays: {:?} was deleted or is unknown",
+            pipeline
+        )]
+    );
+}
@@ -12,7 +12,6 @@ mod event;
 pub mod fs;
 pub mod graphics;
 pub mod native;
-use std::collections::HashMap;
 use std::ops::{Index, IndexMut};
 
 #[cfg(feature = "log-impl")]
@@ -26,44 +25,132 @@ mod default_icon;
 
 pub use native::gl;
 
+/// Generational index of a `ResourceManager` slot.
+///
+/// Slots of deleted resources are reused with a new generation, so an id
+/// kept after `delete_*` never aliases the resource that took its place.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ResourceId {
+    index: u32,
+    generation: u32,
+}
+
+// "3v1" for the slot 3 reused once, same as the slotmap crate
+impl std::fmt::Debug for ResourceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    generation: u32,
+    resource: Option<T>,
+}
+
 #[derive(Clone)]
 pub(crate) struct ResourceManager<T> {
-    id: usize,
-    resources: HashMap<usize, T>,
+    slots: Vec<Slot<T>>,
+    // indices of the empty slots
+    free: Vec<u32>,
 }
 
 impl<T> Default for ResourceManager<T> {
     fn default() -> Self {
         Self {
-            id: 0,
-            resources: HashMap::new(),
+            slots: vec![],
+            free: vec![],
         }
     }
 }
 
 impl<T> ResourceManager<T> {
-    pub fn add(&mut self, resource: T) -> usize {
-        self.resources.insert(self.id, resource);
-        self.id += 1;
-        self.id - 1
+    pub fn add(&mut self, resource: T) -> ResourceId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.resource = Some(resource);
+                ResourceId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    resource: Some(resource),
+                });
+                ResourceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
     }
 
-    pub fn remove(&mut self, id: usize) -> T {
-        // Let it crash if the resource is not found
-        self.resources.remove(&id).unwrap()
+    /// `None` if the resource was already removed.
+    pub fn remove(&mut self, id: ResourceId) -> Option<T> {
+        self.get(id)?;
+        let slot = &mut self.slots[id.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        slot.resource.take()
+    }
+
+    pub fn get(&self, id: ResourceId) -> Option<&T> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.resource.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: ResourceId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.resource.as_mut())
+    }
+
+    pub fn contains(&self, id: ResourceId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Number of live resources.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.resource.as_mut())
+    }
+
+    fn missing(&self, id: ResourceId) -> ! {
+        // generations are bumped on remove, so a slot with the same generation is never empty
+        match self.slots.get(id.index as usize) {
+            Some(_) => panic!("Stale resource id {:?}, the resource was deleted", id),
+            None => panic!("Resource {:?} never existed", id),
+        }
     }
 }
 
-impl<T> Index<usize> for ResourceManager<T> {
+impl<T> Index<ResourceId> for ResourceManager<T> {
     type Output = T;
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.resources[&index]
+    fn index(&self, id: ResourceId) -> &Self::Output {
+        match self.get(id) {
+            Some(resource) => resource,
+            None => self.missing(id),
+        }
     }
 }
 
-impl<T> IndexMut<usize> for ResourceManager<T> {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.resources.get_mut(&index).unwrap()
+impl<T> IndexMut<ResourceId> for ResourceManager<T> {
+    fn index_mut(&mut self, id: ResourceId) -> &mut Self::Output {
+        if !self.contains(id) {
+            self.missing(id)
+        }
+        self.get_mut(id).unwrap()
     }
 }
 
@@ -114,6 +201,13 @@ fn native_display() -> &'static Mutex<native::NativeDisplayData> {
         .expect("Backend has not initialized NATIVE_DISPLAY yet.") //|| Mutex::new(Default::default()))
 }
 
+static PROGRAM_CACHE_DIR: Mutex<Option<std::path::PathBuf>> = Mutex::new(None);
+
+/// `Conf::program_cache_dir` of the running app.
+pub(crate) fn program_cache_dir() -> Option<std::path::PathBuf> {
+    PROGRAM_CACHE_DIR.lock().unwrap().clone()
+}
+
 /// Window and associated to window rendering context related functions.
 /// in macroquad <= 0.3, it was ctx.screen_size(). Now it is window::screen_size()
 pub mod window {
@@ -462,6 +556,8 @@ pub fn start<F>(conf: conf::Conf, f: F)
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
 {
+    *PROGRAM_CACHE_DIR.lock().unwrap() = conf.program_cache_dir.clone();
+
     #[cfg(target_os = "linux")]
     {
         let mut f = Some(f);
@@ -486,6 +582,9 @@ where
                     native::linux_x11::run(&conf, f).unwrap()
                 }
             }
+            conf::LinuxBackend::Headless => {
+                native::linux_headless::run(&conf, f).expect("Headless backend failed")
+            }
         }
     }
 
@@ -514,3 +613,28 @@ where
         native::ios::run(conf, f);
     }
 }
+
+#[test]
+fn test_resource_manager() {
+    let mut resources = ResourceManager::default();
+    let a = resources.add("a");
+    let b = resources.add("b");
+    assert_eq!(resources[a], "a");
+    assert_eq!(resources.remove(a), Some("a"));
+    assert_eq!(resources.remove(a), None);
+
+    // the slot of "a" is reused, but the old id does not alias "c"
+    let c = resources.add("c");
+    assert_eq!(c.index, a.index);
+    assert!(resources.get(a).is_none());
+    assert_eq!(resources[c], "c");
+    assert_eq!(resources.iter_mut().count(), 2);
+    assert_eq!(format!("{:?}", c), "0v1");
+
+    let stale = std::panic::catch_unwind(|| resources[a]).unwrap_err();
+    assert_eq!(
+        stale.downcast_ref::<String>().unwrap(),
+        "Stale resource id 0v0, the resource was deleted"
+    );
+    assert_eq!(resources[b], "b");
+}
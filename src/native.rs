@@ -93,6 +93,9 @@ pub mod linux_x11;
 #[cfg(target_os = "linux")]
 pub mod linux_wayland;
 
+#[cfg(target_os = "linux")]
+pub mod linux_headless;
+
 #[cfg(target_os = "android")]
 pub mod android;
 
@@ -124,5 +127,3 @@ pub mod gl;
 
 #[cfg(target_arch = "wasm32")]
 pub use wasm::webgl as gl;
-
-pub mod query_stab;
@@ -567,6 +567,32 @@ pub enum MTLPixelFormat {
     Depth32Float_Stencil8 = 260,
     RGBA8Unorm = 70,
     RGBA16Float = 115,
+    R8Unorm = 10,
+    R16Float = 25,
+    RG8Unorm = 30,
+    R32Uint = 53,
+    R32Float = 55,
+    RG16Float = 65,
+    RGBA8Unorm_sRGB = 71,
+    RGB10A2Unorm = 90,
+    RGBA32Float = 125,
+    BC1_RGBA = 130,
+    BC2_RGBA = 132,
+    BC3_RGBA = 134,
+    BC4_RUnorm = 140,
+    BC5_RGUnorm = 142,
+    BC6H_RGBUfloat = 151,
+    BC7_RGBAUnorm = 152,
+    EAC_R11Unorm = 170,
+    EAC_RG11Unorm = 174,
+    EAC_RGBA8 = 178,
+    ETC2_RGB8 = 180,
+    ASTC_4x4_LDR = 204,
+    ASTC_5x5_LDR = 206,
+    ASTC_6x6_LDR = 208,
+    ASTC_8x8_LDR = 212,
+    ASTC_10x10_LDR = 216,
+    ASTC_12x12_LDR = 218,
 }
 
 /// See <https://developer.apple.com/documentation/metal/mtlsamplerminmagfilter>
@@ -598,6 +624,15 @@ pub enum MTLSamplerAddressMode {
     ClampToBorderColor = 5,
 }
 
+/// See <https://developer.apple.com/documentation/metal/mtlsamplerbordercolor>
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLSamplerBorderColor {
+    TransparentBlack = 0,
+    OpaqueBlack = 1,
+    OpaqueWhite = 2,
+}
+
 #[repr(u64)]
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -718,6 +753,14 @@ pub enum MTLIndexType {
     UInt32 = 1,
 }
 
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLVisibilityResultMode {
+    Disabled = 0,
+    Boolean = 1,
+    Counting = 2,
+}
+
 #[repr(u64)]
 pub enum MTLCompareFunction {
     Never = 0,
@@ -730,6 +773,18 @@ pub enum MTLCompareFunction {
     Always = 7,
 }
 
+#[repr(u64)]
+pub enum MTLStencilOperation {
+    Keep = 0,
+    Zero = 1,
+    Replace = 2,
+    IncrementClamp = 3,
+    DecrementClamp = 4,
+    Invert = 5,
+    IncrementWrap = 6,
+    DecrementWrap = 7,
+}
+
 #[repr(u64)]
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
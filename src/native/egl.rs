@@ -19,6 +19,7 @@ use std::fmt::Display;
 
 pub const EGL_SUCCESS: u32 = 12288;
 
+pub const EGL_PBUFFER_BIT: u32 = 1;
 pub const EGL_WINDOW_BIT: u32 = 4;
 
 pub const EGL_ALPHA_SIZE: u32 = 12321;
@@ -34,6 +35,8 @@ pub const EGL_HEIGHT: u32 = 12374;
 pub const EGL_SURFACE_TYPE: u32 = 12339;
 pub const EGL_NONE: u32 = 12344;
 pub const EGL_CONTEXT_CLIENT_VERSION: u32 = 12440;
+pub const EGL_EXTENSIONS: u32 = 12373;
+pub const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
 
 pub type NativeDisplayType = EGLNativeDisplayType;
 pub type NativePixmapType = EGLNativePixmapType;
@@ -44,6 +47,7 @@ pub type EGLDisplay = *mut ::core::ffi::c_void;
 pub type EGLConfig = *mut ::core::ffi::c_void;
 pub type EGLSurface = *mut ::core::ffi::c_void;
 pub type EGLContext = *mut ::core::ffi::c_void;
+pub type EGLenum = ::core::ffi::c_uint;
 pub type __eglMustCastToProperFunctionPointerType = ::std::option::Option<unsafe extern "C" fn()>;
 
 crate::declare_module! {
@@ -150,7 +154,10 @@ crate::declare_module! {
 pub enum EglError {
     NoDisplay,
     InitializeFailed,
+    NoMatchingConfig,
     CreateContextFailed,
+    CreateSurfaceFailed,
+    MakeCurrentFailed,
 }
 
 impl Display for EglError {
@@ -158,7 +165,10 @@ impl Display for EglError {
         match self {
             Self::NoDisplay => write!(f, "No display"),
             Self::InitializeFailed => write!(f, "Failed to initialize context"),
+            Self::NoMatchingConfig => write!(f, "No matching config"),
             Self::CreateContextFailed => write!(f, "Faild to create context"),
+            Self::CreateSurfaceFailed => write!(f, "Failed to create surface"),
+            Self::MakeCurrentFailed => write!(f, "Failed to make context current"),
         }
     }
 }
@@ -183,10 +193,63 @@ pub unsafe fn create_egl_context(
         return Err(EglError::InitializeFailed);
     }
 
+    choose_config_and_create_context(egl, display, EGL_WINDOW_BIT, alpha, sample_count)
+}
+
+/// Create a context not bound to any window, for rendering into a pbuffer
+/// or with no default framebuffer at all.
+///
+/// Prefers Mesa's surfaceless platform, so no display server is required,
+/// and falls back to the default display otherwise.
+pub unsafe fn create_egl_headless_context(
+    egl: &mut LibEgl,
+    sample_count: i32,
+) -> Result<(EGLContext, EGLConfig, EGLDisplay), EglError> {
+    type GetPlatformDisplay =
+        unsafe extern "C" fn(EGLenum, *mut std::ffi::c_void, *const EGLint) -> EGLDisplay;
+
+    let mut display: EGLDisplay = null_mut();
+
+    let name = std::ffi::CString::new("eglGetPlatformDisplayEXT").unwrap();
+    if let Some(get_platform_display) = (egl.eglGetProcAddress)(name.as_ptr()) {
+        let get_platform_display: GetPlatformDisplay = std::mem::transmute(get_platform_display);
+        display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, null_mut(), std::ptr::null());
+        if !display.is_null() && (egl.eglInitialize)(display, null_mut(), null_mut()) == 0 {
+            display = null_mut();
+        }
+    }
+
+    if display.is_null() {
+        // EGL_DEFAULT_DISPLAY
+        display = (egl.eglGetDisplay)(null_mut());
+        if display.is_null() {
+            return Err(EglError::NoDisplay);
+        }
+        if (egl.eglInitialize)(display, null_mut(), null_mut()) == 0 {
+            return Err(EglError::InitializeFailed);
+        }
+    }
+
+    match choose_config_and_create_context(egl, display, EGL_PBUFFER_BIT, false, sample_count) {
+        // any config would do for a surfaceless context
+        Err(EglError::NoMatchingConfig) => {
+            choose_config_and_create_context(egl, display, 0, false, sample_count)
+        }
+        res => res,
+    }
+}
+
+unsafe fn choose_config_and_create_context(
+    egl: &mut LibEgl,
+    display: EGLDisplay,
+    surface_type: u32,
+    alpha: bool,
+    sample_count: i32,
+) -> Result<(EGLContext, EGLConfig, EGLDisplay), EglError> {
     let alpha_size = if alpha { 8 } else { 0 };
     #[rustfmt::skip]
     let cfg_attributes = [
-        EGL_SURFACE_TYPE, EGL_WINDOW_BIT,
+        EGL_SURFACE_TYPE, surface_type,
         EGL_RED_SIZE, 8,
         EGL_GREEN_SIZE, 8,
         EGL_BLUE_SIZE, 8,
@@ -206,7 +269,9 @@ pub unsafe fn create_egl_context(
         32,
         &mut cfg_count as *mut _ as *mut _,
     );
-    assert!(cfg_count > 0);
+    if cfg_count == 0 {
+        return Err(EglError::NoMatchingConfig);
+    }
     assert!(cfg_count <= 32);
 
     // find config with 8-bit rgb buffer if available, ndk sample does not trust egl spec
@@ -26,6 +26,13 @@ pub type GLclampf = f32;
 pub type GLdouble = f64;
 pub type GLclampd = f64;
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct __GLsync {
+    _unused: [u8; 0],
+}
+pub type GLsync = *mut __GLsync;
+
 pub const GL_INT_2_10_10_10_REV: u32 = 0x8D9F;
 pub const GL_PROGRAM_POINT_SIZE: u32 = 0x8642;
 pub const GL_STENCIL_ATTACHMENT: u32 = 0x8D20;
@@ -247,6 +254,7 @@ pub const GL_TIME_ELAPSED: u32 = 35007;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
+pub const GL_RENDERER: u32 = 0x1F01;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
 pub const GL_FRONT_AND_BACK: GLenum = 0x0408;
@@ -255,6 +263,116 @@ pub const GL_LINE: GLenum = 0x1B01;
 pub const GL_TEXTURE_BASE_LEVEL: GLenum = 0x813C;
 pub const GL_TEXTURE_MAX_LEVEL: GLenum = 0x813D;
 pub const GL_TEXTURE_CUBE_MAP_SEAMLESS: GLenum = 0x884F;
+pub const GL_COMPUTE_SHADER: GLenum = 0x91B9;
+pub const GL_SHADER_STORAGE_BUFFER: GLenum = 0x90D2;
+pub const GL_READ_ONLY: GLenum = 0x88B8;
+pub const GL_WRITE_ONLY: GLenum = 0x88B9;
+pub const GL_READ_WRITE: GLenum = 0x88BA;
+pub const GL_VERTEX_ATTRIB_ARRAY_BARRIER_BIT: GLenum = 0x00000001;
+pub const GL_ELEMENT_ARRAY_BARRIER_BIT: GLenum = 0x00000002;
+pub const GL_UNIFORM_BARRIER_BIT: GLenum = 0x00000004;
+pub const GL_TEXTURE_FETCH_BARRIER_BIT: GLenum = 0x00000008;
+pub const GL_SHADER_IMAGE_ACCESS_BARRIER_BIT: GLenum = 0x00000020;
+pub const GL_TEXTURE_UPDATE_BARRIER_BIT: GLenum = 0x00000100;
+pub const GL_BUFFER_UPDATE_BARRIER_BIT: GLenum = 0x00000200;
+pub const GL_FRAMEBUFFER_BARRIER_BIT: GLenum = 0x00000400;
+pub const GL_SHADER_STORAGE_BARRIER_BIT: GLenum = 0x00002000;
+pub const GL_ALL_BARRIER_BITS: GLenum = 0xFFFFFFFF;
+pub const GL_UNIFORM_BUFFER: GLenum = 0x8A11;
+pub const GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT: GLenum = 0x8A34;
+pub const GL_INVALID_INDEX: GLenum = 0xFFFFFFFF;
+pub const GL_SRGB8_ALPHA8: GLenum = 0x8C43;
+pub const GL_UNSIGNED_INT_2_10_10_10_REV: GLenum = 0x8368;
+pub const GL_DEPTH24_STENCIL8: GLenum = 0x88F0;
+pub const GL_DEPTH_STENCIL: GLenum = 0x84F9;
+pub const GL_UNSIGNED_INT_24_8: GLenum = 0x84FA;
+pub const GL_HALF_FLOAT: GLenum = 0x140B;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+pub const GL_COMPRESSED_RED_RGTC1: GLenum = 0x8DBB;
+pub const GL_COMPRESSED_RG_RGTC2: GLenum = 0x8DBD;
+pub const GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT: GLenum = 0x8E8F;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: GLenum = 0x8E8C;
+pub const GL_COMPRESSED_RGB8_ETC2: GLenum = 0x9274;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: GLenum = 0x9278;
+pub const GL_COMPRESSED_R11_EAC: GLenum = 0x9270;
+pub const GL_COMPRESSED_RG11_EAC: GLenum = 0x9272;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: GLenum = 0x93B0;
+pub const GL_COMPRESSED_RGBA_ASTC_5x5_KHR: GLenum = 0x93B2;
+pub const GL_COMPRESSED_RGBA_ASTC_6x6_KHR: GLenum = 0x93B4;
+pub const GL_COMPRESSED_RGBA_ASTC_8x8_KHR: GLenum = 0x93B7;
+pub const GL_COMPRESSED_RGBA_ASTC_10x10_KHR: GLenum = 0x93BB;
+pub const GL_COMPRESSED_RGBA_ASTC_12x12_KHR: GLenum = 0x93BD;
+pub const GL_DRAW_INDIRECT_BUFFER: GLenum = 0x8F3F;
+pub const GL_MAP_READ_BIT: GLenum = 0x0001;
+pub const GL_MAP_WRITE_BIT: GLenum = 0x0002;
+pub const GL_MAP_INVALIDATE_RANGE_BIT: GLenum = 0x0004;
+pub const GL_MAP_INVALIDATE_BUFFER_BIT: GLenum = 0x0008;
+pub const GL_TIMESTAMP: GLenum = 0x8E28;
+pub const GL_SAMPLES_PASSED: GLenum = 0x8914;
+pub const GL_ANY_SAMPLES_PASSED: GLenum = 0x8C2F;
+pub const GL_PRIMITIVES_GENERATED: GLenum = 0x8C87;
+pub const GL_PACK_ALIGNMENT: GLenum = 0x0D05;
+pub const GL_PIXEL_PACK_BUFFER: GLenum = 0x88EB;
+pub const GL_STREAM_READ: GLenum = 0x88E1;
+pub const GL_SYNC_GPU_COMMANDS_COMPLETE: GLenum = 0x9117;
+pub const GL_SYNC_STATUS: GLenum = 0x9114;
+pub const GL_SIGNALED: GLenum = 0x9119;
+pub const GL_DEPTH_STENCIL_ATTACHMENT: GLenum = 0x821A;
+pub const GL_LINE_LOOP: GLenum = 0x0002;
+pub const GL_TRIANGLE_FAN: GLenum = 0x0006;
+pub const GL_PRIMITIVE_RESTART_FIXED_INDEX: GLenum = 0x8D69;
+pub const GL_ACTIVE_UNIFORMS: GLenum = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: GLenum = 0x8B89;
+pub const GL_ACTIVE_UNIFORM_BLOCKS: GLenum = 0x8A36;
+pub const GL_UNIFORM_BLOCK_INDEX: GLenum = 0x8A3A;
+pub const GL_UNIFORM_OFFSET: GLenum = 0x8A3B;
+pub const GL_FLOAT_VEC2: GLenum = 0x8B50;
+pub const GL_FLOAT_VEC3: GLenum = 0x8B51;
+pub const GL_FLOAT_VEC4: GLenum = 0x8B52;
+pub const GL_INT_VEC2: GLenum = 0x8B53;
+pub const GL_INT_VEC3: GLenum = 0x8B54;
+pub const GL_INT_VEC4: GLenum = 0x8B55;
+pub const GL_BOOL: GLenum = 0x8B56;
+pub const GL_BOOL_VEC2: GLenum = 0x8B57;
+pub const GL_BOOL_VEC3: GLenum = 0x8B58;
+pub const GL_BOOL_VEC4: GLenum = 0x8B59;
+pub const GL_FLOAT_MAT4: GLenum = 0x8B5C;
+pub const GL_SAMPLER_2D: GLenum = 0x8B5E;
+pub const GL_SAMPLER_3D: GLenum = 0x8B5F;
+pub const GL_SAMPLER_CUBE: GLenum = 0x8B60;
+pub const GL_SAMPLER_2D_SHADOW: GLenum = 0x8B62;
+pub const GL_SAMPLER_2D_ARRAY: GLenum = 0x8DC1;
+pub const GL_SAMPLER_2D_ARRAY_SHADOW: GLenum = 0x8DC4;
+pub const GL_SAMPLER_CUBE_SHADOW: GLenum = 0x8DC5;
+pub const GL_UNSIGNED_INT_VEC2: GLenum = 0x8DC6;
+pub const GL_UNSIGNED_INT_VEC3: GLenum = 0x8DC7;
+pub const GL_UNSIGNED_INT_VEC4: GLenum = 0x8DC8;
+pub const GL_INT_SAMPLER_2D: GLenum = 0x8DCA;
+pub const GL_INT_SAMPLER_3D: GLenum = 0x8DCB;
+pub const GL_INT_SAMPLER_CUBE: GLenum = 0x8DCC;
+pub const GL_INT_SAMPLER_2D_ARRAY: GLenum = 0x8DCF;
+pub const GL_UNSIGNED_INT_SAMPLER_2D: GLenum = 0x8DD2;
+pub const GL_UNSIGNED_INT_SAMPLER_3D: GLenum = 0x8DD3;
+pub const GL_UNSIGNED_INT_SAMPLER_CUBE: GLenum = 0x8DD4;
+pub const GL_UNSIGNED_INT_SAMPLER_2D_ARRAY: GLenum = 0x8DD7;
+pub const GL_OUT_OF_MEMORY: GLenum = 0x0505;
+pub const GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT: GLenum = 0x8CD6;
+pub const GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT: GLenum = 0x8CD7;
+pub const GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS: GLenum = 0x8CD9;
+pub const GL_FRAMEBUFFER_UNSUPPORTED: GLenum = 0x8CDD;
+pub const GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE: GLenum = 0x8D56;
+pub const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: GLenum = 0x8257;
+pub const GL_PROGRAM_BINARY_LENGTH: GLenum = 0x8741;
+pub const GL_NUM_PROGRAM_BINARY_FORMATS: GLenum = 0x87FE;
+pub const GL_MIRROR_CLAMP_TO_EDGE: GLenum = 0x8743;
+pub const GL_TEXTURE_LOD_BIAS: GLenum = 0x8501;
+pub const GL_TEXTURE_COMPARE_MODE: GLenum = 0x884C;
+pub const GL_TEXTURE_COMPARE_FUNC: GLenum = 0x884D;
+pub const GL_COMPARE_REF_TO_TEXTURE: GLenum = 0x884E;
+pub const GL_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+pub const GL_MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
 
 pub const WGL_NUMBER_PIXEL_FORMATS_ARB: u32 = 0x2000;
 pub const WGL_SUPPORT_OPENGL_ARB: u32 = 0x2010;
@@ -426,6 +544,17 @@ gl_loader!(
         imageSize: GLsizei,
         data: *const GLvoid
     ) -> (),
+    fn glCompressedTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        imageSize: GLsizei,
+        data: *const GLvoid
+    ) -> (),
     fn glCompressedTexImage3D(
         target: GLenum,
         level: GLint,
@@ -646,9 +775,163 @@ gl_loader!(
     fn glGenQueries(n: GLsizei, ids: *mut GLuint) -> (),
     fn glGetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint) -> (),
     fn glGetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64) -> (),
+    fn glGetQueryObjectuiv(id: GLuint, pname: GLenum, params: *mut GLuint) -> (),
+    fn glQueryCounter(id: GLuint, target: GLenum) -> (),
+    fn glFenceSync(condition: GLenum, flags: GLbitfield) -> GLsync,
+    fn glDeleteSync(sync: GLsync) -> (),
+    fn glGetSynciv(
+        sync: GLsync,
+        pname: GLenum,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        values: *mut GLint
+    ) -> (),
     fn glFlush() -> (),
     fn glFinish() -> (),
-    fn glPolygonMode(face: GLenum, mode: GLenum) -> ()
+    fn glPolygonMode(face: GLenum, mode: GLenum) -> (),
+    fn glBindBufferBase(target: GLenum, index: GLuint, buffer: GLuint) -> (),
+    fn glBindBufferRange(
+        target: GLenum,
+        index: GLuint,
+        buffer: GLuint,
+        offset: GLintptr,
+        size: GLsizeiptr
+    ) -> (),
+    fn glGetUniformBlockIndex(program: GLuint, uniformBlockName: *const GLchar) -> GLuint,
+    fn glUniformBlockBinding(
+        program: GLuint,
+        uniformBlockIndex: GLuint,
+        uniformBlockBinding: GLuint
+    ) -> (),
+    fn glTexStorage2D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei
+    ) -> (),
+    fn glDispatchCompute(num_groups_x: GLuint, num_groups_y: GLuint, num_groups_z: GLuint) -> (),
+    fn glMemoryBarrier(barriers: GLbitfield) -> (),
+    fn glBindImageTexture(
+        unit: GLuint,
+        texture: GLuint,
+        level: GLint,
+        layered: GLboolean,
+        layer: GLint,
+        access: GLenum,
+        format: GLenum
+    ) -> (),
+    fn glTexStorage3D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei
+    ) -> (),
+    fn glDrawElementsInstancedBaseVertex(
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const ::core::ffi::c_void,
+        instancecount: GLsizei,
+        basevertex: GLint
+    ) -> (),
+    fn glDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::core::ffi::c_void
+    ) -> (),
+    fn glMultiDrawElementsIndirect(
+        mode: GLenum,
+        type_: GLenum,
+        indirect: *const ::core::ffi::c_void,
+        drawcount: GLsizei,
+        stride: GLsizei
+    ) -> (),
+    fn glMapBufferRange(
+        target: GLenum,
+        offset: GLintptr,
+        length: GLsizeiptr,
+        access: GLbitfield
+    ) -> *mut ::core::ffi::c_void,
+    fn glUnmapBuffer(target: GLenum) -> GLboolean,
+    fn glGetBufferSubData(
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *mut ::core::ffi::c_void
+    ) -> (),
+    fn glGetProgramBinary(
+        program: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        binaryFormat: *mut GLenum,
+        binary: *mut ::core::ffi::c_void
+    ) -> (),
+    fn glProgramBinary(
+        program: GLuint,
+        binaryFormat: GLenum,
+        binary: *const ::core::ffi::c_void,
+        length: GLsizei
+    ) -> (),
+    fn glProgramParameteri(program: GLuint, pname: GLenum, value: GLint) -> (),
+    fn glGetFloatv(pname: GLenum, data: *mut GLfloat) -> (),
+    fn glGenSamplers(count: GLsizei, samplers: *mut GLuint) -> (),
+    fn glDeleteSamplers(count: GLsizei, samplers: *const GLuint) -> (),
+    fn glBindSampler(unit: GLuint, sampler: GLuint) -> (),
+    fn glSamplerParameteri(sampler: GLuint, pname: GLenum, param: GLint) -> (),
+    fn glSamplerParameterf(sampler: GLuint, pname: GLenum, param: GLfloat) -> (),
+    fn glSamplerParameterfv(sampler: GLuint, pname: GLenum, param: *const GLfloat) -> (),
+    fn glEnablei(target: GLenum, index: GLuint) -> (),
+    fn glDisablei(target: GLenum, index: GLuint) -> (),
+    fn glBlendEquationSeparatei(buf: GLuint, modeRGB: GLenum, modeAlpha: GLenum) -> (),
+    fn glBlendFuncSeparatei(
+        buf: GLuint,
+        srcRGB: GLenum,
+        dstRGB: GLenum,
+        srcAlpha: GLenum,
+        dstAlpha: GLenum
+    ) -> (),
+    fn glColorMaski(
+        index: GLuint,
+        r: GLboolean,
+        g: GLboolean,
+        b: GLboolean,
+        a: GLboolean
+    ) -> (),
+    fn glGetActiveAttrib(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glGetActiveUniform(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glGetActiveUniformsiv(
+        program: GLuint,
+        uniformCount: GLsizei,
+        uniformIndices: *const GLuint,
+        pname: GLenum,
+        params: *mut GLint
+    ) -> (),
+    fn glGetActiveUniformBlockName(
+        program: GLuint,
+        uniformBlockIndex: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        uniformBlockName: *mut GLchar
+    ) -> ()
 );
 
 // note that glGetString only works after first glSwapBuffer,
@@ -0,0 +1,217 @@
+//! Headless linux backend: an EGL context with no window and no display
+//! server, for offscreen rendering on CI machines (e.g. Mesa's llvmpipe).
+
+use crate::{
+    event::EventHandler,
+    native::{egl, NativeDisplayData, Request},
+};
+
+#[derive(Debug)]
+pub enum HeadlessError {
+    LibraryNotFound(crate::native::module::Error),
+    Egl(egl::EglError),
+}
+
+impl std::fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LibraryNotFound(e) => write!(f, "Library not found error: {e}"),
+            Self::Egl(e) => write!(f, "EGL error: {e}"),
+        }
+    }
+}
+
+impl From<crate::native::module::Error> for HeadlessError {
+    fn from(error: crate::native::module::Error) -> Self {
+        Self::LibraryNotFound(error)
+    }
+}
+
+impl From<egl::EglError> for HeadlessError {
+    fn from(error: egl::EglError) -> Self {
+        Self::Egl(error)
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+/// There is no system clipboard without a display server, keep the
+/// contents in memory so copy/paste inside the application still works.
+struct HeadlessClipboard {
+    contents: Option<String>,
+}
+
+impl crate::native::Clipboard for HeadlessClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.contents.clone()
+    }
+
+    fn set(&mut self, data: &str) {
+        self.contents = Some(data.to_owned());
+    }
+}
+
+struct HeadlessDisplay {
+    egl: egl::LibEgl,
+    egl_display: egl::EGLDisplay,
+    egl_config: egl::EGLConfig,
+    egl_context: egl::EGLContext,
+    surface: egl::EGLSurface,
+}
+
+impl HeadlessDisplay {
+    /// (Re)create the pbuffer backing the default framebuffer.
+    /// Falls back to a surfaceless context (EGL_KHR_surfaceless_context) when
+    /// pbuffers are not available, the default pass then renders nowhere,
+    /// but render passes still work.
+    unsafe fn create_surface(&mut self, width: i32, height: i32) -> Result<(), egl::EglError> {
+        if !self.surface.is_null() {
+            (self.egl.eglMakeCurrent)(
+                self.egl_display,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                self.egl_context,
+            );
+            (self.egl.eglDestroySurface)(self.egl_display, self.surface);
+        }
+
+        let attributes = [
+            egl::EGL_WIDTH as i32,
+            width.max(1),
+            egl::EGL_HEIGHT as i32,
+            height.max(1),
+            egl::EGL_NONE as i32,
+        ];
+        self.surface = (self.egl.eglCreatePbufferSurface)(
+            self.egl_display,
+            self.egl_config,
+            attributes.as_ptr(),
+        );
+
+        if self.surface.is_null() {
+            if (self.egl.eglMakeCurrent)(
+                self.egl_display,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                self.egl_context,
+            ) == 0
+            {
+                return Err(egl::EglError::CreateSurfaceFailed);
+            }
+            return Ok(());
+        }
+
+        if (self.egl.eglMakeCurrent)(
+            self.egl_display,
+            self.surface,
+            self.surface,
+            self.egl_context,
+        ) == 0
+        {
+            return Err(egl::EglError::MakeCurrentFailed);
+        }
+        Ok(())
+    }
+
+    unsafe fn process_request(&mut self, request: Request, event_handler: &mut dyn EventHandler) {
+        // everything else is about a window there is none of
+        if let Request::SetWindowSize {
+            new_width,
+            new_height,
+        } = request
+        {
+            if let Err(err) = self.create_surface(new_width as _, new_height as _) {
+                eprintln!("Failed to resize headless surface: {err}");
+                return;
+            }
+            {
+                let mut d = crate::native_display().try_lock().unwrap();
+                d.screen_width = new_width as _;
+                d.screen_height = new_height as _;
+            }
+            event_handler.resize_event(new_width as _, new_height as _);
+        }
+    }
+}
+
+pub fn run<F>(conf: &crate::conf::Conf, f: &mut Option<F>) -> Result<(), HeadlessError>
+where
+    F: 'static + FnOnce() -> Box<dyn EventHandler>,
+{
+    unsafe {
+        let mut egl = egl::LibEgl::try_load()?;
+        let (egl_context, egl_config, egl_display) =
+            egl::create_egl_headless_context(&mut egl, conf.sample_count)?;
+
+        let mut display = HeadlessDisplay {
+            egl,
+            egl_display,
+            egl_config,
+            egl_context,
+            surface: std::ptr::null_mut(),
+        };
+        display.create_surface(conf.window_width, conf.window_height)?;
+
+        crate::native::gl::load_gl_funcs(|proc| {
+            let name = std::ffi::CString::new(proc).unwrap();
+            (display.egl.eglGetProcAddress)(name.as_ptr() as _)
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let clipboard = Box::new(HeadlessClipboard { contents: None });
+        crate::set_display(NativeDisplayData {
+            high_dpi: conf.high_dpi,
+            // nothing would ever wake up a blocked headless loop
+            blocking_event_loop: false,
+            ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
+        });
+
+        let mut event_handler = (f.take().unwrap())();
+
+        let mut frames = 0;
+        while !crate::native_display().try_lock().unwrap().quit_ordered {
+            if let Some(max_frames) = conf.platform.headless_frames {
+                if frames >= max_frames {
+                    break;
+                }
+            }
+
+            while let Ok(request) = rx.try_recv() {
+                display.process_request(request, &mut *event_handler);
+            }
+
+            {
+                let d = crate::native_display().try_lock().unwrap();
+                if d.quit_requested && !d.quit_ordered {
+                    drop(d);
+                    event_handler.quit_requested_event();
+                    let mut d = crate::native_display().try_lock().unwrap();
+                    if d.quit_requested {
+                        d.quit_ordered = true
+                    }
+                }
+            }
+
+            event_handler.update();
+            event_handler.draw();
+            if !display.surface.is_null() {
+                (display.egl.eglSwapBuffers)(display.egl_display, display.surface);
+            }
+            frames += 1;
+        }
+
+        (display.egl.eglMakeCurrent)(
+            display.egl_display,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        if !display.surface.is_null() {
+            (display.egl.eglDestroySurface)(display.egl_display, display.surface);
+        }
+        (display.egl.eglDestroyContext)(display.egl_display, display.egl_context);
+        (display.egl.eglTerminate)(display.egl_display);
+    }
+
+    Ok(())
+}